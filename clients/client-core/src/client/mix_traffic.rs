@@ -6,6 +6,7 @@ use futures::StreamExt;
 use gateway_client::GatewayClient;
 use log::*;
 use nymsphinx::forwarding::packet::MixPacket;
+use task::ShutdownListener;
 use tokio::task::JoinHandle;
 
 pub type BatchMixMessageSender = mpsc::UnboundedSender<Vec<MixPacket>>;
@@ -65,15 +66,36 @@ impl MixTrafficController {
         }
     }
 
-    pub async fn run(&mut self) {
-        while let Some(mix_packets) = self.mix_rx.next().await {
-            self.on_messages(mix_packets).await;
+    /// Sends packets to the gateway until the shutdown. Afterwards it sends all of the packets
+    /// that are already waiting in the channel, without waiting for any new ones, and closes
+    /// the connection to the gateway.
+    pub async fn run(&mut self, mut shutdown: ShutdownListener) {
+        loop {
+            tokio::select! {
+                mix_packets = self.mix_rx.next() => match mix_packets {
+                    Some(mix_packets) => self.on_messages(mix_packets).await,
+                    None => break,
+                },
+                _ = shutdown.recv() => {
+                    while let Ok(Some(mix_packets)) = self.mix_rx.try_next() {
+                        self.on_messages(mix_packets).await;
+                    }
+                    break;
+                }
+            }
+        }
+
+        if let Err(err) = self.gateway_client.close_connection().await {
+            warn!(
+                "Failed to cleanly close the connection to the gateway - {}",
+                err
+            )
         }
     }
 
-    pub fn start(mut self) -> JoinHandle<()> {
+    pub fn start(mut self, shutdown: ShutdownListener) -> JoinHandle<()> {
         tokio::spawn(async move {
-            self.run().await;
+            self.run(shutdown).await;
         })
     }
 }
@@ -26,6 +26,8 @@ const DEFAULT_TOPOLOGY_RESOLUTION_TIMEOUT: Duration = Duration::from_millis(5_00
 // bought bandwidth tokens to not have time to be spent; Once we remove the gateway from the
 // bandwidth bridging protocol, we can come back to a smaller timeout value
 const DEFAULT_GATEWAY_RESPONSE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// 1GB, i.e. a tenth of the bandwidth bought with a single credential
+const DEFAULT_BANDWIDTH_LOW_WATER_MARK: i64 = 1024 * 1024 * 1024;
 
 pub fn missing_string_value() -> String {
     MISSING_VALUE.to_string()
@@ -249,6 +251,14 @@ impl<T: NymConfig> Config<T> {
         self.debug.gateway_response_timeout
     }
 
+    pub fn get_bandwidth_low_water_mark(&self) -> Option<i64> {
+        if self.debug.bandwidth_low_water_mark > 0 {
+            Some(self.debug.bandwidth_low_water_mark)
+        } else {
+            None
+        }
+    }
+
     pub fn get_topology_refresh_rate(&self) -> Duration {
         self.debug.topology_refresh_rate
     }
@@ -455,6 +465,11 @@ pub struct Debug {
     #[serde(with = "humantime_serde")]
     gateway_response_timeout: Duration,
 
+    /// Amount of remaining bandwidth (in bytes) below which a new bandwidth credential is prepared
+    /// in the background, so that sending packets is not blocked on obtaining it.
+    /// Set it to 0 to only obtain the credential once the bandwidth runs out.
+    bandwidth_low_water_mark: i64,
+
     /// The uniform delay every which clients are querying the directory server
     /// to try to obtain a compatible network topology to send sphinx packets through.
     #[serde(with = "humantime_serde")]
@@ -477,6 +492,7 @@ impl Default for Debug {
             loop_cover_traffic_average_delay: DEFAULT_LOOP_COVER_STREAM_AVERAGE_DELAY,
             message_sending_average_delay: DEFAULT_MESSAGE_STREAM_AVERAGE_DELAY,
             gateway_response_timeout: DEFAULT_GATEWAY_RESPONSE_TIMEOUT,
+            bandwidth_low_water_mark: DEFAULT_BANDWIDTH_LOW_WATER_MARK,
             topology_refresh_rate: DEFAULT_TOPOLOGY_REFRESH_RATE,
             topology_resolution_timeout: DEFAULT_TOPOLOGY_RESOLUTION_TIMEOUT,
         }
@@ -109,5 +109,9 @@ average_ack_delay = '{{ debug.average_ack_delay }}'
 loop_cover_traffic_average_delay = '{{ debug.loop_cover_traffic_average_delay }}'
 message_sending_average_delay = '{{ debug.message_sending_average_delay }}'
 
+# Amount of remaining bandwidth (in bytes) below which a new bandwidth credential is prepared
+# in the background. Set it to 0 to only obtain the credential once the bandwidth runs out.
+bandwidth_low_water_mark = {{ debug.bandwidth_low_water_mark }}
+
 "#
 }
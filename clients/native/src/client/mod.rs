@@ -32,12 +32,18 @@ use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::addressing::nodes::NodeIdentity;
 use nymsphinx::anonymous_replies::ReplySurb;
 use nymsphinx::receiver::ReconstructedMessage;
+use std::time::Duration;
+use task::ShutdownNotifier;
 
 use crate::client::config::{Config, SocketType};
 use crate::websocket;
 
 pub(crate) mod config;
 
+/// Maximum time the client is going to spend, after receiving SIGTERM or SIGINT, on sending
+/// the packets it has already queued up before exiting.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct NymClient {
     /// Client configuration options, including, among other things, packet sending rates,
     /// key filepaths, etc.
@@ -54,6 +60,10 @@ pub struct NymClient {
     /// Channel used for obtaining reconstructed messages received from the mix network.
     /// It is only available if the client started with the websocket listener disabled.
     receive_tx: Option<ReconstructedMessagesReceiver>,
+
+    /// Used for stopping the components of the client, so that all of the already queued
+    /// packets could get sent before it exits.
+    shutdown: ShutdownNotifier,
 }
 
 impl NymClient {
@@ -66,6 +76,7 @@ impl NymClient {
             key_manager,
             input_tx: None,
             receive_tx: None,
+            shutdown: ShutdownNotifier::new(),
         }
     }
 
@@ -195,6 +206,9 @@ impl NymClient {
         if self.config.get_base().get_testnet_mode() {
             gateway_client.set_testnet_mode(true)
         }
+        if let Some(low_water_mark) = self.config.get_base().get_bandwidth_low_water_mark() {
+            gateway_client.with_bandwidth_low_water_mark(low_water_mark)
+        }
         gateway_client
             .authenticate_and_start()
             .await
@@ -240,7 +254,7 @@ impl NymClient {
         gateway_client: GatewayClient,
     ) {
         info!("Starting mix traffic controller...");
-        MixTrafficController::new(mix_rx, gateway_client).start();
+        MixTrafficController::new(mix_rx, gateway_client).start(self.shutdown.subscribe());
     }
 
     fn start_websocket_listener(
@@ -299,19 +313,19 @@ impl NymClient {
             .expect("buffer controller seems to have somehow died!")
     }
 
-    /// blocking version of `start` method. Will run forever (or until SIGINT is sent)
+    /// blocking version of `start` method. Will run forever (or until SIGINT or SIGTERM is sent)
     pub async fn run_forever(&mut self) {
         self.start().await;
-        if let Err(e) = tokio::signal::ctrl_c().await {
-            error!(
-                "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
-                e
-            );
-        }
+        task::wait_for_signal().await;
 
         println!(
-            "Received SIGINT - the client will terminate now (threads are not yet nicely stopped, if you see stack traces that's alright)."
+            "The client is shutting down - sending already queued packets for up to {:?}...",
+            SHUTDOWN_DRAIN_TIMEOUT
         );
+        let shutdown = std::mem::take(&mut self.shutdown);
+        if shutdown.shutdown(SHUTDOWN_DRAIN_TIMEOUT).await {
+            info!("The client has been shut down gracefully")
+        }
     }
 
     pub async fn start(&mut self) {
@@ -87,6 +87,10 @@ impl Config {
     pub fn get_listening_port(&self) -> u16 {
         self.socks5.listening_port
     }
+
+    pub fn get_flow_control(&self) -> bool {
+        self.socks5.flow_control
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -97,6 +101,12 @@ pub struct Socks5 {
 
     /// The mix address of the provider to which all requests are going to be sent.
     provider_mix_address: String,
+
+    /// Indicates whether the connections should use credit-based flow control. It must only
+    /// be enabled if the service provider supports it, otherwise it's going to reject
+    /// all of the connection requests.
+    #[serde(default)]
+    flow_control: bool,
 }
 
 impl Socks5 {
@@ -104,6 +114,7 @@ impl Socks5 {
         Socks5 {
             listening_port: DEFAULT_SOCKS5_LISTENING_PORT,
             provider_mix_address: provider_mix_address.into(),
+            flow_control: false,
         }
     }
 }
@@ -113,6 +124,7 @@ impl Default for Socks5 {
         Socks5 {
             listening_port: DEFAULT_SOCKS5_LISTENING_PORT,
             provider_mix_address: "".into(),
+            flow_control: false,
         }
     }
 }
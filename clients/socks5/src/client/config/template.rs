@@ -89,6 +89,11 @@ provider_mix_address = '{{ socks5.provider_mix_address }}'
 # The port on which the client will be listening for incoming requests
 listening_port = {{ socks5.listening_port }}
 
+# Indicates whether the connections should use credit-based flow control.
+# It must only be enabled if the service provider supports it, otherwise
+# it's going to reject all of the connection requests.
+flow_control = {{ socks5.flow_control }}
+
 
 ##### logging configuration options #####
 
@@ -108,5 +113,9 @@ average_ack_delay = '{{ debug.average_ack_delay }}'
 loop_cover_traffic_average_delay = '{{ debug.loop_cover_traffic_average_delay }}'
 message_sending_average_delay = '{{ debug.message_sending_average_delay }}'
 
+# Amount of remaining bandwidth (in bytes) below which a new bandwidth credential is prepared
+# in the background. Set it to 0 to only obtain the credential once the bandwidth runs out.
+bandwidth_low_water_mark = {{ debug.bandwidth_low_water_mark }}
+
 "#
 }
@@ -28,6 +28,8 @@ use gateway_client::{
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::addressing::nodes::NodeIdentity;
+use std::time::Duration;
+use task::ShutdownNotifier;
 
 use crate::client::config::Config;
 use crate::socks::{
@@ -37,6 +39,10 @@ use crate::socks::{
 
 pub(crate) mod config;
 
+/// Maximum time the client is going to spend, after receiving SIGTERM or SIGINT, on sending
+/// the packets it has already queued up before exiting.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct NymClient {
     /// Client configuration options, including, among other things, packet sending rates,
     /// key filepaths, etc.
@@ -44,6 +50,10 @@ pub struct NymClient {
 
     /// KeyManager object containing smart pointers to all relevant keys used by the client.
     key_manager: KeyManager,
+
+    /// Used for stopping the components of the client, so that all of the already queued
+    /// packets could get sent before it exits.
+    shutdown: ShutdownNotifier,
 }
 
 impl NymClient {
@@ -54,6 +64,7 @@ impl NymClient {
         NymClient {
             config,
             key_manager,
+            shutdown: ShutdownNotifier::new(),
         }
     }
 
@@ -183,6 +194,9 @@ impl NymClient {
         if self.config.get_base().get_testnet_mode() {
             gateway_client.set_testnet_mode(true)
         }
+        if let Some(low_water_mark) = self.config.get_base().get_bandwidth_low_water_mark() {
+            gateway_client.with_bandwidth_low_water_mark(low_water_mark)
+        }
         gateway_client
             .authenticate_and_start()
             .await
@@ -228,7 +242,7 @@ impl NymClient {
         gateway_client: GatewayClient,
     ) {
         info!("Starting mix traffic controller...");
-        MixTrafficController::new(mix_rx, gateway_client).start();
+        MixTrafficController::new(mix_rx, gateway_client).start(self.shutdown.subscribe());
     }
 
     fn start_socks5_listener(
@@ -246,23 +260,24 @@ impl NymClient {
             authenticator,
             self.config.get_provider_mix_address(),
             self.as_mix_recipient(),
+            self.config.get_flow_control(),
         );
         tokio::spawn(async move { sphinx_socks.serve(msg_input, buffer_requester).await });
     }
 
-    /// blocking version of `start` method. Will run forever (or until SIGINT is sent)
+    /// blocking version of `start` method. Will run forever (or until SIGINT or SIGTERM is sent)
     pub async fn run_forever(&mut self) {
         self.start().await;
-        if let Err(e) = tokio::signal::ctrl_c().await {
-            error!(
-                "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
-                e
-            );
-        }
+        task::wait_for_signal().await;
 
         println!(
-            "Received SIGINT - the client will terminate now (threads are not yet nicely stopped, if you see stack traces that's alright)."
+            "The client is shutting down - sending already queued packets for up to {:?}...",
+            SHUTDOWN_DRAIN_TIMEOUT
         );
+        let shutdown = std::mem::take(&mut self.shutdown);
+        if shutdown.shutdown(SHUTDOWN_DRAIN_TIMEOUT).await {
+            info!("The client has been shut down gracefully")
+        }
     }
 
     pub async fn start(&mut self) {
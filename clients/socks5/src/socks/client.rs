@@ -14,6 +14,7 @@ use pin_project::pin_project;
 use proxy_helpers::connection_controller::{
     ConnectionReceiver, ControllerCommand, ControllerSender,
 };
+use proxy_helpers::flow_control::FlowControl;
 use proxy_helpers::proxy_runner::ProxyRunner;
 use rand::RngCore;
 use socks5_requests::{ConnectionId, RemoteAddress, Request};
@@ -140,6 +141,10 @@ pub(crate) struct SocksClient {
     service_provider: Recipient,
     self_address: Recipient,
     started_proxy: bool,
+
+    /// Indicates whether the service provider is known to support flow control.
+    /// Otherwise we must not send it any window updates nor wait for its own.
+    flow_control: bool,
 }
 
 impl Drop for SocksClient {
@@ -163,6 +168,7 @@ impl SocksClient {
         service_provider: Recipient,
         controller_sender: ControllerSender,
         self_address: Recipient,
+        flow_control: bool,
     ) -> Self {
         let connection_id = Self::generate_random();
         SocksClient {
@@ -176,6 +182,7 @@ impl SocksClient {
             service_provider,
             self_address,
             started_proxy: false,
+            flow_control,
         }
     }
 
@@ -223,13 +230,26 @@ impl SocksClient {
     }
 
     async fn send_connect_to_mixnet(&mut self, remote_address: RemoteAddress) {
-        let req = Request::new_connect(self.connection_id, remote_address, self.self_address);
+        let req = Request::new_connect(
+            self.connection_id,
+            remote_address,
+            self.self_address,
+            self.flow_control,
+            // the capabilities byte is only understood by the service providers that
+            // support flow control, so error responses are advertised alongside it
+            self.flow_control,
+        );
 
         let input_message = InputMessage::new_fresh(self.service_provider, req.into_bytes(), false);
         self.input_sender.unbounded_send(input_message).unwrap();
     }
 
-    async fn run_proxy(&mut self, conn_receiver: ConnectionReceiver, remote_proxy_target: String) {
+    async fn run_proxy(
+        &mut self,
+        conn_receiver: ConnectionReceiver,
+        flow_control: FlowControl,
+        remote_proxy_target: String,
+    ) {
         self.send_connect_to_mixnet(remote_proxy_target.clone())
             .await;
 
@@ -249,11 +269,18 @@ impl SocksClient {
             conn_receiver,
             input_sender,
             connection_id,
+            flow_control,
+        )
+        .run(
+            move |conn_id, read_data, socket_closed| {
+                let provider_request = Request::new_send(conn_id, read_data, socket_closed);
+                InputMessage::new_fresh(recipient, provider_request.into_bytes(), false)
+            },
+            move |conn_id, credit| {
+                let provider_request = Request::new_window_update(conn_id, credit);
+                InputMessage::new_fresh(recipient, provider_request.into_bytes(), false)
+            },
         )
-        .run(move |conn_id, read_data, socket_closed| {
-            let provider_request = Request::new_send(conn_id, read_data, socket_closed);
-            InputMessage::new_fresh(recipient, provider_request.into_bytes(), false)
-        })
         .await
         .into_inner();
         // recover stream from the proxy
@@ -269,6 +296,7 @@ impl SocksClient {
 
         // setup for receiving from the mixnet
         let (mix_sender, mix_receiver) = mpsc::unbounded();
+        let flow_control = FlowControl::negotiated(self.flow_control);
 
         match request.command {
             // Use the Proxy to connect to the specified addr/port
@@ -278,7 +306,11 @@ impl SocksClient {
 
                 self.started_proxy = true;
                 self.controller_sender
-                    .unbounded_send(ControllerCommand::Insert(self.connection_id, mix_sender))
+                    .unbounded_send(ControllerCommand::Insert(
+                        self.connection_id,
+                        mix_sender,
+                        flow_control.clone(),
+                    ))
                     .unwrap();
 
                 info!(
@@ -286,7 +318,8 @@ impl SocksClient {
                     remote_address.clone(),
                     self.connection_id
                 );
-                self.run_proxy(mix_receiver, remote_address.clone()).await;
+                self.run_proxy(mix_receiver, flow_control, remote_address.clone())
+                    .await;
                 info!(
                     "Proxy for {} is finished (id: {})",
                     remote_address, self.connection_id
@@ -5,7 +5,7 @@ use futures::StreamExt;
 use log::*;
 use nymsphinx::receiver::ReconstructedMessage;
 use proxy_helpers::connection_controller::{ControllerCommand, ControllerSender};
-use socks5_requests::Response;
+use socks5_requests::ResponseMessage;
 
 pub(crate) struct MixnetResponseListener {
     buffer_requester: ReceivedBufferRequestSender,
@@ -44,7 +44,7 @@ impl MixnetResponseListener {
             warn!("this message had a surb - we didn't do anything with it");
         }
 
-        let response = match Response::try_from_bytes(&raw_message) {
+        let response = match ResponseMessage::try_from_bytes(&raw_message) {
             Err(err) => {
                 warn!("failed to parse received response - {:?}", err);
                 return;
@@ -52,12 +52,25 @@ impl MixnetResponseListener {
             Ok(data) => data,
         };
 
+        let controller_command = match response {
+            ResponseMessage::Data(response) => {
+                ControllerCommand::Send(response.connection_id, response.data, response.is_closed)
+            }
+            ResponseMessage::WindowUpdate(connection_id, credit) => {
+                ControllerCommand::WindowUpdate(connection_id, credit)
+            }
+            ResponseMessage::Error(connection_id, kind) => {
+                warn!(
+                    "the service provider refused to handle connection {} - {}",
+                    connection_id, kind
+                );
+                // there's nothing more we're going to receive for it, so close the local socket
+                ControllerCommand::Send(connection_id, Vec::new(), true)
+            }
+        };
+
         self.controller_sender
-            .unbounded_send(ControllerCommand::Send(
-                response.connection_id,
-                response.data,
-                response.is_closed,
-            ))
+            .unbounded_send(controller_command)
             .unwrap();
     }
 
@@ -19,6 +19,7 @@ pub struct SphinxSocksServer {
     listening_address: SocketAddr,
     service_provider: Recipient,
     self_address: Recipient,
+    flow_control: bool,
 }
 
 impl SphinxSocksServer {
@@ -28,6 +29,7 @@ impl SphinxSocksServer {
         authenticator: Authenticator,
         service_provider: Recipient,
         self_address: Recipient,
+        flow_control: bool,
     ) -> Self {
         // hardcode ip as we (presumably) ONLY want to listen locally. If we change it, we can
         // just modify the config
@@ -38,6 +40,7 @@ impl SphinxSocksServer {
             listening_address: format!("{}:{}", ip, port).parse().unwrap(),
             service_provider,
             self_address,
+            flow_control,
         }
     }
 
@@ -75,6 +78,7 @@ impl SphinxSocksServer {
                     self.service_provider,
                     controller_sender.clone(),
                     self.self_address,
+                    self.flow_control,
                 );
 
                 tokio::spawn(async move {
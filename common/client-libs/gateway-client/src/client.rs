@@ -8,14 +8,16 @@ use crate::packet_router::PacketRouter;
 pub use crate::packet_router::{
     AcknowledgementReceiver, AcknowledgementSender, MixnetMessageReceiver, MixnetMessageSender,
 };
-use crate::socket_state::{PartiallyDelegated, SocketState};
+use crate::socket_state::{BandwidthUpdateHandler, PartiallyDelegated, SocketState};
 #[cfg(feature = "coconut")]
 use coconut_interface::Credential;
 #[cfg(not(feature = "coconut"))]
 use credentials::token::bandwidth::TokenCredential;
 use crypto::asymmetric::identity;
+use futures::channel::{mpsc, oneshot};
 use futures::{FutureExt, SinkExt, StreamExt};
 use gateway_requests::authentication::encrypted_address::EncryptedAddressBytes;
+use gateway_requests::bandwidth::sent_packet_cost;
 use gateway_requests::iv::IV;
 use gateway_requests::registration::handshake::{client_handshake, SharedKeys};
 use gateway_requests::{BinaryRequest, ClientControlRequest, ServerResponse};
@@ -27,8 +29,14 @@ use std::sync::Arc;
 use std::time::Duration;
 use tungstenite::protocol::Message;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::connection::{GatewayConnection, GatewayTransport};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::net::TcpStream;
 #[cfg(not(target_arch = "wasm32"))]
 use tokio_tungstenite::connect_async;
+#[cfg(not(target_arch = "wasm32"))]
+use url::Url;
 
 #[cfg(target_arch = "wasm32")]
 use fluvio_wasm_timer as wasm_timer;
@@ -38,6 +46,20 @@ use wasm_utils::websocket::JSWebsocket;
 const DEFAULT_RECONNECTION_ATTEMPTS: usize = 10;
 const DEFAULT_RECONNECTION_BACKOFF: Duration = Duration::from_secs(5);
 
+/// Callback notified about the outcome of every automatic bandwidth top-up, i.e. either
+/// the total bandwidth available after claiming the credential or the reason for the failure.
+pub type BandwidthTopUpCallback = Box<dyn Fn(Result<i64, &GatewayClientError>) + Send + Sync>;
+
+/// Callback notified about every update of the available bandwidth pushed by the gateway.
+pub type BandwidthUpdateCallback = Arc<dyn Fn(i64) + Send + Sync>;
+
+#[cfg(feature = "coconut")]
+type BandwidthCredential = Credential;
+#[cfg(not(feature = "coconut"))]
+type BandwidthCredential = TokenCredential;
+
+type PendingCredential = oneshot::Receiver<Result<BandwidthCredential, GatewayClientError>>;
+
 pub struct GatewayClient {
     authenticated: bool,
     testnet_mode: bool,
@@ -50,6 +72,25 @@ pub struct GatewayClient {
     packet_router: PacketRouter,
     response_timeout_duration: Duration,
     bandwidth_controller: Option<BandwidthController>,
+    #[cfg(not(target_arch = "wasm32"))]
+    transport: GatewayTransport,
+
+    // bandwidth top-up related variables
+    /// If specified, new bandwidth is claimed as soon as the remaining bandwidth drops below
+    /// this value rather than once it's insufficient for sending a packet.
+    bandwidth_low_water_mark: Option<i64>,
+    /// Set after a failed attempt to top up the bandwidth upon reaching the low-water mark,
+    /// so that it's not retried on every subsequent packet.
+    bandwidth_top_up_failed: bool,
+    /// Credential that is being prepared in the background after the remaining bandwidth
+    /// dropped below the low-water mark.
+    pending_credential: Option<PendingCredential>,
+    bandwidth_top_up_callback: Option<BandwidthTopUpCallback>,
+
+    // bandwidth updates pushed by the gateway while the stream is delegated to the mixnet listener
+    bandwidth_update_sender: mpsc::UnboundedSender<i64>,
+    bandwidth_update_receiver: mpsc::UnboundedReceiver<i64>,
+    bandwidth_update_callback: Option<BandwidthUpdateCallback>,
 
     // reconnection related variables
     /// Specifies whether client should try to reconnect to gateway on connection failure.
@@ -74,6 +115,8 @@ impl GatewayClient {
         response_timeout_duration: Duration,
         bandwidth_controller: Option<BandwidthController>,
     ) -> Self {
+        let (bandwidth_update_sender, bandwidth_update_receiver) = mpsc::unbounded();
+
         GatewayClient {
             authenticated: false,
             testnet_mode: false,
@@ -86,6 +129,15 @@ impl GatewayClient {
             packet_router: PacketRouter::new(ack_sender, mixnet_message_sender),
             response_timeout_duration,
             bandwidth_controller,
+            #[cfg(not(target_arch = "wasm32"))]
+            transport: Default::default(),
+            bandwidth_low_water_mark: None,
+            bandwidth_top_up_failed: false,
+            pending_credential: None,
+            bandwidth_top_up_callback: None,
+            bandwidth_update_sender,
+            bandwidth_update_receiver,
+            bandwidth_update_callback: None,
             should_reconnect_on_failure: true,
             reconnection_attempts: DEFAULT_RECONNECTION_ATTEMPTS,
             reconnection_backoff: DEFAULT_RECONNECTION_BACKOFF,
@@ -96,6 +148,12 @@ impl GatewayClient {
         self.testnet_mode = testnet_mode
     }
 
+    /// Sets the transport used for any subsequently established connection with the gateway.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_transport(&mut self, transport: GatewayTransport) {
+        self.transport = transport
+    }
+
     // TODO: later convert into proper builder methods
     pub fn with_reconnection_on_failure(&mut self, should_reconnect_on_failure: bool) {
         self.should_reconnect_on_failure = should_reconnect_on_failure
@@ -109,19 +167,34 @@ impl GatewayClient {
         self.reconnection_backoff = backoff
     }
 
+    /// Makes the client claim more bandwidth as soon as the remaining bandwidth drops below
+    /// the specified value, so that sending packets is not blocked on obtaining the credential.
+    pub fn with_bandwidth_low_water_mark(&mut self, low_water_mark: i64) {
+        self.bandwidth_low_water_mark = Some(low_water_mark)
+    }
+
+    pub fn with_bandwidth_top_up_callback(&mut self, callback: BandwidthTopUpCallback) {
+        self.bandwidth_top_up_callback = Some(callback)
+    }
+
+    /// Notifies the application about the available bandwidth every time the gateway pushes an update.
+    /// Note it only applies to any subsequently started mixnet listener.
+    pub fn with_bandwidth_update_callback(&mut self, callback: BandwidthUpdateCallback) {
+        self.bandwidth_update_callback = Some(callback)
+    }
+
     pub fn new_init(
         gateway_address: String,
         gateway_identity: identity::PublicKey,
         local_identity: Arc<identity::KeyPair>,
         response_timeout_duration: Duration,
     ) -> Self {
-        use futures::channel::mpsc;
-
         // note: this packet_router is completely invalid in normal circumstances, but "works"
         // perfectly fine here, because it's not meant to be used
         let (ack_tx, _) = mpsc::unbounded();
         let (mix_tx, _) = mpsc::unbounded();
         let packet_router = PacketRouter::new(ack_tx, mix_tx);
+        let (bandwidth_update_sender, bandwidth_update_receiver) = mpsc::unbounded();
 
         GatewayClient {
             authenticated: false,
@@ -135,6 +208,15 @@ impl GatewayClient {
             packet_router,
             response_timeout_duration,
             bandwidth_controller: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            transport: Default::default(),
+            bandwidth_low_water_mark: None,
+            bandwidth_top_up_failed: false,
+            pending_credential: None,
+            bandwidth_top_up_callback: None,
+            bandwidth_update_sender,
+            bandwidth_update_receiver,
+            bandwidth_update_callback: None,
             should_reconnect_on_failure: false,
             reconnection_attempts: DEFAULT_RECONNECTION_ATTEMPTS,
             reconnection_backoff: DEFAULT_RECONNECTION_BACKOFF,
@@ -152,7 +234,7 @@ impl GatewayClient {
     #[cfg(not(target_arch = "wasm32"))]
     async fn _close_connection(&mut self) -> Result<(), GatewayClientError> {
         match std::mem::replace(&mut self.connection, SocketState::NotConnected) {
-            SocketState::Available(mut socket) => Ok((*socket).close(None).await?),
+            SocketState::Available(mut socket) => Ok((*socket).close().await?),
             SocketState::PartiallyDelegated(_) => {
                 unreachable!("this branch should have never been reached!")
             }
@@ -184,12 +266,30 @@ impl GatewayClient {
 
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn establish_connection(&mut self) -> Result<(), GatewayClientError> {
-        let ws_stream = match connect_async(&self.gateway_address).await {
-            Ok((ws_stream, _)) => ws_stream,
-            Err(e) => return Err(GatewayClientError::NetworkError(e)),
+        let connection = match self.transport {
+            GatewayTransport::WebSocket => match connect_async(&self.gateway_address).await {
+                Ok((ws_stream, _)) => GatewayConnection::WebSocket(ws_stream),
+                Err(e) => return Err(GatewayClientError::NetworkError(e)),
+            },
+            GatewayTransport::Tcp { port } => {
+                let gateway_url = Url::parse(&self.gateway_address)
+                    .map_err(|err| GatewayClientError::InvalidURL(err.to_string()))?;
+                let host = gateway_url.host_str().ok_or_else(|| {
+                    GatewayClientError::InvalidURL(format!(
+                        "{} does not contain a host",
+                        self.gateway_address
+                    ))
+                })?;
+                // ipv6 hosts are kept in square brackets in the url
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                match TcpStream::connect((host, port)).await {
+                    Ok(stream) => GatewayConnection::new_tcp(stream),
+                    Err(e) => return Err(GatewayClientError::NetworkError(e.into())),
+                }
+            }
         };
 
-        self.connection = SocketState::Available(Box::new(ws_stream));
+        self.connection = SocketState::Available(Box::new(connection));
         Ok(())
     }
 
@@ -284,7 +384,16 @@ impl GatewayClient {
                             self.packet_router.route_received(vec![bin_msg]);
                         }
                         Message::Text(txt_msg) => {
-                            break ServerResponse::try_from(txt_msg).map_err(|_| GatewayClientError::MalformedResponse);
+                            match ServerResponse::try_from(txt_msg) {
+                                // pushed by the gateway on its own accord - it's not the response we're after
+                                Ok(ServerResponse::BandwidthUpdate { available_total }) => {
+                                    self.bandwidth_remaining = available_total;
+                                    if let Some(callback) = &self.bandwidth_update_callback {
+                                        callback(available_total)
+                                    }
+                                }
+                                res => break res.map_err(|_| GatewayClientError::MalformedResponse),
+                            }
                         }
                         _ => (),
                     }
@@ -379,6 +488,15 @@ impl GatewayClient {
         }
     }
 
+    fn warn_about_dropped_messages(&self, dropped_messages: u64) {
+        if dropped_messages > 0 {
+            warn!(
+                "The gateway has dropped {} messages that were sent to us while we were offline (they were either too old or we have exceeded our storage quota)",
+                dropped_messages
+            )
+        }
+    }
+
     async fn register(&mut self) -> Result<(), GatewayClientError> {
         if !self.connection.is_established() {
             return Err(GatewayClientError::ConnectionNotEstablished);
@@ -402,7 +520,13 @@ impl GatewayClient {
             _ => unreachable!(),
         }?;
         self.authenticated = match self.read_control_response().await? {
-            ServerResponse::Register { status } => Ok(status),
+            ServerResponse::Register {
+                status,
+                dropped_messages,
+            } => {
+                self.warn_about_dropped_messages(dropped_messages);
+                Ok(status)
+            }
             ServerResponse::Error { message } => Err(GatewayClientError::GatewayError(message)),
             _ => Err(GatewayClientError::UnexpectedResponse),
         }?;
@@ -440,22 +564,138 @@ impl GatewayClient {
         let encrypted_address = EncryptedAddressBytes::new(&self_address, shared_key, &iv);
 
         let msg =
-            ClientControlRequest::new_authenticate(self_address, encrypted_address, iv).into();
+            ClientControlRequest::new_authenticate(self_address, encrypted_address, iv, true, true)
+                .into();
 
-        match self.send_websocket_message(msg).await? {
+        let last_stored_message_id = match self.send_websocket_message(msg).await? {
             ServerResponse::Authenticate {
                 status,
                 bandwidth_remaining,
+                dropped_messages,
+                last_stored_message_id,
             } => {
                 self.authenticated = status;
                 self.bandwidth_remaining = bandwidth_remaining;
-                Ok(())
+                self.warn_about_dropped_messages(dropped_messages);
+                last_stored_message_id
+            }
+            ServerResponse::Error { message } => {
+                return Err(GatewayClientError::GatewayError(message))
             }
+            _ => return Err(GatewayClientError::UnexpectedResponse),
+        };
+
+        // by now all of the stored messages went through the packet router
+        if let Some(up_to_id) = last_stored_message_id {
+            if let Err(err) = self.acknowledge_stored_messages(up_to_id).await {
+                // it's not a critical failure - we might just receive the same messages again
+                // the next time we connect
+                warn!("Failed to acknowledge stored messages - {}", err)
+            }
+        }
+        Ok(())
+    }
+
+    async fn acknowledge_stored_messages(
+        &mut self,
+        up_to_id: i64,
+    ) -> Result<(), GatewayClientError> {
+        let msg = ClientControlRequest::new_acknowledge_stored_messages(up_to_id).into();
+        match self.send_websocket_message(msg).await? {
+            ServerResponse::StoredMessagesAcknowledged => Ok(()),
             ServerResponse::Error { message } => Err(GatewayClientError::GatewayError(message)),
             _ => Err(GatewayClientError::UnexpectedResponse),
         }
     }
 
+    /// Performs a fresh handshake with the gateway over the already authenticated connection
+    /// in order to replace the shared keys, for example if the current ones might have got compromised.
+    /// Returns the new keys, which should be persisted in place of the previous ones.
+    pub async fn rekey(&mut self) -> Result<Arc<SharedKeys>, GatewayClientError> {
+        if !self.authenticated {
+            return Err(GatewayClientError::NotAuthenticated);
+        }
+
+        let should_restart_mixnet_listener = if self.connection.is_partially_delegated() {
+            self.recover_socket_connection().await?;
+            true
+        } else {
+            false
+        };
+
+        // it's fine to instantiate it here as it's only used once
+        let mut rng = OsRng;
+
+        let handshake_result = match &mut self.connection {
+            SocketState::Available(ws_stream) => client_handshake(
+                &mut rng,
+                ws_stream,
+                self.local_identity.as_ref(),
+                self.gateway_identity,
+            )
+            .await
+            .map_err(GatewayClientError::RegistrationFailure),
+            SocketState::NotConnected => return Err(GatewayClientError::ConnectionNotEstablished),
+            _ => return Err(GatewayClientError::ConnectionInInvalidState),
+        };
+
+        // the gateway concludes every re-key attempt with a control response,
+        // even if the handshake itself has failed
+        let response = self.read_control_response().await;
+        let shared_key = handshake_result?;
+        match response? {
+            ServerResponse::Rekey { status: true } => {
+                self.shared_key = Some(Arc::new(shared_key));
+            }
+            ServerResponse::Rekey { status: false } => {
+                return Err(GatewayClientError::AuthenticationFailure)
+            }
+            ServerResponse::Error { message } => {
+                return Err(GatewayClientError::GatewayError(message))
+            }
+            _ => return Err(GatewayClientError::UnexpectedResponse),
+        }
+
+        // the listener has to be restarted in order to use the new keys
+        if should_restart_mixnet_listener {
+            self.start_listening_for_mixnet_messages()?;
+        }
+
+        // we have just set the new key
+        Ok(Arc::clone(self.shared_key.as_ref().unwrap()))
+    }
+
+    /// Removes all data the gateway holds for this client, i.e. its shared keys, stored messages
+    /// and remaining bandwidth. Afterwards the connection is closed and the client would have
+    /// to register again in order to use the gateway.
+    pub async fn unregister(&mut self) -> Result<(), GatewayClientError> {
+        if !self.authenticated {
+            return Err(GatewayClientError::NotAuthenticated);
+        }
+
+        let msg = ClientControlRequest::Unregister.into();
+        match self.send_websocket_message(msg).await? {
+            ServerResponse::Unregistered => (),
+            ServerResponse::Error { message } => {
+                return Err(GatewayClientError::GatewayError(message))
+            }
+            _ => return Err(GatewayClientError::UnexpectedResponse),
+        }
+
+        self.authenticated = false;
+        self.shared_key = None;
+        self.bandwidth_remaining = 0;
+
+        // the gateway is going to close the connection on its own
+        if let Err(err) = self.close_connection().await {
+            debug!(
+                "Failed to cleanly close the connection after unregistering - {}",
+                err
+            )
+        }
+        Ok(())
+    }
+
     /// Helper method to either call register or authenticate based on self.shared_key value
     pub async fn perform_initial_authentication(
         &mut self,
@@ -531,6 +771,16 @@ impl GatewayClient {
         Ok(())
     }
 
+    async fn claim_credential(
+        &mut self,
+        credential: BandwidthCredential,
+    ) -> Result<(), GatewayClientError> {
+        #[cfg(feature = "coconut")]
+        return self.claim_coconut_bandwidth(credential).await;
+        #[cfg(not(feature = "coconut"))]
+        return self.claim_token_bandwidth(credential).await;
+    }
+
     pub async fn claim_bandwidth(&mut self) -> Result<(), GatewayClientError> {
         if !self.authenticated {
             return Err(GatewayClientError::NotAuthenticated);
@@ -542,38 +792,154 @@ impl GatewayClient {
             return Err(GatewayClientError::NoBandwidthControllerAvailable);
         }
 
-        warn!("Not enough bandwidth. Trying to get more bandwidth, this might take a while");
         if self.testnet_mode {
             info!("The client is running in testnet mode - attempting to claim bandwidth without a credential");
-            return self.try_claim_testnet_bandwidth().await;
+            self.try_claim_testnet_bandwidth().await?;
+        } else {
+            let credential = prepare_credential(
+                self.bandwidth_controller.as_ref().unwrap(),
+                self.gateway_identity,
+            )
+            .await?;
+            self.claim_credential(credential).await?;
         }
 
-        #[cfg(feature = "coconut")]
-        let credential = self
-            .bandwidth_controller
-            .as_ref()
-            .unwrap()
-            .prepare_coconut_credential()
-            .await?;
-        #[cfg(not(feature = "coconut"))]
-        let credential = self
-            .bandwidth_controller
-            .as_ref()
-            .unwrap()
-            .prepare_token_credential(self.gateway_identity)
-            .await?;
+        self.bandwidth_top_up_failed = false;
+        Ok(())
+    }
 
-        #[cfg(feature = "coconut")]
-        return self.claim_coconut_bandwidth(credential).await;
-        #[cfg(not(feature = "coconut"))]
-        return self.claim_token_bandwidth(credential).await;
+    /// Records the outcome of a bandwidth top-up and reports it to the top-up callback,
+    /// if one was provided.
+    fn report_top_up(
+        &mut self,
+        res: Result<(), GatewayClientError>,
+    ) -> Result<(), GatewayClientError> {
+        self.bandwidth_top_up_failed = res.is_err();
+        if let Some(callback) = &self.bandwidth_top_up_callback {
+            match &res {
+                Ok(_) => callback(Ok(self.bandwidth_remaining)),
+                Err(err) => callback(Err(err)),
+            }
+        }
+        res
+    }
+
+    /// Claims more bandwidth, using the credential being prepared in the background if there is one.
+    async fn top_up_bandwidth(&mut self) -> Result<(), GatewayClientError> {
+        // there's no point in obtaining yet another credential if one is already on its way
+        if let Some(pending) = self.pending_credential.take() {
+            return self.claim_prepared_credential(pending.await).await;
+        }
+        let res = self.claim_bandwidth().await;
+        self.report_top_up(res)
+    }
+
+    async fn claim_prepared_credential(
+        &mut self,
+        prepared: Result<Result<BandwidthCredential, GatewayClientError>, oneshot::Canceled>,
+    ) -> Result<(), GatewayClientError> {
+        let res = match prepared {
+            Ok(Ok(credential)) => self.claim_credential(credential).await,
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(GatewayClientError::CredentialPreparationInterrupted),
+        };
+        self.report_top_up(res)
+    }
+
+    /// Claims the bandwidth with the credential prepared in the background, but only if it's
+    /// already available, so that sending packets is never blocked on preparing it.
+    async fn claim_ready_credential(&mut self) {
+        let prepared = match self
+            .pending_credential
+            .as_mut()
+            .map(|pending| pending.try_recv())
+        {
+            None | Some(Ok(None)) => return,
+            Some(Ok(Some(prepared))) => Ok(prepared),
+            Some(Err(canceled)) => Err(canceled),
+        };
+        self.pending_credential = None;
+
+        // the failure is only reported to the callback. Another attempt will be made once
+        // the bandwidth is insufficient for sending a packet.
+        if let Err(err) = self.claim_prepared_credential(prepared).await {
+            warn!("Failed to top up the bandwidth - {}", err);
+        }
+    }
+
+    fn start_credential_preparation(&mut self) {
+        let bandwidth_controller = match &self.bandwidth_controller {
+            Some(bandwidth_controller) => bandwidth_controller.clone(),
+            None => return,
+        };
+        let gateway_identity = self.gateway_identity;
+        let (credential_sender, credential_receiver) = oneshot::channel();
+
+        let preparation = async move {
+            let res = prepare_credential(&bandwidth_controller, gateway_identity).await;
+            // the client might have been dropped in the meantime, in which case there's
+            // nobody left to claim the credential
+            let _ = credential_sender.send(res);
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        tokio::spawn(preparation);
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(preparation);
+
+        self.pending_credential = Some(credential_receiver);
+    }
+
+    /// Accounts for the bandwidth consumed by the sent packets and, if the remaining bandwidth
+    /// dropped below the low-water mark, starts preparing a new credential in the background.
+    /// The credential is claimed on one of the subsequent sends once it's ready.
+    fn record_consumed_bandwidth(&mut self, consumed: i64) {
+        self.bandwidth_remaining -= consumed;
+
+        let low_water_mark = match self.bandwidth_low_water_mark {
+            Some(low_water_mark) => low_water_mark,
+            None => return,
+        };
+        if self.bandwidth_remaining >= low_water_mark {
+            // the bandwidth got replenished in the meantime, so any future drop below the mark
+            // should be acted upon again
+            self.bandwidth_top_up_failed = false;
+            return;
+        }
+
+        // in testnet mode there's no credential to prepare, and claiming the free bandwidth
+        // is cheap enough to be done once it's actually insufficient
+        if self.bandwidth_top_up_failed
+            || self.pending_credential.is_some()
+            || self.testnet_mode
+            || self.bandwidth_controller.is_none()
+        {
+            return;
+        }
+
+        info!(
+            "Remaining bandwidth ({}) dropped below the low-water mark ({}). Preparing a new bandwidth credential in the background",
+            self.bandwidth_remaining, low_water_mark
+        );
+        self.start_credential_preparation();
+    }
+
+    /// Applies the most recent bandwidth update pushed by the gateway while the stream was delegated.
+    fn apply_pushed_bandwidth_updates(&mut self) {
+        let mut latest_update = None;
+        while let Ok(Some(available_total)) = self.bandwidth_update_receiver.try_next() {
+            latest_update = Some(available_total)
+        }
+        if let Some(available_total) = latest_update {
+            self.bandwidth_remaining = available_total
+        }
     }
 
     fn estimate_required_bandwidth(&self, packets: &[MixPacket]) -> i64 {
         packets
             .iter()
-            .map(|packet| packet.sphinx_packet().len())
-            .sum::<usize>() as i64
+            .map(|packet| sent_packet_cost(packet.sphinx_packet().len()))
+            .sum()
     }
 
     pub async fn batch_send_mix_packets(
@@ -583,13 +949,17 @@ impl GatewayClient {
         if !self.authenticated {
             return Err(GatewayClientError::NotAuthenticated);
         }
-        if self.estimate_required_bandwidth(&packets) > self.bandwidth_remaining {
+        self.apply_pushed_bandwidth_updates();
+        self.claim_ready_credential().await;
+        let required_bandwidth = self.estimate_required_bandwidth(&packets);
+        if required_bandwidth > self.bandwidth_remaining {
             // Try to claim more bandwidth first, and return an error only if that is still not
             // enough (the current granularity for bandwidth should be sufficient)
-            self.claim_bandwidth().await?;
-            if self.estimate_required_bandwidth(&packets) > self.bandwidth_remaining {
+            warn!("Not enough bandwidth. Trying to get more bandwidth, this might take a while");
+            self.top_up_bandwidth().await?;
+            if required_bandwidth > self.bandwidth_remaining {
                 return Err(GatewayClientError::NotEnoughBandwidth(
-                    self.estimate_required_bandwidth(&packets),
+                    required_bandwidth,
                     self.bandwidth_remaining,
                 ));
             }
@@ -619,6 +989,7 @@ impl GatewayClient {
                 Err(err)
             }
         } else {
+            self.record_consumed_bandwidth(required_bandwidth);
             Ok(())
         }
     }
@@ -658,13 +1029,17 @@ impl GatewayClient {
         if !self.authenticated {
             return Err(GatewayClientError::NotAuthenticated);
         }
-        if (mix_packet.sphinx_packet().len() as i64) > self.bandwidth_remaining {
+        self.apply_pushed_bandwidth_updates();
+        self.claim_ready_credential().await;
+        let required_bandwidth = sent_packet_cost(mix_packet.sphinx_packet().len());
+        if required_bandwidth > self.bandwidth_remaining {
             // Try to claim more bandwidth first, and return an error only if that is still not
             // enough
-            self.claim_bandwidth().await?;
-            if (mix_packet.sphinx_packet().len() as i64) > self.bandwidth_remaining {
+            warn!("Not enough bandwidth. Trying to get more bandwidth, this might take a while");
+            self.top_up_bandwidth().await?;
+            if required_bandwidth > self.bandwidth_remaining {
                 return Err(GatewayClientError::NotEnoughBandwidth(
-                    mix_packet.sphinx_packet().len() as i64,
+                    required_bandwidth,
                     self.bandwidth_remaining,
                 ));
             }
@@ -679,7 +1054,9 @@ impl GatewayClient {
                 .as_ref()
                 .expect("no shared key present even though we're authenticated!"),
         );
-        self.send_with_reconnection_on_failure(msg).await
+        self.send_with_reconnection_on_failure(msg).await?;
+        self.record_consumed_bandwidth(required_bandwidth);
+        Ok(())
     }
 
     async fn recover_socket_connection(&mut self) -> Result<(), GatewayClientError> {
@@ -722,6 +1099,10 @@ impl GatewayClient {
                                 .as_ref()
                                 .expect("no shared key present even though we're authenticated!"),
                         ),
+                        BandwidthUpdateHandler {
+                            client_sender: self.bandwidth_update_sender.clone(),
+                            callback: self.bandwidth_update_callback.clone(),
+                        },
                     )
                 }
                 _ => unreachable!(),
@@ -743,3 +1124,105 @@ impl GatewayClient {
         Ok(shared_key)
     }
 }
+
+#[cfg_attr(feature = "coconut", allow(unused_variables))]
+async fn prepare_credential(
+    bandwidth_controller: &BandwidthController,
+    gateway_identity: identity::PublicKey,
+) -> Result<BandwidthCredential, GatewayClientError> {
+    #[cfg(feature = "coconut")]
+    return bandwidth_controller.prepare_coconut_credential().await;
+    #[cfg(not(feature = "coconut"))]
+    return bandwidth_controller
+        .prepare_token_credential(gateway_identity)
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn test_client() -> GatewayClient {
+        let mut rng = OsRng;
+        let gateway_identity = *identity::KeyPair::new(&mut rng).public_key();
+        let mut client = GatewayClient::new_init(
+            "ws://localhost:9000".to_string(),
+            gateway_identity,
+            Arc::new(identity::KeyPair::new(&mut rng)),
+            Duration::from_secs(1),
+        );
+        client.with_bandwidth_low_water_mark(100);
+        client
+    }
+
+    #[test]
+    fn failed_top_up_is_retried_after_bandwidth_is_replenished() {
+        let mut client = test_client();
+        client.bandwidth_remaining = 150;
+        client.bandwidth_top_up_failed = true;
+
+        client.record_consumed_bandwidth(10);
+        assert!(!client.bandwidth_top_up_failed);
+
+        client.bandwidth_top_up_failed = true;
+        client.record_consumed_bandwidth(100);
+        assert_eq!(client.bandwidth_remaining, 40);
+        assert!(client.bandwidth_top_up_failed);
+    }
+
+    #[test]
+    fn credential_preparation_is_not_started_without_bandwidth_controller() {
+        let mut client = test_client();
+        client.bandwidth_remaining = 150;
+
+        client.record_consumed_bandwidth(100);
+        assert!(client.pending_credential.is_none());
+    }
+
+    #[tokio::test]
+    async fn sending_is_not_blocked_on_credential_preparation() {
+        let mut client = test_client();
+        let (_credential_sender, credential_receiver) = oneshot::channel();
+        client.pending_credential = Some(credential_receiver);
+
+        client.claim_ready_credential().await;
+        assert!(client.pending_credential.is_some());
+        assert!(!client.bandwidth_top_up_failed);
+    }
+
+    #[tokio::test]
+    async fn failed_credential_preparation_is_reported() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = Arc::clone(&reported);
+
+        let mut client = test_client();
+        client.with_bandwidth_top_up_callback(Box::new(move |res| {
+            reported_clone.lock().unwrap().push(res.is_ok())
+        }));
+
+        let (credential_sender, credential_receiver) = oneshot::channel();
+        client.pending_credential = Some(credential_receiver);
+        credential_sender
+            .send(Err(GatewayClientError::NoBandwidthControllerAvailable))
+            .unwrap();
+        client.claim_ready_credential().await;
+        assert!(client.pending_credential.is_none());
+        assert!(client.bandwidth_top_up_failed);
+
+        // failed preparation is not retried until the bandwidth is replenished
+        client.bandwidth_remaining = 50;
+        client.record_consumed_bandwidth(10);
+        assert!(client.pending_credential.is_none());
+
+        // and neither is one that got interrupted
+        let (credential_sender, credential_receiver) = oneshot::channel();
+        client.pending_credential = Some(credential_receiver);
+        drop(credential_sender);
+        client.claim_ready_credential().await;
+        assert!(client.pending_credential.is_none());
+        assert!(client.bandwidth_top_up_failed);
+
+        assert_eq!(*reported.lock().unwrap(), vec![false, false]);
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use futures::{Sink, SinkExt, Stream};
+use gateway_requests::codec::GatewayCodec;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio_util::codec::Framed;
+use tungstenite::{protocol::Message, Error as WsError};
+
+/// Transport used for communicating with the gateway.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GatewayTransport {
+    /// Websocket connection established with the gateway address.
+    WebSocket,
+
+    /// Raw TCP connection, using length-prefixed framing, established with the host of the gateway
+    /// address on the specified port.
+    Tcp { port: u16 },
+}
+
+impl Default for GatewayTransport {
+    fn default() -> Self {
+        GatewayTransport::WebSocket
+    }
+}
+
+/// Connection to the gateway, regardless of the used transport. Both variants exchange exactly
+/// the same messages, so the rest of the client does not need to care which one is in use.
+pub(crate) enum GatewayConnection {
+    WebSocket(WebSocketStream<MaybeTlsStream<TcpStream>>),
+    Tcp(Framed<TcpStream, GatewayCodec>),
+}
+
+impl GatewayConnection {
+    pub(crate) fn new_tcp(stream: TcpStream) -> Self {
+        GatewayConnection::Tcp(Framed::new(stream, GatewayCodec))
+    }
+
+    /// Notifies the gateway we're about to close the connection and closes the underlying socket.
+    pub(crate) async fn close(&mut self) -> Result<(), WsError> {
+        match self {
+            GatewayConnection::WebSocket(ws_stream) => ws_stream.close(None).await,
+            GatewayConnection::Tcp(framed_stream) => {
+                framed_stream.send(Message::Close(None)).await?;
+                SinkExt::close(framed_stream).await
+            }
+        }
+    }
+}
+
+impl Stream for GatewayConnection {
+    type Item = Result<Message, WsError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_next(cx),
+            GatewayConnection::Tcp(framed_stream) => Pin::new(framed_stream).poll_next(cx),
+        }
+    }
+}
+
+impl Sink<Message> for GatewayConnection {
+    type Error = WsError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_ready(cx),
+            GatewayConnection::Tcp(framed_stream) => Pin::new(framed_stream).poll_ready(cx),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).start_send(item),
+            GatewayConnection::Tcp(framed_stream) => Pin::new(framed_stream).start_send(item),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_flush(cx),
+            GatewayConnection::Tcp(framed_stream) => Pin::new(framed_stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_close(cx),
+            GatewayConnection::Tcp(framed_stream) => Pin::new(framed_stream).poll_close(cx),
+        }
+    }
+}
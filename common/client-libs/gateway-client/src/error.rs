@@ -79,6 +79,9 @@ pub enum GatewayClientError {
 
     #[error("Timed out")]
     Timeout,
+
+    #[error("Preparation of the bandwidth credential got interrupted")]
+    CredentialPreparationInterrupted,
 }
 
 impl GatewayClientError {
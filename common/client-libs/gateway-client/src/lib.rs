@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::error::GatewayClientError;
-pub use client::GatewayClient;
+pub use client::{BandwidthTopUpCallback, BandwidthUpdateCallback, GatewayClient};
+#[cfg(not(target_arch = "wasm32"))]
+pub use connection::GatewayTransport;
 pub use packet_router::{
     AcknowledgementReceiver, AcknowledgementSender, MixnetMessageReceiver, MixnetMessageSender,
 };
@@ -10,6 +12,8 @@ use tungstenite::{protocol::Message, Error as WsError};
 
 pub mod bandwidth;
 pub mod client;
+#[cfg(not(target_arch = "wasm32"))]
+mod connection;
 pub mod error;
 pub mod packet_router;
 pub mod socket_state;
@@ -4,19 +4,19 @@
 use crate::cleanup_socket_message;
 use crate::error::GatewayClientError;
 use crate::packet_router::PacketRouter;
-use futures::channel::oneshot;
+use crate::BandwidthUpdateCallback;
+use futures::channel::{mpsc, oneshot};
 use futures::stream::{SplitSink, SplitStream};
 use futures::{FutureExt, SinkExt, StreamExt};
 use gateway_requests::registration::handshake::SharedKeys;
-use gateway_requests::BinaryResponse;
+use gateway_requests::{BinaryResponse, ServerResponse};
 use log::*;
+use std::convert::TryFrom;
 use std::sync::Arc;
 use tungstenite::Message;
 
 #[cfg(not(target_arch = "wasm32"))]
-use tokio::net::TcpStream;
-#[cfg(not(target_arch = "wasm32"))]
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use crate::connection::GatewayConnection;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen_futures;
@@ -26,7 +26,7 @@ use wasm_utils::websocket::JSWebsocket;
 // type alias for not having to type the whole thing every single time (and now it makes it easier
 // to use different types based on compilation target)
 #[cfg(not(target_arch = "wasm32"))]
-type WsConn = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsConn = GatewayConnection;
 
 #[cfg(target_arch = "wasm32")]
 type WsConn = JSWebsocket;
@@ -36,6 +36,24 @@ type WsConn = JSWebsocket;
 
 type SplitStreamReceiver = oneshot::Receiver<Result<SplitStream<WsConn>, GatewayClientError>>;
 
+/// Handles the bandwidth updates pushed by the gateway while the stream is delegated, by passing
+/// them back to the client and notifying the application, if it asked for it.
+#[derive(Clone)]
+pub(crate) struct BandwidthUpdateHandler {
+    pub(crate) client_sender: mpsc::UnboundedSender<i64>,
+    pub(crate) callback: Option<BandwidthUpdateCallback>,
+}
+
+impl BandwidthUpdateHandler {
+    pub(crate) fn handle_update(&self, available_total: i64) {
+        // the client might have just been dropped, in which case nobody cares about the update
+        let _ = self.client_sender.unbounded_send(available_total);
+        if let Some(callback) = &self.callback {
+            callback(available_total)
+        }
+    }
+}
+
 pub(crate) struct PartiallyDelegated {
     sink_half: SplitSink<WsConn, Message>,
     delegated_stream: (SplitStreamReceiver, oneshot::Sender<()>),
@@ -46,6 +64,7 @@ impl PartiallyDelegated {
         ws_msg: Message,
         packet_router: &PacketRouter,
         shared_key: &SharedKeys,
+        bandwidth_update_handler: &BandwidthUpdateHandler,
     ) {
         match ws_msg {
             Message::Binary(bin_msg) => {
@@ -74,10 +93,16 @@ impl PartiallyDelegated {
             // This would also require NOT discarding any text responses here.
 
             // TODO: those can return the "send confirmations" - perhaps it should be somehow worked around?
-            Message::Text(text) => debug!(
-                "received a text message - probably a response to some previous query! - {}",
-                text
-            ),
+            Message::Text(text) => match ServerResponse::try_from(text) {
+                Ok(ServerResponse::BandwidthUpdate { available_total }) => {
+                    bandwidth_update_handler.handle_update(available_total)
+                }
+                Ok(response) => debug!(
+                    "received a text message - probably a response to some previous query! - {:?}",
+                    response
+                ),
+                Err(err) => warn!("received a malformed text message - {}", err),
+            },
             _ => (),
         };
     }
@@ -86,6 +111,7 @@ impl PartiallyDelegated {
         conn: WsConn,
         packet_router: PacketRouter,
         shared_key: Arc<SharedKeys>,
+        bandwidth_update_handler: BandwidthUpdateHandler,
     ) -> Self {
         // when called for, it NEEDS TO yield back the stream so that we could merge it and
         // read control request responses.
@@ -108,7 +134,7 @@ impl PartiallyDelegated {
                             Err(err) => break Err(err),
                             Ok(msg) => msg
                         };
-                        Self::route_socket_message(ws_msg, &packet_router, shared_key.as_ref());
+                        Self::route_socket_message(ws_msg, &packet_router, shared_key.as_ref(), &bandwidth_update_handler);
                     }
                 };
             };
@@ -14,10 +14,13 @@ use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use task::ShutdownListener;
 use tokio::net::TcpStream;
+use tokio::sync::watch;
 use tokio::time::sleep;
 use tokio_util::codec::Framed;
 
+#[derive(Clone)]
 pub struct Config {
     initial_reconnection_backoff: Duration,
     maximum_reconnection_backoff: Duration,
@@ -41,6 +44,24 @@ impl Config {
     }
 }
 
+/// Implemented by the configs of the nodes forwarding packets into the mix network, so that all of
+/// them create their clients out of the same settings.
+pub trait PacketForwardingConfig {
+    fn get_packet_forwarding_initial_backoff(&self) -> Duration;
+    fn get_packet_forwarding_maximum_backoff(&self) -> Duration;
+    fn get_initial_connection_timeout(&self) -> Duration;
+    fn get_maximum_connection_buffer_size(&self) -> usize;
+
+    fn mixnet_client_config(&self) -> Config {
+        Config::new(
+            self.get_packet_forwarding_initial_backoff(),
+            self.get_packet_forwarding_maximum_backoff(),
+            self.get_initial_connection_timeout(),
+            self.get_maximum_connection_buffer_size(),
+        )
+    }
+}
+
 pub trait SendWithoutResponse {
     // Without response in this context means we will not listen for anything we might get back (not
     // that we should get anything), including any possible io errors
@@ -54,7 +75,8 @@ pub trait SendWithoutResponse {
 
 pub struct Client {
     conn_new: HashMap<NymNodeRoutingAddress, ConnectionSender>,
-    config: Config,
+    config: watch::Receiver<Config>,
+    shutdown: Option<ShutdownListener>,
 }
 
 struct ConnectionSender {
@@ -73,12 +95,28 @@ impl ConnectionSender {
 
 impl Client {
     pub fn new(config: Config) -> Client {
+        // the sender is dropped straight away, so the initial config is never going to change
+        let (_, config) = watch::channel(config);
+        Self::new_with_config_updates(config)
+    }
+
+    /// Creates a client whose config can be changed while it is running. New values only apply
+    /// to the connections (re-)established after the change.
+    pub fn new_with_config_updates(config: watch::Receiver<Config>) -> Client {
         Client {
             conn_new: HashMap::new(),
             config,
+            shutdown: None,
         }
     }
 
+    /// Makes the shutdown wait until all of the packets handed over to the client have been
+    /// written to their connections, which happens once the client itself gets dropped.
+    /// Connections that are still waiting to be re-established are abandoned instead.
+    pub fn with_shutdown(&mut self, shutdown: ShutdownListener) {
+        self.shutdown = Some(shutdown);
+    }
+
     async fn manage_connection(
         address: SocketAddr,
         receiver: mpsc::Receiver<FramedSphinxPacket>,
@@ -137,15 +175,16 @@ impl Client {
             // limit of tokio delay of about 2 years.
             // let's ensure our delay is always on a sane side of being maximum 1 hour.
             let maximum_sane_delay = Duration::from_secs(60 * 60);
+            let config = self.config.borrow();
 
             Some(std::cmp::min(
                 maximum_sane_delay,
                 std::cmp::min(
-                    self.config
+                    config
                         .initial_reconnection_backoff
                         .checked_mul(2_u32.pow(current_attempt))
-                        .unwrap_or(self.config.maximum_reconnection_backoff),
-                    self.config.maximum_reconnection_backoff,
+                        .unwrap_or(config.maximum_reconnection_backoff),
+                    config.maximum_reconnection_backoff,
                 ),
             ))
         }
@@ -156,10 +195,11 @@ impl Client {
         address: NymNodeRoutingAddress,
         pending_packet: FramedSphinxPacket,
     ) {
-        let (mut sender, receiver) = mpsc::channel(self.config.maximum_connection_buffer_size);
+        let maximum_connection_buffer_size = self.config.borrow().maximum_connection_buffer_size;
+        let (mut sender, receiver) = mpsc::channel(maximum_connection_buffer_size);
 
         // this CAN'T fail because we just created the channel which has a non-zero capacity
-        if self.config.maximum_connection_buffer_size > 0 {
+        if maximum_connection_buffer_size > 0 {
             sender.try_send(pending_packet).unwrap();
         }
 
@@ -179,13 +219,27 @@ impl Client {
         let backoff = self.determine_backoff(reconnection_attempt);
 
         // copy the value before moving into another task
-        let initial_connection_timeout = self.config.initial_connection_timeout;
+        let initial_connection_timeout = self.config.borrow().initial_connection_timeout;
+
+        // the connection manager holds onto the listener until it has flushed all of its packets
+        let mut shutdown = self.shutdown.clone();
 
         tokio::spawn(async move {
             // before executing the manager, wait for what was specified, if anything
             if let Some(backoff) = backoff {
                 trace!("waiting for {:?} before attempting connection", backoff);
-                sleep(backoff).await;
+                match shutdown.as_mut() {
+                    Some(shutdown) => {
+                        tokio::select! {
+                            _ = sleep(backoff) => (),
+                            _ = shutdown.recv() => {
+                                debug!("not reconnecting to {} as we're shutting down", address);
+                                return;
+                            }
+                        }
+                    }
+                    None => sleep(backoff).await,
+                }
             }
 
             Self::manage_connection(
@@ -194,7 +248,8 @@ impl Client {
                 initial_connection_timeout,
                 &*current_reconnection_attempt,
             )
-            .await
+            .await;
+            drop(shutdown)
         });
     }
 }
@@ -254,3 +309,71 @@ impl SendWithoutResponse for Client {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nymsphinx::builder::SphinxPacketBuilder;
+    use nymsphinx::params::packet_sizes::PacketSize;
+    use nymsphinx::{
+        crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node, NodeAddressBytes,
+        DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH, NODE_ADDRESS_LENGTH,
+    };
+    use task::ShutdownNotifier;
+    use tokio::net::TcpListener;
+
+    fn make_valid_sphinx_packet() -> SphinxPacket {
+        let route: Vec<_> = [5u8, 4, 2]
+            .iter()
+            .map(|&address| {
+                Node::new(
+                    NodeAddressBytes::from_bytes([address; NODE_ADDRESS_LENGTH]),
+                    crypto::keygen().1,
+                )
+            })
+            .collect();
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays: Vec<_> = route
+            .iter()
+            .map(|_| SphinxDelay::new_from_nanos(42))
+            .collect();
+        SphinxPacketBuilder::new()
+            .with_payload_size(PacketSize::default().payload_size())
+            .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn queued_packets_are_flushed_before_the_shutdown_completes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = NymNodeRoutingAddress::from(listener.local_addr().unwrap());
+        let received = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            Framed::new(stream, SphinxCodec).count().await
+        });
+
+        let shutdown = ShutdownNotifier::new();
+        let mut client = Client::new(Config::new(
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            16,
+        ));
+        client.with_shutdown(shutdown.subscribe());
+        for _ in 0..5 {
+            // the packets get queued up while the connection is still being established
+            let _ = client.send_without_response(
+                address,
+                make_valid_sphinx_packet(),
+                PacketMode::default(),
+            );
+        }
+        drop(client);
+
+        assert!(shutdown.shutdown(Duration::from_secs(5)).await);
+        assert_eq!(5, received.await.unwrap());
+    }
+}
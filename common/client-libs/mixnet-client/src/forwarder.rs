@@ -7,6 +7,8 @@ use futures::StreamExt;
 use log::*;
 use nymsphinx::forwarding::packet::MixPacket;
 use std::time::Duration;
+use task::ShutdownListener;
+use tokio::sync::watch;
 
 pub type MixForwardingSender = mpsc::UnboundedSender<MixPacket>;
 type MixForwardingReceiver = mpsc::UnboundedReceiver<MixPacket>;
@@ -32,33 +34,73 @@ impl PacketForwarder {
             maximum_connection_buffer_size,
         );
 
+        // the sender is dropped straight away, so the initial config is never going to change
+        let (_, client_config) = watch::channel(client_config);
+        Self::new_with_config_updates(client_config)
+    }
+
+    /// Creates a forwarder whose mixnet client config can be changed while it is running.
+    pub fn new_with_config_updates(
+        client_config: watch::Receiver<Config>,
+    ) -> (PacketForwarder, MixForwardingSender) {
         let (packet_sender, packet_receiver) = mpsc::unbounded();
 
         (
             PacketForwarder {
-                mixnet_client: Client::new(client_config),
+                mixnet_client: Client::new_with_config_updates(client_config),
                 packet_receiver,
             },
             packet_sender,
         )
     }
 
+    fn forward_packet(&mut self, mix_packet: MixPacket) {
+        trace!("Going to forward packet to {:?}", mix_packet.next_hop());
+
+        let next_hop = mix_packet.next_hop();
+        let packet_mode = mix_packet.packet_mode();
+        let sphinx_packet = mix_packet.into_sphinx_packet();
+        // we don't care about responses, we just want to fire packets
+        // as quickly as possible
+
+        if let Err(err) =
+            self.mixnet_client
+                .send_without_response(next_hop, sphinx_packet, packet_mode)
+        {
+            debug!("failed to forward the packet - {}", err)
+        }
+    }
+
     pub async fn run(&mut self) {
         while let Some(mix_packet) = self.packet_receiver.next().await {
-            trace!("Going to forward packet to {:?}", mix_packet.next_hop());
+            self.forward_packet(mix_packet)
+        }
+    }
 
-            let next_hop = mix_packet.next_hop();
-            let packet_mode = mix_packet.packet_mode();
-            let sphinx_packet = mix_packet.into_sphinx_packet();
-            // we don't care about responses, we just want to fire packets
-            // as quickly as possible
+    /// Forwards packets until the shutdown is signalled. Afterwards it forwards all of the packets
+    /// that are already waiting in the channel, without waiting for any new ones, and returns.
+    pub async fn run_with_shutdown(&mut self, mut shutdown: ShutdownListener) {
+        // all of the connections are only ever created from within this method
+        self.mixnet_client.with_shutdown(shutdown.clone());
 
-            if let Err(err) =
-                self.mixnet_client
-                    .send_without_response(next_hop, sphinx_packet, packet_mode)
-            {
-                debug!("failed to forward the packet - {}", err)
+        loop {
+            tokio::select! {
+                mix_packet = self.packet_receiver.next() => match mix_packet {
+                    Some(mix_packet) => self.forward_packet(mix_packet),
+                    None => return,
+                },
+                _ = shutdown.recv() => break,
             }
         }
+
+        let mut remaining = 0;
+        while let Ok(Some(mix_packet)) = self.packet_receiver.try_next() {
+            self.forward_packet(mix_packet);
+            remaining += 1;
+        }
+        debug!(
+            "Forwarded {} remaining packets before shutting down",
+            remaining
+        );
     }
 }
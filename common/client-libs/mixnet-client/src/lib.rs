@@ -4,4 +4,4 @@
 pub mod client;
 pub mod forwarder;
 
-pub use client::{Client, Config, SendWithoutResponse};
+pub use client::{Client, Config, PacketForwardingConfig, SendWithoutResponse};
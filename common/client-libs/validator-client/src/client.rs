@@ -2,8 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{validator_api, ValidatorClientError};
-use coconut_interface::{BlindSignRequestBody, BlindedSignatureResponse, VerificationKeyResponse};
-use mixnet_contract_common::{GatewayBond, IdentityKeyRef, MixNodeBond};
+use coconut_interface::{
+    BlindSignRequestBody, BlindedSignatureResponse, SpendCredentialRequestBody,
+    SpendCredentialResponse, VerificationKeyResponse,
+};
+use mixnet_contract_common::{GatewayBond, IdentityKeyRef, IdentityRotation, MixNodeBond};
 use url::Url;
 use validator_api_requests::models::{
     CoreNodeStatusResponse, MixnodeStatusResponse, RewardEstimationResponse,
@@ -453,6 +456,56 @@ impl<C> Client<C> {
         Ok(gateways)
     }
 
+    pub async fn get_all_nymd_mixnode_identity_rotations(
+        &self,
+    ) -> Result<Vec<IdentityRotation>, ValidatorClientError>
+    where
+        C: CosmWasmClient + Sync,
+    {
+        let mut rotations = Vec::new();
+        let mut start_after = None;
+        loop {
+            let mut paged_response = self
+                .nymd
+                .get_mixnode_identity_rotations_paged(start_after.take(), self.mixnode_page_limit)
+                .await?;
+            rotations.append(&mut paged_response.rotations);
+
+            if let Some(start_after_res) = paged_response.start_next_after {
+                start_after = Some(start_after_res)
+            } else {
+                break;
+            }
+        }
+
+        Ok(rotations)
+    }
+
+    pub async fn get_all_nymd_gateway_identity_rotations(
+        &self,
+    ) -> Result<Vec<IdentityRotation>, ValidatorClientError>
+    where
+        C: CosmWasmClient + Sync,
+    {
+        let mut rotations = Vec::new();
+        let mut start_after = None;
+        loop {
+            let mut paged_response = self
+                .nymd
+                .get_gateway_identity_rotations_paged(start_after.take(), self.gateway_page_limit)
+                .await?;
+            rotations.append(&mut paged_response.rotations);
+
+            if let Some(start_after_res) = paged_response.start_next_after {
+                start_after = Some(start_after_res)
+            } else {
+                break;
+            }
+        }
+
+        Ok(rotations)
+    }
+
     pub async fn get_all_nymd_single_mixnode_delegations(
         &self,
         identity: IdentityKey,
@@ -551,6 +604,16 @@ impl<C> Client<C> {
     ) -> Result<VerificationKeyResponse, ValidatorClientError> {
         Ok(self.validator_api.get_coconut_verification_key().await?)
     }
+
+    pub async fn spend_coconut_credential(
+        &self,
+        request_body: &SpendCredentialRequestBody,
+    ) -> Result<SpendCredentialResponse, ValidatorClientError> {
+        Ok(self
+            .validator_api
+            .spend_coconut_credential(request_body)
+            .await?)
+    }
 }
 
 pub struct ApiClient {
@@ -653,4 +716,14 @@ impl ApiClient {
     ) -> Result<VerificationKeyResponse, ValidatorClientError> {
         Ok(self.validator_api.get_coconut_verification_key().await?)
     }
+
+    pub async fn spend_coconut_credential(
+        &self,
+        request_body: &SpendCredentialRequestBody,
+    ) -> Result<SpendCredentialResponse, ValidatorClientError> {
+        Ok(self
+            .validator_api
+            .spend_coconut_credential(request_body)
+            .await?)
+    }
 }
@@ -21,6 +21,8 @@ pub enum Operation {
     UnbondMixnode,
     UnbondMixnodeOnBehalf,
     UpdateMixnodeConfig,
+    UpdateMixnodeIdentity,
+    MigrateMixnodeDelegations,
     DelegateToMixnode,
     DelegateToMixnodeOnBehalf,
     UndelegateFromMixnode,
@@ -30,6 +32,7 @@ pub enum Operation {
     BondGatewayOnBehalf,
     UnbondGateway,
     UnbondGatewayOnBehalf,
+    UpdateGatewayIdentity,
 
     UpdateContractSettings,
 
@@ -64,11 +67,14 @@ impl fmt::Display for Operation {
             Operation::BondMixnodeOnBehalf => f.write_str("BondMixnodeOnBehalf"),
             Operation::UnbondMixnode => f.write_str("UnbondMixnode"),
             Operation::UpdateMixnodeConfig => f.write_str("UpdateMixnodeConfig"),
+            Operation::UpdateMixnodeIdentity => f.write_str("UpdateMixnodeIdentity"),
+            Operation::MigrateMixnodeDelegations => f.write_str("MigrateMixnodeDelegations"),
             Operation::UnbondMixnodeOnBehalf => f.write_str("UnbondMixnodeOnBehalf"),
             Operation::BondGateway => f.write_str("BondGateway"),
             Operation::BondGatewayOnBehalf => f.write_str("BondGatewayOnBehalf"),
             Operation::UnbondGateway => f.write_str("UnbondGateway"),
             Operation::UnbondGatewayOnBehalf => f.write_str("UnbondGatewayOnBehalf"),
+            Operation::UpdateGatewayIdentity => f.write_str("UpdateGatewayIdentity"),
             Operation::DelegateToMixnode => f.write_str("DelegateToMixnode"),
             Operation::DelegateToMixnodeOnBehalf => f.write_str("DelegateToMixnodeOnBehalf"),
             Operation::UndelegateFromMixnode => f.write_str("UndelegateFromMixnode"),
@@ -106,6 +112,9 @@ impl Operation {
             Operation::UnbondMixnode => 175_000u64.into(),
             Operation::UnbondMixnodeOnBehalf => 175_000u64.into(),
             Operation::UpdateMixnodeConfig => 175_000u64.into(),
+            // both of them move a single page of delegations of the node
+            Operation::UpdateMixnodeIdentity => 1_500_000u64.into(),
+            Operation::MigrateMixnodeDelegations => 1_500_000u64.into(),
             Operation::DelegateToMixnode => 175_000u64.into(),
             Operation::DelegateToMixnodeOnBehalf => 175_000u64.into(),
             Operation::UndelegateFromMixnode => 175_000u64.into(),
@@ -115,6 +124,7 @@ impl Operation {
             Operation::BondGatewayOnBehalf => 200_000u64.into(),
             Operation::UnbondGateway => 175_000u64.into(),
             Operation::UnbondGatewayOnBehalf => 200_000u64.into(),
+            Operation::UpdateGatewayIdentity => 175_000u64.into(),
 
             Operation::UpdateContractSettings => 175_000u64.into(),
             Operation::BeginMixnodeRewarding => 175_000u64.into(),
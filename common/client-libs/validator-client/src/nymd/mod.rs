@@ -17,8 +17,9 @@ use mixnet_contract_common::{
     ContractStateParams, Delegation, ExecuteMsg, Gateway, GatewayBond, GatewayOwnershipResponse,
     IdentityKey, Interval, LayerDistribution, MixNode, MixNodeBond, MixOwnershipResponse,
     MixnetContractVersion, MixnodeRewardingStatusResponse, PagedAllDelegationsResponse,
-    PagedDelegatorDelegationsResponse, PagedGatewayResponse, PagedMixDelegationsResponse,
-    PagedMixnodeResponse, PagedRewardedSetResponse, QueryMsg, RewardedSetUpdateDetails,
+    PagedDelegatorDelegationsResponse, PagedGatewayResponse, PagedIdentityRotationsResponse,
+    PagedMixDelegationsResponse, PagedMixnodeResponse, PagedRewardedSetResponse, QueryMsg,
+    RewardedSetUpdateDetails,
 };
 use serde::Serialize;
 use std::convert::TryInto;
@@ -481,6 +482,42 @@ impl<C> NymdClient<C> {
             .await
     }
 
+    /// Gets list of all identity keys replaced by bonded mixnodes on particular page.
+    pub async fn get_mixnode_identity_rotations_paged(
+        &self,
+        start_after: Option<IdentityKey>,
+        page_limit: Option<u32>,
+    ) -> Result<PagedIdentityRotationsResponse, NymdError>
+    where
+        C: CosmWasmClient + Sync,
+    {
+        let request = QueryMsg::GetMixnodeIdentityRotations {
+            start_after,
+            limit: page_limit,
+        };
+        self.client
+            .query_contract_smart(self.mixnet_contract_address()?, &request)
+            .await
+    }
+
+    /// Gets list of all identity keys replaced by bonded gateways on particular page.
+    pub async fn get_gateway_identity_rotations_paged(
+        &self,
+        start_after: Option<IdentityKey>,
+        page_limit: Option<u32>,
+    ) -> Result<PagedIdentityRotationsResponse, NymdError>
+    where
+        C: CosmWasmClient + Sync,
+    {
+        let request = QueryMsg::GetGatewayIdentityRotations {
+            start_after,
+            limit: page_limit,
+        };
+        self.client
+            .query_contract_smart(self.mixnet_contract_address()?, &request)
+            .await
+    }
+
     /// Gets list of all delegations towards particular mixnode on particular page.
     pub async fn get_mix_delegations_paged(
         &self,
@@ -861,6 +898,60 @@ impl<C> NymdClient<C> {
             .await
     }
 
+    /// Replaces the identity key of the mixnode, keeping its bond and delegations.
+    /// If the node has more delegations than fit in a single page, the remaining ones have to be
+    /// moved with [`Self::migrate_next_mixnode_delegations`].
+    pub async fn update_mixnode_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    ) -> Result<ExecuteResult, NymdError>
+    where
+        C: SigningCosmWasmClient + Sync,
+    {
+        let fee = self.operation_fee(Operation::UpdateMixnodeIdentity);
+
+        let req = ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        };
+        self.client
+            .execute(
+                self.address(),
+                self.mixnet_contract_address()?,
+                &req,
+                fee,
+                "Updating mixnode identity from rust!",
+                Vec::new(),
+            )
+            .await
+    }
+
+    /// Moves the next page of delegations of a mixnode that has rotated its identity key.
+    pub async fn migrate_next_mixnode_delegations(
+        &self,
+        mix_identity: IdentityKey,
+    ) -> Result<ExecuteResult, NymdError>
+    where
+        C: SigningCosmWasmClient + Sync,
+    {
+        let fee = self.operation_fee(Operation::MigrateMixnodeDelegations);
+
+        let req = ExecuteMsg::MigrateNextMixnodeDelegations { mix_identity };
+        self.client
+            .execute(
+                self.address(),
+                self.mixnet_contract_address()?,
+                &req,
+                fee,
+                "Migrating mixnode delegations from rust!",
+                Vec::new(),
+            )
+            .await
+    }
+
     /// Delegates specified amount of stake to particular mixnode.
     pub async fn delegate_to_mixnode(
         &self,
@@ -1033,6 +1124,35 @@ impl<C> NymdClient<C> {
             .await
     }
 
+    /// Replaces the identity key of the gateway, keeping its bond.
+    pub async fn update_gateway_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    ) -> Result<ExecuteResult, NymdError>
+    where
+        C: SigningCosmWasmClient + Sync,
+    {
+        let fee = self.operation_fee(Operation::UpdateGatewayIdentity);
+
+        let req = ExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        };
+        self.client
+            .execute(
+                self.address(),
+                self.mixnet_contract_address()?,
+                &req,
+                fee,
+                "Updating gateway identity from rust!",
+                Vec::new(),
+            )
+            .await
+    }
+
     /// Announce a gateway on behalf of the owner, paying a fee.
     pub async fn bond_gateway_on_behalf(
         &self,
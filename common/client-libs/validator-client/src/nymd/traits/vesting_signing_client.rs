@@ -29,6 +29,13 @@ pub trait VestingSigningClient {
 
     async fn vesting_unbond_gateway(&self) -> Result<ExecuteResult, NymdError>;
 
+    async fn vesting_update_gateway_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    ) -> Result<ExecuteResult, NymdError>;
+
     async fn vesting_track_unbond_gateway(
         &self,
         owner: &str,
@@ -43,6 +50,13 @@ pub trait VestingSigningClient {
     ) -> Result<ExecuteResult, NymdError>;
     async fn vesting_unbond_mixnode(&self) -> Result<ExecuteResult, NymdError>;
 
+    async fn vesting_update_mixnode_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    ) -> Result<ExecuteResult, NymdError>;
+
     async fn vesting_track_unbond_mixnode(
         &self,
         owner: &str,
@@ -341,4 +355,52 @@ impl<C: SigningCosmWasmClient + Sync + Send> VestingSigningClient for NymdClient
             )
             .await
     }
+
+    async fn vesting_update_mixnode_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    ) -> Result<ExecuteResult, NymdError> {
+        let fee = self.operation_fee(Operation::UpdateMixnodeIdentity);
+        let req = VestingExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        };
+        self.client
+            .execute(
+                self.address(),
+                self.vesting_contract_address()?,
+                &req,
+                fee,
+                "VestingContract::UpdateMixnodeIdentity",
+                vec![],
+            )
+            .await
+    }
+
+    async fn vesting_update_gateway_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    ) -> Result<ExecuteResult, NymdError> {
+        let fee = self.operation_fee(Operation::UpdateGatewayIdentity);
+        let req = VestingExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        };
+        self.client
+            .execute(
+                self.address(),
+                self.vesting_contract_address()?,
+                &req,
+                fee,
+                "VestingContract::UpdateGatewayIdentity",
+                vec![],
+            )
+            .await
+    }
 }
@@ -3,7 +3,10 @@
 
 use crate::validator_api::error::ValidatorAPIError;
 use crate::validator_api::routes::{CORE_STATUS_COUNT, SINCE_ARG};
-use coconut_interface::{BlindSignRequestBody, BlindedSignatureResponse, VerificationKeyResponse};
+use coconut_interface::{
+    BlindSignRequestBody, BlindedSignatureResponse, SpendCredentialRequestBody,
+    SpendCredentialResponse, VerificationKeyResponse,
+};
 use mixnet_contract_common::{GatewayBond, IdentityKeyRef, MixNodeBond};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -270,6 +273,18 @@ impl Client {
         )
         .await
     }
+
+    pub async fn spend_coconut_credential(
+        &self,
+        request_body: &SpendCredentialRequestBody,
+    ) -> Result<SpendCredentialResponse, ValidatorAPIError> {
+        self.post_validator_api(
+            &[routes::API_VERSION, routes::COCONUT_SPEND_CREDENTIAL],
+            NO_PARAMS,
+            request_body,
+        )
+        .await
+    }
 }
 
 // utility function that should solve the double slash problem in validator API forever.
@@ -35,6 +35,12 @@ impl Credential {
         self.public_attributes.clone()
     }
 
+    /// Base58-encoded commitment to the serial number (zeta) of the credential, which is used
+    /// to prevent the same credential from being spent more than once.
+    pub fn blinded_serial_number(&self) -> String {
+        self.theta.blinded_serial_number_bs58()
+    }
+
     pub fn verify(&self, verification_key: &VerificationKey) -> bool {
         let params = Parameters::new(self.n_params).unwrap();
         let public_attributes = self
@@ -127,6 +133,51 @@ impl BlindedSignatureResponse {
     }
 }
 
+/// Request of a gateway to mark the credential it has just redeemed as spent in the shared set.
+#[derive(Serialize, Deserialize)]
+pub struct SpendCredentialRequestBody {
+    /// The redeemed credential, so that the validators could check it's a valid one.
+    pub credential: Credential,
+
+    /// Base58-encoded identity key of the bonded gateway that has redeemed the credential.
+    pub gateway_identity: String,
+
+    /// Base58-encoded signature on the blinded serial number of the credential,
+    /// made with the identity key of the gateway.
+    pub signature: String,
+}
+
+impl SpendCredentialRequestBody {
+    pub fn new(
+        credential: Credential,
+        gateway_identity: String,
+        signature: String,
+    ) -> SpendCredentialRequestBody {
+        SpendCredentialRequestBody {
+            credential,
+            gateway_identity,
+            signature,
+        }
+    }
+
+    /// The message that has to be signed by the gateway for the request to be accepted.
+    pub fn signed_message(credential: &Credential) -> Vec<u8> {
+        credential.blinded_serial_number().into_bytes()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpendCredentialResponse {
+    /// Indicates whether the credential has been spent for the first time with this request.
+    pub newly_spent: bool,
+}
+
+impl SpendCredentialResponse {
+    pub fn new(newly_spent: bool) -> SpendCredentialResponse {
+        SpendCredentialResponse { newly_spent }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct VerificationKeyResponse {
     pub key: VerificationKey,
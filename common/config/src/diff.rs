@@ -0,0 +1,82 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use toml::Value;
+
+/// Returns the dotted paths, such as `debug.packet_forwarding_initial_backoff`, of all values
+/// that differ between the two configs, in alphabetical order. Values present in only one of them
+/// are also considered changed. Arrays are compared as a whole.
+pub fn changed_fields<T: Serialize>(old: &T, new: &T) -> Result<Vec<String>, toml::ser::Error> {
+    let old = Value::try_from(old)?;
+    let new = Value::try_from(new)?;
+
+    let mut changed = Vec::new();
+    diff_values("", Some(&old), Some(&new), &mut changed);
+    Ok(changed)
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, changed: &mut Vec<String>) {
+    match (old, new) {
+        (Some(Value::Table(old)), Some(Value::Table(new))) => {
+            let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let nested_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_values(&nested_path, old.get(key), new.get(key), changed)
+            }
+        }
+        (old, new) if old != new => changed.push(path.to_string()),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Section {
+        value: u32,
+        list: Vec<u32>,
+        optional: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        id: String,
+        section: Section,
+    }
+
+    fn config(value: u32, list: Vec<u32>, optional: Option<String>) -> Config {
+        Config {
+            id: "foo".to_string(),
+            section: Section {
+                value,
+                list,
+                optional,
+            },
+        }
+    }
+
+    #[test]
+    fn identical_configs_have_no_changed_fields() {
+        let old = config(1, vec![1, 2], None);
+        let new = config(1, vec![1, 2], None);
+        assert!(changed_fields(&old, &new).unwrap().is_empty())
+    }
+
+    #[test]
+    fn nested_changes_are_reported_with_their_full_paths() {
+        let old = config(1, vec![1, 2], None);
+        let new = config(2, vec![2, 1], Some("bar".to_string()));
+        assert_eq!(
+            changed_fields(&old, &new).unwrap(),
+            vec!["section.list", "section.optional", "section.value"]
+        )
+    }
+}
@@ -8,6 +8,9 @@ use std::path::PathBuf;
 use std::{fs, io};
 
 pub mod defaults;
+pub mod diff;
+#[cfg(feature = "reload")]
+pub mod reload;
 
 pub trait NymConfig: Default + Serialize + DeserializeOwned {
     fn template() -> &'static str;
@@ -73,4 +76,11 @@ pub trait NymConfig: Default + Serialize + DeserializeOwned {
         toml::from_str(&config_contents)
             .map_err(|toml_err| io::Error::new(io::ErrorKind::Other, toml_err))
     }
+
+    /// Returns the dotted paths of all values that differ between this and the other config,
+    /// for example `debug.packet_forwarding_initial_backoff`.
+    fn changed_fields(&self, other: &Self) -> io::Result<Vec<String>> {
+        diff::changed_fields(self, other)
+            .map_err(|toml_err| io::Error::new(io::ErrorKind::Other, toml_err))
+    }
 }
@@ -0,0 +1,179 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::NymConfig;
+use log::*;
+use std::io;
+use task::ShutdownListener;
+
+/// Field holding the log level, which every node can change without a restart.
+pub const LOG_LEVEL: &str = "logging.level";
+
+/// Fields used by the mixnet client of the nodes forwarding packets into the mix network.
+pub const PACKET_FORWARDING_FIELDS: &[&str] = &[
+    "debug.packet_forwarding_initial_backoff",
+    "debug.packet_forwarding_maximum_backoff",
+    "debug.initial_connection_timeout",
+    "debug.maximum_connection_buffer_size",
+];
+
+/// Checks whether any of the changed fields is one of the given ones.
+pub fn any_changed(changed_fields: &[&String], fields: &[&str]) -> bool {
+    changed_fields
+        .iter()
+        .any(|field| fields.contains(&field.as_str()))
+}
+
+/// Reloads the config file upon receiving SIGHUP. The settings that are safe to change while the
+/// node is running, as told by `is_live`, are handed over to `apply` straight away, while the
+/// changes of all other ones are reported as requiring a restart.
+#[cfg_attr(not(unix), allow(dead_code))]
+pub struct ConfigReloader<C, A> {
+    /// Kind of the node, such as "mixnode", used in the log messages.
+    node_kind: &'static str,
+
+    id: String,
+
+    /// Config file as it was when the node has started, i.e. without the command line overrides.
+    startup_config: C,
+
+    /// Config file as it was during the last successful reload, if there was any.
+    reloaded_config: Option<C>,
+
+    is_live: fn(&str) -> bool,
+    apply: A,
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+impl<C, A> ConfigReloader<C, A>
+where
+    C: NymConfig,
+    A: Fn(&C, &[&String]),
+{
+    /// Loads the config file of the node with the given id. The reloaded files are compared
+    /// against the file itself rather than against the config the node is running with, since the
+    /// latter might contain the overrides from the command line arguments.
+    pub fn new(
+        node_kind: &'static str,
+        id: String,
+        is_live: fn(&str) -> bool,
+        apply: A,
+    ) -> io::Result<Self> {
+        Ok(ConfigReloader {
+            node_kind,
+            startup_config: C::load_from_file(Some(&id))?,
+            id,
+            reloaded_config: None,
+            is_live,
+            apply,
+        })
+    }
+
+    fn current_config(&self) -> &C {
+        self.reloaded_config
+            .as_ref()
+            .unwrap_or(&self.startup_config)
+    }
+
+    fn reload(&mut self) {
+        info!("Reloading the config file...");
+
+        let new_config = match C::load_from_file(Some(&self.id)) {
+            Ok(config) => config,
+            Err(err) => {
+                error!(
+                    "Failed to reload the config file - the current settings are going to be kept (Error was: {})",
+                    err
+                );
+                return;
+            }
+        };
+
+        let (changed, changed_since_startup) = match (
+            self.current_config().changed_fields(&new_config),
+            self.startup_config.changed_fields(&new_config),
+        ) {
+            (Ok(changed), Ok(changed_since_startup)) => (changed, changed_since_startup),
+            (Err(err), _) | (_, Err(err)) => {
+                error!(
+                    "Failed to compare the reloaded config file - the current settings are going to be kept (Error was: {})",
+                    err
+                );
+                return;
+            }
+        };
+
+        self.reloaded_config = Some(new_config);
+        let applied = changed
+            .iter()
+            .filter(|field| (self.is_live)(field))
+            .collect::<Vec<_>>();
+        if applied.is_empty() {
+            info!("None of the settings that can be changed at runtime have been modified")
+        } else {
+            for field in &applied {
+                info!("Applying the new value of '{}'", field)
+            }
+            (self.apply)(self.current_config(), &applied)
+        }
+
+        let requiring_restart = changed_since_startup
+            .iter()
+            .filter(|field| !(self.is_live)(field))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !requiring_restart.is_empty() {
+            warn!(
+                "The following settings have been changed, but the {} has to be restarted for the changes to take effect: {}",
+                self.node_kind,
+                requiring_restart.join(", ")
+            )
+        }
+    }
+
+    /// Reloads the config file every time SIGHUP is received, until the shutdown.
+    #[cfg(unix)]
+    pub async fn run(mut self, mut shutdown: ShutdownListener) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                error!(
+                    "Failed to listen for SIGHUP - config changes are going to require a restart (Error was: {})",
+                    err
+                );
+                return;
+            }
+        };
+
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => self.reload(),
+                _ = shutdown.recv() => return,
+            }
+        }
+    }
+
+    /// SIGHUP only exists on unix systems, so elsewhere all config changes require a restart.
+    #[cfg(not(unix))]
+    pub async fn run(self, _shutdown: ShutdownListener) {
+        info!("Reloading the config file is only supported on unix systems - config changes are going to require a restart")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_given_fields_are_matched() {
+        let log_level = LOG_LEVEL.to_string();
+        let backoff = "debug.packet_forwarding_initial_backoff".to_string();
+        let port = "mixnode.mix_port".to_string();
+
+        assert!(any_changed(&[&port, &backoff], PACKET_FORWARDING_FIELDS));
+        assert!(!any_changed(&[&port, &log_level], PACKET_FORWARDING_FIELDS));
+        assert!(!any_changed(&[], PACKET_FORWARDING_FIELDS));
+    }
+}
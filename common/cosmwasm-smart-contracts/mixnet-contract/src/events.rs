@@ -14,6 +14,9 @@ pub const GATEWAY_BONDING_EVENT_TYPE: &str = "gateway_bonding";
 pub const GATEWAY_UNBONDING_EVENT_TYPE: &str = "gateway_unbonding";
 pub const MIXNODE_BONDING_EVENT_TYPE: &str = "mixnode_bonding";
 pub const MIXNODE_UNBONDING_EVENT_TYPE: &str = "mixnode_unbonding";
+pub const MIXNODE_IDENTITY_UPDATE_EVENT_TYPE: &str = "mixnode_identity_update";
+pub const GATEWAY_IDENTITY_UPDATE_EVENT_TYPE: &str = "gateway_identity_update";
+pub const MIXNODE_DELEGATIONS_MIGRATION_EVENT_TYPE: &str = "mixnode_delegations_migration";
 pub const SETTINGS_UPDATE_EVENT_TYPE: &str = "settings_update";
 pub const OPERATOR_REWARDING_EVENT_TYPE: &str = "mix_rewarding";
 pub const MIX_DELEGATORS_REWARDING_EVENT_TYPE: &str = "mix_delegators_rewarding";
@@ -36,6 +39,12 @@ pub const DELEGATION_HEIGHT_KEY: &str = "delegation_latest_block_height";
 pub const NODE_IDENTITY_KEY: &str = "identity";
 pub const ASSIGNED_LAYER_KEY: &str = "assigned_layer";
 
+// identity update
+pub const OLD_NODE_IDENTITY_KEY: &str = "old_identity";
+pub const NEW_NODE_IDENTITY_KEY: &str = "new_identity";
+pub const MOVED_DELEGATIONS_KEY: &str = "moved_delegations";
+pub const FURTHER_DELEGATIONS_TO_MIGRATE_KEY: &str = "further_delegations_to_migrate";
+
 // settings change
 pub const OLD_MINIMUM_MIXNODE_PLEDGE_KEY: &str = "old_minimum_mixnode_pledge";
 pub const OLD_MINIMUM_GATEWAY_PLEDGE_KEY: &str = "old_minimum_gateway_pledge";
@@ -62,6 +71,7 @@ pub const NO_REWARD_REASON_KEY: &str = "no_reward_reason";
 pub const BOND_NOT_FOUND_VALUE: &str = "bond_not_found";
 pub const BOND_TOO_FRESH_VALUE: &str = "bond_too_fresh";
 pub const ZERO_UPTIME_VALUE: &str = "zero_uptime";
+pub const DELEGATIONS_PENDING_MIGRATION_VALUE: &str = "delegations_pending_migration";
 
 // rewarded set update
 pub const ACTIVE_SET_SIZE_KEY: &str = "active_set_size";
@@ -186,6 +196,63 @@ pub fn new_mixnode_unbonding_event(
     event.add_attribute(AMOUNT_KEY, amount.to_string())
 }
 
+pub fn new_mixnode_identity_update_event(
+    owner: &Addr,
+    proxy: &Option<Addr>,
+    old_identity: IdentityKeyRef<'_>,
+    new_identity: IdentityKeyRef<'_>,
+    moved_delegations: usize,
+    further_delegations: bool,
+) -> Event {
+    let mut event = Event::new(MIXNODE_IDENTITY_UPDATE_EVENT_TYPE).add_attribute(OWNER_KEY, owner);
+
+    if let Some(proxy) = proxy {
+        event = event.add_attribute(PROXY_KEY, proxy)
+    }
+
+    event
+        .add_attribute(OLD_NODE_IDENTITY_KEY, old_identity)
+        .add_attribute(NEW_NODE_IDENTITY_KEY, new_identity)
+        .add_attribute(MOVED_DELEGATIONS_KEY, moved_delegations.to_string())
+        .add_attribute(
+            FURTHER_DELEGATIONS_TO_MIGRATE_KEY,
+            further_delegations.to_string(),
+        )
+}
+
+pub fn new_mixnode_delegations_migration_event(
+    old_identity: IdentityKeyRef<'_>,
+    new_identity: IdentityKeyRef<'_>,
+    moved_delegations: usize,
+    further_delegations: bool,
+) -> Event {
+    Event::new(MIXNODE_DELEGATIONS_MIGRATION_EVENT_TYPE)
+        .add_attribute(OLD_NODE_IDENTITY_KEY, old_identity)
+        .add_attribute(NEW_NODE_IDENTITY_KEY, new_identity)
+        .add_attribute(MOVED_DELEGATIONS_KEY, moved_delegations.to_string())
+        .add_attribute(
+            FURTHER_DELEGATIONS_TO_MIGRATE_KEY,
+            further_delegations.to_string(),
+        )
+}
+
+pub fn new_gateway_identity_update_event(
+    owner: &Addr,
+    proxy: &Option<Addr>,
+    old_identity: IdentityKeyRef<'_>,
+    new_identity: IdentityKeyRef<'_>,
+) -> Event {
+    let mut event = Event::new(GATEWAY_IDENTITY_UPDATE_EVENT_TYPE).add_attribute(OWNER_KEY, owner);
+
+    if let Some(proxy) = proxy {
+        event = event.add_attribute(PROXY_KEY, proxy)
+    }
+
+    event
+        .add_attribute(OLD_NODE_IDENTITY_KEY, old_identity)
+        .add_attribute(NEW_NODE_IDENTITY_KEY, new_identity)
+}
+
 pub fn new_settings_update_event(
     old_params: &ContractStateParams,
     new_params: &ContractStateParams,
@@ -263,6 +330,16 @@ pub fn new_too_fresh_bond_mix_operator_rewarding_event(
         .add_attribute(NO_REWARD_REASON_KEY, BOND_TOO_FRESH_VALUE)
 }
 
+pub fn new_pending_delegations_migration_mix_operator_rewarding_event(
+    interval_id: u32,
+    identity: IdentityKeyRef<'_>,
+) -> Event {
+    Event::new(OPERATOR_REWARDING_EVENT_TYPE)
+        .add_attribute(INTERVAL_ID_KEY, interval_id.to_string())
+        .add_attribute(NODE_IDENTITY_KEY, identity)
+        .add_attribute(NO_REWARD_REASON_KEY, DELEGATIONS_PENDING_MIGRATION_VALUE)
+}
+
 pub fn new_zero_uptime_mix_operator_rewarding_event(
     interval_id: u32,
     identity: IdentityKeyRef<'_>,
@@ -11,6 +11,7 @@ mod msg;
 mod types;
 
 pub const MIXNODE_DELEGATORS_PAGE_LIMIT: usize = 250;
+pub const MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT: usize = 25;
 
 pub use cosmwasm_std::{Addr, Coin};
 pub use delegation::{
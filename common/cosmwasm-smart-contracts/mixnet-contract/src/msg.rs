@@ -32,6 +32,24 @@ pub enum ExecuteMsg {
         owner_signature: String,
     },
     UnbondGateway {},
+    // replaces the identity key of the sender's mixnode, keeping its pledge and delegations.
+    // `owner_signature` is the signature of the owner address made with the new key,
+    // while `identity_signature` is the signature of the new key made with the old one
+    UpdateMixnodeIdentity {
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    },
+    // moves the next page of delegations of a mixnode that has rotated its identity;
+    // it can be sent by anyone until all of them have been moved
+    MigrateNextMixnodeDelegations {
+        mix_identity: IdentityKey,
+    },
+    UpdateGatewayIdentity {
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    },
     UpdateContractStateParams(ContractStateParams),
 
     DelegateToMixnode {
@@ -79,6 +97,18 @@ pub enum ExecuteMsg {
     UnbondGatewayOnBehalf {
         owner: String,
     },
+    UpdateMixnodeIdentityOnBehalf {
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+        owner: String,
+    },
+    UpdateGatewayIdentityOnBehalf {
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+        owner: String,
+    },
     WriteRewardedSet {
         rewarded_set: Vec<IdentityKey>,
         expected_active_set_size: u32,
@@ -154,6 +184,15 @@ pub enum QueryMsg {
     GetCurrentRewardedSetHeight {},
     GetCurrentInterval {},
     GetRewardedSetRefreshBlocks {},
+    // gets all [paged] identity keys replaced by bonded mixnodes, alongside their replacements
+    GetMixnodeIdentityRotations {
+        start_after: Option<IdentityKey>,
+        limit: Option<u32>,
+    },
+    GetGatewayIdentityRotations {
+        start_after: Option<IdentityKey>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
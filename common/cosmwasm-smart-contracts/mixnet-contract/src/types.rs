@@ -143,3 +143,17 @@ pub struct IntervalRewardedSetHeightsResponse {
     pub interval_id: u32,
     pub heights: Vec<u64>,
 }
+
+/// Record of a bonded node that has replaced its identity key while keeping its bond.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+pub struct IdentityRotation {
+    pub previous_identity: IdentityKey,
+    pub new_identity: IdentityKey,
+    pub block_height: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+pub struct PagedIdentityRotationsResponse {
+    pub rotations: Vec<IdentityRotation>,
+    pub start_next_after: Option<IdentityKey>,
+}
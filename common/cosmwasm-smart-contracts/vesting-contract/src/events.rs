@@ -16,10 +16,13 @@ pub const VESTING_GATEWAY_UNBONDING_EVENT_TYPE: &str = "vesting_gateway_unbondin
 pub const VESTING_MIXNODE_BONDING_EVENT_TYPE: &str = "vesting_mixnode_bonding";
 pub const VESTING_MIXNODE_UNBONDING_EVENT_TYPE: &str = "vesting_mixnode_unbonding";
 pub const VESTING_UPDATE_MIXNODE_CONFIG_EVENT_TYPE: &str = "vesting_update_mixnode_config";
+pub const VESTING_UPDATE_MIXNODE_IDENTITY_EVENT_TYPE: &str = "vesting_update_mixnode_identity";
+pub const VESTING_UPDATE_GATEWAY_IDENTITY_EVENT_TYPE: &str = "vesting_update_gateway_identity";
 
 pub const TRACK_MIXNODE_UNBOND_EVENT_TYPE: &str = "track_mixnode_unbond";
 pub const TRACK_GATEWAY_UNBOND_EVENT_TYPE: &str = "track_gateway_unbond";
 pub const TRACK_UNDELEGATION_EVENT_TYPE: &str = "track_undelegation";
+pub const TRACK_MIXNODE_IDENTITY_UPDATE_EVENT_TYPE: &str = "track_mixnode_identity_update";
 
 // attributes that are used in multiple places
 pub const OWNER_KEY: &str = "owner";
@@ -113,6 +116,14 @@ pub fn new_vesting_update_mixnode_config_event() -> Event {
     Event::new(VESTING_UPDATE_MIXNODE_CONFIG_EVENT_TYPE)
 }
 
+pub fn new_vesting_update_mixnode_identity_event() -> Event {
+    Event::new(VESTING_UPDATE_MIXNODE_IDENTITY_EVENT_TYPE)
+}
+
+pub fn new_vesting_update_gateway_identity_event() -> Event {
+    Event::new(VESTING_UPDATE_GATEWAY_IDENTITY_EVENT_TYPE)
+}
+
 pub fn new_vesting_mixnode_unbonding_event() -> Event {
     Event::new(VESTING_MIXNODE_UNBONDING_EVENT_TYPE)
 }
@@ -136,3 +147,7 @@ pub fn new_track_gateway_unbond_event() -> Event {
 pub fn new_track_undelegation_event() -> Event {
     Event::new(TRACK_UNDELEGATION_EVENT_TYPE)
 }
+
+pub fn new_track_mixnode_identity_update_event() -> Event {
+    Event::new(TRACK_MIXNODE_IDENTITY_UPDATE_EVENT_TYPE)
+}
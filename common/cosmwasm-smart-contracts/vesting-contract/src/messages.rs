@@ -75,12 +75,22 @@ pub enum ExecuteMsg {
         mix_identity: IdentityKey,
         amount: Coin,
     },
+    TrackMixnodeIdentityUpdate {
+        owner: String,
+        old_identity: IdentityKey,
+        new_identity: IdentityKey,
+    },
     BondMixnode {
         mix_node: MixNode,
         owner_signature: String,
         amount: Coin,
     },
     UnbondMixnode {},
+    UpdateMixnodeIdentity {
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    },
     TrackUnbondMixnode {
         owner: String,
         amount: Coin,
@@ -91,6 +101,11 @@ pub enum ExecuteMsg {
         amount: Coin,
     },
     UnbondGateway {},
+    UpdateGatewayIdentity {
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+    },
     TrackUnbondGateway {
         owner: String,
         amount: Coin,
@@ -4,13 +4,15 @@
 use crypto::asymmetric::identity;
 use serde::{Serialize, Serializer};
 use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{self, Display, Formatter};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 
 pub struct AtomicVerlocResult {
     inner: Arc<RwLock<VerlocResult>>,
+    history: Arc<RwLock<VerlocHistory>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -23,8 +25,24 @@ pub struct VerlocResult {
     results: Vec<Verloc>,
 }
 
+impl VerlocResult {
+    /// Number of nodes tested during the current (or the last completed) run.
+    pub fn total_tested(&self) -> usize {
+        self.total_tested
+    }
+
+    /// Time at which the last run has finished, if it has finished at all.
+    pub fn run_finished(&self) -> Option<std::time::SystemTime> {
+        self.run_finished
+    }
+
+    pub fn results(&self) -> &[Verloc] {
+        &self.results
+    }
+}
+
 impl AtomicVerlocResult {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(history_retention: Duration) -> Self {
         AtomicVerlocResult {
             inner: Arc::new(RwLock::new(VerlocResult {
                 total_tested: 0,
@@ -32,6 +50,7 @@ impl AtomicVerlocResult {
                 run_finished: None,
                 results: Vec::new(),
             })),
+            history: Arc::new(RwLock::new(VerlocHistory::new(history_retention))),
         }
     }
 
@@ -39,6 +58,7 @@ impl AtomicVerlocResult {
     pub(crate) fn clone_data_pointer(&self) -> Self {
         AtomicVerlocResult {
             inner: Arc::clone(&self.inner),
+            history: Arc::clone(&self.history),
         }
     }
 
@@ -51,6 +71,11 @@ impl AtomicVerlocResult {
     }
 
     pub(crate) async fn append_results(&self, mut new_data: Vec<Verloc>) {
+        self.history
+            .write()
+            .await
+            .record(&new_data, SystemTime::now());
+
         let mut write_permit = self.inner.write().await;
         write_permit.results.append(&mut new_data);
         // make sure the data always stays in order.
@@ -68,6 +93,114 @@ impl AtomicVerlocResult {
     pub async fn clone_data(&self) -> VerlocResult {
         self.inner.read().await.clone()
     }
+
+    /// Returns all of the retained measurements performed within the specified time range
+    /// (inclusive), grouped by the measured node. Nodes with no measurements in the range are omitted.
+    pub async fn history(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Vec<NodeVerlocHistory> {
+        self.history.read().await.query(since, until)
+    }
+}
+
+/// Rolling history of measurements of all nodes tested within the retention period.
+struct VerlocHistory {
+    retention: Duration,
+    // keyed by base58 identity of the node
+    nodes: HashMap<String, NodeVerlocHistory>,
+}
+
+impl VerlocHistory {
+    fn new(retention: Duration) -> Self {
+        VerlocHistory {
+            retention,
+            nodes: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, new_data: &[Verloc], now: SystemTime) {
+        for verloc in new_data {
+            self.nodes
+                .entry(verloc.identity.to_base58_string())
+                .or_insert_with(|| NodeVerlocHistory {
+                    identity: verloc.identity,
+                    measurements: VecDeque::new(),
+                })
+                .measurements
+                .push_back(HistoricalMeasurement {
+                    timestamp: now,
+                    measurement: verloc.latest_measurement,
+                });
+        }
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: SystemTime) {
+        let cutoff = match now.checked_sub(self.retention) {
+            Some(cutoff) => cutoff,
+            None => return,
+        };
+
+        for node in self.nodes.values_mut() {
+            // the measurements are always pushed in chronological order
+            while matches!(node.measurements.front(), Some(oldest) if oldest.timestamp < cutoff) {
+                node.measurements.pop_front();
+            }
+        }
+        self.nodes.retain(|_, node| !node.measurements.is_empty());
+    }
+
+    fn query(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Vec<NodeVerlocHistory> {
+        let mut history = self
+            .nodes
+            .values()
+            .filter_map(|node| {
+                let measurements = node
+                    .measurements
+                    .iter()
+                    .filter(|historical| {
+                        since.map_or(true, |since| historical.timestamp >= since)
+                            && until.map_or(true, |until| historical.timestamp <= until)
+                    })
+                    .copied()
+                    .collect::<VecDeque<_>>();
+                if measurements.is_empty() {
+                    None
+                } else {
+                    Some(NodeVerlocHistory {
+                        identity: node.identity,
+                        measurements,
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // make the output stable regardless of the hashmap ordering
+        history.sort_by_cached_key(|node| node.identity.to_base58_string());
+        history
+    }
+}
+
+/// All retained measurements of a single node, oldest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeVerlocHistory {
+    #[serde(serialize_with = "serialize_identity_as_string")]
+    pub identity: identity::PublicKey,
+    pub measurements: VecDeque<HistoricalMeasurement>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoricalMeasurement {
+    /// Time at which the measurement got completed.
+    #[serde(with = "humantime_serde")]
+    pub timestamp: SystemTime,
+    pub measurement: Option<Measurement>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
@@ -144,24 +277,78 @@ pub struct Measurement {
     pub maximum: Duration,
     #[serde(serialize_with = "humantime_serde::serialize")]
     pub standard_deviation: Duration,
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    pub median: Duration,
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    pub percentile_90: Duration,
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    pub percentile_99: Duration,
+    /// Mean difference between round-trip times of subsequent packets.
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    pub jitter: Duration,
+    pub packets_sent: usize,
+    /// Number of echo packets we haven't received a reply to in time.
+    pub packets_lost: usize,
 }
 
 impl Measurement {
-    pub(crate) fn new(raw_results: &[Duration]) -> Self {
+    pub(crate) fn new(raw_results: &[Duration], packets_sent: usize) -> Self {
         let minimum = *raw_results.iter().min().expect("didn't get any results!");
         let maximum = *raw_results.iter().max().expect("didn't get any results!");
 
         let mean = Self::duration_mean(raw_results);
         let standard_deviation = Self::duration_standard_deviation(raw_results, mean);
 
+        let mut sorted = raw_results.to_vec();
+        sorted.sort_unstable();
+
         Measurement {
             minimum,
             mean,
             maximum,
             standard_deviation,
+            median: Self::duration_percentile(&sorted, 50),
+            percentile_90: Self::duration_percentile(&sorted, 90),
+            percentile_99: Self::duration_percentile(&sorted, 99),
+            jitter: Self::duration_jitter(raw_results),
+            packets_sent,
+            packets_lost: packets_sent.saturating_sub(raw_results.len()),
+        }
+    }
+
+    /// Packet loss as a fraction of sent packets.
+    pub fn loss_ratio(&self) -> f64 {
+        if self.packets_sent == 0 {
+            0.0
+        } else {
+            self.packets_lost as f64 / self.packets_sent as f64
         }
     }
 
+    // uses the nearest-rank method on the already sorted data
+    fn duration_percentile(sorted: &[Duration], percentile: usize) -> Duration {
+        let rank = (percentile * sorted.len() + 99) / 100;
+        sorted[rank.max(1) - 1]
+    }
+
+    fn duration_jitter(data: &[Duration]) -> Duration {
+        if data.len() < 2 {
+            return Duration::ZERO;
+        }
+
+        let total_difference = data
+            .windows(2)
+            .map(|pair| {
+                if pair[0] > pair[1] {
+                    pair[0] - pair[1]
+                } else {
+                    pair[1] - pair[0]
+                }
+            })
+            .sum::<Duration>();
+        total_difference / (data.len() - 1) as u32
+    }
+
     fn duration_mean(data: &[Duration]) -> Duration {
         let sum = data.iter().sum::<Duration>();
         let count = data.len() as u32;
@@ -196,8 +383,14 @@ impl Display for Measurement {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "rtt min/avg/max/mdev = {:?} / {:?} / {:?} / {:?}",
-            self.minimum, self.mean, self.maximum, self.standard_deviation
+            "rtt min/avg/max/mdev = {:?} / {:?} / {:?} / {:?}, jitter {:?}, {}/{} packets lost",
+            self.minimum,
+            self.mean,
+            self.maximum,
+            self.standard_deviation,
+            self.jitter,
+            self.packets_lost,
+            self.packets_sent
         )
     }
 }
@@ -244,6 +437,12 @@ mod tests {
                 mean: Duration::from_millis(43),
                 maximum: Duration::from_millis(44),
                 standard_deviation: Duration::from_millis(45),
+                median: Duration::from_millis(43),
+                percentile_90: Duration::from_millis(44),
+                percentile_99: Duration::from_millis(44),
+                jitter: Duration::from_millis(1),
+                packets_sent: 100,
+                packets_lost: 0,
             }),
         );
         let higher_min = Verloc::new(
@@ -253,6 +452,12 @@ mod tests {
                 mean: Duration::from_millis(430),
                 maximum: Duration::from_millis(440),
                 standard_deviation: Duration::from_millis(450),
+                median: Duration::from_millis(448),
+                percentile_90: Duration::from_millis(449),
+                percentile_99: Duration::from_millis(449),
+                jitter: Duration::from_millis(1),
+                packets_sent: 100,
+                packets_lost: 0,
             }),
         );
 
@@ -262,4 +467,67 @@ mod tests {
         let expected_sorted = vec![low_min, higher_min, no_measurement, no_measurement];
         assert_eq!(expected_sorted, vec_verloc);
     }
+
+    #[test]
+    fn measurement_includes_loss_jitter_and_percentiles() {
+        let rtts = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+        let measurement = Measurement::new(&rtts, 20);
+
+        assert_eq!(Duration::from_millis(1), measurement.minimum);
+        assert_eq!(Duration::from_millis(10), measurement.maximum);
+        assert_eq!(Duration::from_millis(5), measurement.median);
+        assert_eq!(Duration::from_millis(9), measurement.percentile_90);
+        assert_eq!(Duration::from_millis(10), measurement.percentile_99);
+        assert_eq!(Duration::from_millis(1), measurement.jitter);
+        assert_eq!(10, measurement.packets_lost);
+        assert!((measurement.loss_ratio() - 0.5).abs() < f64::EPSILON);
+
+        let alternating = [10, 20, 10, 20].map(Duration::from_millis);
+        assert_eq!(
+            Duration::from_millis(10),
+            Measurement::new(&alternating, 4).jitter
+        );
+    }
+
+    #[test]
+    fn history_is_pruned_and_queried_by_time_range() {
+        let some_identity =
+            identity::PublicKey::from_base58_string("Be9wH7xuXBRJAuV1pC7MALZv6a61RvWQ3SypsNarqTt")
+                .unwrap();
+        let measurement = Measurement::new(&[Duration::from_millis(42)], 1);
+        let verloc = Verloc::new(some_identity, Some(measurement));
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let hour = Duration::from_secs(60 * 60);
+        let mut history = VerlocHistory::new(3 * hour);
+        for i in 0..5 {
+            history.record(&[verloc], start + i * hour);
+        }
+
+        // only the measurements within the last 3 hours are retained
+        let all = history.query(None, None);
+        assert_eq!(1, all.len());
+        let timestamps = all[0]
+            .measurements
+            .iter()
+            .map(|historical| historical.timestamp)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                start + hour,
+                start + 2 * hour,
+                start + 3 * hour,
+                start + 4 * hour
+            ],
+            timestamps
+        );
+
+        let ranged = history.query(Some(start + 2 * hour), Some(start + 3 * hour));
+        assert_eq!(2, ranged[0].measurements.len());
+        assert!(history.query(Some(start + 5 * hour), None).is_empty());
+
+        // nodes that haven't been measured within the retention period are forgotten
+        history.record(&[], start + 10 * hour);
+        assert!(history.query(None, None).is_empty());
+    }
 }
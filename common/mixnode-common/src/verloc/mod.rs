@@ -1,8 +1,11 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::verloc::error::RttError;
 use crate::verloc::listener::PacketListener;
-pub use crate::verloc::measurement::{AtomicVerlocResult, Verloc, VerlocResult};
+pub use crate::verloc::measurement::{
+    AtomicVerlocResult, HistoricalMeasurement, Measurement, NodeVerlocHistory, Verloc, VerlocResult,
+};
 use crate::verloc::sender::{PacketSender, TestedNode};
 use crypto::asymmetric::identity;
 use futures::stream::FuturesUnordered;
@@ -36,6 +39,7 @@ const DEFAULT_DELAY_BETWEEN_PACKETS: Duration = Duration::from_millis(50);
 const DEFAULT_BATCH_SIZE: usize = 50;
 const DEFAULT_TESTING_INTERVAL: Duration = Duration::from_secs(60 * 60 * 12);
 const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_secs(60 * 30);
+const DEFAULT_HISTORY_RETENTION: Duration = Duration::from_secs(60 * 60 * 24 * 7);
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -67,6 +71,9 @@ pub struct Config {
     /// due to being unable to get the list of nodes.
     retry_timeout: Duration,
 
+    /// Specifies for how long the results of past measurements are kept around.
+    history_retention: Duration,
+
     /// URLs to the validator apis for obtaining network topology.
     validator_api_urls: Vec<Url>,
 }
@@ -130,6 +137,11 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn history_retention(mut self, history_retention: Duration) -> Self {
+        self.0.history_retention = history_retention;
+        self
+    }
+
     pub fn validator_api_urls(mut self, validator_api_urls: Vec<Url>) -> Self {
         self.0.validator_api_urls = validator_api_urls;
         self
@@ -156,11 +168,32 @@ impl Default for ConfigBuilder {
             tested_nodes_batch_size: DEFAULT_BATCH_SIZE,
             testing_interval: DEFAULT_TESTING_INTERVAL,
             retry_timeout: DEFAULT_RETRY_TIMEOUT,
+            history_retention: DEFAULT_HISTORY_RETENTION,
             validator_api_urls: vec![],
         })
     }
 }
 
+/// Performs a one-off measurement of a single node, using the packet parameters of the provided
+/// config, outside of the regular measurement runs.
+pub async fn measure_node(
+    config: &Config,
+    identity: Arc<identity::KeyPair>,
+    address: SocketAddr,
+    node_identity: identity::PublicKey,
+) -> Result<Measurement, RttError> {
+    let packet_sender = Arc::new(PacketSender::new(
+        identity,
+        config.packets_per_node,
+        config.packet_timeout,
+        config.connection_timeout,
+        config.delay_between_packets,
+    ));
+    packet_sender
+        .send_packets_to_node(TestedNode::new(address, node_identity))
+        .await
+}
+
 pub struct VerlocMeasurer {
     config: Config,
     packet_sender: Arc<PacketSender>,
@@ -196,8 +229,8 @@ impl VerlocMeasurer {
             validator_client: validator_client::ApiClient::new(
                 config.validator_api_urls[0].clone(),
             ),
+            results: AtomicVerlocResult::new(config.history_retention),
             config,
-            results: AtomicVerlocResult::new(),
         }
     }
 
@@ -15,6 +15,10 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::time::sleep;
 
+/// Number of subsequent echo packets without a reply after which the node is considered
+/// unresponsive and the rest of the packets are not sent at all.
+const MAX_CONSECUTIVE_LOST_PACKETS: usize = 10;
+
 #[derive(Copy, Clone)]
 pub(crate) struct TestedNode {
     pub(crate) address: SocketAddr,
@@ -64,6 +68,39 @@ impl PacketSender {
         }
     }
 
+    // note: this is cancellation safe as all of the read state is kept in `buf` and `filled`
+    async fn read_reply_packet(
+        conn: &mut TcpStream,
+        buf: &mut [u8; ReplyPacket::SIZE],
+        filled: &mut usize,
+        tested_node: &TestedNode,
+    ) -> Result<ReplyPacket, RttError> {
+        while *filled < ReplyPacket::SIZE {
+            match conn.read(&mut buf[*filled..]).await {
+                Ok(0) => {
+                    return Err(RttError::UnexpectedConnectionFailureRead(
+                        tested_node.identity.to_base58_string(),
+                        io::ErrorKind::UnexpectedEof.into(),
+                    ))
+                }
+                Ok(n) => *filled += n,
+                Err(err) => {
+                    debug!(
+                        "failed to read reply packet from {} - {}. Stopping the test.",
+                        tested_node.identity.to_base58_string(),
+                        err
+                    );
+                    return Err(RttError::UnexpectedConnectionFailureRead(
+                        tested_node.identity.to_base58_string(),
+                        err,
+                    ));
+                }
+            }
+        }
+        *filled = 0;
+        ReplyPacket::try_from_bytes(buf, &tested_node.identity)
+    }
+
     // TODO: split this function
     pub(super) async fn send_packets_to_node(
         self: Arc<Self>,
@@ -92,7 +129,15 @@ impl PacketSender {
 
         let mut results = Vec::with_capacity(self.packets_per_node);
 
-        let mut seq = self.random_sequence_number();
+        // the read buffer lives outside the timed out futures, so that if a reply only partially
+        // arrives before the timeout, its remaining bytes are not mistaken for the next reply
+        let mut buf = [0u8; ReplyPacket::SIZE];
+        let mut filled = 0;
+
+        let first_seq = self.random_sequence_number();
+        let mut seq = first_seq;
+        let mut packets_sent = 0;
+        let mut consecutive_lost = 0;
         for _ in 0..self.packets_per_node {
             let packet = EchoPacket::new(seq, &self.identity);
             let start = tokio::time::Instant::now();
@@ -126,59 +171,78 @@ impl PacketSender {
                         err,
                     ));
                 }
-                Ok(Ok(_)) => {}
+                Ok(Ok(_)) => packets_sent += 1,
             }
 
             // there's absolutely no need to put a codec on ReplyPackets as we know exactly
             // when and how many we expect to receive and can easily deal with any io errors.
             let reply_packet_future = async {
-                let mut buf = [0u8; ReplyPacket::SIZE];
-                if let Err(err) = conn.read_exact(&mut buf).await {
-                    debug!(
-                        "failed to read reply packet from {} - {}. Stopping the test.",
-                        tested_node.identity.to_base58_string(),
-                        err
-                    );
-                    return Err(RttError::UnexpectedConnectionFailureRead(
-                        tested_node.identity.to_base58_string(),
-                        err,
-                    ));
+                loop {
+                    let reply_packet =
+                        Self::read_reply_packet(&mut conn, &mut buf, &mut filled, &tested_node)
+                            .await?;
+                    // replies to packets we have already considered lost might still arrive late
+                    if reply_packet.base_sequence_number() >= first_seq
+                        && reply_packet.base_sequence_number() < seq
+                    {
+                        trace!(
+                            "Received late reply packet with sequence number {}",
+                            reply_packet.base_sequence_number()
+                        );
+                        continue;
+                    }
+                    return Ok(reply_packet);
                 }
-                ReplyPacket::try_from_bytes(&buf, &tested_node.identity)
             };
 
-            let reply_packet =
-                match tokio::time::timeout(self.packet_timeout, reply_packet_future).await {
-                    Ok(reply_packet) => reply_packet,
-                    Err(_timeout) => {
-                        // TODO: should we continue regardless (with the rest of the packets, or abandon the whole thing?)
-                        // Note: if we decide to continue, it would increase the complexity of the whole thing
-                        debug!(
-                        "failed to receive reply to our echo packet within {:?}. Stopping the test",
+            match tokio::time::timeout(self.packet_timeout, reply_packet_future).await {
+                Err(_timeout) => {
+                    debug!(
+                        "failed to receive reply to our echo packet {} from {} within {:?}. Considering it lost",
+                        seq,
+                        tested_node.identity.to_base58_string(),
                         self.packet_timeout
                     );
-                        return Err(RttError::ConnectionReadTimeout(
-                            tested_node.identity.to_base58_string(),
-                        ));
+                    consecutive_lost += 1;
+                    if consecutive_lost >= MAX_CONSECUTIVE_LOST_PACKETS {
+                        debug!(
+                            "{} subsequent echo packets to {} got lost. Not sending any more",
+                            consecutive_lost,
+                            tested_node.identity.to_base58_string()
+                        );
+                        break;
+                    }
+                }
+                Ok(reply_packet) => {
+                    let reply_packet = reply_packet?;
+                    // make sure it's actually the expected packet...
+                    // note that we cannot receive newer packets as we are not sending a next packet until
+                    // we have either received the previous one or considered it lost
+                    if reply_packet.base_sequence_number() != seq {
+                        debug!("Received reply packet with invalid sequence number! Got {} expected {}. Stopping the test", reply_packet.base_sequence_number(), seq);
+                        return Err(RttError::UnexpectedReplySequence);
                     }
-                };
-
-            let reply_packet = reply_packet?;
-            // make sure it's actually the expected packet...
-            // note that we cannot receive packets not in order as we are not sending a next packet until
-            // we have received the previous one
-            if reply_packet.base_sequence_number() != seq {
-                debug!("Received reply packet with invalid sequence number! Got {} expected {}. Stopping the test", reply_packet.base_sequence_number(), seq);
-                return Err(RttError::UnexpectedReplySequence);
-            }
 
-            let time_taken = tokio::time::Instant::now().duration_since(start);
-            results.push(time_taken);
+                    let time_taken = tokio::time::Instant::now().duration_since(start);
+                    results.push(time_taken);
+                    consecutive_lost = 0;
+                }
+            }
 
             seq += 1;
             sleep(self.delay_between_packets).await;
         }
 
-        Ok(Measurement::new(&results))
+        if results.is_empty() {
+            debug!(
+                "did not receive any replies from {}. Stopping the test",
+                tested_node.identity.to_base58_string()
+            );
+            return Err(RttError::ConnectionReadTimeout(
+                tested_node.identity.to_base58_string(),
+            ));
+        }
+
+        Ok(Measurement::new(&results, packets_sent))
     }
 }
@@ -133,6 +133,8 @@ pub const DEFAULT_MIX_LISTENING_PORT: u16 = 1789;
 
 // 'GATEWAY'
 pub const DEFAULT_CLIENT_LISTENING_PORT: u16 = 9000;
+pub const DEFAULT_GATEWAY_HTTP_API_LISTENING_PORT: u16 = 8001;
+pub const DEFAULT_GATEWAY_ADMIN_API_LISTENING_PORT: u16 = 8002;
 
 // 'MIXNODE'
 pub const DEFAULT_VERLOC_LISTENING_PORT: u16 = 1790;
@@ -100,6 +100,12 @@ impl Theta {
     pub fn from_bytes(bytes: &[u8]) -> Result<Theta> {
         Theta::try_from(bytes)
     }
+
+    /// Base58-encoded blinded serial number (zeta) of the credential. It is unique
+    /// for each credential and hence it can be used to detect double spending.
+    pub fn blinded_serial_number_bs58(&self) -> String {
+        bs58::encode(self.blinded_serial_number.to_affine().to_compressed()).into_string()
+    }
 }
 
 impl Bytable for Theta {
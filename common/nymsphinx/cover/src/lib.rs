@@ -6,7 +6,9 @@ use crypto::symmetric::stream_cipher;
 use nymsphinx_acknowledgements::surb_ack::SurbAck;
 use nymsphinx_acknowledgements::AckKey;
 use nymsphinx_addressing::clients::Recipient;
-use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, NymNodeRoutingAddressError};
+use nymsphinx_addressing::nodes::{
+    NymNodeRoutingAddress, NymNodeRoutingAddressError, MAX_NODE_ADDRESS_UNPADDED_LEN,
+};
 use nymsphinx_chunking::fragment::COVER_FRAG_ID;
 use nymsphinx_forwarding::packet::MixPacket;
 use nymsphinx_params::packet_sizes::PacketSize;
@@ -14,14 +16,19 @@ use nymsphinx_params::{
     PacketEncryptionAlgorithm, PacketHkdfAlgorithm, PacketMode, DEFAULT_NUM_MIX_HOPS,
 };
 use nymsphinx_types::builder::SphinxPacketBuilder;
-use nymsphinx_types::{delays, Error as SphinxError};
+use nymsphinx_types::{delays, Delay, Destination, Error as SphinxError};
 use rand::{CryptoRng, RngCore};
 use std::convert::TryFrom;
 use std::time;
-use topology::{NymTopology, NymTopologyError};
+use topology::{mix, NymTopology, NymTopologyError};
 
 pub const LOOP_COVER_MESSAGE_PAYLOAD: &[u8] = b"The cake is a lie!";
 
+/// Prefix of the messages carried by the loop cover packets that mixnodes send to themselves.
+pub const MIX_LOOP_COVER_MESSAGE_PAYLOAD: &[u8] = b"The mix is a loop!";
+
+const MIX_LOOP_ID_LENGTH: usize = std::mem::size_of::<u64>();
+
 #[derive(Debug)]
 pub enum CoverMessageError {
     NoValidProvidersError,
@@ -139,6 +146,77 @@ where
     Ok(MixPacket::new(first_hop_address, packet, PacketMode::Mix))
 }
 
+/// Generates a loop cover packet that goes through a random mixnode on each of the other layers
+/// before coming back to the provided mixnode, which is meant to be the sender itself.
+/// The packet carries the `loop_id` that can be retrieved with [`recover_mix_loop_id`] once
+/// the packet comes back, so that the mixnode could tell which of its loops got lost.
+pub fn generate_mix_loop_cover_packet<R>(
+    rng: &mut R,
+    topology: &NymTopology,
+    mix_node: &mix::Node,
+    loop_id: u64,
+    average_packet_delay: time::Duration,
+) -> Result<MixPacket, CoverMessageError>
+where
+    R: RngCore + CryptoRng,
+{
+    let destination = Destination::new(
+        mix_node.identity_key.derive_destination_address(),
+        Default::default(),
+    );
+
+    // the final hop of every regular packet has to contain an ack, so put a dummy one,
+    // addressed to the node itself, in there. It's going to be dropped upon receiving the loop.
+    let own_address = NymNodeRoutingAddress::from(mix_node.mix_host);
+    let dummy_ack = SphinxPacketBuilder::new()
+        .with_payload_size(PacketSize::AckPacket.payload_size())
+        .build_packet(
+            MIX_LOOP_COVER_MESSAGE_PAYLOAD.to_vec(),
+            &[mix_node.into()],
+            &destination,
+            &[Delay::new_from_nanos(0)],
+        )?;
+
+    // combine it together as follows:
+    // ACK_FIRST_HOP || ACK_DATA || MIX_LOOP_COVER_MESSAGE_PAYLOAD || LOOP_ID || PADDING
+    let packet_payload: Vec<_> = own_address
+        .as_zero_padded_bytes(MAX_NODE_ADDRESS_UNPADDED_LEN)
+        .into_iter()
+        .chain(dummy_ack.to_bytes().into_iter())
+        .chain(MIX_LOOP_COVER_MESSAGE_PAYLOAD.iter().cloned())
+        .chain(loop_id.to_be_bytes().iter().cloned())
+        .chain(std::iter::repeat(0))
+        .take(PacketSize::default().plaintext_size())
+        .collect();
+
+    let route = topology.random_loop_route(rng, DEFAULT_NUM_MIX_HOPS, mix_node)?;
+    let delays = delays::generate_from_average_duration(route.len(), average_packet_delay);
+
+    let packet = SphinxPacketBuilder::new()
+        .with_payload_size(PacketSize::default().payload_size())
+        .build_packet(packet_payload, &route, &destination, &delays)?;
+
+    // the route always contains at least the node itself
+    let first_hop_address = NymNodeRoutingAddress::try_from(route.first().unwrap().address)?;
+
+    Ok(MixPacket::new(first_hop_address, packet, PacketMode::Mix))
+}
+
+/// Recovers the id of the loop out of the message (i.e. with the ack already removed) of
+/// a received mixnode loop cover packet. Returns `None` if it isn't a mixnode loop message.
+pub fn recover_mix_loop_id(message: &[u8]) -> Option<u64> {
+    if message.len() < MIX_LOOP_COVER_MESSAGE_PAYLOAD.len() + MIX_LOOP_ID_LENGTH
+        || !message.starts_with(MIX_LOOP_COVER_MESSAGE_PAYLOAD)
+    {
+        return None;
+    }
+
+    let mut loop_id = [0u8; MIX_LOOP_ID_LENGTH];
+    let id_start = MIX_LOOP_COVER_MESSAGE_PAYLOAD.len();
+    loop_id.copy_from_slice(&message[id_start..id_start + MIX_LOOP_ID_LENGTH]);
+    Some(u64::from_be_bytes(loop_id))
+}
+
 /// Helper function used to determine if given message represents a loop cover message.
 // It kinda seems like there must exist "prefix" or "starts_with" method for bytes
 // or something, but I couldn't find anything
@@ -198,4 +276,26 @@ mod tests {
         let empty = Vec::new();
         assert!(!is_cover(&empty))
     }
+
+    #[test]
+    fn mix_loop_id_is_recovered_from_padded_message() {
+        let message: Vec<_> = MIX_LOOP_COVER_MESSAGE_PAYLOAD
+            .iter()
+            .cloned()
+            .chain(1234u64.to_be_bytes().iter().cloned())
+            .chain(std::iter::repeat(0).take(100))
+            .collect();
+        assert_eq!(Some(1234), recover_mix_loop_id(&message))
+    }
+
+    #[test]
+    fn mix_loop_id_is_not_recovered_from_unrelated_or_truncated_message() {
+        let mut client_loop = LOOP_COVER_MESSAGE_PAYLOAD.to_vec();
+        client_loop.extend_from_slice(&1234u64.to_be_bytes());
+        assert_eq!(None, recover_mix_loop_id(&client_loop));
+
+        let mut truncated = MIX_LOOP_COVER_MESSAGE_PAYLOAD.to_vec();
+        truncated.extend_from_slice(&[0; MIX_LOOP_ID_LENGTH - 1]);
+        assert_eq!(None, recover_mix_loop_id(&truncated))
+    }
 }
@@ -11,6 +11,7 @@ use std::collections::HashMap;
 pub struct OrderedMessageBuffer {
     next_index: u64,
     messages: HashMap<u64, OrderedMessage>,
+    buffered_bytes: usize,
 }
 
 impl OrderedMessageBuffer {
@@ -18,9 +19,16 @@ impl OrderedMessageBuffer {
         OrderedMessageBuffer {
             next_index: 0,
             messages: HashMap::new(),
+            buffered_bytes: 0,
         }
     }
 
+    /// Returns the total number of data bytes currently held in the buffer, i.e. data that
+    /// has been written, but cannot be read yet due to gaps in the sequence.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
     /// Writes a message to the buffer. messages are sort on insertion, so
     /// that later on multiple reads for incomplete sequences don't result in
     /// useless sort work.
@@ -31,7 +39,10 @@ impl OrderedMessageBuffer {
             message.data.len()
         );
 
-        self.messages.insert(message.index, message);
+        self.buffered_bytes += message.data.len();
+        if let Some(replaced) = self.messages.insert(message.index, message) {
+            self.buffered_bytes -= replaced.data.len();
+        }
     }
 
     /// Returns `Option<Vec<u8>>` where it's `Some(bytes)` if there is gapless
@@ -64,6 +75,7 @@ impl OrderedMessageBuffer {
             .into_iter()
             .flat_map(|message| message.data)
             .collect();
+        self.buffered_bytes -= data.len();
 
         trace!("Returning {} bytes from ordered message buffer", data.len());
         Some(data)
@@ -301,6 +313,33 @@ mod test_chunking_and_reassembling {
                 assert!(buffer.read().is_some());
                 assert_eq!(buffer.next_index, 5)
             }
+
+            #[test]
+            fn keeps_track_of_buffered_bytes() {
+                let mut buffer = setup();
+                assert_eq!(buffer.buffered_bytes(), 12);
+
+                buffer.read();
+                assert_eq!(buffer.buffered_bytes(), 4);
+
+                // writing the same message twice does not count it twice
+                let three_message = OrderedMessage {
+                    data: vec![3, 3, 3, 3],
+                    index: 3,
+                };
+                buffer.write(three_message);
+                assert_eq!(buffer.buffered_bytes(), 4);
+
+                let two_message = OrderedMessage {
+                    data: vec![2, 2, 2, 2],
+                    index: 2,
+                };
+                buffer.write(two_message);
+                assert_eq!(buffer.buffered_bytes(), 8);
+
+                buffer.read();
+                assert_eq!(buffer.buffered_bytes(), 0);
+            }
         }
     }
 }
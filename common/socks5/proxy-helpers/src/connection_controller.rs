@@ -1,6 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::flow_control::FlowControl;
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::*;
@@ -30,27 +31,31 @@ pub type ControllerSender = mpsc::UnboundedSender<ControllerCommand>;
 pub type ControllerReceiver = mpsc::UnboundedReceiver<ControllerCommand>;
 
 pub enum ControllerCommand {
-    Insert(ConnectionId, ConnectionSender),
+    Insert(ConnectionId, ConnectionSender, FlowControl),
     Remove(ConnectionId),
     Send(ConnectionId, Vec<u8>, bool),
+    WindowUpdate(ConnectionId, u32),
 }
 
 struct ActiveConnection {
     is_closed: bool,
     connection_sender: Option<ConnectionSender>,
     ordered_buffer: OrderedMessageBuffer,
+    flow_control: FlowControl,
 }
 
 impl ActiveConnection {
-    fn write_to_buf(&mut self, payload: Vec<u8>) {
+    fn write_to_buf(&mut self, payload: Vec<u8>) -> usize {
         let ordered_message = match OrderedMessage::try_from_bytes(payload) {
             Ok(msg) => msg,
             Err(err) => {
                 error!("Malformed ordered message - {:?}", err);
-                return;
+                return 0;
             }
         };
+        let received = ordered_message.data.len();
         self.ordered_buffer.write(ordered_message);
+        received
     }
 
     fn read_from_buf(&mut self) -> Option<Vec<u8>> {
@@ -90,11 +95,17 @@ impl Controller {
         )
     }
 
-    fn insert_connection(&mut self, conn_id: ConnectionId, connection_sender: ConnectionSender) {
+    fn insert_connection(
+        &mut self,
+        conn_id: ConnectionId,
+        connection_sender: ConnectionSender,
+        flow_control: FlowControl,
+    ) {
         let active_connection = ActiveConnection {
             is_closed: false,
             connection_sender: Some(connection_sender),
             ordered_buffer: OrderedMessageBuffer::new(),
+            flow_control,
         };
         if let Some(_active_conn) = self.active_connections.insert(conn_id, active_connection) {
             error!("Received a duplicate 'Connect'!")
@@ -122,8 +133,9 @@ impl Controller {
 
     fn send_to_connection(&mut self, conn_id: ConnectionId, payload: Vec<u8>, is_closed: bool) {
         if let Some(active_connection) = self.active_connections.get_mut(&conn_id) {
+            let mut received = 0;
             if !payload.is_empty() {
-                active_connection.write_to_buf(payload);
+                received = active_connection.write_to_buf(payload);
             } else if !is_closed {
                 error!("Tried to write an empty message to a not-closing connection. Please let us know if you see this message");
             }
@@ -131,7 +143,17 @@ impl Controller {
             // remote socket getting closed!
             active_connection.is_closed |= is_closed;
 
-            if let Some(payload) = active_connection.read_from_buf() {
+            let read_payload = active_connection.read_from_buf();
+            active_connection.flow_control.record_received(
+                received,
+                active_connection.ordered_buffer.buffered_bytes(),
+                read_payload
+                    .as_ref()
+                    .map(|payload| payload.len())
+                    .unwrap_or_default(),
+            );
+
+            if let Some(payload) = read_payload {
                 if let Err(err) = active_connection
                     .connection_sender
                     .as_mut()
@@ -169,16 +191,30 @@ impl Controller {
         }
     }
 
+    fn update_connection_window(&mut self, conn_id: ConnectionId, credit: u32) {
+        if let Some(active_connection) = self.active_connections.get(&conn_id) {
+            active_connection.flow_control.grant_send_credit(credit)
+        } else {
+            debug!(
+                "Received a window update for a connection that doesn't exist ({})",
+                conn_id
+            )
+        }
+    }
+
     pub async fn run(&mut self) {
         while let Some(command) = self.receiver.next().await {
             match command {
                 ControllerCommand::Send(conn_id, data, is_closed) => {
                     self.send_to_connection(conn_id, data, is_closed)
                 }
-                ControllerCommand::Insert(conn_id, sender) => {
-                    self.insert_connection(conn_id, sender)
+                ControllerCommand::Insert(conn_id, sender, flow_control) => {
+                    self.insert_connection(conn_id, sender, flow_control)
                 }
                 ControllerCommand::Remove(conn_id) => self.remove_connection(conn_id),
+                ControllerCommand::WindowUpdate(conn_id, credit) => {
+                    self.update_connection_window(conn_id, credit)
+                }
             }
         }
     }
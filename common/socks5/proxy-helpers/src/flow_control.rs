@@ -0,0 +1,229 @@
+// Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Initial credit (in bytes) each side of the proxy implicitly grants to the other one
+/// when the connection is established. It is equal to the default receive window so that
+/// the remote does not have to wait for an explicit update before starting to send data.
+pub const DEFAULT_WINDOW_SIZE: u32 = 1024 * 1024; // 1MB
+
+#[derive(Debug)]
+struct FlowControlState {
+    /// Indicates whether the remote supports flow control. If it doesn't, it is never going to
+    /// send us any window updates (nor understand ours), so we must fall back to sending
+    /// and receiving without any limits.
+    enabled: bool,
+
+    /// Size of our receive window, i.e. the maximum amount of data, in bytes, we are willing
+    /// to hold in memory for this connection.
+    window_size: u32,
+
+    /// Amount of data, in bytes, we are still allowed to send to the remote. Note that it might
+    /// go (slightly) negative as the whole chunk read from the socket is sent at once.
+    send_credit: i64,
+
+    /// Total credit we have granted to the remote, including the implicit initial window.
+    granted: u64,
+
+    /// Total amount of data, in bytes, we have received from the remote.
+    received: u64,
+
+    /// Amount of data currently held in the `OrderedMessageBuffer` waiting for missing messages.
+    buffered: usize,
+
+    /// Amount of data that got released from the `OrderedMessageBuffer`,
+    /// but has not yet been written to the local socket.
+    unflushed: usize,
+}
+
+/// Credit-based flow control state of a single proxied connection. It is shared between
+/// the `Controller`, which sees every message received from the mix network, and the
+/// `ProxyRunner`, which reads from and writes to the actual socket.
+#[derive(Clone, Debug)]
+pub struct FlowControl {
+    state: Arc<Mutex<FlowControlState>>,
+    credit_notify: Arc<Notify>,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        FlowControl::new(DEFAULT_WINDOW_SIZE)
+    }
+}
+
+impl FlowControl {
+    /// Creates flow control state for a new connection with the specified receive window.
+    /// Since the remote implicitly starts with `DEFAULT_WINDOW_SIZE` worth of credit,
+    /// smaller windows are not supported.
+    pub fn new(window_size: u32) -> Self {
+        FlowControl {
+            state: Arc::new(Mutex::new(FlowControlState {
+                enabled: true,
+                window_size: window_size.max(DEFAULT_WINDOW_SIZE),
+                send_credit: DEFAULT_WINDOW_SIZE as i64,
+                granted: DEFAULT_WINDOW_SIZE as u64,
+                received: 0,
+                buffered: 0,
+                unflushed: 0,
+            })),
+            credit_notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Creates state for a connection with a remote that does not support flow control,
+    /// i.e. one that never limits how much data is being sent in either direction.
+    pub fn disabled() -> Self {
+        let flow_control = FlowControl::default();
+        flow_control.state.lock().unwrap().enabled = false;
+        flow_control
+    }
+
+    /// Creates flow control state for a new connection, either with the default receive window,
+    /// if the remote supports flow control, or with flow control disabled otherwise.
+    pub fn negotiated(remote_supports_flow_control: bool) -> Self {
+        if remote_supports_flow_control {
+            FlowControl::default()
+        } else {
+            FlowControl::disabled()
+        }
+    }
+
+    /// Increases our sending credit after the remote has sent us a window update.
+    pub(crate) fn grant_send_credit(&self, credit: u32) {
+        let mut state = self.state.lock().unwrap();
+        let had_credit = state.send_credit > 0;
+        state.send_credit += credit as i64;
+        if !had_credit && state.send_credit > 0 {
+            self.credit_notify.notify_one();
+        }
+    }
+
+    pub(crate) fn has_send_credit(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.enabled || state.send_credit > 0
+    }
+
+    pub(crate) fn consume_send_credit(&self, amount: usize) {
+        self.state.lock().unwrap().send_credit -= amount as i64;
+    }
+
+    /// Waits until the remote grants us more sending credit.
+    pub(crate) async fn send_credit_available(&self) {
+        self.credit_notify.notified().await
+    }
+
+    /// Records data that was received from the remote alongside the current occupancy of the
+    /// `OrderedMessageBuffer` and the amount of data it has just released for writing.
+    pub(crate) fn record_received(&self, received: usize, buffered: usize, released: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.received += received as u64;
+        state.buffered = buffered;
+        state.unflushed += released;
+    }
+
+    /// Records data that was written to the local socket and, if enough space got freed
+    /// in our receive window, returns the amount of new credit that should be granted to the remote.
+    pub(crate) fn record_flushed(&self, flushed: usize) -> Option<u32> {
+        let mut state = self.state.lock().unwrap();
+        if !state.enabled {
+            return None;
+        }
+        state.unflushed = state.unflushed.saturating_sub(flushed);
+
+        // whatever is still waiting in the buffers is occupying our window
+        let occupied = (state.buffered + state.unflushed) as u64;
+        let available = (state.window_size as u64).saturating_sub(occupied);
+
+        // and so does any credit that the remote has not used yet
+        let outstanding = state.granted.saturating_sub(state.received);
+        let grantable = available.saturating_sub(outstanding);
+
+        // don't spam the remote with tiny updates
+        if grantable < (state.window_size / 4) as u64 {
+            return None;
+        }
+
+        state.granted += grantable;
+        Some(grantable as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_credit_is_consumed_and_granted() {
+        let flow_control = FlowControl::new(DEFAULT_WINDOW_SIZE);
+        assert!(flow_control.has_send_credit());
+
+        flow_control.consume_send_credit(DEFAULT_WINDOW_SIZE as usize + 100);
+        assert!(!flow_control.has_send_credit());
+
+        flow_control.grant_send_credit(100);
+        assert!(!flow_control.has_send_credit());
+
+        flow_control.grant_send_credit(1);
+        assert!(flow_control.has_send_credit());
+    }
+
+    #[test]
+    fn disabled_flow_control_never_limits_nor_updates_the_window() {
+        let window = DEFAULT_WINDOW_SIZE as usize;
+        let flow_control = FlowControl::disabled();
+
+        flow_control.consume_send_credit(window * 10);
+        assert!(flow_control.has_send_credit());
+
+        flow_control.record_received(window * 10, 0, window * 10);
+        assert!(flow_control.record_flushed(window * 10).is_none());
+    }
+
+    #[test]
+    fn window_is_not_updated_until_enough_data_is_flushed() {
+        let window = DEFAULT_WINDOW_SIZE as usize;
+        let flow_control = FlowControl::default();
+        flow_control.record_received(window, 0, window);
+
+        assert!(flow_control.record_flushed(window / 10).is_none());
+        assert_eq!(
+            flow_control.record_flushed(window - window / 10),
+            Some(DEFAULT_WINDOW_SIZE)
+        );
+
+        // the remote has not used any of the new credit yet
+        assert!(flow_control.record_flushed(0).is_none());
+    }
+
+    #[test]
+    fn buffered_data_occupies_the_window() {
+        let window = DEFAULT_WINDOW_SIZE as usize;
+        let flow_control = FlowControl::default();
+
+        // we received everything, but half of it is stuck behind a missing message
+        flow_control.record_received(window, window / 2, window / 2);
+        assert_eq!(
+            flow_control.record_flushed(window / 2),
+            Some(DEFAULT_WINDOW_SIZE / 2)
+        );
+
+        // the gap got filled
+        flow_control.record_received(0, 0, window / 2);
+        assert_eq!(
+            flow_control.record_flushed(window / 2),
+            Some(DEFAULT_WINDOW_SIZE / 2)
+        );
+    }
+
+    #[test]
+    fn larger_windows_are_advertised_on_first_flush() {
+        let flow_control = FlowControl::new(DEFAULT_WINDOW_SIZE * 2);
+        flow_control.record_received(100, 0, 100);
+        assert_eq!(
+            flow_control.record_flushed(100),
+            Some(DEFAULT_WINDOW_SIZE + 100)
+        );
+    }
+}
@@ -4,6 +4,7 @@
 use super::MixProxySender;
 use super::SHUTDOWN_TIMEOUT;
 use crate::available_reader::AvailableReader;
+use crate::flow_control::FlowControl;
 use bytes::Bytes;
 use futures::FutureExt;
 use futures::StreamExt;
@@ -28,6 +29,7 @@ fn send_empty_close<F, S>(
         .unwrap();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn deal_with_data<F, S>(
     read_data: Option<io::Result<Bytes>>,
     local_destination_address: &str,
@@ -36,6 +38,7 @@ fn deal_with_data<F, S>(
     message_sender: &mut OrderedMessageSender,
     mix_sender: &MixProxySender<S>,
     adapter_fn: F,
+    flow_control: &FlowControl,
 ) -> bool
 where
     F: Fn(ConnectionId, Vec<u8>, bool) -> S,
@@ -60,6 +63,8 @@ where
         is_finished
     );
 
+    flow_control.consume_send_credit(read_data.len());
+
     // if we're sending through the mixnet increase the sequence number...
     let ordered_msg = message_sender.wrap_message(read_data.to_vec()).into_bytes();
     mix_sender
@@ -74,6 +79,7 @@ where
     is_finished
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn run_inbound<F, S>(
     mut reader: OwnedReadHalf,
     local_destination_address: String, // addresses are provided for better logging
@@ -81,6 +87,7 @@ pub(super) async fn run_inbound<F, S>(
     connection_id: ConnectionId,
     mix_sender: MixProxySender<S>,
     adapter_fn: F,
+    flow_control: FlowControl,
     shutdown_notify: Arc<Notify>,
 ) -> OwnedReadHalf
 where
@@ -93,12 +100,17 @@ where
     tokio::pin!(shutdown_future);
 
     loop {
+        // stop reading from the socket if the remote can't accept any more data
+        let has_credit = flow_control.has_send_credit();
         select! {
-            read_data = &mut available_reader.next() => {
-                if deal_with_data(read_data, &local_destination_address, &remote_source_address, connection_id, &mut message_sender, &mix_sender, &adapter_fn) {
+            read_data = &mut available_reader.next(), if has_credit => {
+                if deal_with_data(read_data, &local_destination_address, &remote_source_address, connection_id, &mut message_sender, &mix_sender, &adapter_fn, &flow_control) {
                     break
                 }
             }
+            _ = flow_control.send_credit_available(), if !has_credit => {
+                trace!(target: &*format!("({}) socks5 inbound", connection_id), "received more sending credit from the remote");
+            }
             _ = &mut shutdown_future => {
                 debug!("closing inbound proxy after outbound was closed {:?} ago", SHUTDOWN_TIMEOUT);
                 // inform remote just in case it was closed because of lack of heartbeat.
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::connection_controller::ConnectionReceiver;
+use crate::flow_control::FlowControl;
 use futures::channel::mpsc;
 use socks5_requests::ConnectionId;
 use std::{sync::Arc, time::Duration};
@@ -44,6 +45,10 @@ pub struct ProxyRunner<S> {
     local_destination_address: String,
     remote_source_address: String,
     connection_id: ConnectionId,
+
+    /// shared with the connection controller to keep track of available sending credit
+    /// and of free space in our receive window
+    flow_control: FlowControl,
 }
 
 impl<S> ProxyRunner<S>
@@ -57,6 +62,7 @@ where
         mix_receiver: ConnectionReceiver,
         mix_sender: MixProxySender<S>,
         connection_id: ConnectionId,
+        flow_control: FlowControl,
     ) -> Self {
         ProxyRunner {
             mix_receiver: Some(mix_receiver),
@@ -65,14 +71,18 @@ where
             local_destination_address,
             remote_source_address,
             connection_id,
+            flow_control,
         }
     }
 
     // The `adapter_fn` is used to transform whatever was read into appropriate
     // request/response as required by entity running particular side of the proxy.
-    pub async fn run<F>(mut self, adapter_fn: F) -> Self
+    // Similarly, the `window_update_fn` is used to produce request/response granting
+    // the remote additional sending credit.
+    pub async fn run<F, G>(mut self, adapter_fn: F, window_update_fn: G) -> Self
     where
         F: Fn(ConnectionId, Vec<u8>, bool) -> S + Send + 'static,
+        G: Fn(ConnectionId, u32) -> S + Send + 'static,
     {
         let (read_half, write_half) = self.socket.take().unwrap().into_split();
         let shutdown_notify = Arc::new(Notify::new());
@@ -85,6 +95,7 @@ where
             self.connection_id,
             self.mix_sender.clone(),
             adapter_fn,
+            self.flow_control.clone(),
             Arc::clone(&shutdown_notify),
         );
 
@@ -93,7 +104,10 @@ where
             self.local_destination_address.clone(),
             self.remote_source_address.clone(),
             self.mix_receiver.take().unwrap(),
+            self.mix_sender.clone(),
+            window_update_fn,
             self.connection_id,
+            self.flow_control.clone(),
             shutdown_notify,
         );
 
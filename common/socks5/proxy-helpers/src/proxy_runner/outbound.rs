@@ -1,8 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use super::MixProxySender;
 use super::SHUTDOWN_TIMEOUT;
 use crate::connection_controller::{ConnectionMessage, ConnectionReceiver};
+use crate::flow_control::FlowControl;
 use futures::FutureExt;
 use futures::StreamExt;
 use log::*;
@@ -43,14 +45,43 @@ async fn deal_with_message(
     false
 }
 
-pub(super) async fn run_outbound(
+fn update_remote_window<F, S>(
+    flushed: usize,
+    connection_id: ConnectionId,
+    mix_sender: &MixProxySender<S>,
+    window_update_fn: F,
+    flow_control: &FlowControl,
+) where
+    F: Fn(ConnectionId, u32) -> S,
+{
+    // the data is out of our buffers now, so the remote might be able to send us more
+    if let Some(credit) = flow_control.record_flushed(flushed) {
+        trace!(target: &*format!("({}) socks5 outbound", connection_id), "granting {} bytes of additional credit to the remote", credit);
+        if mix_sender
+            .unbounded_send(window_update_fn(connection_id, credit))
+            .is_err()
+        {
+            // this can only happen if we're shutting down
+            warn!(target: &*format!("({}) socks5 outbound", connection_id), "failed to send window update to the remote");
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn run_outbound<F, S>(
     mut writer: OwnedWriteHalf,
     local_destination_address: String, // addresses are provided for better logging
     remote_source_address: String,
     mut mix_receiver: ConnectionReceiver,
+    mix_sender: MixProxySender<S>,
+    window_update_fn: F,
     connection_id: ConnectionId,
+    flow_control: FlowControl,
     shutdown_notify: Arc<Notify>,
-) -> (OwnedWriteHalf, ConnectionReceiver) {
+) -> (OwnedWriteHalf, ConnectionReceiver)
+where
+    F: Fn(ConnectionId, u32) -> S + Send + 'static,
+{
     let shutdown_future = shutdown_notify.notified().then(|_| sleep(SHUTDOWN_TIMEOUT));
     tokio::pin!(shutdown_future);
 
@@ -60,9 +91,11 @@ pub(super) async fn run_outbound(
         select! {
             connection_message = &mut mix_receiver.next() => {
                 if let Some(connection_message) = connection_message {
+                    let flushed = connection_message.payload.len();
                     if deal_with_message(connection_message, &mut writer, &local_destination_address, &remote_source_address, connection_id).await {
                         break;
                     }
+                    update_remote_window(flushed, connection_id, &mix_sender, &window_update_fn, &flow_control);
                     mix_timeout.as_mut().reset(Instant::now() + MIX_TTL);
                 } else {
                     warn!("mix receiver is none so we already got removed somewhere. This isn't really a warning, but shouldn't happen to begin with, so please say if you see this message");
@@ -10,8 +10,15 @@ pub type RemoteAddress = String;
 pub enum RequestFlag {
     Connect = 0,
     Send = 1,
+    WindowUpdate = 2,
 }
 
+/// Optional features supported by the requesting application, sent as a single byte following
+/// the return address of a `Connect` request. The byte is omitted altogether if none of them
+/// are supported, so that such requests remain readable by older service providers.
+pub const FLOW_CONTROL_CAPABILITY: u8 = 0b0000_0001;
+pub const ERROR_RESPONSES_CAPABILITY: u8 = 0b0000_0010;
+
 #[derive(Debug)]
 pub enum RequestError {
     AddressLengthTooShort,
@@ -19,6 +26,7 @@ pub enum RequestError {
     ConnectionIdTooShort,
     NoData,
     UnknownRequestFlag,
+    WindowUpdateTooShort,
     ReturnAddressTooShort,
     MalformedReturnAddress(RecipientFormattingError),
 }
@@ -35,6 +43,9 @@ impl fmt::Display for RequestError {
             }
             RequestError::NoData => write!(f, "no data provided"),
             RequestError::UnknownRequestFlag => write!(f, "request of unknown type"),
+            RequestError::WindowUpdateTooShort => {
+                write!(f, "not enough bytes to recover the window update")
+            }
             RequestError::ReturnAddressTooShort => write!(f, "too short return address"),
             RequestError::MalformedReturnAddress(recipient_err) => {
                 write!(f, "malformed return address - {}", recipient_err)
@@ -58,6 +69,7 @@ impl TryFrom<u8> for RequestFlag {
         match value {
             _ if value == (RequestFlag::Connect as u8) => Ok(Self::Connect),
             _ if value == (RequestFlag::Send as u8) => Ok(Self::Send),
+            _ if value == (RequestFlag::WindowUpdate as u8) => Ok(Self::WindowUpdate),
             _ => Err(RequestError::UnknownRequestFlag),
         }
     }
@@ -68,6 +80,15 @@ pub struct ConnectRequest {
     pub conn_id: ConnectionId,
     pub remote_addr: RemoteAddress,
     pub return_address: Recipient,
+
+    /// Indicates whether the requesting application supports credit-based flow control
+    /// on this connection, i.e. whether it is going to send (and expects to receive)
+    /// window updates.
+    pub flow_control: bool,
+
+    /// Indicates whether the requesting application understands `ResponseMessage::Error`.
+    /// Otherwise refused connections must be reported by simply closing them.
+    pub error_responses: bool,
 }
 
 /// A request from a SOCKS5 client that a Nym Socks5 service provider should
@@ -81,6 +102,10 @@ pub enum Request {
 
     /// Re-use an existing TCP connection, sending more request data up it.
     Send(ConnectionId, Vec<u8>, bool),
+
+    /// Grant the service provider additional credit (in bytes) for sending data
+    /// back on the specified `ConnectionId`.
+    WindowUpdate(ConnectionId, u32),
 }
 
 impl Request {
@@ -89,11 +114,15 @@ impl Request {
         conn_id: ConnectionId,
         remote_addr: RemoteAddress,
         return_address: Recipient,
+        flow_control: bool,
+        error_responses: bool,
     ) -> Request {
         Request::Connect(Box::new(ConnectRequest {
             conn_id,
             remote_addr,
             return_address,
+            flow_control,
+            error_responses,
         }))
     }
 
@@ -102,6 +131,11 @@ impl Request {
         Request::Send(conn_id, data, local_closed)
     }
 
+    /// Construct a new Request::WindowUpdate instance
+    pub fn new_window_update(conn_id: ConnectionId, credit: u32) -> Request {
+        Request::WindowUpdate(conn_id, credit)
+    }
+
     /// Deserialize the request type, connection id, destination address and port,
     /// and the request body from bytes.
     ///
@@ -112,9 +146,13 @@ impl Request {
     ///        1      |       8       |      2         |    address_length    |    ...       |
     /// --------------------------------------------------------------------------------------
     ///
+    /// where `request_data` of a connect request is the return address, optionally followed
+    /// by a single byte of capabilities (such as `FLOW_CONTROL_CAPABILITY`).
+    ///
     /// The request_flag tells us whether this is a new connection request (`new_connect`),
-    /// an already-established connection we should send up (`new_send`), or
-    /// a request to close an established connection (`new_close`).
+    /// an already-established connection we should send up (`new_send`),
+    /// a request to close an established connection (`new_close`) or
+    /// additional sending credit for an established connection (`new_window_update`).
     pub fn try_from_bytes(b: &[u8]) -> Result<Request, RequestError> {
         // each request needs to at least contain flag and ConnectionId
         if b.is_empty() {
@@ -150,9 +188,12 @@ impl Request {
                 // just a temporary reference to mid-slice for ease of use
                 let recipient_data_bytes = &connect_request_bytes[address_end..];
 
-                if recipient_data_bytes.len() != Recipient::LEN {
-                    return Err(RequestError::ReturnAddressTooShort);
-                }
+                // older clients do not send the capabilities byte at all
+                let capabilities = match recipient_data_bytes.len() {
+                    len if len == Recipient::LEN => 0,
+                    len if len == Recipient::LEN + 1 => recipient_data_bytes[Recipient::LEN],
+                    _ => return Err(RequestError::ReturnAddressTooShort),
+                };
 
                 let mut return_bytes = [0u8; Recipient::LEN];
                 return_bytes.copy_from_slice(&recipient_data_bytes[..Recipient::LEN]);
@@ -163,6 +204,8 @@ impl Request {
                     connection_id,
                     remote_address,
                     return_address,
+                    capabilities & FLOW_CONTROL_CAPABILITY != 0,
+                    capabilities & ERROR_RESPONSES_CAPABILITY != 0,
                 ))
             }
             RequestFlag::Send => {
@@ -171,6 +214,14 @@ impl Request {
 
                 Ok(Request::Send(connection_id, data, local_closed))
             }
+            RequestFlag::WindowUpdate => {
+                if b.len() != 13 {
+                    return Err(RequestError::WindowUpdateTooShort);
+                }
+                let credit = u32::from_be_bytes([b[9], b[10], b[11], b[12]]);
+
+                Ok(Request::WindowUpdate(connection_id, credit))
+            }
         }
     }
 
@@ -179,16 +230,20 @@ impl Request {
     /// service provider which will make the request.
     pub fn into_bytes(self) -> Vec<u8> {
         match self {
-            // connect is: CONN_FLAG || CONN_ID || REMOTE_LEN || REMOTE || RETURN
+            // connect is: CONN_FLAG || CONN_ID || REMOTE_LEN || REMOTE || RETURN || [CAPABILITIES]
             Request::Connect(req) => {
                 let remote_address_bytes = req.remote_addr.into_bytes();
                 let remote_address_bytes_len = remote_address_bytes.len() as u16;
+                let capabilities = (req.flow_control as u8 * FLOW_CONTROL_CAPABILITY)
+                    | (req.error_responses as u8 * ERROR_RESPONSES_CAPABILITY);
+                let capabilities = (capabilities != 0).then(|| capabilities);
 
                 std::iter::once(RequestFlag::Connect as u8)
                     .chain(req.conn_id.to_be_bytes().iter().cloned())
                     .chain(remote_address_bytes_len.to_be_bytes().iter().cloned())
                     .chain(remote_address_bytes.into_iter())
                     .chain(req.return_address.to_bytes().iter().cloned())
+                    .chain(capabilities.into_iter())
                     .collect()
             }
             Request::Send(conn_id, data, local_closed) => std::iter::once(RequestFlag::Send as u8)
@@ -196,6 +251,13 @@ impl Request {
                 .chain(std::iter::once(local_closed as u8))
                 .chain(data.into_iter())
                 .collect(),
+            // window update is: WINDOW_UPDATE_FLAG || CONN_ID || CREDIT
+            Request::WindowUpdate(conn_id, credit) => {
+                std::iter::once(RequestFlag::WindowUpdate as u8)
+                    .chain(conn_id.to_be_bytes().iter().cloned())
+                    .chain(credit.to_be_bytes().iter().cloned())
+                    .collect()
+            }
         }
     }
 }
@@ -426,6 +488,56 @@ mod request_deserialization_tests {
                         req.return_address.to_bytes().to_vec(),
                         recipient.to_bytes().to_vec()
                     );
+                    assert!(!req.flow_control);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn flow_control_capability_is_optional() {
+            let recipient = Recipient::try_from_base58_string("CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f").unwrap();
+
+            // without the capability the request is identical to what older clients used to send
+            let legacy_bytes =
+                Request::new_connect(42, "foo.com".to_string(), recipient, false, false)
+                    .into_bytes();
+            assert_eq!(1 + 8 + 2 + 7 + Recipient::LEN, legacy_bytes.len());
+            match Request::try_from_bytes(&legacy_bytes).unwrap() {
+                Request::Connect(req) => {
+                    assert!(!req.flow_control);
+                    assert!(!req.error_responses);
+                }
+                _ => unreachable!(),
+            }
+
+            let request_bytes =
+                Request::new_connect(42, "foo.com".to_string(), recipient, true, false)
+                    .into_bytes();
+            assert_eq!(legacy_bytes.len() + 1, request_bytes.len());
+            match Request::try_from_bytes(&request_bytes).unwrap() {
+                Request::Connect(req) => {
+                    assert_eq!("foo.com".to_string(), req.remote_addr);
+                    assert_eq!(42, req.conn_id);
+                    assert!(req.flow_control);
+                    assert!(!req.error_responses);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn error_responses_capability_is_independent_of_flow_control() {
+            let recipient = Recipient::try_from_base58_string("CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f").unwrap();
+
+            let request_bytes =
+                Request::new_connect(42, "foo.com".to_string(), recipient, false, true)
+                    .into_bytes();
+            assert_eq!(Some(&ERROR_RESPONSES_CAPABILITY), request_bytes.last());
+            match Request::try_from_bytes(&request_bytes).unwrap() {
+                Request::Connect(req) => {
+                    assert!(!req.flow_control);
+                    assert!(req.error_responses);
                 }
                 _ => unreachable!(),
             }
@@ -482,4 +594,46 @@ mod request_deserialization_tests {
             }
         }
     }
+
+    #[cfg(test)]
+    mod updating_window_of_an_existing_connection {
+        use super::*;
+
+        #[test]
+        fn returns_error_when_credit_is_too_short() {
+            // correct 8 bytes of connection_id, 3 bytes of credit (4 were expected)
+            let request_bytes = [
+                RequestFlag::WindowUpdate as u8,
+                1,
+                2,
+                3,
+                4,
+                5,
+                6,
+                7,
+                8,
+                0,
+                0,
+                1,
+            ]
+            .to_vec();
+            match Request::try_from_bytes(&request_bytes).unwrap_err() {
+                RequestError::WindowUpdateTooShort => {}
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn works_when_request_is_sized_properly() {
+            let request_bytes = Request::new_window_update(42, 1024).into_bytes();
+            let request = Request::try_from_bytes(&request_bytes).unwrap();
+            match request {
+                Request::WindowUpdate(conn_id, credit) => {
+                    assert_eq!(42, conn_id);
+                    assert_eq!(1024, credit);
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
 }
@@ -1,10 +1,148 @@
 use crate::ConnectionId;
+use std::fmt;
 
 #[derive(Debug, PartialEq)]
 pub enum ResponseError {
     ConnectionIdTooShort,
     NoData,
+    WindowUpdateTooShort,
+    ErrorResponseTooShort,
+    UnknownErrorKind(u8),
 }
+
+/// Data responses are prefixed with their `is_closed` flag, hence any leading byte other than
+/// `0` or `1` can be used to indicate a different type of message. Note that older clients
+/// would interpret such messages as closing data responses, so they must only be sent
+/// to the clients that have advertised support for them.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+pub enum ResponseFlag {
+    WindowUpdate = 2,
+    Error = 3,
+}
+
+/// Reasons for the Socks5 service provider to refuse handling a request on a particular connection.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The client has reached the maximum number of concurrently open connections.
+    TooManyConnections = 1,
+
+    /// The client has opened too many new connections within the last minute.
+    ConnectionRateExceeded = 2,
+
+    /// The client has sent or received too much data within the current bandwidth window.
+    BandwidthExceeded = 3,
+}
+
+impl TryFrom<u8> for ErrorKind {
+    type Error = ResponseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ErrorKind::TooManyConnections),
+            2 => Ok(ErrorKind::ConnectionRateExceeded),
+            3 => Ok(ErrorKind::BandwidthExceeded),
+            value => Err(ResponseError::UnknownErrorKind(value)),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::TooManyConnections => write!(f, "too many concurrent connections"),
+            ErrorKind::ConnectionRateExceeded => write!(f, "too many new connections per minute"),
+            ErrorKind::BandwidthExceeded => write!(f, "bandwidth limit exceeded"),
+        }
+    }
+}
+
+/// A message sent back by the Socks5 service provider for a particular connection.
+#[derive(Debug)]
+pub enum ResponseMessage {
+    /// Data read from the remote connection.
+    Data(Response),
+
+    /// Additional credit (in bytes) granted to the requesting application for sending
+    /// data on the specified `ConnectionId`.
+    WindowUpdate(ConnectionId, u32),
+
+    /// The request on the specified `ConnectionId` got refused. The connection is closed
+    /// (or has never been opened) by the service provider.
+    Error(ConnectionId, ErrorKind),
+}
+
+impl ResponseMessage {
+    pub fn new_window_update(connection_id: ConnectionId, credit: u32) -> Self {
+        ResponseMessage::WindowUpdate(connection_id, credit)
+    }
+
+    pub fn new_error(connection_id: ConnectionId, kind: ErrorKind) -> Self {
+        ResponseMessage::Error(connection_id, kind)
+    }
+
+    pub fn try_from_bytes(b: &[u8]) -> Result<ResponseMessage, ResponseError> {
+        if b.is_empty() {
+            return Err(ResponseError::NoData);
+        }
+
+        if b[0] == ResponseFlag::WindowUpdate as u8 {
+            if b.len() < 9 {
+                return Err(ResponseError::ConnectionIdTooShort);
+            }
+            if b.len() != 13 {
+                return Err(ResponseError::WindowUpdateTooShort);
+            }
+            let connection_id =
+                u64::from_be_bytes([b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8]]);
+            let credit = u32::from_be_bytes([b[9], b[10], b[11], b[12]]);
+            Ok(ResponseMessage::WindowUpdate(connection_id, credit))
+        } else if b[0] == ResponseFlag::Error as u8 {
+            if b.len() < 9 {
+                return Err(ResponseError::ConnectionIdTooShort);
+            }
+            if b.len() != 10 {
+                return Err(ResponseError::ErrorResponseTooShort);
+            }
+            let connection_id =
+                u64::from_be_bytes([b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8]]);
+            let kind = ErrorKind::try_from(b[9])?;
+            Ok(ResponseMessage::Error(connection_id, kind))
+        } else {
+            Response::try_from_bytes(b).map(ResponseMessage::Data)
+        }
+    }
+
+    /// Serializes the message into bytes so that it can be sent back through
+    /// the mixnet to the requesting application.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            ResponseMessage::Data(response) => response.into_bytes(),
+            // window update is: WINDOW_UPDATE_FLAG || CONN_ID || CREDIT
+            ResponseMessage::WindowUpdate(connection_id, credit) => {
+                std::iter::once(ResponseFlag::WindowUpdate as u8)
+                    .chain(connection_id.to_be_bytes().iter().cloned())
+                    .chain(credit.to_be_bytes().iter().cloned())
+                    .collect()
+            }
+            // error is: ERROR_FLAG || CONN_ID || ERROR_KIND
+            ResponseMessage::Error(connection_id, kind) => {
+                std::iter::once(ResponseFlag::Error as u8)
+                    .chain(connection_id.to_be_bytes().iter().cloned())
+                    .chain(std::iter::once(kind as u8))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl From<Response> for ResponseMessage {
+    fn from(response: Response) -> Self {
+        ResponseMessage::Data(response)
+    }
+}
+
 /// A remote network response retrieved by the Socks5 service provider. This
 /// can be serialized and sent back through the mixnet to the requesting
 /// application.
@@ -114,4 +252,72 @@ mod constructing_socks5_responses_from_bytes {
         assert_eq!(expected.data, actual.data);
         assert_eq!(expected.is_closed, actual.is_closed);
     }
+
+    #[test]
+    fn data_messages_are_compatible_with_plain_responses() {
+        let response_bytes = vec![1, 0, 1, 2, 3, 4, 5, 6, 7, 255, 255, 255];
+        match ResponseMessage::try_from_bytes(&response_bytes).unwrap() {
+            ResponseMessage::Data(response) => {
+                assert_eq!(
+                    u64::from_be_bytes([0, 1, 2, 3, 4, 5, 6, 7]),
+                    response.connection_id
+                );
+                assert_eq!(vec![255, 255, 255], response.data);
+                assert!(response.is_closed);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn window_update_fails_when_credit_bytes_are_too_short() {
+        let response_bytes = vec![ResponseFlag::WindowUpdate as u8, 0, 1, 2, 3, 4, 5, 6, 7, 1];
+        assert_eq!(
+            ResponseError::WindowUpdateTooShort,
+            ResponseMessage::try_from_bytes(&response_bytes).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn window_update_works() {
+        let response_bytes = ResponseMessage::new_window_update(42, 1024).into_bytes();
+        match ResponseMessage::try_from_bytes(&response_bytes).unwrap() {
+            ResponseMessage::WindowUpdate(connection_id, credit) => {
+                assert_eq!(42, connection_id);
+                assert_eq!(1024, credit);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn error_fails_when_kind_is_missing() {
+        let response_bytes = vec![ResponseFlag::Error as u8, 0, 1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(
+            ResponseError::ErrorResponseTooShort,
+            ResponseMessage::try_from_bytes(&response_bytes).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn error_fails_when_kind_is_unknown() {
+        let response_bytes = vec![ResponseFlag::Error as u8, 0, 1, 2, 3, 4, 5, 6, 7, 42];
+        assert_eq!(
+            ResponseError::UnknownErrorKind(42),
+            ResponseMessage::try_from_bytes(&response_bytes).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn error_works() {
+        let response_bytes =
+            ResponseMessage::new_error(42, ErrorKind::BandwidthExceeded).into_bytes();
+        match ResponseMessage::try_from_bytes(&response_bytes).unwrap() {
+            ResponseMessage::Error(connection_id, kind) => {
+                assert_eq!(42, connection_id);
+                assert_eq!(ErrorKind::BandwidthExceeded, kind);
+            }
+            _ => unreachable!(),
+        }
+    }
 }
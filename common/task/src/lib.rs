@@ -0,0 +1,6 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+pub mod shutdown;
+
+pub use shutdown::{wait_for_signal, ShutdownListener, ShutdownNotifier};
@@ -0,0 +1,180 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Coordination of a graceful shutdown of a node: the `ShutdownNotifier` tells all of the tasks
+//! holding a `ShutdownListener` to finish their work and then waits, up to a timeout, until all
+//! of them are done, i.e. until all of the listeners are dropped.
+
+use log::*;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// Waits until the process receives either SIGINT or, on unix systems, SIGTERM.
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => info!("Received SIGTERM"),
+                    res = tokio::signal::ctrl_c() => log_ctrl_c(res),
+                }
+                return;
+            }
+            Err(err) => error!("Failed to register the SIGTERM handler - {}", err),
+        }
+    }
+
+    log_ctrl_c(tokio::signal::ctrl_c().await)
+}
+
+fn log_ctrl_c(res: std::io::Result<()>) {
+    match res {
+        Ok(_) => info!("Received SIGINT"),
+        Err(err) => error!(
+            "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
+            err
+        ),
+    }
+}
+
+/// Announces the shutdown to all of the subscribed tasks.
+pub struct ShutdownNotifier {
+    notify_tx: watch::Sender<bool>,
+    notify_rx: watch::Receiver<bool>,
+
+    // every listener holds a clone of the sender, so once all of them are dropped,
+    // the receiver gets closed
+    done_tx: mpsc::Sender<()>,
+    done_rx: mpsc::Receiver<()>,
+}
+
+impl Default for ShutdownNotifier {
+    fn default() -> Self {
+        ShutdownNotifier::new()
+    }
+}
+
+impl ShutdownNotifier {
+    pub fn new() -> Self {
+        let (notify_tx, notify_rx) = watch::channel(false);
+        let (done_tx, done_rx) = mpsc::channel(1);
+
+        ShutdownNotifier {
+            notify_tx,
+            notify_rx,
+            done_tx,
+            done_rx,
+        }
+    }
+
+    /// Creates a listener for a task that should get notified about the shutdown. The shutdown
+    /// is not going to be considered complete until the listener, and all of its clones, are dropped.
+    pub fn subscribe(&self) -> ShutdownListener {
+        ShutdownListener {
+            notify_rx: self.notify_rx.clone(),
+            _done_tx: self.done_tx.clone(),
+        }
+    }
+
+    /// Notifies all of the listeners that they should finish their work.
+    pub fn signal_shutdown(&self) {
+        // the notifier holds a receiver itself, so this can't fail
+        let _ = self.notify_tx.send(true);
+    }
+
+    /// Notifies all of the listeners about the shutdown and waits until all of them are dropped
+    /// or until the timeout is reached. Returns whether all of the tasks managed to finish in time.
+    pub async fn shutdown(self, timeout: Duration) -> bool {
+        let ShutdownNotifier {
+            notify_tx,
+            notify_rx: _,
+            done_tx,
+            mut done_rx,
+        } = self;
+
+        let _ = notify_tx.send(true);
+        drop(done_tx);
+
+        // nothing is ever sent on the channel, it only gets closed once all listeners are gone
+        if tokio::time::timeout(timeout, done_rx.recv()).await.is_err() {
+            warn!(
+                "Not all tasks have finished within {:?}, some data might have been lost",
+                timeout
+            );
+            false
+        } else {
+            true
+        }
+    }
+}
+
+/// Allows the task to learn the node is shutting down. The task should drop it once it's done.
+#[derive(Clone)]
+pub struct ShutdownListener {
+    notify_rx: watch::Receiver<bool>,
+    _done_tx: mpsc::Sender<()>,
+}
+
+impl ShutdownListener {
+    /// Checks whether the shutdown has already been signalled.
+    pub fn is_shutdown(&self) -> bool {
+        *self.notify_rx.borrow()
+    }
+
+    /// Waits until the shutdown is signalled. Resolves immediately if it already has been.
+    pub async fn recv(&mut self) {
+        while !*self.notify_rx.borrow() {
+            if self.notify_rx.changed().await.is_err() {
+                // the notifier got dropped without signalling the shutdown. Treat it as
+                // the shutdown as nobody is going to be able to announce it anymore.
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn listeners_get_notified() {
+        let notifier = ShutdownNotifier::new();
+        let mut listener = notifier.subscribe();
+        let listener_clone = listener.clone();
+        assert!(!listener.is_shutdown());
+
+        notifier.signal_shutdown();
+        listener.recv().await;
+        assert!(listener_clone.is_shutdown());
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_listeners_to_be_dropped() {
+        let notifier = ShutdownNotifier::new();
+        let mut listener = notifier.subscribe();
+        let finished = Arc::new(AtomicBool::new(false));
+        let finished_clone = Arc::clone(&finished);
+        tokio::spawn(async move {
+            listener.recv().await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            finished_clone.store(true, Ordering::SeqCst);
+        });
+
+        assert!(notifier.shutdown(Duration::from_secs(5)).await);
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn shutdown_times_out_if_listeners_are_still_alive() {
+        let notifier = ShutdownNotifier::new();
+        let _listener = notifier.subscribe();
+
+        assert!(!notifier.shutdown(Duration::from_millis(10)).await);
+    }
+}
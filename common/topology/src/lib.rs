@@ -175,6 +175,69 @@ impl NymTopology {
             .collect())
     }
 
+    /// Creates a route that goes through a random mixnode on each of the remaining layers,
+    /// starting from the layer directly after the one of the provided mixnode, and finally back
+    /// to the mixnode itself, i.e. for a layer 2 node it's: layer 3 -> layer 1 -> layer 2 (self).
+    /// Gateways are skipped, as they only deliver packets to their clients and never relay them
+    /// back into the network.
+    pub fn random_loop_route<R>(
+        &self,
+        rng: &mut R,
+        num_mix_hops: u8,
+        mix_node: &mix::Node,
+    ) -> Result<Vec<SphinxNode>, NymTopologyError>
+    where
+        R: Rng + ?Sized,
+    {
+        use rand::seq::SliceRandom;
+
+        let own_layer = mix_node.layer as MixLayer;
+        if own_layer == 0 || own_layer > num_mix_hops {
+            return Err(NymTopologyError::InvalidMixLayerError);
+        }
+
+        let mut route = Vec::with_capacity(num_mix_hops as usize);
+        for offset in 1..num_mix_hops {
+            // layers are numbered from 1 so shift them before wrapping around
+            let layer = (own_layer - 1 + offset) % num_mix_hops + 1;
+            let random_mix = self
+                .mixes
+                .get(&layer)
+                .and_then(|layer_mixes| layer_mixes.choose(rng))
+                .ok_or(NymTopologyError::NoMixesOnLayerAvailable(layer))?;
+            route.push(random_mix.into());
+        }
+        route.push(mix_node.into());
+
+        Ok(route)
+    }
+
+    /// Returns mix addresses of all nodes that might send packets to a mixnode on the specified
+    /// layer: the mixnodes on the preceding layer and, for the first layer, also the gateways.
+    /// The layers wrap around, i.e. the last layer precedes the first one, as that's what the
+    /// routes of the mixnode loop cover packets look like.
+    pub fn previous_hops(
+        &self,
+        layer: MixLayer,
+        num_mix_hops: u8,
+    ) -> Result<Vec<SocketAddr>, NymTopologyError> {
+        if layer == 0 || layer > num_mix_hops {
+            return Err(NymTopologyError::InvalidMixLayerError);
+        }
+
+        let previous_layer = (layer + num_mix_hops - 2) % num_mix_hops + 1;
+        let mut hops = self
+            .mixes
+            .get(&previous_layer)
+            .map(|mixes| mixes.iter().map(|mix| mix.mix_host).collect())
+            .unwrap_or_else(Vec::new);
+        if layer == 1 {
+            hops.extend(self.gateways.iter().map(|gateway| gateway.mix_host));
+        }
+
+        Ok(hops)
+    }
+
     /// Overwrites the existing nodes in the specified layer
     pub fn set_mixes_in_layer(&mut self, layer: u8, mixes: Vec<mix::Node>) {
         self.mixes.insert(layer, mixes);
@@ -328,3 +391,115 @@ mod converting_mixes_to_vec {
         }
     }
 }
+
+#[cfg(test)]
+mod loop_routes {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+    use mixnet_contract_common::Layer;
+    use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
+    use std::convert::TryFrom;
+
+    fn mix_on_layer(layer: Layer) -> mix::Node {
+        let port = 1789 + layer as u16;
+        mix::Node {
+            owner: "N/A".to_string(),
+            stake: 0,
+            delegation: 0,
+            host: "3.3.3.3".parse().unwrap(),
+            mix_host: format!("3.3.3.3:{}", port).parse().unwrap(),
+            identity_key: identity::PublicKey::from_base58_string(
+                "3ebjp1Fb9hdcS1AR6AZihgeJiMHkB5jjJUsvqNnfQwU7",
+            )
+            .unwrap(),
+            sphinx_key: encryption::PublicKey::from_base58_string(
+                "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX",
+            )
+            .unwrap(),
+            layer,
+            version: "0.x.0".to_string(),
+        }
+    }
+
+    fn route_hosts(route: &[SphinxNode]) -> Vec<SocketAddr> {
+        route
+            .iter()
+            .map(|node| {
+                NymNodeRoutingAddress::try_from(node.address)
+                    .unwrap()
+                    .into()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn goes_through_remaining_layers_and_ends_at_the_node() {
+        let layer1 = mix_on_layer(Layer::One);
+        let layer2 = mix_on_layer(Layer::Two);
+        let layer3 = mix_on_layer(Layer::Three);
+
+        let mut mixes = HashMap::new();
+        mixes.insert(1, vec![layer1.clone()]);
+        mixes.insert(2, vec![layer2.clone()]);
+        mixes.insert(3, vec![layer3.clone()]);
+        let topology = NymTopology::new(mixes, vec![]);
+
+        let mut rng = rand::thread_rng();
+        let route = topology.random_loop_route(&mut rng, 3, &layer2).unwrap();
+        assert_eq!(
+            vec![layer3.mix_host, layer1.mix_host, layer2.mix_host],
+            route_hosts(&route)
+        );
+
+        let route = topology.random_loop_route(&mut rng, 3, &layer3).unwrap();
+        assert_eq!(
+            vec![layer1.mix_host, layer2.mix_host, layer3.mix_host],
+            route_hosts(&route)
+        );
+    }
+
+    #[test]
+    fn previous_hops_wrap_around_the_layers() {
+        let layer1 = mix_on_layer(Layer::One);
+        let layer2 = mix_on_layer(Layer::Two);
+        let layer3 = mix_on_layer(Layer::Three);
+        let gateway = gateway::Node {
+            owner: "N/A".to_string(),
+            stake: 0,
+            location: "N/A".to_string(),
+            host: "4.4.4.4".parse().unwrap(),
+            mix_host: "4.4.4.4:1789".parse().unwrap(),
+            clients_port: 9000,
+            identity_key: layer1.identity_key,
+            sphinx_key: layer1.sphinx_key,
+            version: "0.x.0".to_string(),
+        };
+
+        let mut mixes = HashMap::new();
+        mixes.insert(1, vec![layer1.clone()]);
+        mixes.insert(2, vec![layer2.clone()]);
+        mixes.insert(3, vec![layer3.clone()]);
+        let topology = NymTopology::new(mixes, vec![gateway.clone()]);
+
+        assert_eq!(
+            vec![layer3.mix_host, gateway.mix_host],
+            topology.previous_hops(1, 3).unwrap()
+        );
+        assert_eq!(vec![layer1.mix_host], topology.previous_hops(2, 3).unwrap());
+        assert_eq!(vec![layer2.mix_host], topology.previous_hops(3, 3).unwrap());
+        assert!(topology.previous_hops(4, 3).is_err());
+    }
+
+    #[test]
+    fn fails_if_any_remaining_layer_is_empty() {
+        let layer1 = mix_on_layer(Layer::One);
+        let mut mixes = HashMap::new();
+        mixes.insert(1, vec![layer1.clone()]);
+        mixes.insert(2, vec![mix_on_layer(Layer::Two)]);
+        let topology = NymTopology::new(mixes, vec![]);
+
+        assert!(topology
+            .random_loop_route(&mut rand::thread_rng(), 3, &layer1)
+            .is_err());
+    }
+}
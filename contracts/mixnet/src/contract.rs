@@ -10,6 +10,7 @@ use crate::delegations::queries::query_delegator_delegations_paged;
 use crate::delegations::queries::query_mixnode_delegation;
 use crate::delegations::queries::query_mixnode_delegations_paged;
 use crate::error::ContractError;
+use crate::gateways::queries::query_gateway_identity_rotations_paged;
 use crate::gateways::queries::query_gateways_paged;
 use crate::gateways::queries::query_owns_gateway;
 use crate::interval::queries::{
@@ -145,6 +146,33 @@ pub fn execute(
         ExecuteMsg::UnbondGateway {} => {
             crate::gateways::transactions::try_remove_gateway(deps, info)
         }
+        ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        } => crate::mixnodes::transactions::try_update_mixnode_identity(
+            deps,
+            env,
+            info,
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        ),
+        ExecuteMsg::MigrateNextMixnodeDelegations { mix_identity } => {
+            crate::mixnodes::transactions::try_migrate_next_mixnode_delegations(deps, mix_identity)
+        }
+        ExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        } => crate::gateways::transactions::try_update_gateway_identity(
+            deps,
+            env,
+            info,
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        ),
         ExecuteMsg::UpdateContractStateParams(params) => {
             crate::mixnet_contract_settings::transactions::try_update_contract_settings(
                 deps, info, params,
@@ -230,6 +258,34 @@ pub fn execute(
         ExecuteMsg::UnbondGatewayOnBehalf { owner } => {
             crate::gateways::transactions::try_remove_gateway_on_behalf(deps, info, owner)
         }
+        ExecuteMsg::UpdateMixnodeIdentityOnBehalf {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            owner,
+        } => crate::mixnodes::transactions::try_update_mixnode_identity_on_behalf(
+            deps,
+            env,
+            info,
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            owner,
+        ),
+        ExecuteMsg::UpdateGatewayIdentityOnBehalf {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            owner,
+        } => crate::gateways::transactions::try_update_gateway_identity_on_behalf(
+            deps,
+            env,
+            info,
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            owner,
+        ),
         ExecuteMsg::WriteRewardedSet {
             rewarded_set,
             expected_active_set_size,
@@ -321,6 +377,12 @@ pub fn query(deps: Deps<'_>, env: Env, msg: QueryMsg) -> Result<QueryResponse, C
         QueryMsg::GetRewardedSetRefreshBlocks {} => {
             to_binary(&query_rewarded_set_refresh_minimum_blocks())
         }
+        QueryMsg::GetMixnodeIdentityRotations { start_after, limit } => to_binary(
+            &mixnode_queries::query_mixnode_identity_rotations_paged(deps, start_after, limit)?,
+        ),
+        QueryMsg::GetGatewayIdentityRotations { start_after, limit } => to_binary(
+            &query_gateway_identity_rotations_paged(deps, start_after, limit)?,
+        ),
     };
 
     Ok(query_res?)
@@ -3,6 +3,7 @@
 use super::storage;
 use crate::error::ContractError;
 use crate::mixnodes::storage as mixnodes_storage;
+use crate::mixnodes::transactions::ensure_no_pending_delegation_migration;
 use crate::support::helpers::generate_storage_key;
 use config::defaults::DENOM;
 use cosmwasm_std::{coins, wasm_execute, Addr, BankMsg, Coin, DepsMut, Env, MessageInfo, Response};
@@ -77,6 +78,9 @@ pub(crate) fn _try_delegate_to_mixnode(
 ) -> Result<Response, ContractError> {
     let delegate = deps.api.addr_validate(delegate)?;
 
+    // the existing delegation of this delegator might not have been moved to the new identity yet
+    ensure_no_pending_delegation_migration(deps.storage, &mix_identity)?;
+
     // check if the target node actually exists
     if mixnodes_storage::mixnodes()
         .may_load(deps.storage, &mix_identity)?
@@ -155,6 +159,8 @@ pub(crate) fn _try_remove_delegation_from_mixnode(
     proxy: Option<Addr>,
 ) -> Result<Response, ContractError> {
     let delegate = deps.api.addr_validate(delegate)?;
+    ensure_no_pending_delegation_migration(deps.storage, &mix_identity)?;
+
     let delegation_map = storage::delegations();
     let maybe_proxy_storage = generate_storage_key(&delegate, proxy.as_ref());
     let storage_key = (mix_identity.clone(), maybe_proxy_storage).joined_key();
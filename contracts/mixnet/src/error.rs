@@ -63,6 +63,9 @@ pub enum ContractError {
     #[error("MIXNET ({}): Gateway with this identity already exists. Its owner is {owner}", line!())]
     DuplicateGateway { owner: Addr },
 
+    #[error("MIXNET ({}): Identity key {identity} has already been used by a bonded node", line!())]
+    IdentityKeyAlreadyUsed { identity: IdentityKey },
+
     #[error("MIXNET ({}): No funds were provided for the delegation", line!())]
     EmptyDelegation,
 
@@ -84,6 +87,12 @@ pub enum ContractError {
     #[error("MIXNET ({}): Some of mixnodes {identity} delegators are still pending reward", line!())]
     DelegatorsPendingReward { identity: IdentityKey },
 
+    #[error("MIXNET ({}): Delegations of mixnode {identity} are still being migrated after its identity rotation", line!())]
+    DelegationsPendingMigration { identity: IdentityKey },
+
+    #[error("MIXNET ({}): Mixnode {identity} does not have any delegations pending migration", line!())]
+    NoDelegationsPendingMigration { identity: IdentityKey },
+
     #[error("MIXNET ({}): Mixnode's {identity} operator has not been rewarded yet - cannot perform delegator rewarding until that happens", line!())]
     MixnodeOperatorNotRewarded { identity: IdentityKey },
 
@@ -6,7 +6,8 @@ use crate::mixnodes::storage::{BOND_PAGE_DEFAULT_LIMIT, BOND_PAGE_MAX_LIMIT}; //
 use cosmwasm_std::{Deps, Order, StdResult};
 use cw_storage_plus::Bound;
 use mixnet_contract_common::{
-    GatewayBond, GatewayOwnershipResponse, IdentityKey, PagedGatewayResponse,
+    GatewayBond, GatewayOwnershipResponse, IdentityKey, IdentityRotation, PagedGatewayResponse,
+    PagedIdentityRotationsResponse,
 };
 
 pub(crate) fn query_gateways_paged(
@@ -30,6 +31,32 @@ pub(crate) fn query_gateways_paged(
     Ok(PagedGatewayResponse::new(nodes, limit, start_next_after))
 }
 
+pub(crate) fn query_gateway_identity_rotations_paged(
+    deps: Deps<'_>,
+    start_after: Option<IdentityKey>,
+    limit: Option<u32>,
+) -> StdResult<PagedIdentityRotationsResponse> {
+    let limit = limit
+        .unwrap_or(BOND_PAGE_DEFAULT_LIMIT)
+        .min(BOND_PAGE_MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let rotations = storage::IDENTITY_ROTATIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|res| res.map(|item| item.1))
+        .collect::<StdResult<Vec<IdentityRotation>>>()?;
+
+    let start_next_after = rotations
+        .last()
+        .map(|rotation| rotation.previous_identity.clone());
+
+    Ok(PagedIdentityRotationsResponse {
+        rotations,
+        start_next_after,
+    })
+}
+
 pub(crate) fn query_owns_gateway(
     deps: Deps<'_>,
     address: String,
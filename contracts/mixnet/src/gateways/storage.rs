@@ -2,12 +2,17 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use cosmwasm_std::Addr;
-use cw_storage_plus::{Index, IndexList, IndexedMap, UniqueIndex};
-use mixnet_contract_common::{GatewayBond, IdentityKeyRef};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Map, UniqueIndex};
+use mixnet_contract_common::{GatewayBond, IdentityKeyRef, IdentityRotation};
 
 // storage prefixes
 const GATEWAYS_PK_NAMESPACE: &str = "gt";
 const GATEWAYS_OWNER_IDX_NAMESPACE: &str = "gto";
+const GATEWAYS_IDENTITY_ROTATIONS_NAMESPACE: &str = "gtr";
+
+// keyed by the replaced identity, so that it could never be bonded again
+pub(crate) const IDENTITY_ROTATIONS: Map<'_, IdentityKeyRef<'_>, IdentityRotation> =
+    Map::new(GATEWAYS_IDENTITY_ROTATIONS_NAMESPACE);
 
 pub(crate) struct GatewayBondIndex<'a> {
     pub(crate) owner: UniqueIndex<'a, Addr, GatewayBond>,
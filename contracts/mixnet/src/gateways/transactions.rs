@@ -4,13 +4,18 @@
 use super::storage;
 use crate::error::ContractError;
 use crate::mixnet_contract_settings::storage as mixnet_params_storage;
-use crate::support::helpers::{ensure_no_existing_bond, validate_node_identity_signature};
+use crate::support::helpers::{
+    decode_identity_key, ensure_no_existing_bond, ensure_unused_identity,
+    validate_identity_signature, validate_node_identity_signature,
+};
 use config::defaults::DENOM;
 use cosmwasm_std::{
     wasm_execute, Addr, BankMsg, Coin, DepsMut, Env, MessageInfo, Response, Uint128,
 };
-use mixnet_contract_common::events::{new_gateway_bonding_event, new_gateway_unbonding_event};
-use mixnet_contract_common::{Gateway, GatewayBond, Layer};
+use mixnet_contract_common::events::{
+    new_gateway_bonding_event, new_gateway_identity_update_event, new_gateway_unbonding_event,
+};
+use mixnet_contract_common::{Gateway, GatewayBond, IdentityKey, IdentityRotation, Layer};
 use vesting_contract_common::messages::ExecuteMsg as VestingContractExecuteMsg;
 use vesting_contract_common::one_ucoin;
 
@@ -80,6 +85,16 @@ pub(crate) fn _try_add_gateway(
     // if the client has an active bonded mixnode or gateway, don't allow bonding
     ensure_no_existing_bond(deps.storage, &owner)?;
 
+    // identities that have been rotated away from are considered compromised
+    if storage::IDENTITY_ROTATIONS
+        .may_load(deps.storage, &gateway.identity_key)?
+        .is_some()
+    {
+        return Err(ContractError::IdentityKeyAlreadyUsed {
+            identity: gateway.identity_key,
+        });
+    }
+
     // check if somebody else has already bonded a gateway with this identity
     if let Some(existing_bond) =
         storage::gateways().may_load(deps.storage, &gateway.identity_key)?
@@ -189,6 +204,124 @@ pub(crate) fn _try_remove_gateway(
     )))
 }
 
+/// Replaces the identity key of the gateway owned by the sender, keeping its bond. The new key
+/// has to sign the owner address, like during bonding, and the old key has to sign the raw bytes
+/// of the new key.
+pub(crate) fn try_update_gateway_identity(
+    deps: DepsMut<'_>,
+    env: Env,
+    info: MessageInfo,
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+) -> Result<Response, ContractError> {
+    let owner = info.sender;
+    _try_update_gateway_identity(
+        deps,
+        env,
+        new_identity_key,
+        owner_signature,
+        identity_signature,
+        owner,
+        None,
+    )
+}
+
+pub(crate) fn try_update_gateway_identity_on_behalf(
+    deps: DepsMut<'_>,
+    env: Env,
+    info: MessageInfo,
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+    owner: String,
+) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let proxy = deps.api.addr_validate(info.sender.as_ref())?;
+    _try_update_gateway_identity(
+        deps,
+        env,
+        new_identity_key,
+        owner_signature,
+        identity_signature,
+        owner,
+        Some(proxy),
+    )
+}
+
+pub(crate) fn _try_update_gateway_identity(
+    deps: DepsMut<'_>,
+    env: Env,
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+    owner: Addr,
+    proxy: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let mut gateway_bond = storage::gateways()
+        .idx
+        .owner
+        .item(deps.storage, owner.clone())?
+        .ok_or(ContractError::NoAssociatedGatewayBond {
+            owner: owner.clone(),
+        })?
+        .1;
+
+    if proxy != gateway_bond.proxy {
+        return Err(ContractError::ProxyMismatch {
+            existing: gateway_bond
+                .proxy
+                .map_or_else(|| "None".to_string(), |a| a.as_str().to_string()),
+            incoming: proxy.map_or_else(|| "None".to_string(), |a| a.as_str().to_string()),
+        });
+    }
+
+    let old_identity = gateway_bond.identity().clone();
+
+    ensure_unused_identity(deps.storage, &new_identity_key)?;
+
+    // the owner must control the new key...
+    validate_node_identity_signature(deps.as_ref(), &owner, owner_signature, &new_identity_key)?;
+    // ...and the gateway must have endorsed it with its old one
+    validate_identity_signature(
+        deps.as_ref(),
+        &decode_identity_key(&new_identity_key)?,
+        identity_signature,
+        &old_identity,
+    )?;
+
+    storage::gateways().remove(deps.storage, &old_identity)?;
+    gateway_bond.gateway.identity_key = new_identity_key.clone();
+    storage::gateways().save(deps.storage, &new_identity_key, &gateway_bond)?;
+
+    storage::IDENTITY_ROTATIONS.save(
+        deps.storage,
+        &old_identity,
+        &IdentityRotation {
+            previous_identity: old_identity.clone(),
+            new_identity: new_identity_key.clone(),
+            block_height: env.block.height,
+        },
+    )?;
+
+    let mut response = Response::new();
+    if let Some(proxy) = &proxy {
+        // returns the one_ucoin the proxy had to send to execute the contract to contract transaction
+        let return_one_ucoin = BankMsg::Send {
+            to_address: proxy.as_str().to_string(),
+            amount: vec![one_ucoin()],
+        };
+        response = response.add_message(return_one_ucoin);
+    }
+
+    Ok(response.add_event(new_gateway_identity_update_event(
+        &owner,
+        &proxy,
+        &old_identity,
+        &new_identity_key,
+    )))
+}
+
 fn validate_gateway_pledge(
     mut pledge: Vec<Coin>,
     minimum_pledge: Uint128,
@@ -228,8 +361,9 @@ pub mod tests {
     use crate::support::tests::test_helpers;
     use config::defaults::DENOM;
     use cosmwasm_std::testing::{mock_env, mock_info};
-    use cosmwasm_std::{coins, BankMsg, Response};
+    use cosmwasm_std::{coins, BankMsg, Response, SubMsg};
     use cosmwasm_std::{from_binary, Addr, Uint128};
+    use crypto::asymmetric::identity;
     use mixnet_contract_common::{ExecuteMsg, Gateway, PagedGatewayResponse, QueryMsg};
 
     #[test]
@@ -365,6 +499,187 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn updating_gateway_identity() {
+        let mut deps = test_helpers::init_contract();
+        let mut rng = rand::thread_rng();
+        let owner = "gateway-owner";
+
+        let old_keypair = identity::KeyPair::new(&mut rng);
+        let new_keypair = identity::KeyPair::new(&mut rng);
+        let old_identity = old_keypair.public_key().to_base58_string();
+        let new_identity = new_keypair.public_key().to_base58_string();
+
+        let info = mock_info(owner, &tests::fixtures::good_gateway_pledge());
+        let msg = ExecuteMsg::BondGateway {
+            gateway: Gateway {
+                identity_key: old_identity.clone(),
+                ..tests::fixtures::gateway_fixture()
+            },
+            owner_signature: old_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key: new_identity.clone(),
+            owner_signature: new_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+            identity_signature: old_keypair
+                .private_key()
+                .sign(&new_keypair.public_key().to_bytes())
+                .to_base58_string(),
+        };
+
+        // only the owner of the gateway can rotate its key
+        let ret = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("not-the-owner", &[]),
+            msg.clone(),
+        );
+        assert_eq!(
+            Err(ContractError::NoAssociatedGatewayBond {
+                owner: Addr::unchecked("not-the-owner")
+            }),
+            ret
+        );
+
+        execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg).unwrap();
+
+        assert!(storage::gateways()
+            .may_load(&deps.storage, &old_identity)
+            .unwrap()
+            .is_none());
+        let bond = storage::gateways()
+            .load(&deps.storage, &new_identity)
+            .unwrap();
+        assert_eq!(Addr::unchecked(owner), bond.owner);
+        assert_eq!(
+            old_identity,
+            storage::IDENTITY_ROTATIONS
+                .load(&deps.storage, &old_identity)
+                .unwrap()
+                .previous_identity
+        );
+    }
+
+    #[test]
+    fn gateway_identity_cannot_be_updated_to_one_used_by_a_mixnode() {
+        let mut deps = test_helpers::init_contract();
+
+        test_helpers::add_gateway(
+            "gateway-owner",
+            tests::fixtures::good_gateway_pledge(),
+            deps.as_mut(),
+        );
+        let mix_identity = test_helpers::add_mixnode(
+            "mix-owner",
+            tests::fixtures::good_mixnode_pledge(),
+            deps.as_mut(),
+        );
+
+        // the key is rejected before any of the signatures are even looked at
+        let msg = ExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key: mix_identity.clone(),
+            owner_signature: "foomp".to_string(),
+            identity_signature: "foomp".to_string(),
+        };
+        let ret = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gateway-owner", &[]),
+            msg,
+        );
+        assert_eq!(
+            Err(ContractError::IdentityKeyAlreadyUsed {
+                identity: mix_identity
+            }),
+            ret
+        );
+    }
+
+    #[test]
+    fn updating_identity_of_gateway_bonded_on_behalf() {
+        let mut deps = test_helpers::init_contract();
+        let mut rng = rand::thread_rng();
+        let owner = "gateway-owner";
+        let proxy = "vesting-contract";
+
+        let old_keypair = identity::KeyPair::new(&mut rng);
+        let new_keypair = identity::KeyPair::new(&mut rng);
+        let new_identity = new_keypair.public_key().to_base58_string();
+
+        let info = mock_info(proxy, &tests::fixtures::good_gateway_pledge());
+        let msg = ExecuteMsg::BondGatewayOnBehalf {
+            gateway: Gateway {
+                identity_key: old_keypair.public_key().to_base58_string(),
+                ..tests::fixtures::gateway_fixture()
+            },
+            owner: owner.to_string(),
+            owner_signature: old_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let owner_signature = new_keypair
+            .private_key()
+            .sign(owner.as_bytes())
+            .to_base58_string();
+        let identity_signature = old_keypair
+            .private_key()
+            .sign(&new_keypair.public_key().to_bytes())
+            .to_base58_string();
+
+        // the owner can't bypass the proxy the gateway was bonded with
+        let msg = ExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key: new_identity.clone(),
+            owner_signature: owner_signature.clone(),
+            identity_signature: identity_signature.clone(),
+        };
+        let ret = execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg);
+        assert_eq!(
+            Err(ContractError::ProxyMismatch {
+                existing: proxy.to_string(),
+                incoming: "None".to_string(),
+            }),
+            ret
+        );
+
+        let msg = ExecuteMsg::UpdateGatewayIdentityOnBehalf {
+            new_identity_key: new_identity.clone(),
+            owner_signature,
+            identity_signature,
+            owner: owner.to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(proxy, &[one_ucoin()]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: proxy.to_string(),
+                amount: vec![one_ucoin()],
+            })],
+            res.messages
+        );
+
+        let bond = storage::gateways()
+            .load(&deps.storage, &new_identity)
+            .unwrap();
+        assert_eq!(Addr::unchecked(owner), bond.owner);
+        assert_eq!(Some(Addr::unchecked(proxy)), bond.proxy);
+    }
+
     #[test]
     fn adding_gateway_with_existing_owner() {
         let mut deps = test_helpers::init_contract();
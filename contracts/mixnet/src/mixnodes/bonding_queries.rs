@@ -5,7 +5,8 @@ use super::storage;
 use cosmwasm_std::{Deps, Order, StdResult};
 use cw_storage_plus::Bound;
 use mixnet_contract_common::{
-    IdentityKey, MixNodeBond, MixOwnershipResponse, PagedMixnodeResponse,
+    IdentityKey, IdentityRotation, MixNodeBond, MixOwnershipResponse,
+    PagedIdentityRotationsResponse, PagedMixnodeResponse,
 };
 
 pub fn query_mixnodes_paged(
@@ -39,6 +40,33 @@ pub fn query_mixnodes_paged(
     Ok(PagedMixnodeResponse::new(nodes, limit, start_next_after))
 }
 
+pub fn query_mixnode_identity_rotations_paged(
+    deps: Deps<'_>,
+    start_after: Option<IdentityKey>,
+    limit: Option<u32>,
+) -> StdResult<PagedIdentityRotationsResponse> {
+    let limit = limit
+        .unwrap_or(storage::BOND_PAGE_DEFAULT_LIMIT)
+        .min(storage::BOND_PAGE_MAX_LIMIT) as usize;
+
+    let start = start_after.map(Bound::exclusive);
+
+    let rotations = storage::IDENTITY_ROTATIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|res| res.map(|item| item.1))
+        .collect::<StdResult<Vec<IdentityRotation>>>()?;
+
+    let start_next_after = rotations
+        .last()
+        .map(|rotation| rotation.previous_identity.clone());
+
+    Ok(PagedIdentityRotationsResponse {
+        rotations,
+        start_next_after,
+    })
+}
+
 pub fn query_owns_mixnode(deps: Deps<'_>, address: String) -> StdResult<MixOwnershipResponse> {
     let validated_addr = deps.api.addr_validate(&address)?;
     let stored_bond = storage::mixnodes()
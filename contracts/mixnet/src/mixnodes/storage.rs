@@ -4,7 +4,9 @@
 use config::defaults::DENOM;
 use cosmwasm_std::{StdResult, Storage, Uint128};
 use cw_storage_plus::{Index, IndexList, IndexedMap, Map, UniqueIndex};
-use mixnet_contract_common::{Addr, Coin, IdentityKeyRef, Layer, MixNode, MixNodeBond};
+use mixnet_contract_common::{
+    Addr, Coin, IdentityKey, IdentityKeyRef, IdentityRotation, Layer, MixNode, MixNodeBond,
+};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
@@ -12,6 +14,8 @@ use std::fmt::{Display, Formatter};
 const TOTAL_DELEGATION_NAMESPACE: &str = "td";
 const MIXNODES_PK_NAMESPACE: &str = "mn";
 const MIXNODES_OWNER_IDX_NAMESPACE: &str = "mno";
+const MIXNODES_IDENTITY_ROTATIONS_NAMESPACE: &str = "mnr";
+const MIXNODES_PENDING_DELEGATION_MIGRATIONS_NAMESPACE: &str = "mnm";
 
 // paged retrieval limits for all queries and transactions
 pub(crate) const BOND_PAGE_MAX_LIMIT: u32 = 75;
@@ -20,6 +24,14 @@ pub(crate) const BOND_PAGE_DEFAULT_LIMIT: u32 = 50;
 pub(crate) const TOTAL_DELEGATION: Map<'_, IdentityKeyRef<'_>, Uint128> =
     Map::new(TOTAL_DELEGATION_NAMESPACE);
 
+// keyed by the replaced identity, so that it could never be bonded again
+pub(crate) const IDENTITY_ROTATIONS: Map<'_, IdentityKeyRef<'_>, IdentityRotation> =
+    Map::new(MIXNODES_IDENTITY_ROTATIONS_NAMESPACE);
+
+// keyed by the new identity of a node, pointing to the old one its delegations are still kept under
+pub(crate) const PENDING_DELEGATION_MIGRATIONS: Map<'_, IdentityKeyRef<'_>, IdentityKey> =
+    Map::new(MIXNODES_PENDING_DELEGATION_MIGRATIONS_NAMESPACE);
+
 pub(crate) struct MixnodeBondIndex<'a> {
     pub(crate) owner: UniqueIndex<'a, Addr, StoredMixnodeBond>,
 }
@@ -2,17 +2,32 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::storage;
+use crate::delegations::storage as delegations_storage;
 use crate::error::ContractError;
+use crate::interval::storage as interval_storage;
 use crate::mixnet_contract_settings::storage as mixnet_params_storage;
 use crate::mixnodes::layer_queries::query_layer_distribution;
 use crate::mixnodes::storage::StoredMixnodeBond;
-use crate::support::helpers::{ensure_no_existing_bond, validate_node_identity_signature};
+use crate::rewards::storage as rewards_storage;
+use crate::support::helpers::{
+    decode_identity_key, ensure_no_existing_bond, ensure_unused_identity, generate_storage_key,
+    validate_identity_signature, validate_node_identity_signature,
+};
 use config::defaults::DENOM;
 use cosmwasm_std::{
-    wasm_execute, Addr, BankMsg, Coin, DepsMut, Env, MessageInfo, Response, Uint128,
+    wasm_execute, Addr, BankMsg, Coin, DepsMut, Env, MessageInfo, Order, Response, StdResult,
+    Storage, Uint128,
+};
+use cw_storage_plus::PrimaryKey;
+use mixnet_contract_common::events::{
+    new_mixnode_bonding_event, new_mixnode_delegations_migration_event,
+    new_mixnode_identity_update_event, new_mixnode_unbonding_event,
+};
+use mixnet_contract_common::{
+    IdentityKey, IdentityKeyRef, IdentityRotation, MixNode, RewardingStatus,
+    MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT,
 };
-use mixnet_contract_common::events::{new_mixnode_bonding_event, new_mixnode_unbonding_event};
-use mixnet_contract_common::MixNode;
+use std::collections::BTreeSet;
 use vesting_contract_common::messages::ExecuteMsg as VestingContractExecuteMsg;
 use vesting_contract_common::one_ucoin;
 
@@ -88,6 +103,16 @@ fn _try_add_mixnode(
         ));
     }
 
+    // identities that have been rotated away from are considered compromised
+    if storage::IDENTITY_ROTATIONS
+        .may_load(deps.storage, &mix_node.identity_key)?
+        .is_some()
+    {
+        return Err(ContractError::IdentityKeyAlreadyUsed {
+            identity: mix_node.identity_key,
+        });
+    }
+
     // check if somebody else has already bonded a mixnode with this identity
     if let Some(existing_bond) =
         storage::mixnodes().may_load(deps.storage, &mix_node.identity_key)?
@@ -292,6 +317,297 @@ pub(crate) fn _try_update_mixnode_config(
     Ok(response)
 }
 
+/// Replaces the identity key of the mixnode owned by the sender, moving its bond, delegations,
+/// rewarding status and rewarded set membership over to the new key. The new key has to sign
+/// the owner address, like during bonding, and the old key has to sign the raw bytes of the new
+/// key, proving the rotation was requested by the node operator.
+///
+/// Only the first page of delegations is moved right away. If there are any more, they have to be
+/// moved with `MigrateNextMixnodeDelegations` and until that's done, delegating to and
+/// undelegating from the node is not possible and it is not going to get rewarded.
+pub(crate) fn try_update_mixnode_identity(
+    deps: DepsMut<'_>,
+    env: Env,
+    info: MessageInfo,
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+) -> Result<Response, ContractError> {
+    let owner = info.sender;
+    _try_update_mixnode_identity(
+        deps,
+        env,
+        new_identity_key,
+        owner_signature,
+        identity_signature,
+        owner,
+        None,
+    )
+}
+
+pub(crate) fn try_update_mixnode_identity_on_behalf(
+    deps: DepsMut<'_>,
+    env: Env,
+    info: MessageInfo,
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+    owner: String,
+) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let proxy = deps.api.addr_validate(info.sender.as_ref())?;
+    _try_update_mixnode_identity(
+        deps,
+        env,
+        new_identity_key,
+        owner_signature,
+        identity_signature,
+        owner,
+        Some(proxy),
+    )
+}
+
+pub(crate) fn _try_update_mixnode_identity(
+    deps: DepsMut<'_>,
+    env: Env,
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+    owner: Addr,
+    proxy: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let mut mixnode_bond = storage::mixnodes()
+        .idx
+        .owner
+        .item(deps.storage, owner.clone())?
+        .ok_or(ContractError::NoAssociatedMixNodeBond {
+            owner: owner.clone(),
+        })?
+        .1;
+
+    if proxy != mixnode_bond.proxy {
+        return Err(ContractError::ProxyMismatch {
+            existing: mixnode_bond
+                .proxy
+                .map_or_else(|| "None".to_string(), |a| a.as_str().to_string()),
+            incoming: proxy.map_or_else(|| "None".to_string(), |a| a.as_str().to_string()),
+        });
+    }
+
+    let old_identity = mixnode_bond.identity().clone();
+
+    ensure_no_pending_delegation_migration(deps.storage, &old_identity)?;
+    ensure_unused_identity(deps.storage, &new_identity_key)?;
+
+    // the owner must control the new key...
+    validate_node_identity_signature(deps.as_ref(), &owner, owner_signature, &new_identity_key)?;
+    // ...and the node must have endorsed it with its old one
+    validate_identity_signature(
+        deps.as_ref(),
+        &decode_identity_key(&new_identity_key)?,
+        identity_signature,
+        &old_identity,
+    )?;
+
+    let interval_id = interval_storage::CURRENT_INTERVAL.load(deps.storage)?.id();
+    match rewards_storage::REWARDING_STATUS
+        .may_load(deps.storage, (interval_id, old_identity.clone()))?
+    {
+        Some(RewardingStatus::PendingNextDelegatorPage(_)) => {
+            return Err(ContractError::DelegatorsPendingReward {
+                identity: old_identity,
+            })
+        }
+        Some(status) => {
+            rewards_storage::REWARDING_STATUS
+                .remove(deps.storage, (interval_id, old_identity.clone()));
+            rewards_storage::REWARDING_STATUS.save(
+                deps.storage,
+                (interval_id, new_identity_key.clone()),
+                &status,
+            )?;
+        }
+        None => (),
+    }
+
+    let rewarded_set_height = interval_storage::CURRENT_REWARDED_SET_HEIGHT.load(deps.storage)?;
+    if let Some(set_status) = interval_storage::REWARDED_SET
+        .may_load(deps.storage, (rewarded_set_height, old_identity.clone()))?
+    {
+        interval_storage::REWARDED_SET
+            .remove(deps.storage, (rewarded_set_height, old_identity.clone()));
+        interval_storage::REWARDED_SET.save(
+            deps.storage,
+            (rewarded_set_height, new_identity_key.clone()),
+            &set_status,
+        )?;
+    }
+
+    storage::mixnodes().remove(deps.storage, &old_identity)?;
+    mixnode_bond.mix_node.identity_key = new_identity_key.clone();
+    storage::mixnodes().save(deps.storage, &new_identity_key, &mixnode_bond)?;
+
+    let total_delegation = storage::TOTAL_DELEGATION
+        .may_load(deps.storage, &old_identity)?
+        .unwrap_or_default();
+    storage::TOTAL_DELEGATION.remove(deps.storage, &old_identity);
+    storage::TOTAL_DELEGATION.save(deps.storage, &new_identity_key, &total_delegation)?;
+
+    let migrated = migrate_delegations_page(deps.storage, &old_identity, &new_identity_key)?;
+    if migrated.further_delegations {
+        storage::PENDING_DELEGATION_MIGRATIONS.save(
+            deps.storage,
+            &new_identity_key,
+            &old_identity,
+        )?;
+    }
+
+    storage::IDENTITY_ROTATIONS.save(
+        deps.storage,
+        &old_identity,
+        &IdentityRotation {
+            previous_identity: old_identity.clone(),
+            new_identity: new_identity_key.clone(),
+            block_height: env.block.height,
+        },
+    )?;
+
+    let mut response = migrated.vesting_tracking_response(&old_identity, &new_identity_key)?;
+    if let Some(proxy) = &proxy {
+        // returns the one_ucoin the proxy had to send to execute the contract to contract transaction
+        let return_one_ucoin = BankMsg::Send {
+            to_address: proxy.as_str().to_string(),
+            amount: vec![one_ucoin()],
+        };
+        response = response.add_message(return_one_ucoin);
+    }
+
+    Ok(response.add_event(new_mixnode_identity_update_event(
+        &owner,
+        &proxy,
+        &old_identity,
+        &new_identity_key,
+        migrated.moved_delegations,
+        migrated.further_delegations,
+    )))
+}
+
+/// Moves the next page of delegations of a mixnode that has rotated its identity key over to
+/// the new key. Anyone can send it, as it only finishes what the operator has already started.
+pub(crate) fn try_migrate_next_mixnode_delegations(
+    deps: DepsMut<'_>,
+    mix_identity: IdentityKey,
+) -> Result<Response, ContractError> {
+    let old_identity = storage::PENDING_DELEGATION_MIGRATIONS
+        .may_load(deps.storage, &mix_identity)?
+        .ok_or_else(|| ContractError::NoDelegationsPendingMigration {
+            identity: mix_identity.clone(),
+        })?;
+
+    let migrated = migrate_delegations_page(deps.storage, &old_identity, &mix_identity)?;
+    if !migrated.further_delegations {
+        storage::PENDING_DELEGATION_MIGRATIONS.remove(deps.storage, &mix_identity);
+    }
+
+    let response = migrated.vesting_tracking_response(&old_identity, &mix_identity)?;
+    Ok(response.add_event(new_mixnode_delegations_migration_event(
+        &old_identity,
+        &mix_identity,
+        migrated.moved_delegations,
+        migrated.further_delegations,
+    )))
+}
+
+/// Fails if delegations of the node, known either under its current or under one of its previous
+/// identities, are still being moved after an identity rotation.
+pub(crate) fn ensure_no_pending_delegation_migration(
+    storage: &dyn Storage,
+    identity: IdentityKeyRef<'_>,
+) -> Result<(), ContractError> {
+    let current_identity = match storage::IDENTITY_ROTATIONS.may_load(storage, identity)? {
+        Some(rotation) => rotation.new_identity,
+        None => identity.to_string(),
+    };
+
+    if storage::PENDING_DELEGATION_MIGRATIONS.has(storage, &current_identity) {
+        return Err(ContractError::DelegationsPendingMigration {
+            identity: current_identity,
+        });
+    }
+    Ok(())
+}
+
+struct MigratedDelegations {
+    moved_delegations: usize,
+    further_delegations: bool,
+    // delegations made via the vesting contract are also tracked there under the node identity
+    proxied_delegators: BTreeSet<(Addr, Addr)>,
+}
+
+impl MigratedDelegations {
+    fn vesting_tracking_response(
+        &self,
+        old_identity: IdentityKeyRef<'_>,
+        new_identity: IdentityKeyRef<'_>,
+    ) -> Result<Response, ContractError> {
+        let mut response = Response::new();
+        for (proxy, delegator) in &self.proxied_delegators {
+            let msg = VestingContractExecuteMsg::TrackMixnodeIdentityUpdate {
+                owner: delegator.to_string(),
+                old_identity: old_identity.to_string(),
+                new_identity: new_identity.to_string(),
+            };
+            response = response.add_message(wasm_execute(proxy, &msg, vec![])?);
+        }
+        Ok(response)
+    }
+}
+
+// moved delegations are removed from under the old identity, so every page starts at the beginning
+fn migrate_delegations_page(
+    storage: &mut dyn Storage,
+    old_identity: IdentityKeyRef<'_>,
+    new_identity: IdentityKeyRef<'_>,
+) -> Result<MigratedDelegations, ContractError> {
+    let mut delegations = delegations_storage::delegations()
+        .idx
+        .mixnode
+        .prefix(old_identity.to_string())
+        .range(storage, None, None, Order::Ascending)
+        .take(MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT + 1)
+        .map(|record| record.map(|r| r.1))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let further_delegations = delegations.len() > MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT;
+    delegations.truncate(MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT);
+
+    let mut proxied_delegators = BTreeSet::new();
+    for mut delegation in delegations.iter().cloned() {
+        let delegator_key = generate_storage_key(&delegation.owner, delegation.proxy.as_ref());
+        delegations_storage::delegations().remove(
+            storage,
+            (old_identity.to_string(), delegator_key.clone()).joined_key(),
+        )?;
+
+        delegation.node_identity = new_identity.to_string();
+        delegations_storage::delegations().save(
+            storage,
+            (new_identity.to_string(), delegator_key).joined_key(),
+            &delegation,
+        )?;
+
+        if let Some(proxy) = delegation.proxy {
+            proxied_delegators.insert((proxy, delegation.owner));
+        }
+    }
+
+    Ok(MigratedDelegations {
+        moved_delegations: delegations.len(),
+        further_delegations,
+        proxied_delegators,
+    })
+}
+
 fn validate_mixnode_pledge(
     mut pledge: Vec<Coin>,
     minimum_pledge: Uint128,
@@ -331,10 +647,13 @@ pub mod tests {
     use crate::support::tests::test_helpers;
     use config::defaults::DENOM;
     use cosmwasm_std::testing::{mock_env, mock_info};
-    use cosmwasm_std::{coins, BankMsg, Response};
+    use cosmwasm_std::{coins, BankMsg, Response, SubMsg};
     use cosmwasm_std::{from_binary, Addr, Uint128};
+    use crypto::asymmetric::identity;
+    use mixnet_contract_common::events::FURTHER_DELEGATIONS_TO_MIGRATE_KEY;
     use mixnet_contract_common::{
-        ExecuteMsg, Layer, LayerDistribution, MixNode, PagedMixnodeResponse, QueryMsg,
+        ExecuteMsg, Layer, LayerDistribution, MixNode, PagedIdentityRotationsResponse,
+        PagedMixnodeResponse, QueryMsg,
     };
 
     #[test]
@@ -739,6 +1058,458 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn updating_mixnode_identity() {
+        let mut deps = test_helpers::init_contract();
+        let mut rng = rand::thread_rng();
+        let owner = "mix-owner";
+
+        let old_keypair = identity::KeyPair::new(&mut rng);
+        let new_keypair = identity::KeyPair::new(&mut rng);
+        let old_identity = old_keypair.public_key().to_base58_string();
+        let new_identity = new_keypair.public_key().to_base58_string();
+
+        let info = mock_info(owner, &tests::fixtures::good_mixnode_pledge());
+        let msg = ExecuteMsg::BondMixnode {
+            mix_node: MixNode {
+                identity_key: old_identity.clone(),
+                ..tests::fixtures::mix_node_fixture()
+            },
+            owner_signature: old_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        test_helpers::save_dummy_delegation(&mut deps.storage, &old_identity, "delegator");
+
+        let owner_signature = new_keypair
+            .private_key()
+            .sign(owner.as_bytes())
+            .to_base58_string();
+        let identity_signature = old_keypair
+            .private_key()
+            .sign(&new_keypair.public_key().to_bytes())
+            .to_base58_string();
+
+        // the new key has to be signed with the old one
+        let msg = ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key: new_identity.clone(),
+            owner_signature: owner_signature.clone(),
+            identity_signature: owner_signature.clone(),
+        };
+        let ret = execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg);
+        assert_eq!(Err(ContractError::InvalidEd25519Signature), ret);
+
+        let msg = ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key: new_identity.clone(),
+            owner_signature: owner_signature.clone(),
+            identity_signature: identity_signature.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg).unwrap();
+
+        // the bond and its delegations are now under the new key
+        assert!(storage::mixnodes()
+            .may_load(&deps.storage, &old_identity)
+            .unwrap()
+            .is_none());
+        let bond = storage::mixnodes()
+            .load(&deps.storage, &new_identity)
+            .unwrap();
+        assert_eq!(Addr::unchecked(owner), bond.owner);
+        assert_eq!(
+            tests::fixtures::good_mixnode_pledge()[0].amount,
+            bond.pledge_amount.amount
+        );
+        assert!(test_helpers::read_delegation(&deps.storage, &old_identity, "delegator").is_none());
+        assert_eq!(
+            new_identity,
+            test_helpers::read_delegation(&deps.storage, &new_identity, "delegator")
+                .unwrap()
+                .node_identity
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetMixnodeIdentityRotations {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let rotations: PagedIdentityRotationsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, rotations.rotations.len());
+        assert_eq!(old_identity, rotations.rotations[0].previous_identity);
+        assert_eq!(new_identity, rotations.rotations[0].new_identity);
+
+        // the replaced key can't be bonded again
+        let info = mock_info("someone-else", &tests::fixtures::good_mixnode_pledge());
+        let msg = ExecuteMsg::BondMixnode {
+            mix_node: MixNode {
+                identity_key: old_identity.clone(),
+                ..tests::fixtures::mix_node_fixture()
+            },
+            owner_signature: old_keypair
+                .private_key()
+                .sign(b"someone-else")
+                .to_base58_string(),
+        };
+        assert_eq!(
+            Err(ContractError::IdentityKeyAlreadyUsed {
+                identity: old_identity
+            }),
+            execute(deps.as_mut(), mock_env(), info, msg)
+        );
+    }
+
+    #[test]
+    fn mixnode_identity_cannot_be_updated_to_one_a_gateway_rotated_away_from() {
+        let mut deps = test_helpers::init_contract();
+
+        test_helpers::add_mixnode(
+            "mix-owner",
+            tests::fixtures::good_mixnode_pledge(),
+            deps.as_mut(),
+        );
+        crate::gateways::storage::IDENTITY_ROTATIONS
+            .save(
+                &mut deps.storage,
+                "old-gateway-identity",
+                &IdentityRotation {
+                    previous_identity: "old-gateway-identity".to_string(),
+                    new_identity: "new-gateway-identity".to_string(),
+                    block_height: 123,
+                },
+            )
+            .unwrap();
+
+        let msg = ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key: "old-gateway-identity".to_string(),
+            owner_signature: "foomp".to_string(),
+            identity_signature: "foomp".to_string(),
+        };
+        let ret = execute(deps.as_mut(), mock_env(), mock_info("mix-owner", &[]), msg);
+        assert_eq!(
+            Err(ContractError::IdentityKeyAlreadyUsed {
+                identity: "old-gateway-identity".to_string()
+            }),
+            ret
+        );
+    }
+
+    #[test]
+    fn updating_identity_of_mixnode_bonded_on_behalf() {
+        let mut deps = test_helpers::init_contract();
+        let mut rng = rand::thread_rng();
+        let owner = "mix-owner";
+        let proxy = "vesting-contract";
+
+        let old_keypair = identity::KeyPair::new(&mut rng);
+        let new_keypair = identity::KeyPair::new(&mut rng);
+        let new_identity = new_keypair.public_key().to_base58_string();
+
+        let info = mock_info(proxy, &tests::fixtures::good_mixnode_pledge());
+        let msg = ExecuteMsg::BondMixnodeOnBehalf {
+            mix_node: MixNode {
+                identity_key: old_keypair.public_key().to_base58_string(),
+                ..tests::fixtures::mix_node_fixture()
+            },
+            owner: owner.to_string(),
+            owner_signature: old_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let owner_signature = new_keypair
+            .private_key()
+            .sign(owner.as_bytes())
+            .to_base58_string();
+        let identity_signature = old_keypair
+            .private_key()
+            .sign(&new_keypair.public_key().to_bytes())
+            .to_base58_string();
+
+        // the owner can't bypass the proxy the node was bonded with...
+        let msg = ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key: new_identity.clone(),
+            owner_signature: owner_signature.clone(),
+            identity_signature: identity_signature.clone(),
+        };
+        let ret = execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg);
+        assert_eq!(
+            Err(ContractError::ProxyMismatch {
+                existing: proxy.to_string(),
+                incoming: "None".to_string(),
+            }),
+            ret
+        );
+
+        // ...nor can anybody else claim to be one
+        let msg = ExecuteMsg::UpdateMixnodeIdentityOnBehalf {
+            new_identity_key: new_identity.clone(),
+            owner_signature: owner_signature.clone(),
+            identity_signature: identity_signature.clone(),
+            owner: owner.to_string(),
+        };
+        let ret = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("fake-proxy", &[]),
+            msg.clone(),
+        );
+        assert_eq!(
+            Err(ContractError::ProxyMismatch {
+                existing: proxy.to_string(),
+                incoming: "fake-proxy".to_string(),
+            }),
+            ret
+        );
+
+        // while the proxy gets back the coin it had to send
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(proxy, &[one_ucoin()]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(&SubMsg::new(BankMsg::Send {
+                to_address: proxy.to_string(),
+                amount: vec![one_ucoin()],
+            })),
+            res.messages.last()
+        );
+
+        let bond = storage::mixnodes()
+            .load(&deps.storage, &new_identity)
+            .unwrap();
+        assert_eq!(Addr::unchecked(owner), bond.owner);
+        assert_eq!(Some(Addr::unchecked(proxy)), bond.proxy);
+    }
+
+    #[test]
+    fn identity_of_directly_bonded_mixnode_cannot_be_updated_on_behalf() {
+        let mut deps = test_helpers::init_contract();
+        let mut rng = rand::thread_rng();
+        let owner = "mix-owner";
+
+        let old_keypair = identity::KeyPair::new(&mut rng);
+        let new_keypair = identity::KeyPair::new(&mut rng);
+
+        let info = mock_info(owner, &tests::fixtures::good_mixnode_pledge());
+        let msg = ExecuteMsg::BondMixnode {
+            mix_node: MixNode {
+                identity_key: old_keypair.public_key().to_base58_string(),
+                ..tests::fixtures::mix_node_fixture()
+            },
+            owner_signature: old_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateMixnodeIdentityOnBehalf {
+            new_identity_key: new_keypair.public_key().to_base58_string(),
+            owner_signature: new_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+            identity_signature: old_keypair
+                .private_key()
+                .sign(&new_keypair.public_key().to_bytes())
+                .to_base58_string(),
+            owner: owner.to_string(),
+        };
+        let ret = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("vesting-contract", &[one_ucoin()]),
+            msg,
+        );
+        assert_eq!(
+            Err(ContractError::ProxyMismatch {
+                existing: "None".to_string(),
+                incoming: "vesting-contract".to_string(),
+            }),
+            ret
+        );
+    }
+
+    #[test]
+    fn updating_identity_of_mixnode_with_many_delegations() {
+        let mut deps = test_helpers::init_contract();
+        let mut rng = rand::thread_rng();
+        let owner = "mix-owner";
+        let vesting_contract = "vesting-contract";
+        let direct_delegators = 2 * MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT;
+        let proxied_delegators = MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT + 5;
+        let all_delegators = direct_delegators + proxied_delegators;
+
+        let old_keypair = identity::KeyPair::new(&mut rng);
+        let new_keypair = identity::KeyPair::new(&mut rng);
+        let old_identity = old_keypair.public_key().to_base58_string();
+        let new_identity = new_keypair.public_key().to_base58_string();
+
+        let info = mock_info(owner, &tests::fixtures::good_mixnode_pledge());
+        let msg = ExecuteMsg::BondMixnode {
+            mix_node: MixNode {
+                identity_key: old_identity.clone(),
+                ..tests::fixtures::mix_node_fixture()
+            },
+            owner_signature: old_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for i in 0..direct_delegators {
+            let info = mock_info(&format!("delegator{}", i), &coins(1000, DENOM));
+            let msg = ExecuteMsg::DelegateToMixnode {
+                mix_identity: old_identity.clone(),
+            };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        for i in 0..proxied_delegators {
+            let info = mock_info(vesting_contract, &coins(1000, DENOM));
+            let msg = ExecuteMsg::DelegateToMixnodeOnBehalf {
+                mix_identity: old_identity.clone(),
+                delegate: format!("vesting-delegator{}", i),
+            };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let count_delegations = |storage: &dyn Storage, identity: &str| {
+            delegations_storage::delegations()
+                .idx
+                .mixnode
+                .prefix(identity.to_string())
+                .range(storage, None, None, Order::Ascending)
+                .count()
+        };
+        let further_delegations = |response: &Response| {
+            response.events[0]
+                .attributes
+                .iter()
+                .find(|attr| attr.key == FURTHER_DELEGATIONS_TO_MIGRATE_KEY)
+                .unwrap()
+                .value
+                == "true"
+        };
+
+        let msg = ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key: new_identity.clone(),
+            owner_signature: new_keypair
+                .private_key()
+                .sign(owner.as_bytes())
+                .to_base58_string(),
+            identity_signature: old_keypair
+                .private_key()
+                .sign(&new_keypair.public_key().to_bytes())
+                .to_base58_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg).unwrap();
+        assert!(further_delegations(&res));
+        assert_eq!(
+            MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT,
+            count_delegations(&deps.storage, &new_identity)
+        );
+        assert_eq!(
+            all_delegators - MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT,
+            count_delegations(&deps.storage, &old_identity)
+        );
+        let mut vesting_messages = res.messages.len();
+
+        // delegations can't change until all of them have been moved
+        let pending_migration = Err(ContractError::DelegationsPendingMigration {
+            identity: new_identity.clone(),
+        });
+        let msg = ExecuteMsg::DelegateToMixnode {
+            mix_identity: new_identity.clone(),
+        };
+        let info = mock_info("delegator0", &coins(1000, DENOM));
+        assert_eq!(
+            pending_migration,
+            execute(deps.as_mut(), mock_env(), info, msg)
+        );
+        let msg = ExecuteMsg::UndelegateFromMixnode {
+            mix_identity: old_identity.clone(),
+        };
+        let info = mock_info(&format!("delegator{}", direct_delegators - 1), &[]);
+        assert_eq!(
+            pending_migration,
+            execute(deps.as_mut(), mock_env(), info, msg)
+        );
+
+        // and anyone can move the remaining ones
+        let msg = ExecuteMsg::MigrateNextMixnodeDelegations {
+            mix_identity: new_identity.clone(),
+        };
+        let mut migrations = 0;
+        loop {
+            let info = mock_info("anyone", &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+            vesting_messages += res.messages.len();
+            migrations += 1;
+            if !further_delegations(&res) {
+                break;
+            }
+        }
+        assert_eq!(
+            all_delegators / MIXNODE_DELEGATIONS_MIGRATION_PAGE_LIMIT,
+            migrations
+        );
+        assert_eq!(
+            Err(ContractError::NoDelegationsPendingMigration {
+                identity: new_identity.clone()
+            }),
+            execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg)
+        );
+
+        assert_eq!(0, count_delegations(&deps.storage, &old_identity));
+        assert_eq!(
+            all_delegators,
+            count_delegations(&deps.storage, &new_identity)
+        );
+        assert_eq!(
+            Uint128::new(1000 * all_delegators as u128),
+            storage::TOTAL_DELEGATION
+                .load(&deps.storage, &new_identity)
+                .unwrap()
+        );
+        // every proxied delegator had its delegation tracked by the vesting contract
+        assert_eq!(proxied_delegators, vesting_messages);
+        let proxied_key = generate_storage_key(
+            &Addr::unchecked("vesting-delegator0"),
+            Some(&Addr::unchecked(vesting_contract)),
+        );
+        let proxied = delegations_storage::delegations()
+            .load(
+                &deps.storage,
+                (new_identity.clone(), proxied_key).joined_key(),
+            )
+            .unwrap();
+        assert_eq!(new_identity, proxied.node_identity);
+        assert_eq!(Some(Addr::unchecked(vesting_contract)), proxied.proxy);
+
+        // and once they have been moved, delegations work normally again
+        let msg = ExecuteMsg::UndelegateFromMixnode {
+            mix_identity: new_identity.clone(),
+        };
+        let info = mock_info("delegator0", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::DelegateToMixnode {
+            mix_identity: new_identity,
+        };
+        let info = mock_info("delegator0", &coins(1000, DENOM));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
     #[test]
     fn validating_mixnode_bond() {
         // you must send SOME funds
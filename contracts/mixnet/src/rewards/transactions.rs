@@ -13,8 +13,9 @@ use cosmwasm_std::{Addr, DepsMut, Env, MessageInfo, Response, StdResult, Storage
 use cw_storage_plus::{Bound, PrimaryKey};
 use mixnet_contract_common::events::{
     new_mix_delegators_rewarding_event, new_mix_operator_rewarding_event,
-    new_not_found_mix_operator_rewarding_event, new_too_fresh_bond_mix_operator_rewarding_event,
-    new_zero_uptime_mix_operator_rewarding_event,
+    new_not_found_mix_operator_rewarding_event,
+    new_pending_delegations_migration_mix_operator_rewarding_event,
+    new_too_fresh_bond_mix_operator_rewarding_event, new_zero_uptime_mix_operator_rewarding_event,
 };
 use mixnet_contract_common::mixnode::{DelegatorRewardParams, NodeRewardParams};
 use mixnet_contract_common::{
@@ -244,6 +245,17 @@ pub(crate) fn try_reward_mixnode(
         }
     };
 
+    // its delegators can't be rewarded until all of them are known under its current identity,
+    // but no status is saved so that it could still get rewarded once the migration is done
+    if mixnodes_storage::PENDING_DELEGATION_MIGRATIONS.has(deps.storage, &mix_identity) {
+        return Ok(Response::new().add_event(
+            new_pending_delegations_migration_mix_operator_rewarding_event(
+                interval_id,
+                &mix_identity,
+            ),
+        ));
+    }
+
     // check if node is old enough for rewarding
     if current_bond.block_height + constants::MINIMUM_BLOCK_AGE_FOR_REWARDING > env.block.height {
         storage::REWARDING_STATUS.save(
@@ -47,30 +47,59 @@ pub(crate) fn ensure_no_existing_bond(
     Ok(())
 }
 
+// check if the identity key has ever been used by any node, i.e. whether it has been bonded
+// by a mixnode or a gateway, delegated to or rotated away from by either of them
+pub(crate) fn ensure_unused_identity(
+    storage: &dyn Storage,
+    identity: IdentityKeyRef<'_>,
+) -> Result<(), ContractError> {
+    if mixnodes_storage::mixnodes()
+        .may_load(storage, identity)?
+        .is_some()
+        || mixnodes_storage::TOTAL_DELEGATION
+            .may_load(storage, identity)?
+            .is_some()
+        || mixnodes_storage::IDENTITY_ROTATIONS
+            .may_load(storage, identity)?
+            .is_some()
+        || gateways_storage::gateways()
+            .may_load(storage, identity)?
+            .is_some()
+        || gateways_storage::IDENTITY_ROTATIONS
+            .may_load(storage, identity)?
+            .is_some()
+    {
+        return Err(ContractError::IdentityKeyAlreadyUsed {
+            identity: identity.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
 pub(crate) fn validate_node_identity_signature(
     deps: Deps<'_>,
     owner: &Addr,
     signature: String,
     identity: IdentityKeyRef<'_>,
 ) -> Result<(), ContractError> {
-    let owner_bytes = owner.as_bytes();
+    validate_identity_signature(deps, owner.as_bytes(), signature, identity)
+}
 
-    let mut identity_bytes = [0u8; 32];
+// checks whether the provided signature of the message was created with the specified identity key
+pub(crate) fn validate_identity_signature(
+    deps: Deps<'_>,
+    message: &[u8],
+    signature: String,
+    identity: IdentityKeyRef<'_>,
+) -> Result<(), ContractError> {
+    let identity_bytes = decode_identity_key(identity)?;
     let mut signature_bytes = [0u8; 64];
 
-    let identity_used_bytes = bs58::decode(identity)
-        .into(&mut identity_bytes)
-        .map_err(|err| ContractError::MalformedEd25519IdentityKey(err.to_string()))?;
     let signature_used_bytes = bs58::decode(signature)
         .into(&mut signature_bytes)
         .map_err(|err| ContractError::MalformedEd25519Signature(err.to_string()))?;
 
-    if identity_used_bytes != 32 {
-        return Err(ContractError::MalformedEd25519IdentityKey(
-            "Too few bytes provided".into(),
-        ));
-    }
-
     if signature_used_bytes != 64 {
         return Err(ContractError::MalformedEd25519Signature(
             "Too few bytes provided".into(),
@@ -79,7 +108,7 @@ pub(crate) fn validate_node_identity_signature(
 
     let res = deps
         .api
-        .ed25519_verify(owner_bytes, &signature_bytes, &identity_bytes)
+        .ed25519_verify(message, &signature_bytes, &identity_bytes)
         .map_err(cosmwasm_std::StdError::verification_err)?;
     if !res {
         Err(ContractError::InvalidEd25519Signature)
@@ -88,11 +117,31 @@ pub(crate) fn validate_node_identity_signature(
     }
 }
 
+pub(crate) fn decode_identity_key(identity: IdentityKeyRef<'_>) -> Result<[u8; 32], ContractError> {
+    let mut identity_bytes = [0u8; 32];
+
+    let identity_used_bytes = bs58::decode(identity)
+        .into(&mut identity_bytes)
+        .map_err(|err| ContractError::MalformedEd25519IdentityKey(err.to_string()))?;
+
+    if identity_used_bytes != 32 {
+        return Err(ContractError::MalformedEd25519IdentityKey(
+            "Too few bytes provided".into(),
+        ));
+    }
+
+    Ok(identity_bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::support::tests;
+    use crate::support::tests::test_helpers;
     use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::Uint128;
     use crypto::asymmetric::identity;
+    use mixnet_contract_common::IdentityRotation;
     use rand_chacha::rand_core::SeedableRng;
 
     #[test]
@@ -221,4 +270,59 @@ mod tests {
         )
         .is_ok());
     }
+
+    #[test]
+    fn identities_used_by_any_node_are_rejected() {
+        let mut deps = test_helpers::init_contract();
+        let rotation = |identity: &str| IdentityRotation {
+            previous_identity: identity.to_string(),
+            new_identity: "whatever".to_string(),
+            block_height: 123,
+        };
+
+        let bonded_mixnode = test_helpers::add_mixnode(
+            "mix-owner",
+            tests::fixtures::good_mixnode_pledge(),
+            deps.as_mut(),
+        );
+        let bonded_gateway = test_helpers::add_gateway(
+            "gateway-owner",
+            tests::fixtures::good_gateway_pledge(),
+            deps.as_mut(),
+        );
+        mixnodes_storage::TOTAL_DELEGATION
+            .save(&mut deps.storage, "delegated-to", &Uint128::zero())
+            .unwrap();
+        mixnodes_storage::IDENTITY_ROTATIONS
+            .save(
+                &mut deps.storage,
+                "rotated-mixnode",
+                &rotation("rotated-mixnode"),
+            )
+            .unwrap();
+        gateways_storage::IDENTITY_ROTATIONS
+            .save(
+                &mut deps.storage,
+                "rotated-gateway",
+                &rotation("rotated-gateway"),
+            )
+            .unwrap();
+
+        for identity in [
+            bonded_mixnode.as_str(),
+            bonded_gateway.as_str(),
+            "delegated-to",
+            "rotated-mixnode",
+            "rotated-gateway",
+        ] {
+            assert_eq!(
+                Err(ContractError::IdentityKeyAlreadyUsed {
+                    identity: identity.to_string()
+                }),
+                ensure_unused_identity(&deps.storage, identity)
+            );
+        }
+
+        assert!(ensure_unused_identity(&deps.storage, "never-used").is_ok());
+    }
 }
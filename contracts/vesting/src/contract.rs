@@ -13,7 +13,8 @@ use mixnet_contract_common::{Gateway, IdentityKey, MixNode};
 use vesting_contract_common::events::{
     new_ownership_transfer_event, new_periodic_vesting_account_event,
     new_staking_address_update_event, new_track_gateway_unbond_event,
-    new_track_mixnode_unbond_event, new_track_undelegation_event, new_vested_coins_withdraw_event,
+    new_track_mixnode_identity_update_event, new_track_mixnode_unbond_event,
+    new_track_undelegation_event, new_vested_coins_withdraw_event,
 };
 use vesting_contract_common::messages::{
     ExecuteMsg, InitMsg, MigrateMsg, QueryMsg, VestingSpecification,
@@ -78,12 +79,28 @@ pub fn execute(
             mix_identity,
             amount,
         } => try_track_undelegation(&owner, mix_identity, amount, info, deps),
+        ExecuteMsg::TrackMixnodeIdentityUpdate {
+            owner,
+            old_identity,
+            new_identity,
+        } => try_track_mixnode_identity_update(&owner, old_identity, new_identity, info, deps),
         ExecuteMsg::BondMixnode {
             mix_node,
             owner_signature,
             amount,
         } => try_bond_mixnode(mix_node, owner_signature, amount, info, env, deps),
         ExecuteMsg::UnbondMixnode {} => try_unbond_mixnode(info, deps),
+        ExecuteMsg::UpdateMixnodeIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        } => try_update_mixnode_identity(
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            info,
+            deps,
+        ),
         ExecuteMsg::TrackUnbondMixnode { owner, amount } => {
             try_track_unbond_mixnode(&owner, amount, info, deps)
         }
@@ -93,6 +110,17 @@ pub fn execute(
             amount,
         } => try_bond_gateway(gateway, owner_signature, amount, info, env, deps),
         ExecuteMsg::UnbondGateway {} => try_unbond_gateway(info, deps),
+        ExecuteMsg::UpdateGatewayIdentity {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+        } => try_update_gateway_identity(
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            info,
+            deps,
+        ),
         ExecuteMsg::TrackUnbondGateway { owner, amount } => {
             try_track_unbond_gateway(&owner, amount, info, deps)
         }
@@ -223,6 +251,22 @@ pub fn try_unbond_gateway(info: MessageInfo, deps: DepsMut<'_>) -> Result<Respon
     account.try_unbond_gateway(deps.storage)
 }
 
+pub fn try_update_gateway_identity(
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+    info: MessageInfo,
+    deps: DepsMut<'_>,
+) -> Result<Response, ContractError> {
+    let account = account_from_address(info.sender.as_str(), deps.storage, deps.api)?;
+    account.try_update_gateway_identity(
+        new_identity_key,
+        owner_signature,
+        identity_signature,
+        deps.storage,
+    )
+}
+
 pub fn try_track_unbond_gateway(
     owner: &str,
     amount: Coin,
@@ -255,6 +299,22 @@ pub fn try_unbond_mixnode(info: MessageInfo, deps: DepsMut<'_>) -> Result<Respon
     account.try_unbond_mixnode(deps.storage)
 }
 
+pub fn try_update_mixnode_identity(
+    new_identity_key: IdentityKey,
+    owner_signature: String,
+    identity_signature: String,
+    info: MessageInfo,
+    deps: DepsMut<'_>,
+) -> Result<Response, ContractError> {
+    let account = account_from_address(info.sender.as_str(), deps.storage, deps.api)?;
+    account.try_update_mixnode_identity(
+        new_identity_key,
+        owner_signature,
+        identity_signature,
+        deps.storage,
+    )
+}
+
 pub fn try_track_unbond_mixnode(
     owner: &str,
     amount: Coin,
@@ -284,6 +344,21 @@ fn try_track_undelegation(
     Ok(Response::new().add_event(new_track_undelegation_event()))
 }
 
+fn try_track_mixnode_identity_update(
+    address: &str,
+    old_identity: IdentityKey,
+    new_identity: IdentityKey,
+    info: MessageInfo,
+    deps: DepsMut<'_>,
+) -> Result<Response, ContractError> {
+    if info.sender != MIXNET_CONTRACT_ADDRESS.load(deps.storage)? {
+        return Err(ContractError::NotMixnetContract(info.sender));
+    }
+    let account = account_from_address(address, deps.storage, deps.api)?;
+    account.move_delegations_for_mix(&old_identity, &new_identity, deps.storage)?;
+    Ok(Response::new().add_event(new_track_mixnode_identity_update_event()))
+}
+
 fn try_delegate_to_mixnode(
     mix_identity: IdentityKey,
     amount: Coin,
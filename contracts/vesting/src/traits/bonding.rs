@@ -1,6 +1,6 @@
 use crate::errors::ContractError;
 use cosmwasm_std::{Coin, Env, Response, Storage};
-use mixnet_contract_common::{Gateway, MixNode};
+use mixnet_contract_common::{Gateway, IdentityKey, MixNode};
 
 pub trait MixnodeBondingAccount {
     fn try_bond_mixnode(
@@ -25,6 +25,14 @@ pub trait MixnodeBondingAccount {
         profit_margin_percent: u8,
         storage: &mut dyn Storage,
     ) -> Result<Response, ContractError>;
+
+    fn try_update_mixnode_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+        storage: &dyn Storage,
+    ) -> Result<Response, ContractError>;
 }
 
 pub trait GatewayBondingAccount {
@@ -44,4 +52,12 @@ pub trait GatewayBondingAccount {
         amount: Coin,
         storage: &mut dyn Storage,
     ) -> Result<(), ContractError>;
+
+    fn try_update_gateway_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+        storage: &dyn Storage,
+    ) -> Result<Response, ContractError>;
 }
@@ -3,9 +3,10 @@ use crate::errors::ContractError;
 use crate::storage::MIXNET_CONTRACT_ADDRESS;
 use crate::traits::GatewayBondingAccount;
 use cosmwasm_std::{wasm_execute, Coin, Env, Response, Storage, Uint128};
-use mixnet_contract_common::{ExecuteMsg as MixnetExecuteMsg, Gateway};
+use mixnet_contract_common::{ExecuteMsg as MixnetExecuteMsg, Gateway, IdentityKey};
 use vesting_contract_common::events::{
     new_vesting_gateway_bonding_event, new_vesting_gateway_unbonding_event,
+    new_vesting_update_gateway_identity_event,
 };
 use vesting_contract_common::one_ucoin;
 
@@ -92,4 +93,35 @@ impl GatewayBondingAccount for Account {
         self.remove_gateway_pledge(storage)?;
         Ok(())
     }
+
+    fn try_update_gateway_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+        storage: &dyn Storage,
+    ) -> Result<Response, ContractError> {
+        let msg = MixnetExecuteMsg::UpdateGatewayIdentityOnBehalf {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            owner: self.owner_address().into_string(),
+        };
+
+        if self.load_gateway_pledge(storage)?.is_some() {
+            let update_identity_msg = wasm_execute(
+                MIXNET_CONTRACT_ADDRESS.load(storage)?,
+                &msg,
+                vec![one_ucoin()],
+            )?;
+
+            Ok(Response::new()
+                .add_message(update_identity_msg)
+                .add_event(new_vesting_update_gateway_identity_event()))
+        } else {
+            Err(ContractError::NoBondFound(
+                self.owner_address().as_str().to_string(),
+            ))
+        }
+    }
 }
@@ -3,10 +3,10 @@ use crate::errors::ContractError;
 use crate::storage::MIXNET_CONTRACT_ADDRESS;
 use crate::traits::MixnodeBondingAccount;
 use cosmwasm_std::{wasm_execute, Coin, Env, Response, Storage, Uint128};
-use mixnet_contract_common::{ExecuteMsg as MixnetExecuteMsg, MixNode};
+use mixnet_contract_common::{ExecuteMsg as MixnetExecuteMsg, IdentityKey, MixNode};
 use vesting_contract_common::events::{
     new_vesting_mixnode_bonding_event, new_vesting_mixnode_unbonding_event,
-    new_vesting_update_mixnode_config_event,
+    new_vesting_update_mixnode_config_event, new_vesting_update_mixnode_identity_event,
 };
 use vesting_contract_common::one_ucoin;
 
@@ -114,4 +114,35 @@ impl MixnodeBondingAccount for Account {
         self.remove_mixnode_pledge(storage)?;
         Ok(())
     }
+
+    fn try_update_mixnode_identity(
+        &self,
+        new_identity_key: IdentityKey,
+        owner_signature: String,
+        identity_signature: String,
+        storage: &dyn Storage,
+    ) -> Result<Response, ContractError> {
+        let msg = MixnetExecuteMsg::UpdateMixnodeIdentityOnBehalf {
+            new_identity_key,
+            owner_signature,
+            identity_signature,
+            owner: self.owner_address().into_string(),
+        };
+
+        if self.load_mixnode_pledge(storage)?.is_some() {
+            let update_identity_msg = wasm_execute(
+                MIXNET_CONTRACT_ADDRESS.load(storage)?,
+                &msg,
+                vec![one_ucoin()],
+            )?;
+
+            Ok(Response::new()
+                .add_message(update_identity_msg)
+                .add_event(new_vesting_update_mixnode_identity_event()))
+        } else {
+            Err(ContractError::NoBondFound(
+                self.owner_address().as_str().to_string(),
+            ))
+        }
+    }
 }
@@ -2,10 +2,10 @@ use super::{PledgeData, VestingPeriod};
 use crate::errors::ContractError;
 use crate::storage::{
     load_balance, load_bond_pledge, load_gateway_pledge, remove_bond_pledge, remove_delegation,
-    remove_gateway_pledge, save_account, save_balance, save_bond_pledge, save_gateway_pledge,
-    DELEGATIONS, KEY,
+    remove_gateway_pledge, save_account, save_balance, save_bond_pledge, save_delegation,
+    save_gateway_pledge, DELEGATIONS, KEY,
 };
-use cosmwasm_std::{Addr, Coin, Order, Storage, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Coin, Order, StdResult, Storage, Timestamp, Uint128};
 use cw_storage_plus::Bound;
 use mixnet_contract_common::IdentityKey;
 use schemars::JsonSchema;
@@ -214,6 +214,33 @@ impl Account {
         Ok(())
     }
 
+    // moves all delegations made towards a mixnode that has changed its identity key,
+    // keeping their original block heights
+    pub fn move_delegations_for_mix(
+        &self,
+        old_mix: &str,
+        new_mix: &str,
+        storage: &mut dyn Storage,
+    ) -> Result<(), ContractError> {
+        let delegations = DELEGATIONS
+            .prefix((self.storage_key(), old_mix.to_string()))
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (block_height, amount) in delegations {
+            remove_delegation(
+                (self.storage_key(), old_mix.to_string(), block_height),
+                storage,
+            )?;
+            save_delegation(
+                (self.storage_key(), new_mix.to_string(), block_height),
+                amount,
+                storage,
+            )?;
+        }
+        Ok(())
+    }
+
     pub fn total_delegations_for_mix(
         &self,
         mix: IdentityKey,
@@ -51,9 +51,10 @@ mod tests {
     use crate::vesting::Period;
     use config::defaults::DENOM;
     use cosmwasm_std::testing::{mock_env, mock_info};
-    use cosmwasm_std::{coins, Addr, Coin, Timestamp, Uint128};
+    use cosmwasm_std::{coins, Addr, Coin, CosmosMsg, Timestamp, Uint128, WasmMsg};
     use mixnet_contract_common::{Gateway, MixNode};
     use vesting_contract_common::messages::ExecuteMsg;
+    use vesting_contract_common::one_ucoin;
 
     #[test]
     fn test_account_creation() {
@@ -464,6 +465,76 @@ mod tests {
         assert_eq!(Uint128::zero(), bonded_vesting.amount);
     }
 
+    #[test]
+    fn test_node_identity_updates() {
+        let mut deps = init_contract();
+        let env = mock_env();
+
+        let account = vesting_account_fixture(&mut deps.storage, &env);
+        let pledge = Coin {
+            amount: Uint128::new(500_000_000_000),
+            denom: DENOM.to_string(),
+        };
+
+        // there's nothing to update without a bond
+        let err = account.try_update_mixnode_identity(
+            "new-identity".to_string(),
+            "owner-signature".to_string(),
+            "identity-signature".to_string(),
+            &deps.storage,
+        );
+        assert!(err.is_err());
+        let err = account.try_update_gateway_identity(
+            "new-identity".to_string(),
+            "owner-signature".to_string(),
+            "identity-signature".to_string(),
+            &deps.storage,
+        );
+        assert!(err.is_err());
+
+        let mix_node = MixNode {
+            host: "mix.node.org".to_string(),
+            mix_port: 1789,
+            verloc_port: 1790,
+            http_api_port: 8000,
+            sphinx_key: "sphinx".to_string(),
+            identity_key: "identity".to_string(),
+            version: "0.10.0".to_string(),
+            profit_margin_percent: 10,
+        };
+        account
+            .try_bond_mixnode(
+                mix_node,
+                "alice".to_string(),
+                pledge,
+                &env,
+                &mut deps.storage,
+            )
+            .unwrap();
+
+        // the update is forwarded to the mixnet contract with the coin it's going to return
+        let res = account
+            .try_update_mixnode_identity(
+                "new-identity".to_string(),
+                "owner-signature".to_string(),
+                "identity-signature".to_string(),
+                &deps.storage,
+            )
+            .unwrap();
+        assert_eq!(1, res.messages.len());
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                funds,
+                ..
+            }) => {
+                assert_eq!("test", contract_addr);
+                assert_eq!(&vec![one_ucoin()], funds);
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
     #[test]
     fn test_gateway_bonds() {
         let mut deps = init_contract();
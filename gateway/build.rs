@@ -11,7 +11,7 @@ async fn main() {
         .await
         .expect("Failed to create SQLx database connection");
 
-    sqlx::migrate!("./migrations")
+    sqlx::migrate!("./migrations/sqlite")
         .run(&mut conn)
         .await
         .expect("Failed to perform SQLx migrations");
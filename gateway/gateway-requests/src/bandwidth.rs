@@ -0,0 +1,72 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bandwidth accounting shared by the gateway and its clients, so that both sides agree on how
+//! much of the available bandwidth each sent or received packet consumes.
+//!
+//! Packets are charged by the size of the sphinx packet they travel in, i.e. a received message
+//! is charged as much as the packet carrying it through the mix network rather than by
+//! the length of the (smaller) extracted plaintext.
+
+use nymsphinx::params::PacketSize;
+
+// sorted from the smallest to the largest
+const PACKET_SIZES: [PacketSize; 3] = [
+    PacketSize::AckPacket,
+    PacketSize::RegularPacket,
+    PacketSize::ExtendedPacket,
+];
+
+/// Amount of bandwidth consumed by forwarding a sphinx packet of the specified length into the mix network.
+pub fn sent_packet_cost(packet_length: usize) -> i64 {
+    PacketSize::get_type(packet_length)
+        .map(|packet_size| packet_size.size())
+        .unwrap_or(packet_length) as i64
+}
+
+/// Amount of bandwidth consumed by pushing a message of the specified length, extracted from
+/// a sphinx packet received from the mix network, to the client.
+pub fn received_message_cost(message_length: usize) -> i64 {
+    PACKET_SIZES
+        .iter()
+        .find(|packet_size| packet_size.plaintext_size() >= message_length)
+        .map(|packet_size| packet_size.size())
+        // this shouldn't ever happen, but charge at least the length of the message itself
+        .unwrap_or(message_length) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sent_packets_are_charged_by_their_size() {
+        for packet_size in PACKET_SIZES {
+            assert_eq!(
+                packet_size.size() as i64,
+                sent_packet_cost(packet_size.size())
+            );
+        }
+        assert_eq!(42, sent_packet_cost(42));
+    }
+
+    #[test]
+    fn received_messages_are_charged_by_size_of_the_packet_carrying_them() {
+        let regular = PacketSize::RegularPacket;
+        assert_eq!(
+            regular.size() as i64,
+            received_message_cost(regular.plaintext_size())
+        );
+        assert_eq!(
+            regular.size() as i64,
+            received_message_cost(PacketSize::AckPacket.plaintext_size() + 1)
+        );
+        assert_eq!(
+            PacketSize::AckPacket.size() as i64,
+            received_message_cost(0)
+        );
+
+        let too_big = PacketSize::ExtendedPacket.plaintext_size() + 1;
+        assert_eq!(too_big as i64, received_message_cost(too_big));
+    }
+}
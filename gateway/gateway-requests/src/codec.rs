@@ -0,0 +1,193 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Length-prefixed framing allowing the gateway request/response protocol to run directly
+//! over a TCP stream rather than through a websocket connection.
+//!
+//! Each frame consists of a single byte indicating its kind, followed by 4 bytes of big-endian
+//! encoded length of the payload and the payload itself:
+//!
+//! ```text
+//! +------+----------------+---------------------+
+//! | kind | payload length |       payload       |
+//! |  1B  |      4B        | `payload length` B  |
+//! +------+----------------+---------------------+
+//! ```
+//!
+//! Frames map directly onto the websocket messages used by the protocol, i.e. text frames
+//! carry json-encoded control requests and responses, binary frames carry encrypted sphinx
+//! packets and the close frame indicates the remote is about to terminate the connection.
+
+use bytes::{Buf, BufMut, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+use tungstenite::error::CapacityError;
+use tungstenite::{Error as WsError, Message};
+
+/// Maximum size of payload of a single frame.
+pub const MAX_FRAME_PAYLOAD_LENGTH: usize = 1024 * 1024;
+
+const HEADER_LENGTH: usize = 5;
+
+const TEXT_FRAME: u8 = 0;
+const BINARY_FRAME: u8 = 1;
+const CLOSE_FRAME: u8 = 2;
+
+#[derive(Debug, Default)]
+pub struct GatewayCodec;
+
+impl Encoder<Message> for GatewayCodec {
+    type Error = WsError;
+
+    fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (kind, payload) = match item {
+            Message::Text(text) => (TEXT_FRAME, text.into_bytes()),
+            Message::Binary(data) => (BINARY_FRAME, data),
+            // the reason for closing the connection is not relevant for the protocol
+            Message::Close(_) => (CLOSE_FRAME, Vec::new()),
+            // keepalive is left to the tcp stack
+            Message::Ping(_) | Message::Pong(_) => return Ok(()),
+        };
+
+        if payload.len() > MAX_FRAME_PAYLOAD_LENGTH {
+            return Err(WsError::Capacity(CapacityError::MessageTooLong {
+                size: payload.len(),
+                max_size: MAX_FRAME_PAYLOAD_LENGTH,
+            }));
+        }
+
+        dst.reserve(HEADER_LENGTH + payload.len());
+        dst.put_u8(kind);
+        dst.put_u32(payload.len() as u32);
+        dst.put_slice(&payload);
+        Ok(())
+    }
+}
+
+impl Decoder for GatewayCodec {
+    type Item = Message;
+    type Error = WsError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < HEADER_LENGTH {
+            // we don't have enough bytes to read the header
+            return Ok(None);
+        }
+
+        let kind = src[0];
+        let payload_length = u32::from_be_bytes([src[1], src[2], src[3], src[4]]) as usize;
+        if payload_length > MAX_FRAME_PAYLOAD_LENGTH {
+            return Err(WsError::Capacity(CapacityError::MessageTooLong {
+                size: payload_length,
+                max_size: MAX_FRAME_PAYLOAD_LENGTH,
+            }));
+        }
+
+        let frame_length = HEADER_LENGTH + payload_length;
+        if src.len() < frame_length {
+            // we don't have enough bytes to read the rest of frame
+            src.reserve(frame_length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(HEADER_LENGTH);
+        let payload = src.split_to(payload_length).to_vec();
+
+        let message = match kind {
+            TEXT_FRAME => Message::Text(String::from_utf8(payload).map_err(|_| WsError::Utf8)?),
+            BINARY_FRAME => Message::Binary(payload),
+            CLOSE_FRAME => Message::Close(None),
+            other => {
+                return Err(WsError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("received frame of unknown kind {}", other),
+                )))
+            }
+        };
+
+        Ok(Some(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(message: Message) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        GatewayCodec.encode(message, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn messages_survive_encoding_and_decoding() {
+        let messages = vec![
+            Message::Text("{\"type\":\"bandwidthCredential\"}".to_string()),
+            Message::Binary(vec![42; 2048]),
+            Message::Binary(Vec::new()),
+            Message::Close(None),
+        ];
+
+        let mut bytes = BytesMut::new();
+        for message in messages.clone() {
+            GatewayCodec.encode(message, &mut bytes).unwrap();
+        }
+
+        for message in messages {
+            assert_eq!(Some(message), GatewayCodec.decode(&mut bytes).unwrap())
+        }
+        assert!(bytes.is_empty());
+        assert!(GatewayCodec.decode(&mut bytes).unwrap().is_none());
+    }
+
+    #[test]
+    fn partial_frames_are_not_decoded() {
+        let message = Message::Binary(vec![1, 2, 3, 4, 5]);
+        let full = encode(message.clone());
+
+        let mut partial = BytesMut::from(&full[..3]);
+        assert!(GatewayCodec.decode(&mut partial).unwrap().is_none());
+
+        let mut partial = BytesMut::from(&full[..full.len() - 1]);
+        assert!(GatewayCodec.decode(&mut partial).unwrap().is_none());
+
+        partial.put_u8(full[full.len() - 1]);
+        assert_eq!(Some(message), GatewayCodec.decode(&mut partial).unwrap());
+    }
+
+    #[test]
+    fn pings_and_pongs_are_not_sent() {
+        assert!(encode(Message::Ping(vec![1, 2, 3])).is_empty());
+        assert!(encode(Message::Pong(vec![1, 2, 3])).is_empty());
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(BINARY_FRAME);
+        bytes.put_u32(MAX_FRAME_PAYLOAD_LENGTH as u32 + 1);
+        assert!(GatewayCodec.decode(&mut bytes).is_err());
+
+        let oversized = Message::Binary(vec![0; MAX_FRAME_PAYLOAD_LENGTH + 1]);
+        assert!(GatewayCodec
+            .encode(oversized, &mut BytesMut::new())
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_frames_are_rejected() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(42);
+        bytes.put_u32(0);
+        assert!(GatewayCodec.decode(&mut bytes).is_err());
+    }
+
+    #[test]
+    fn text_frames_must_be_valid_utf8() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(TEXT_FRAME);
+        bytes.put_u32(2);
+        bytes.put_slice(&[0xc3, 0x28]);
+        assert!(GatewayCodec.decode(&mut bytes).is_err());
+    }
+}
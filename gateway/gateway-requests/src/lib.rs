@@ -7,6 +7,9 @@ use nymsphinx::params::GatewayIntegrityHmacAlgorithm;
 pub use types::*;
 
 pub mod authentication;
+pub mod bandwidth;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod codec;
 pub mod iv;
 pub mod registration;
 pub mod types;
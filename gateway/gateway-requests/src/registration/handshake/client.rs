@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::registration::handshake::shared_key::SharedKeys;
-use crate::registration::handshake::state::State;
+use crate::registration::handshake::state::{InitResponse, State};
 use crate::registration::handshake::{error::HandshakeError, WsItem};
 use crypto::asymmetric::encryption::PUBLIC_KEY_SIZE;
 use crypto::asymmetric::identity::SIGNATURE_LENGTH;
@@ -52,10 +52,25 @@ impl<'a> ClientHandshake<'a> {
                 }
 
                 let init_message = state.init_message();
-                state.send_handshake_data(init_message).await?;
+                state.send_init_message(init_message.clone()).await?;
 
                 // <- g^y || AES(k, sig(gate_priv, (g^y || g^x))
-                let mid_res = state.receive_handshake_message().await?;
+                // unless the gateway is under load and wants us to solve a puzzle first
+                let mid_res = match state.receive_init_response().await? {
+                    InitResponse::Payload(data) => data,
+                    InitResponse::PuzzleChallenge(puzzle) => {
+                        // solving the puzzle is cpu-bound, so make sure not to block the executor
+                        let solution = check_processing_error(
+                            puzzle.solve_in_background(init_message.clone()).await,
+                            &mut state,
+                        )
+                        .await?;
+                        state
+                            .send_solved_init_message(init_message, solution)
+                            .await?;
+                        state.receive_handshake_message().await?
+                    }
+                };
                 let (remote_ephemeral_key, remote_key_material) =
                     check_processing_error(Self::parse_mid_response(mid_res), &mut state).await?;
 
@@ -25,6 +25,10 @@ pub enum HandshakeError {
     MalformedRequest,
     #[error("sent request was malformed")]
     HandshakeFailure,
+    #[error("the gateway requires solving a puzzle of excessive difficulty ({0})")]
+    PuzzleTooDifficult(u8),
+    #[error("failed to solve the registration puzzle")]
+    PuzzleSolvingFailure,
     #[error("could not deserialize from slice: {source}")]
     DeserializationError {
         #[from]
@@ -4,6 +4,7 @@
 use crate::registration::handshake::error::HandshakeError;
 use crate::registration::handshake::shared_key::{SharedKeySize, SharedKeys};
 use crate::registration::handshake::WsItem;
+use crate::registration::puzzle::RegistrationPuzzle;
 use crate::types;
 use crypto::{
     asymmetric::{encryption, identity},
@@ -18,6 +19,12 @@ use rand::{CryptoRng, RngCore};
 use std::convert::{TryFrom, TryInto};
 use tungstenite::Message as WsMessage;
 
+/// Response of the gateway to the handshake init message.
+pub(crate) enum InitResponse {
+    Payload(Vec<u8>),
+    PuzzleChallenge(RegistrationPuzzle),
+}
+
 /// Handshake state.
 pub(crate) struct State<'a, S> {
     /// The underlying WebSocket stream.
@@ -200,7 +207,9 @@ impl<'a, S> State<'a, S> {
         self.remote_pubkey = Some(remote_pubkey)
     }
 
-    pub(crate) async fn receive_handshake_message(&mut self) -> Result<Vec<u8>, HandshakeError>
+    async fn receive_registration_handshake(
+        &mut self,
+    ) -> Result<types::RegistrationHandshake, HandshakeError>
     where
         S: Stream<Item = WsItem> + Unpin,
     {
@@ -209,10 +218,7 @@ impl<'a, S> State<'a, S> {
                 if let Ok(msg) = msg {
                     match msg {
                         WsMessage::Text(ws_msg) => match types::RegistrationHandshake::try_from(ws_msg) {
-                            Ok(reg_handshake_msg) => return match reg_handshake_msg {
-                                types::RegistrationHandshake::HandshakePayload { data } => Ok(data),
-                                types::RegistrationHandshake::HandshakeError { message } => Err(HandshakeError::RemoteError(message)),
-                            },
+                            Ok(reg_handshake_msg) => return Ok(reg_handshake_msg),
                             Err(_) => error!("Received a non-handshake message during the registration handshake! It's getting dropped."),
                         },
                         _ => error!("Received non-text message during registration handshake"),
@@ -226,6 +232,44 @@ impl<'a, S> State<'a, S> {
         }
     }
 
+    pub(crate) async fn receive_handshake_message(&mut self) -> Result<Vec<u8>, HandshakeError>
+    where
+        S: Stream<Item = WsItem> + Unpin,
+    {
+        match self.receive_registration_handshake().await? {
+            types::RegistrationHandshake::HandshakePayload { data, .. } => Ok(data),
+            types::RegistrationHandshake::HandshakeError { message } => {
+                Err(HandshakeError::RemoteError(message))
+            }
+            // puzzles can only be requested in response to the init message
+            types::RegistrationHandshake::PuzzleChallenge { .. } => {
+                Err(HandshakeError::MalformedResponse)
+            }
+        }
+    }
+
+    /// Receives the response to the init message, which, apart from continuing the handshake,
+    /// might be a puzzle the gateway requires solving before it does so.
+    pub(crate) async fn receive_init_response(&mut self) -> Result<InitResponse, HandshakeError>
+    where
+        S: Stream<Item = WsItem> + Unpin,
+    {
+        match self.receive_registration_handshake().await? {
+            types::RegistrationHandshake::HandshakePayload { data, .. } => {
+                Ok(InitResponse::Payload(data))
+            }
+            types::RegistrationHandshake::HandshakeError { message } => {
+                Err(HandshakeError::RemoteError(message))
+            }
+            types::RegistrationHandshake::PuzzleChallenge {
+                challenge,
+                difficulty,
+            } => Ok(InitResponse::PuzzleChallenge(
+                RegistrationPuzzle::try_from_parts(&challenge, difficulty)?,
+            )),
+        }
+    }
+
     // upon receiving this, the receiver should terminate the handshake
     pub(crate) async fn send_handshake_error<M: Into<String>>(
         &mut self,
@@ -255,6 +299,38 @@ impl<'a, S> State<'a, S> {
             .map_err(|_| HandshakeError::ClosedStream)
     }
 
+    /// Sends the init message of the handshake, advertising that we're able to solve the
+    /// registration puzzle.
+    pub(crate) async fn send_init_message(
+        &mut self,
+        init_message: Vec<u8>,
+    ) -> Result<(), HandshakeError>
+    where
+        S: Sink<WsMessage> + Unpin,
+    {
+        let handshake_message = types::RegistrationHandshake::new_init_payload(init_message);
+        self.ws_stream
+            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
+            .await
+            .map_err(|_| HandshakeError::ClosedStream)
+    }
+
+    pub(crate) async fn send_solved_init_message(
+        &mut self,
+        init_message: Vec<u8>,
+        puzzle_solution: u64,
+    ) -> Result<(), HandshakeError>
+    where
+        S: Sink<WsMessage> + Unpin,
+    {
+        let handshake_message =
+            types::RegistrationHandshake::new_solved_payload(init_message, puzzle_solution);
+        self.ws_stream
+            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
+            .await
+            .map_err(|_| HandshakeError::ClosedStream)
+    }
+
     /// Finish the handshake, yielding the derived shared key and implicitly dropping all borrowed
     /// values.
     pub(crate) fn finalize_handshake(self) -> SharedKeys {
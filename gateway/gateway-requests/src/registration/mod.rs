@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod handshake;
+pub mod puzzle;
 
 // TODO: is it perhaps possible to replace the 'custom' handshake with an existing
 // implementation like with one of the variants on the Noise framework?
@@ -0,0 +1,227 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client puzzle (proof of work) the gateway might require registering clients to solve when
+//! it's under load, before it commits to the comparatively expensive registration handshake.
+//!
+//! A solution is a number such that blake3(challenge || init_data || solution) starts with at
+//! least `difficulty` zero bits. As `init_data` contains the ephemeral key of the client,
+//! a solution can't be reused for another registration.
+
+use crate::registration::handshake::error::HandshakeError;
+use crypto::blake3;
+use rand::{CryptoRng, RngCore};
+use std::ops::Range;
+
+#[cfg(target_arch = "wasm32")]
+use futures::task::{Context, Poll};
+#[cfg(target_arch = "wasm32")]
+use std::future::Future;
+#[cfg(target_arch = "wasm32")]
+use std::pin::Pin;
+
+pub const PUZZLE_CHALLENGE_SIZE: usize = 32;
+
+/// Maximum difficulty of a puzzle the client is willing to solve. Solving it requires
+/// computing 2^`MAX_PUZZLE_DIFFICULTY` hashes on average.
+pub const MAX_PUZZLE_DIFFICULTY: u8 = 24;
+
+/// Number of candidate solutions checked in between yielding to the executor
+/// when solving the puzzle in the background on wasm.
+#[cfg(target_arch = "wasm32")]
+const SOLVING_BATCH_SIZE: u64 = 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistrationPuzzle {
+    challenge: [u8; PUZZLE_CHALLENGE_SIZE],
+    difficulty: u8,
+}
+
+impl RegistrationPuzzle {
+    pub fn new(rng: &mut (impl RngCore + CryptoRng), difficulty: u8) -> Self {
+        let mut challenge = [0u8; PUZZLE_CHALLENGE_SIZE];
+        rng.fill_bytes(&mut challenge);
+        RegistrationPuzzle {
+            challenge,
+            difficulty,
+        }
+    }
+
+    pub fn try_from_parts(challenge: &[u8], difficulty: u8) -> Result<Self, HandshakeError> {
+        if challenge.len() != PUZZLE_CHALLENGE_SIZE {
+            return Err(HandshakeError::MalformedResponse);
+        }
+
+        let mut challenge_bytes = [0u8; PUZZLE_CHALLENGE_SIZE];
+        challenge_bytes.copy_from_slice(challenge);
+        Ok(RegistrationPuzzle {
+            challenge: challenge_bytes,
+            difficulty,
+        })
+    }
+
+    pub fn challenge(&self) -> &[u8] {
+        &self.challenge
+    }
+
+    pub fn difficulty(&self) -> u8 {
+        self.difficulty
+    }
+
+    fn hash(&self, init_data: &[u8], solution: u64) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.challenge);
+        hasher.update(init_data);
+        hasher.update(&solution.to_be_bytes());
+        hasher.finalize()
+    }
+
+    /// Checks whether the provided number solves the puzzle for the given handshake init message.
+    pub fn verify(&self, init_data: &[u8], solution: u64) -> bool {
+        leading_zero_bits(self.hash(init_data, solution).as_bytes()) >= self.difficulty as u32
+    }
+
+    fn ensure_solvable(&self) -> Result<(), HandshakeError> {
+        if self.difficulty > MAX_PUZZLE_DIFFICULTY {
+            Err(HandshakeError::PuzzleTooDifficult(self.difficulty))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn solve_within(&self, init_data: &[u8], candidates: Range<u64>) -> Option<u64> {
+        candidates.find(|&solution| self.verify(init_data, solution))
+    }
+
+    /// Attempts to find a solution to the puzzle for the given handshake init message,
+    /// as long as it's not excessively difficult.
+    ///
+    /// Note that this is a blocking, cpu-bound, operation.
+    pub fn solve(&self, init_data: &[u8]) -> Result<u64, HandshakeError> {
+        self.ensure_solvable()?;
+
+        // with difficulty capped, the solution is certain to be found way before running out of numbers
+        Ok(self.solve_within(init_data, 0..u64::MAX).unwrap())
+    }
+
+    /// Attempts to find a solution to the puzzle without blocking the async executor,
+    /// by solving it on a dedicated blocking thread.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn solve_in_background(&self, init_data: Vec<u8>) -> Result<u64, HandshakeError> {
+        self.ensure_solvable()?;
+
+        let puzzle = self.clone();
+        tokio::task::spawn_blocking(move || puzzle.solve(&init_data))
+            .await
+            .map_err(|_| HandshakeError::PuzzleSolvingFailure)?
+    }
+
+    /// Attempts to find a solution to the puzzle without blocking the async executor. As there are
+    /// no threads to offload the work to in wasm, it periodically yields to let other tasks progress.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn solve_in_background(&self, init_data: Vec<u8>) -> Result<u64, HandshakeError> {
+        self.ensure_solvable()?;
+
+        let mut batch_start = 0;
+        loop {
+            let batch = batch_start..batch_start.saturating_add(SOLVING_BATCH_SIZE);
+            if let Some(solution) = self.solve_within(&init_data, batch) {
+                return Ok(solution);
+            }
+            batch_start += SOLVING_BATCH_SIZE;
+            YieldNow { yielded: false }.await
+        }
+    }
+}
+
+/// Future that is pending exactly once, allowing other tasks to be polled in the meantime.
+#[cfg(target_arch = "wasm32")]
+struct YieldNow {
+    yielded: bool,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut zeros = 0;
+    for byte in bytes {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_zero_bits_are_counted_across_bytes() {
+        assert_eq!(0, leading_zero_bits(&[0b1000_0000, 0]));
+        assert_eq!(3, leading_zero_bits(&[0b0001_0000, 0]));
+        assert_eq!(8, leading_zero_bits(&[0, 0b1000_0000]));
+        assert_eq!(13, leading_zero_bits(&[0, 0b0000_0100, 0]));
+        assert_eq!(16, leading_zero_bits(&[0, 0]));
+    }
+
+    #[test]
+    fn solved_puzzle_is_verified() {
+        let puzzle = RegistrationPuzzle::try_from_parts(&[42; PUZZLE_CHALLENGE_SIZE], 12).unwrap();
+        let init_data = [1; 64];
+
+        let solution = puzzle.solve(&init_data).unwrap();
+        assert!(puzzle.verify(&init_data, solution));
+    }
+
+    #[test]
+    fn solution_is_bound_to_the_challenge_and_init_data() {
+        let puzzle = RegistrationPuzzle::try_from_parts(&[42; PUZZLE_CHALLENGE_SIZE], 16).unwrap();
+        let other_puzzle =
+            RegistrationPuzzle::try_from_parts(&[43; PUZZLE_CHALLENGE_SIZE], 16).unwrap();
+        let init_data = [1; 64];
+
+        let solution = puzzle.solve(&init_data).unwrap();
+        assert!(!puzzle.verify(&[2; 64], solution));
+        assert!(!other_puzzle.verify(&init_data, solution));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn puzzle_solved_in_background_is_verified() {
+        let puzzle = RegistrationPuzzle::try_from_parts(&[42; PUZZLE_CHALLENGE_SIZE], 12).unwrap();
+        let init_data = vec![1; 64];
+
+        let solution = puzzle.solve_in_background(init_data.clone()).await.unwrap();
+        assert!(puzzle.verify(&init_data, solution));
+    }
+
+    #[test]
+    fn excessively_difficult_puzzles_are_not_solved() {
+        let puzzle = RegistrationPuzzle::try_from_parts(
+            &[42; PUZZLE_CHALLENGE_SIZE],
+            MAX_PUZZLE_DIFFICULTY + 1,
+        )
+        .unwrap();
+        assert!(puzzle.solve(&[1; 64]).is_err());
+    }
+
+    #[test]
+    fn challenge_of_invalid_size_is_rejected() {
+        assert!(RegistrationPuzzle::try_from_parts(&[42; PUZZLE_CHALLENGE_SIZE - 1], 8).is_err());
+    }
+}
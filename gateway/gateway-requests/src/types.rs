@@ -4,6 +4,7 @@
 use crate::authentication::encrypted_address::EncryptedAddressBytes;
 use crate::iv::IV;
 use crate::registration::handshake::SharedKeys;
+use crate::registration::puzzle::RegistrationPuzzle;
 use crate::GatewayMacSize;
 use crypto::generic_array::typenum::Unsigned;
 use crypto::hmac::recompute_keyed_hmac_and_verify_tag;
@@ -28,13 +29,57 @@ use credentials::token::bandwidth::TokenCredential;
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum RegistrationHandshake {
-    HandshakePayload { data: Vec<u8> },
-    HandshakeError { message: String },
+    HandshakePayload {
+        data: Vec<u8>,
+        /// Solution to the registration puzzle, if the gateway has requested one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        puzzle_solution: Option<u64>,
+        /// Indicates whether the client is able to solve the registration puzzle. Only ever set
+        /// on the init message, as older clients don't understand the `PuzzleChallenge`.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        supports_puzzle: bool,
+    },
+    HandshakeError {
+        message: String,
+    },
+    /// Sent by the gateway under load instead of continuing the handshake. The client is expected
+    /// to resend its init message alongside the solution of the puzzle.
+    PuzzleChallenge {
+        challenge: Vec<u8>,
+        difficulty: u8,
+    },
 }
 
 impl RegistrationHandshake {
     pub fn new_payload(data: Vec<u8>) -> Self {
-        RegistrationHandshake::HandshakePayload { data }
+        RegistrationHandshake::HandshakePayload {
+            data,
+            puzzle_solution: None,
+            supports_puzzle: false,
+        }
+    }
+
+    pub fn new_init_payload(data: Vec<u8>) -> Self {
+        RegistrationHandshake::HandshakePayload {
+            data,
+            puzzle_solution: None,
+            supports_puzzle: true,
+        }
+    }
+
+    pub fn new_solved_payload(data: Vec<u8>, puzzle_solution: u64) -> Self {
+        RegistrationHandshake::HandshakePayload {
+            data,
+            puzzle_solution: Some(puzzle_solution),
+            supports_puzzle: true,
+        }
+    }
+
+    pub fn new_puzzle_challenge(puzzle: &RegistrationPuzzle) -> Self {
+        RegistrationHandshake::PuzzleChallenge {
+            challenge: puzzle.challenge().to_vec(),
+            difficulty: puzzle.difficulty(),
+        }
     }
 
     pub fn new_error<S: Into<String>>(message: S) -> Self {
@@ -116,16 +161,34 @@ pub enum ClientControlRequest {
         address: String,
         enc_address: String,
         iv: String,
+        /// Indicates whether the client is going to acknowledge receiving its stored messages,
+        /// in which case the gateway keeps them until it receives the acknowledgement.
+        #[serde(default)]
+        acknowledges_stored_messages: bool,
+        /// Indicates whether the client understands `BandwidthUpdate` responses pushed by the gateway
+        /// without any prior request. Older clients treat them as an error.
+        #[serde(default)]
+        accepts_bandwidth_updates: bool,
     },
     #[serde(alias = "handshakePayload")]
     RegisterHandshakeInitRequest {
         data: Vec<u8>,
+        #[serde(default)]
+        puzzle_solution: Option<u64>,
+        #[serde(default)]
+        supports_puzzle: bool,
     },
     BandwidthCredential {
         enc_credential: Vec<u8>,
         iv: Vec<u8>,
     },
     ClaimFreeTestnetBandwidth,
+    AcknowledgeStoredMessages {
+        up_to_id: i64,
+    },
+    /// Requests removal of all data the gateway keeps for the authenticated client,
+    /// i.e. its shared keys, stored messages and available bandwidth.
+    Unregister,
 }
 
 impl ClientControlRequest {
@@ -133,14 +196,22 @@ impl ClientControlRequest {
         address: DestinationAddressBytes,
         enc_address: EncryptedAddressBytes,
         iv: IV,
+        acknowledges_stored_messages: bool,
+        accepts_bandwidth_updates: bool,
     ) -> Self {
         ClientControlRequest::Authenticate {
             address: address.as_base58_string(),
             enc_address: enc_address.to_base58_string(),
             iv: iv.to_base58_string(),
+            acknowledges_stored_messages,
+            accepts_bandwidth_updates,
         }
     }
 
+    pub fn new_acknowledge_stored_messages(up_to_id: i64) -> Self {
+        ClientControlRequest::AcknowledgeStoredMessages { up_to_id }
+    }
+
     #[cfg(feature = "coconut")]
     pub fn new_enc_coconut_bandwidth_credential(
         credential: &Credential,
@@ -227,9 +298,19 @@ pub enum ServerResponse {
     Authenticate {
         status: bool,
         bandwidth_remaining: i64,
+        /// Number of messages that got removed by the gateway before the client managed to retrieve them.
+        #[serde(default)]
+        dropped_messages: u64,
+        /// Id of the last stored message pushed to the client, which it should acknowledge once received.
+        /// Only present if the client has declared it acknowledges stored messages.
+        #[serde(default)]
+        last_stored_message_id: Option<i64>,
     },
     Register {
         status: bool,
+        /// Number of messages that got removed by the gateway before the client managed to retrieve them.
+        #[serde(default)]
+        dropped_messages: u64,
     },
     Bandwidth {
         available_total: i64,
@@ -237,6 +318,19 @@ pub enum ServerResponse {
     Send {
         remaining_bandwidth: i64,
     },
+    /// Pushed by the gateway, without any prior request, once the available bandwidth of
+    /// the client has changed considerably or some time has passed since the last update.
+    /// Only sent to clients that have declared they accept it upon authentication.
+    BandwidthUpdate {
+        available_total: i64,
+    },
+    StoredMessagesAcknowledged,
+    /// Sent once the client, that was already authenticated, has completed a fresh handshake
+    /// and the gateway has replaced the previously stored shared keys.
+    Rekey {
+        status: bool,
+    },
+    Unregistered,
     Error {
         message: String,
     },
@@ -383,15 +477,104 @@ mod tests {
     #[test]
     fn handshake_payload_can_be_deserialized_into_register_handshake_init_request() {
         let handshake_data = vec![1, 2, 3, 4, 5, 6];
-        let handshake_payload = RegistrationHandshake::HandshakePayload {
-            data: handshake_data.clone(),
-        };
+        let handshake_payload = RegistrationHandshake::new_payload(handshake_data.clone());
+        let serialized = serde_json::to_string(&handshake_payload).unwrap();
+        let deserialized = ClientControlRequest::try_from(serialized).unwrap();
+
+        match deserialized {
+            ClientControlRequest::RegisterHandshakeInitRequest {
+                data,
+                puzzle_solution,
+                supports_puzzle,
+            } => {
+                assert_eq!(data, handshake_data);
+                assert!(puzzle_solution.is_none());
+                // as sent by the older clients
+                assert!(!supports_puzzle)
+            }
+            _ => unreachable!("this branch shouldn't have been reached!"),
+        }
+    }
+
+    #[test]
+    fn solved_handshake_payload_can_be_deserialized_into_register_handshake_init_request() {
+        let handshake_data = vec![1, 2, 3, 4, 5, 6];
+        let handshake_payload =
+            RegistrationHandshake::new_solved_payload(handshake_data.clone(), 42);
         let serialized = serde_json::to_string(&handshake_payload).unwrap();
         let deserialized = ClientControlRequest::try_from(serialized).unwrap();
 
         match deserialized {
-            ClientControlRequest::RegisterHandshakeInitRequest { data } => {
-                assert_eq!(data, handshake_data)
+            ClientControlRequest::RegisterHandshakeInitRequest {
+                data,
+                puzzle_solution,
+                supports_puzzle,
+            } => {
+                assert_eq!(data, handshake_data);
+                assert_eq!(Some(42), puzzle_solution);
+                assert!(supports_puzzle)
+            }
+            _ => unreachable!("this branch shouldn't have been reached!"),
+        }
+    }
+
+    #[test]
+    fn init_payload_advertises_puzzle_support() {
+        let handshake_data = vec![1, 2, 3, 4, 5, 6];
+        let handshake_payload = RegistrationHandshake::new_init_payload(handshake_data.clone());
+        let serialized = serde_json::to_string(&handshake_payload).unwrap();
+        let deserialized = ClientControlRequest::try_from(serialized).unwrap();
+
+        match deserialized {
+            ClientControlRequest::RegisterHandshakeInitRequest {
+                data,
+                puzzle_solution,
+                supports_puzzle,
+            } => {
+                assert_eq!(data, handshake_data);
+                assert!(puzzle_solution.is_none());
+                assert!(supports_puzzle)
+            }
+            _ => unreachable!("this branch shouldn't have been reached!"),
+        }
+    }
+
+    #[test]
+    fn authenticate_response_without_dropped_messages_can_be_deserialized() {
+        // sent by gateways that do not report dropped messages
+        let raw_response = r#"{"type":"authenticate","status":true,"bandwidth_remaining":42}"#;
+        let deserialized: ServerResponse = serde_json::from_str(raw_response).unwrap();
+
+        match deserialized {
+            ServerResponse::Authenticate {
+                status,
+                bandwidth_remaining,
+                dropped_messages,
+                last_stored_message_id,
+            } => {
+                assert!(status);
+                assert_eq!(bandwidth_remaining, 42);
+                assert_eq!(dropped_messages, 0);
+                assert!(last_stored_message_id.is_none());
+            }
+            _ => unreachable!("this branch shouldn't have been reached!"),
+        }
+    }
+
+    #[test]
+    fn authenticate_request_from_legacy_client_does_not_acknowledge_stored_messages() {
+        let raw_request =
+            r#"{"type":"authenticate","address":"foo","enc_address":"bar","iv":"baz"}"#;
+        let deserialized = ClientControlRequest::try_from(raw_request.to_string()).unwrap();
+
+        match deserialized {
+            ClientControlRequest::Authenticate {
+                acknowledges_stored_messages,
+                accepts_bandwidth_updates,
+                ..
+            } => {
+                assert!(!acknowledges_stored_messages);
+                assert!(!accepts_bandwidth_updates)
             }
             _ => unreachable!("this branch shouldn't have been reached!"),
         }
@@ -0,0 +1,40 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+CREATE TABLE shared_keys
+(
+    client_address_bs58                      TEXT NOT NULL PRIMARY KEY,
+    derived_aes128_ctr_blake3_hmac_keys_bs58 TEXT NOT NULL
+);
+
+CREATE TABLE message_store
+(
+    id                  BIGSERIAL NOT NULL PRIMARY KEY,
+    client_address_bs58 TEXT      NOT NULL,
+    content             BYTEA     NOT NULL,
+    timestamp           BIGINT    NOT NULL
+);
+
+CREATE INDEX message_store_index ON message_store (client_address_bs58, id);
+CREATE INDEX message_store_timestamp_index ON message_store (timestamp);
+
+CREATE TABLE available_bandwidth
+(
+    client_address_bs58 TEXT   NOT NULL PRIMARY KEY,
+    available           BIGINT NOT NULL
+);
+
+-- number of messages that got removed before the client managed to retrieve them
+CREATE TABLE dropped_messages
+(
+    client_address_bs58 TEXT   NOT NULL PRIMARY KEY,
+    count               BIGINT NOT NULL
+);
+
+CREATE TABLE spent_credentials
+(
+    blinded_serial_number_bs58 TEXT NOT NULL PRIMARY KEY,
+    client_address_bs58        TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+CREATE TABLE spent_credentials
+(
+    blinded_serial_number_bs58 TEXT NOT NULL PRIMARY KEY UNIQUE,
+    client_address_bs58        TEXT NOT NULL
+);
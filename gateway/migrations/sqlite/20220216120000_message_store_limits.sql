@@ -0,0 +1,22 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- default exists here since otherwise a column couldn't have been added
+ALTER TABLE message_store
+    ADD COLUMN timestamp INTEGER NOT NULL default 0;
+
+-- treat all existing messages as if they were received during the migration
+-- so that they wouldn't get purged straight away
+UPDATE message_store
+SET timestamp = strftime('%s', 'now');
+
+CREATE INDEX `message_store_timestamp_index` ON `message_store` (`timestamp`);
+
+-- number of messages that got removed before the client managed to retrieve them
+CREATE TABLE dropped_messages
+(
+    client_address_bs58 TEXT    NOT NULL PRIMARY KEY UNIQUE,
+    count               INTEGER NOT NULL
+);
@@ -0,0 +1,9 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- whether the message has already been pushed to (and paid for by) the client,
+-- so that redelivering unacknowledged messages wouldn't charge it again
+ALTER TABLE message_store
+    ADD COLUMN delivered BOOLEAN NOT NULL default FALSE;
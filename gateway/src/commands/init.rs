@@ -38,6 +38,18 @@ pub fn command_args<'a, 'b>() -> clap::App<'a, 'b> {
                 .help("The port on which the gateway will be listening for clients gateway-requests")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name(CLIENTS_TCP_PORT_ARG_NAME)
+                .long(CLIENTS_TCP_PORT_ARG_NAME)
+                .help("The port on which the gateway will be listening for clients gateway-requests sent over raw TCP. Setting it to 0 disables the listener")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(HTTP_API_PORT_ARG_NAME)
+                .long(HTTP_API_PORT_ARG_NAME)
+                .help("The port on which the gateway will be listening for its HTTP API requests")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name(ANNOUNCE_HOST_ARG_NAME)
                 .long(ANNOUNCE_HOST_ARG_NAME)
@@ -11,6 +11,7 @@ use url::Url;
 
 pub(crate) mod init;
 pub(crate) mod node_details;
+pub(crate) mod rotate_identity;
 pub(crate) mod run;
 pub(crate) mod sign;
 pub(crate) mod upgrade;
@@ -19,6 +20,8 @@ pub(crate) const ID_ARG_NAME: &str = "id";
 pub(crate) const HOST_ARG_NAME: &str = "host";
 pub(crate) const MIX_PORT_ARG_NAME: &str = "mix-port";
 pub(crate) const CLIENTS_PORT_ARG_NAME: &str = "clients-port";
+pub(crate) const CLIENTS_TCP_PORT_ARG_NAME: &str = "clients-tcp-port";
+pub(crate) const HTTP_API_PORT_ARG_NAME: &str = "http-api-port";
 pub(crate) const VALIDATOR_APIS_ARG_NAME: &str = "validator-apis";
 #[cfg(not(feature = "coconut"))]
 pub(crate) const VALIDATORS_ARG_NAME: &str = "validators";
@@ -79,6 +82,28 @@ pub(crate) fn override_config(mut config: Config, matches: &ArgMatches<'_>) -> C
         config = config.with_clients_port(clients_port.unwrap());
     }
 
+    if let Some(clients_tcp_port) = matches
+        .value_of(CLIENTS_TCP_PORT_ARG_NAME)
+        .map(|port| port.parse::<u16>())
+    {
+        if let Err(err) = clients_tcp_port {
+            // if port was overridden, it must be parsable
+            panic!("Invalid port value provided - {:?}", err);
+        }
+        config = config.with_clients_tcp_port(clients_tcp_port.unwrap());
+    }
+
+    if let Some(http_api_port) = matches
+        .value_of(HTTP_API_PORT_ARG_NAME)
+        .map(|port| port.parse::<u16>())
+    {
+        if let Err(err) = http_api_port {
+            // if port was overridden, it must be parsable
+            panic!("Invalid port value provided - {:?}", err);
+        }
+        config = config.with_http_api_port(http_api_port.unwrap());
+    }
+
     if let Some(announce_host) = matches.value_of(ANNOUNCE_HOST_ARG_NAME) {
         config = config.with_announce_address(announce_host);
     } else if was_host_overridden {
@@ -0,0 +1,135 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::commands::sign::load_identity_keys;
+use crate::commands::*;
+use crate::config::{persistence::pathfinder::GatewayPathfinder, Config};
+use clap::{App, Arg, ArgMatches};
+use config::NymConfig;
+use crypto::asymmetric::identity;
+use log::error;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const WALLET_ADDRESS_ARG_NAME: &str = "wallet-address";
+
+pub fn command_args<'a, 'b>() -> App<'a, 'b> {
+    App::new("rotate-identity")
+        .about("Replace the identity key of the gateway, keeping the old one until the change is submitted on chain")
+        .arg(
+            Arg::with_name(ID_ARG_NAME)
+                .long(ID_ARG_NAME)
+                .help("The id of the gateway whose identity key you want to replace")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(WALLET_ADDRESS_ARG_NAME)
+                .long(WALLET_ADDRESS_ARG_NAME)
+                .help("Address of the wallet that owns the gateway bond")
+                .takes_value(true)
+                .required(true),
+        )
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(".old");
+    backup.into()
+}
+
+// keeps the current keys around until the rotation is confirmed on chain. If the backup already
+// exists, a previous rotation might have not been submitted yet, so it must not be overwritten.
+fn backup_identity_keys(pathfinder: &GatewayPathfinder) -> io::Result<()> {
+    let key_paths = [
+        pathfinder.private_identity_key(),
+        pathfinder.public_identity_key(),
+    ];
+
+    for path in key_paths {
+        let backup = backup_path(path);
+        if backup.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{:?} already exists. If the previous rotation has already been submitted on chain, remove it and try again",
+                    backup
+                ),
+            ));
+        }
+    }
+
+    for path in key_paths {
+        fs::copy(path, backup_path(path))?;
+    }
+    Ok(())
+}
+
+pub fn execute(matches: &ArgMatches<'_>) {
+    let id = matches.value_of(ID_ARG_NAME).unwrap();
+
+    let config = match Config::load_from_file(Some(id)) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("Failed to load config for {}. Are you sure you have run `init` before? (Error was: {})", id, err);
+            return;
+        }
+    };
+
+    if !version_check(&config) {
+        error!("failed the local version check");
+        return;
+    }
+
+    let wallet_address = matches.value_of(WALLET_ADDRESS_ARG_NAME).unwrap().trim();
+    validate_bech32_address_or_exit(wallet_address);
+
+    let pathfinder = GatewayPathfinder::new_from_config(&config);
+    let old_identity_keypair = load_identity_keys(&pathfinder);
+
+    if let Err(err) = backup_identity_keys(&pathfinder) {
+        error!("Failed to back up the current identity keys - {}", err);
+        return;
+    }
+
+    let mut rng = rand::rngs::OsRng;
+    let new_identity_keypair = identity::KeyPair::new(&mut rng);
+    if let Err(err) = pemstore::store_keypair(
+        &new_identity_keypair,
+        &pemstore::KeyPairPath::new(
+            pathfinder.private_identity_key().to_owned(),
+            pathfinder.public_identity_key().to_owned(),
+        ),
+    ) {
+        error!(
+            "Failed to save the new identity keys - restore them from the '.old' backups before trying again (Error was: {})",
+            err
+        );
+        return;
+    }
+
+    // the old key vouches for the new one, while the new one proves it belongs to the bond owner
+    let identity_signature = old_identity_keypair
+        .private_key()
+        .sign(&new_identity_keypair.public_key().to_bytes())
+        .to_base58_string();
+    let owner_signature = new_identity_keypair.private_key().sign_text(wallet_address);
+
+    println!(
+        "Replaced the identity key {}. The previous keys were backed up alongside the new ones with the '.old' suffix.",
+        old_identity_keypair.public_key().to_base58_string(),
+    );
+    println!(
+        "New identity key: {}",
+        new_identity_keypair.public_key().to_base58_string()
+    );
+    println!("Owner signature: {}", owner_signature);
+    println!("Identity signature: {}", identity_signature);
+    println!(
+        "\nSubmit the above in the 'update_gateway_identity' transaction from {} and restart the gateway right after it gets included in a block. \
+        Until then, keep the backed up keys - if the transaction fails, restore them to go back to the previous identity.",
+        wallet_address
+    );
+}
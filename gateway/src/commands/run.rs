@@ -37,6 +37,18 @@ pub fn command_args<'a, 'b>() -> clap::App<'a, 'b> {
                 .help("The port on which the gateway will be listening for clients gateway-requests")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name(CLIENTS_TCP_PORT_ARG_NAME)
+                .long(CLIENTS_TCP_PORT_ARG_NAME)
+                .help("The port on which the gateway will be listening for clients gateway-requests sent over raw TCP. Setting it to 0 disables the listener")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name(HTTP_API_PORT_ARG_NAME)
+                .long(HTTP_API_PORT_ARG_NAME)
+                .help("The port on which the gateway will be listening for its HTTP API requests")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name(ANNOUNCE_HOST_ARG_NAME)
                 .long(ANNOUNCE_HOST_ARG_NAME)
@@ -108,6 +120,7 @@ pub async fn execute(matches: ArgMatches<'static>) {
     };
 
     config = override_config(config, &matches);
+    crate::set_log_level(config.get_log_level());
 
     if !version_check(&config) {
         error!("failed the local version check");
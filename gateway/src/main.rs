@@ -1,3 +1,6 @@
+#[macro_use]
+extern crate rocket;
+
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
@@ -23,6 +26,7 @@ async fn main() {
         .subcommand(commands::sign::command_args())
         .subcommand(commands::upgrade::command_args())
         .subcommand(commands::node_details::command_args())
+        .subcommand(commands::rotate_identity::command_args())
         .get_matches();
 
     execute(arg_matches).await;
@@ -35,6 +39,7 @@ async fn execute(matches: ArgMatches<'static>) {
         ("upgrade", Some(m)) => commands::upgrade::execute(m.clone()).await,
         ("sign", Some(m)) => commands::sign::execute(m),
         ("node-details", Some(m)) => commands::node_details::execute(m.clone()).await,
+        ("rotate-identity", Some(m)) => commands::rotate_identity::execute(m),
         _ => println!("{}", usage()),
     }
 }
@@ -96,8 +101,8 @@ fn setup_logging() {
     if let Ok(s) = ::std::env::var("RUST_LOG") {
         log_builder.parse_filters(&s);
     } else {
-        // default to 'Info'
-        log_builder.filter(None, log::LevelFilter::Info);
+        // let everything through the filter, the actual level is controlled with `set_log_level`
+        log_builder.filter(None, log::LevelFilter::Trace);
     }
 
     log_builder
@@ -110,4 +115,24 @@ fn setup_logging() {
         .filter_module("tungstenite", log::LevelFilter::Warn)
         .filter_module("tokio_tungstenite", log::LevelFilter::Warn)
         .init();
+
+    if ::std::env::var("RUST_LOG").is_err() {
+        // default to 'Info'
+        log::set_max_level(log::LevelFilter::Info);
+    }
+}
+
+/// Sets the maximum level of the logged messages, unless it has been specified with `RUST_LOG`.
+pub(crate) fn set_log_level(level: &str) {
+    if ::std::env::var("RUST_LOG").is_ok() {
+        return;
+    }
+
+    match level.parse() {
+        Ok(level) => log::set_max_level(level),
+        Err(_) => log::warn!(
+            "'{}' is not a valid log level - the current one is going to be kept",
+            level
+        ),
+    }
 }
@@ -55,4 +55,30 @@ impl ActiveClientsStore {
     pub(crate) fn insert(&self, client: DestinationAddressBytes, handle: MixMessageSender) {
         self.0.insert(client, handle);
     }
+
+    /// Returns addresses of all clients that are currently connected to the gateway.
+    pub(crate) fn connected_clients(&self) -> Vec<DestinationAddressBytes> {
+        self.0
+            .iter()
+            .filter(|entry| !entry.value().is_closed())
+            .map(|entry| *entry.key())
+            .collect()
+    }
+
+    /// Forcibly closes the connection of the specified client, if it's currently connected.
+    /// Returns whether the client was connected.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: address of the client to disconnect.
+    pub(crate) fn kick(&self, client: DestinationAddressBytes) -> bool {
+        match self.0.remove(&client) {
+            Some((_, handle)) if !handle.is_closed() => {
+                // closing the channel causes the client's connection handler to terminate
+                handle.close_channel();
+                true
+            }
+            _ => false,
+        }
+    }
 }
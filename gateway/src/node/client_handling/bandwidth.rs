@@ -10,10 +10,22 @@ use coconut_interface::Credential;
 use credentials::error::Error;
 #[cfg(not(feature = "coconut"))]
 use credentials::token::bandwidth::TokenCredential;
+use std::time::Duration;
 
 #[cfg(feature = "coconut")]
 const BANDWIDTH_INDEX: usize = 0;
 
+/// Determines when connected clients get notified about their available bandwidth.
+/// `None` disables the particular kind of updates.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BandwidthUpdatePolicy {
+    /// Interval of the periodic updates, sent only if the client has consumed any bandwidth since the last one.
+    pub(crate) interval: Option<Duration>,
+
+    /// Amount of bandwidth the client has to consume to get notified straight away.
+    pub(crate) threshold: Option<i64>,
+}
+
 pub struct Bandwidth {
     value: u64,
 }
@@ -0,0 +1,355 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::statistics::GatewayStats;
+use log::*;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits protecting the gateway from peers opening connections or starting registration
+/// handshakes, both of which are relatively expensive, before they prove anything.
+/// `None` means unlimited.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ConnectionLimits {
+    pub(crate) max_connections_per_ip_per_minute: Option<usize>,
+    pub(crate) max_handshakes_per_ip_per_minute: Option<usize>,
+    pub(crate) max_unauthenticated_connections: Option<usize>,
+    pub(crate) max_unauthenticated_connections_per_ip: Option<usize>,
+
+    /// Maximum time a connection can remain unauthenticated before it's closed.
+    pub(crate) authentication_timeout: Option<Duration>,
+
+    /// Number of unauthenticated connections above which registering clients are required
+    /// to solve a puzzle of the specified difficulty before the handshake is performed.
+    pub(crate) registration_puzzle_threshold: Option<usize>,
+    pub(crate) registration_puzzle_difficulty: u8,
+}
+
+#[derive(Debug, Default)]
+struct PeerActivity {
+    recent_connections: VecDeque<Instant>,
+    recent_handshakes: VecDeque<Instant>,
+    unauthenticated_connections: usize,
+}
+
+impl PeerActivity {
+    fn prune(&mut self, now: Instant) {
+        for recent in [&mut self.recent_connections, &mut self.recent_handshakes] {
+            while let Some(event) = recent.front() {
+                if now.duration_since(*event) < RATE_LIMIT_WINDOW {
+                    break;
+                }
+                recent.pop_front();
+            }
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.recent_connections.is_empty()
+            && self.recent_handshakes.is_empty()
+            && self.unauthenticated_connections == 0
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    unauthenticated_connections: usize,
+    peers: HashMap<IpAddr, PeerActivity>,
+    last_cleanup: Instant,
+}
+
+/// Keeps track of connections and registration handshakes of all peers of the gateway and
+/// decides whether their new connections or handshakes are still within the configured `ConnectionLimits`.
+// note that clone here is fine as upon cloning the same underlying state will be used
+#[derive(Clone)]
+pub(crate) struct ConnectionLimiter {
+    limits: ConnectionLimits,
+    state: Arc<Mutex<LimiterState>>,
+    stats: GatewayStats,
+}
+
+impl ConnectionLimiter {
+    pub(crate) fn new(limits: ConnectionLimits, stats: GatewayStats) -> Self {
+        ConnectionLimiter {
+            limits,
+            state: Arc::new(Mutex::new(LimiterState {
+                unauthenticated_connections: 0,
+                peers: HashMap::new(),
+                last_cleanup: Instant::now(),
+            })),
+            stats,
+        }
+    }
+
+    /// Checks whether a new connection from the specified address can be accepted and if so,
+    /// returns a permit that counts towards the unauthenticated connections until the client
+    /// authenticates or the connection is dropped.
+    pub(crate) fn try_accept(&self, remote_ip: IpAddr) -> Option<ConnectionPermit> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        // get rid of peers that no longer affect any of the limits
+        if now.duration_since(state.last_cleanup) >= RATE_LIMIT_WINDOW {
+            state.peers.retain(|_, activity| {
+                activity.prune(now);
+                !activity.is_idle()
+            });
+            state.last_cleanup = now;
+        }
+
+        if let Some(max_unauthenticated) = self.limits.max_unauthenticated_connections {
+            if state.unauthenticated_connections >= max_unauthenticated {
+                debug!(
+                    "Refusing connection from {} - there are too many unauthenticated connections",
+                    remote_ip
+                );
+                self.stats.record_rejected_connection(true);
+                return None;
+            }
+        }
+
+        let activity = state.peers.entry(remote_ip).or_default();
+        activity.prune(now);
+        if let Some(max_connections) = self.limits.max_connections_per_ip_per_minute {
+            if activity.recent_connections.len() >= max_connections {
+                debug!(
+                    "Refusing connection from {} - it has opened too many connections recently",
+                    remote_ip
+                );
+                self.stats.record_rejected_connection(false);
+                return None;
+            }
+        }
+        if let Some(max_unauthenticated) = self.limits.max_unauthenticated_connections_per_ip {
+            if activity.unauthenticated_connections >= max_unauthenticated {
+                debug!(
+                    "Refusing connection from {} - it has too many unauthenticated connections open",
+                    remote_ip
+                );
+                self.stats.record_rejected_connection(false);
+                return None;
+            }
+        }
+        activity.recent_connections.push_back(now);
+        activity.unauthenticated_connections += 1;
+
+        state.unauthenticated_connections += 1;
+        self.stats
+            .set_unauthenticated_connections(state.unauthenticated_connections as u64);
+
+        Some(ConnectionPermit {
+            limiter: self.clone(),
+            remote_ip,
+            unauthenticated: true,
+        })
+    }
+
+    fn try_start_handshake(&self, remote_ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        let activity = state.peers.entry(remote_ip).or_default();
+        activity.prune(now);
+        if let Some(max_handshakes) = self.limits.max_handshakes_per_ip_per_minute {
+            if activity.recent_handshakes.len() >= max_handshakes {
+                debug!(
+                    "Refusing registration handshake from {} - it has started too many handshakes recently",
+                    remote_ip
+                );
+                self.stats.record_rate_limited_handshake();
+                return false;
+            }
+        }
+        activity.recent_handshakes.push_back(now);
+        true
+    }
+
+    fn required_puzzle_difficulty(&self) -> Option<u8> {
+        let threshold = self.limits.registration_puzzle_threshold?;
+        let state = self.state.lock().unwrap();
+        if state.unauthenticated_connections > threshold {
+            Some(self.limits.registration_puzzle_difficulty)
+        } else {
+            None
+        }
+    }
+
+    fn release_unauthenticated(&self, remote_ip: IpAddr) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(activity) = state.peers.get_mut(&remote_ip) {
+            activity.unauthenticated_connections =
+                activity.unauthenticated_connections.saturating_sub(1);
+        }
+        state.unauthenticated_connections = state.unauthenticated_connections.saturating_sub(1);
+        self.stats
+            .set_unauthenticated_connections(state.unauthenticated_connections as u64);
+    }
+}
+
+/// Permit of an accepted client connection.
+pub(crate) struct ConnectionPermit {
+    limiter: ConnectionLimiter,
+    remote_ip: IpAddr,
+    unauthenticated: bool,
+}
+
+impl ConnectionPermit {
+    /// Checks whether the client is allowed to start another registration handshake.
+    pub(crate) fn try_start_handshake(&self) -> bool {
+        self.limiter.try_start_handshake(self.remote_ip)
+    }
+
+    /// Returns the maximum time the connection can remain unauthenticated, if there's such a limit.
+    pub(crate) fn authentication_timeout(&self) -> Option<Duration> {
+        self.limiter.limits.authentication_timeout
+    }
+
+    /// Returns difficulty of the puzzle registering clients are required to solve
+    /// if the gateway is currently under load.
+    pub(crate) fn required_puzzle_difficulty(&self) -> Option<u8> {
+        self.limiter.required_puzzle_difficulty()
+    }
+
+    /// Stops counting the connection towards the unauthenticated ones.
+    pub(crate) fn mark_authenticated(&mut self) {
+        if self.unauthenticated {
+            self.unauthenticated = false;
+            self.limiter.release_unauthenticated(self.remote_ip);
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        // if the client has never authenticated, its connection is still being counted
+        self.mark_authenticated()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limits: ConnectionLimits) -> ConnectionLimiter {
+        ConnectionLimiter::new(limits, GatewayStats::new())
+    }
+
+    fn unlimited() -> ConnectionLimits {
+        ConnectionLimits {
+            max_connections_per_ip_per_minute: None,
+            max_handshakes_per_ip_per_minute: None,
+            max_unauthenticated_connections: None,
+            max_unauthenticated_connections_per_ip: None,
+            authentication_timeout: None,
+            registration_puzzle_threshold: None,
+            registration_puzzle_difficulty: 0,
+        }
+    }
+
+    #[test]
+    fn unauthenticated_connections_per_ip_are_limited_until_permits_are_dropped() {
+        let limiter = limiter(ConnectionLimits {
+            max_unauthenticated_connections_per_ip: Some(2),
+            ..unlimited()
+        });
+        let peer = "1.2.3.4".parse().unwrap();
+
+        let first = limiter.try_accept(peer).unwrap();
+        let _second = limiter.try_accept(peer).unwrap();
+        assert!(limiter.try_accept(peer).is_none());
+        assert_eq!(2, limiter.stats.snapshot().unauthenticated_connections);
+
+        drop(first);
+        assert_eq!(1, limiter.stats.snapshot().unauthenticated_connections);
+        assert!(limiter.try_accept(peer).is_some());
+        assert_eq!(1, limiter.stats.snapshot().connections_rate_limited);
+    }
+
+    #[test]
+    fn authenticated_connections_release_their_slot() {
+        let limiter = limiter(ConnectionLimits {
+            max_unauthenticated_connections: Some(1),
+            ..unlimited()
+        });
+        let peer = "1.2.3.4".parse().unwrap();
+
+        let mut permit = limiter.try_accept(peer).unwrap();
+        assert!(limiter.try_accept(peer).is_none());
+
+        permit.mark_authenticated();
+        let _other = limiter.try_accept(peer).unwrap();
+
+        // dropping an already authenticated connection doesn't release the slot again
+        drop(permit);
+        assert!(limiter.try_accept(peer).is_none());
+        assert_eq!(2, limiter.stats.snapshot().connections_over_capacity);
+    }
+
+    #[test]
+    fn connections_are_counted_separately_per_address() {
+        let limiter = limiter(ConnectionLimits {
+            max_connections_per_ip_per_minute: Some(2),
+            max_unauthenticated_connections_per_ip: Some(1),
+            ..unlimited()
+        });
+        let peer = "1.2.3.4".parse().unwrap();
+        let other_peer = "4.3.2.1".parse().unwrap();
+
+        let permit = limiter.try_accept(peer).unwrap();
+        assert!(limiter.try_accept(peer).is_none());
+        let _other_permit = limiter.try_accept(other_peer).unwrap();
+
+        // the rate limit still applies after the connection is dropped
+        drop(permit);
+        let permit = limiter.try_accept(peer).unwrap();
+        assert!(limiter.try_accept(peer).is_none());
+        drop(permit);
+        assert!(limiter.try_accept(peer).is_none());
+        assert!(limiter.try_accept(other_peer).is_none());
+    }
+
+    #[test]
+    fn handshakes_per_ip_are_limited() {
+        let limiter = limiter(ConnectionLimits {
+            max_handshakes_per_ip_per_minute: Some(1),
+            ..unlimited()
+        });
+        let peer = "1.2.3.4".parse().unwrap();
+        let other_peer = "4.3.2.1".parse().unwrap();
+
+        let permit = limiter.try_accept(peer).unwrap();
+        assert!(permit.try_start_handshake());
+        assert!(!permit.try_start_handshake());
+
+        // a new connection from the same address doesn't reset the limit
+        let another_permit = limiter.try_accept(peer).unwrap();
+        assert!(!another_permit.try_start_handshake());
+        assert!(limiter
+            .try_accept(other_peer)
+            .unwrap()
+            .try_start_handshake());
+        assert_eq!(2, limiter.stats.snapshot().handshakes_rate_limited);
+    }
+
+    #[test]
+    fn puzzles_are_only_required_above_the_threshold() {
+        let limiter = limiter(ConnectionLimits {
+            registration_puzzle_threshold: Some(1),
+            registration_puzzle_difficulty: 8,
+            ..unlimited()
+        });
+
+        let first = limiter.try_accept("1.2.3.4".parse().unwrap()).unwrap();
+        assert!(first.required_puzzle_difficulty().is_none());
+
+        let second = limiter.try_accept("4.3.2.1".parse().unwrap()).unwrap();
+        assert_eq!(Some(8), second.required_puzzle_difficulty());
+
+        drop(first);
+        assert!(second.required_puzzle_difficulty().is_none());
+    }
+}
@@ -2,7 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub(crate) mod active_clients;
-mod bandwidth;
+pub(crate) mod bandwidth;
+pub(crate) mod connection_limiter;
 pub(crate) mod websocket;
 
 pub(crate) const FREE_TESTNET_BANDWIDTH_VALUE: i64 = 64 * 1024 * 1024 * 1024; // 64GB
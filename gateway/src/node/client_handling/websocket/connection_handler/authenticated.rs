@@ -4,9 +4,12 @@
 use crate::node::client_handling::websocket::connection_handler::{ClientDetails, FreshHandler};
 use crate::node::client_handling::websocket::message_receiver::MixMessageReceiver;
 use crate::node::storage::error::StorageError;
+use crypto::asymmetric::identity;
 use futures::StreamExt;
+use gateway_requests::bandwidth::{received_message_cost, sent_packet_cost};
 use gateway_requests::iv::IVConversionError;
-use gateway_requests::types::{BinaryRequest, ServerResponse};
+use gateway_requests::registration::handshake::error::HandshakeError;
+use gateway_requests::types::{BinaryRequest, RegistrationHandshake, ServerResponse};
 use gateway_requests::{ClientControlRequest, GatewayRequestsError};
 use log::*;
 use nymsphinx::forwarding::packet::MixPacket;
@@ -15,7 +18,8 @@ use std::convert::TryFrom;
 use std::process;
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio::time::Interval;
+use tokio_tungstenite::tungstenite::{protocol::Message, Error as WsError};
 
 use crate::node::client_handling::bandwidth::Bandwidth;
 use crate::node::client_handling::FREE_TESTNET_BANDWIDTH_VALUE;
@@ -26,6 +30,9 @@ pub(crate) enum RequestHandlingError {
     #[error("Internal gateway storage error")]
     StorageError(#[from] StorageError),
 
+    #[error("Experienced connection error - {0}")]
+    ConnectionError(#[from] WsError),
+
     #[error("Provided bandwidth IV is malformed - {0}")]
     MalformedIV(#[from] IVConversionError),
 
@@ -38,12 +45,31 @@ pub(crate) enum RequestHandlingError {
     #[error("The received request is not valid in the current context")]
     IllegalRequest,
 
+    #[error("Failed to perform the re-key handshake - {0}")]
+    HandshakeError(#[from] HandshakeError),
+
+    #[error(
+        "The re-key handshake can only be performed for the identity of the authenticated client"
+    )]
+    MismatchedRekeyIdentity,
+
+    #[error("The client is no longer registered with the gateway")]
+    ClientNotRegistered,
+
     #[error("Provided bandwidth credential asks for more bandwidth than it is supported to add at once (credential value: {0}, supported: {}). Try to split it before attempting again", i64::MAX)]
     UnsupportedBandwidthValue(u64),
 
     #[error("Provided bandwidth credential did not verify correctly")]
     InvalidBandwidthCredential,
 
+    #[cfg(feature = "coconut")]
+    #[error("Provided bandwidth credential has already been spent")]
+    BandwidthCredentialAlreadySpent,
+
+    #[cfg(feature = "coconut")]
+    #[error("Could not reach any validator API to check whether the bandwidth credential has already been spent. Try again later")]
+    SharedSpentSetUnavailable,
+
     #[error("This gateway is not running in the testnet mode")]
     NotInTestnetMode,
 
@@ -93,6 +119,24 @@ pub(crate) struct AuthenticatedHandler<R, S> {
     inner: FreshHandler<R, S>,
     client: ClientDetails,
     mix_receiver: MixMessageReceiver,
+    /// Indicates the client has removed all of its data from the gateway
+    /// and the connection should be closed.
+    unregistered: bool,
+    /// Indicates the client has declared it accepts bandwidth updates pushed without a prior request.
+    accepts_bandwidth_updates: bool,
+    /// Amount of bandwidth consumed by the client since it has last been notified about
+    /// its available bandwidth.
+    consumed_since_bandwidth_update: i64,
+}
+
+/// Completes upon the next tick of the interval or never, if there's no interval to tick.
+async fn maybe_tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => futures::future::pending().await,
+    }
 }
 
 // explicitly remove handle from the global store upon being dropped
@@ -118,15 +162,20 @@ where
     /// * `fresh`: fresh, unauthenticated, connection handler.
     /// * `client`: details (i.e. address and shared keys) of the registered client
     /// * `mix_receiver`: channel used for receiving messages from the mixnet destined for this client.
+    /// * `accepts_bandwidth_updates`: whether the client accepts bandwidth updates pushed without a prior request.
     pub(crate) fn upgrade(
         fresh: FreshHandler<R, S>,
         client: ClientDetails,
         mix_receiver: MixMessageReceiver,
+        accepts_bandwidth_updates: bool,
     ) -> Self {
         AuthenticatedHandler {
             inner: fresh,
             client,
             mix_receiver,
+            unregistered: false,
+            accepts_bandwidth_updates,
+            consumed_since_bandwidth_update: 0,
         }
     }
 
@@ -174,6 +223,59 @@ where
         Ok(())
     }
 
+    /// Decreases the amount of available bandwidth of the connected client by the cost of the sent
+    /// or received packets and notifies the client about its remaining bandwidth if it has consumed
+    /// more than the update threshold since the last notification.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount`: amount to decrease the available bandwidth by.
+    async fn charge_bandwidth(&mut self, amount: i64) -> Result<(), RequestHandlingError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.consume_bandwidth(amount).await?;
+        if !self.accepts_bandwidth_updates {
+            return Ok(());
+        }
+        self.consumed_since_bandwidth_update += amount;
+
+        if let Some(threshold) = self.inner.bandwidth_updates.threshold {
+            if self.consumed_since_bandwidth_update >= threshold {
+                self.send_bandwidth_update().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decreases the amount of available bandwidth of the connected client by the cost of the packets
+    /// received from the mix network. They're delivered even if the client can't afford them,
+    /// so the charge is capped at whatever bandwidth the client still has left.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount`: cost of the received packets.
+    async fn charge_received_bandwidth(&mut self, amount: i64) -> Result<(), RequestHandlingError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let available = self.get_available_bandwidth().await?;
+        self.charge_bandwidth(amount.min(available.max(0))).await
+    }
+
+    /// Pushes the current amount of available bandwidth to the client.
+    async fn send_bandwidth_update(&mut self) -> Result<(), RequestHandlingError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let available_total = self.get_available_bandwidth().await?;
+        self.inner
+            .send_websocket_message(ServerResponse::BandwidthUpdate { available_total }.into())
+            .await?;
+        self.consumed_since_bandwidth_update = 0;
+        Ok(())
+    }
+
     /// Forwards the received mix packet from the client into the mix network.
     ///
     /// # Arguments
@@ -206,11 +308,12 @@ where
             iv,
         )?;
 
-        if !credential.verify(&self.inner.aggregated_verification_key) {
+        if !self.inner.coconut_verifier.verify(&credential) {
             return Err(RequestHandlingError::InvalidBandwidthCredential);
         }
 
-        let bandwidth = Bandwidth::try_from(credential)?;
+        let blinded_serial_number = credential.blinded_serial_number();
+        let bandwidth = Bandwidth::try_from(credential.clone())?;
         let bandwidth_value = bandwidth.value();
 
         if bandwidth_value > i64::MAX as u64 {
@@ -223,7 +326,30 @@ where
             ));
         }
 
+        // marking the credential as spent has to happen atomically with checking whether
+        // it has been spent before so that it could not be redeemed on multiple connections at once
+        if !self
+            .inner
+            .storage
+            .mark_credential_spent(self.client.address, &blinded_serial_number)
+            .await?
+        {
+            return Err(RequestHandlingError::BandwidthCredentialAlreadySpent);
+        }
+
+        if let Err(err) = self.inner.coconut_verifier.spend_shared(&credential).await {
+            if matches!(err, RequestHandlingError::SharedSpentSetUnavailable) {
+                // the credential hasn't been redeemed anywhere, so let the client try again later
+                self.inner
+                    .storage
+                    .unmark_credential_spent(&blinded_serial_number)
+                    .await?;
+            }
+            return Err(err);
+        }
+
         self.increase_bandwidth(bandwidth_value as i64).await?;
+        self.inner.stats.record_redeemed_credential();
         let available_total = self.get_available_bandwidth().await?;
 
         Ok(ServerResponse::Bandwidth { available_total })
@@ -275,6 +401,7 @@ where
         }
 
         self.increase_bandwidth(bandwidth_value as i64).await?;
+        self.inner.stats.record_redeemed_credential();
         let available_total = self.get_available_bandwidth().await?;
         debug!("Increased bandwidth for client: {:?}", self.client.address);
 
@@ -306,6 +433,91 @@ where
         Ok(ServerResponse::Bandwidth { available_total })
     }
 
+    /// Removes the stored messages that the client has acknowledged receiving.
+    ///
+    /// # Arguments
+    ///
+    /// * `up_to_id`: id of the last stored message received by the client.
+    async fn handle_acknowledge_stored_messages(
+        &self,
+        up_to_id: i64,
+    ) -> Result<ServerResponse, RequestHandlingError> {
+        self.inner
+            .storage
+            .remove_acknowledged_messages(self.client.address, up_to_id)
+            .await?;
+
+        Ok(ServerResponse::StoredMessagesAcknowledged)
+    }
+
+    /// Removes all data stored for the client, after which the connection is going to be closed.
+    async fn handle_unregister(&mut self) -> Result<ServerResponse, RequestHandlingError> {
+        self.inner
+            .storage
+            .remove_client(self.client.address)
+            .await?;
+        self.unregistered = true;
+        info!("{} has unregistered from the gateway", self.client.address);
+
+        Ok(ServerResponse::Unregistered)
+    }
+
+    /// Checks whether the received handshake init message was created by the authenticated client.
+    ///
+    /// # Arguments
+    ///
+    /// * `init_data`: received init message that should start with client's public key.
+    fn is_own_handshake_init(&self, init_data: &[u8]) -> bool {
+        init_data
+            .get(..identity::PUBLIC_KEY_LENGTH)
+            .and_then(|key_bytes| identity::PublicKey::from_bytes(key_bytes).ok())
+            .map(|identity| identity.derive_destination_address() == self.client.address)
+            .unwrap_or_default()
+    }
+
+    /// Performs a fresh handshake with the already authenticated client and replaces
+    /// the stored shared keys with the newly derived ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `init_data`: init payload of the handshake.
+    async fn handle_rekey(
+        &mut self,
+        init_data: Vec<u8>,
+    ) -> Result<ServerResponse, RequestHandlingError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        if !self.is_own_handshake_init(&init_data) {
+            // the client is waiting for the handshake to continue, so let it know it has failed
+            let handshake_error = RegistrationHandshake::new_error(
+                RequestHandlingError::MismatchedRekeyIdentity.to_string(),
+            );
+            // serialization of the handshake message can't fail
+            let handshake_error = Message::Text(handshake_error.try_into().unwrap());
+            if let Err(err) = self.inner.send_websocket_message(handshake_error).await {
+                debug!("Failed to send handshake error response - {}", err);
+            }
+            return Err(RequestHandlingError::MismatchedRekeyIdentity);
+        }
+
+        let shared_keys = self.inner.perform_registration_handshake(init_data).await?;
+        // only replace the keys if the client hasn't been unregistered in the meantime
+        // (for example through another of its connections), as otherwise we'd resurrect it
+        if !self
+            .inner
+            .storage
+            .replace_shared_keys(self.client.address, shared_keys)
+            .await?
+        {
+            return Err(RequestHandlingError::ClientNotRegistered);
+        }
+        self.client.shared_keys = shared_keys;
+        debug!("Replaced shared keys of {}", self.client.address);
+
+        Ok(ServerResponse::Rekey { status: true })
+    }
+
     /// Tries to handle request to forward sphinx packet into the network. The request can only succeed
     /// if the client has enough available bandwidth.
     ///
@@ -315,10 +527,13 @@ where
     ///
     /// * `mix_packet`: packet received from the client that should get forwarded into the network.
     async fn handle_forward_sphinx(
-        &self,
+        &mut self,
         mix_packet: MixPacket,
-    ) -> Result<ServerResponse, RequestHandlingError> {
-        let consumed_bandwidth = mix_packet.sphinx_packet().len() as i64;
+    ) -> Result<ServerResponse, RequestHandlingError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let consumed_bandwidth = sent_packet_cost(mix_packet.sphinx_packet().len());
 
         let available_bandwidth = self.get_available_bandwidth().await?;
 
@@ -328,8 +543,11 @@ where
             ));
         }
 
-        self.consume_bandwidth(consumed_bandwidth).await?;
+        self.charge_bandwidth(consumed_bandwidth).await?;
         self.forward_packet(mix_packet);
+        self.inner
+            .stats
+            .record_forwarded_packet(consumed_bandwidth as u64);
 
         Ok(ServerResponse::Send {
             remaining_bandwidth: available_bandwidth - consumed_bandwidth,
@@ -341,7 +559,10 @@ where
     /// # Arguments
     ///
     /// * `bin_msg`: raw message to handle.
-    async fn handle_binary(&self, bin_msg: Vec<u8>) -> Message {
+    async fn handle_binary(&mut self, bin_msg: Vec<u8>) -> Message
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         // this function decrypts the request and checks the MAC
         match BinaryRequest::try_from_encrypted_tagged_bytes(bin_msg, &self.client.shared_keys) {
             Err(e) => RequestHandlingError::InvalidBinaryRequest(e).into_error_message(),
@@ -357,12 +578,16 @@ where
 
     /// Attempts to handle a text data frame websocket message.
     ///
-    /// Currently the bandwidth credential request is the only one we can receive after authentication.
+    /// After authentication we can only receive bandwidth-related requests, acknowledgements
+    /// of the stored messages, re-key handshakes and requests to unregister.
     ///
     /// # Arguments
     ///
     /// * `raw_request`: raw message to handle.
-    async fn handle_text(&mut self, raw_request: String) -> Message {
+    async fn handle_text(&mut self, raw_request: String) -> Message
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
         match ClientControlRequest::try_from(raw_request) {
             Err(e) => RequestHandlingError::InvalidTextRequest(e).into_error_message(),
             Ok(request) => match request {
@@ -374,6 +599,16 @@ where
                     .handle_claim_testnet_bandwidth()
                     .await
                     .into_ws_message(),
+                ClientControlRequest::AcknowledgeStoredMessages { up_to_id } => self
+                    .handle_acknowledge_stored_messages(up_to_id)
+                    .await
+                    .into_ws_message(),
+                ClientControlRequest::RegisterHandshakeInitRequest { data, .. } => {
+                    self.handle_rekey(data).await.into_ws_message()
+                }
+                ClientControlRequest::Unregister => {
+                    self.handle_unregister().await.into_ws_message()
+                }
                 _ => RequestHandlingError::IllegalRequest.into_error_message(),
             },
         }
@@ -384,7 +619,10 @@ where
     /// # Arguments
     ///
     /// * `raw_request`: raw received websocket message.
-    async fn handle_request(&mut self, raw_request: Message) -> Option<Message> {
+    async fn handle_request(&mut self, raw_request: Message) -> Option<Message>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
         // apparently tungstenite auto-handles ping/pong/close messages so for now let's ignore
         // them and let's test that claim. If that's not the case, just copy code from
         // desktop nym-client websocket as I've manually handled everything there
@@ -400,10 +638,17 @@ where
     /// and for sphinx packets received from the mix network that should be sent back to the client.
     pub(crate) async fn listen_for_requests(mut self)
     where
-        S: AsyncRead + AsyncWrite + Unpin,
+        S: AsyncRead + AsyncWrite + Unpin + Send,
     {
         trace!("Started listening for ALL incoming requests...");
 
+        let mut bandwidth_update_interval = self
+            .inner
+            .bandwidth_updates
+            .interval
+            .filter(|_| self.accepts_bandwidth_updates)
+            .map(tokio::time::interval);
+
         loop {
             tokio::select! {
                 socket_msg = self.inner.read_websocket_message() => {
@@ -429,13 +674,48 @@ where
                             break;
                         }
                     }
+
+                    if self.unregistered {
+                        break;
+                    }
                 },
                 mix_messages = self.mix_receiver.next() => {
-                    let mix_messages = mix_messages.expect("sender was unexpectedly closed! this shouldn't have ever happened!");
+                    let mix_messages = match mix_messages {
+                        Some(mix_messages) => mix_messages,
+                        None => {
+                            // the only way for the sender to get closed is for the client to be
+                            // explicitly disconnected by the gateway operator
+                            info!("The connection to {} was closed by the gateway", self.client.address);
+                            break;
+                        }
+                    };
+                    let consumed_bandwidth = mix_messages
+                        .iter()
+                        .map(|message| received_message_cost(message.len()))
+                        .sum();
                     if let Err(e) = self.inner.push_packets_to_client(self.client.shared_keys, mix_messages).await {
                         warn!("failed to send the unwrapped sphinx packets back to the client - {:?}, assuming the connection is dead", e);
                         break;
                     }
+                    // note that the messages are delivered even if the client has run out of bandwidth,
+                    // in which case it won't be able to send anything until it claims more of it
+                    if let Err(err) = self.charge_received_bandwidth(consumed_bandwidth).await {
+                        warn!("failed to charge {} for the received packets - {}", self.client.address, err);
+                        if matches!(err, RequestHandlingError::ConnectionError(_)) {
+                            break;
+                        }
+                    }
+                }
+                _ = maybe_tick(&mut bandwidth_update_interval) => {
+                    if self.consumed_since_bandwidth_update == 0 {
+                        continue;
+                    }
+                    if let Err(err) = self.send_bandwidth_update().await {
+                        warn!("failed to send bandwidth update to {} - {}", self.client.address, err);
+                        if matches!(err, RequestHandlingError::ConnectionError(_)) {
+                            break;
+                        }
+                    }
                 }
             }
         }
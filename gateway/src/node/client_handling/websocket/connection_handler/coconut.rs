@@ -0,0 +1,95 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::client_handling::websocket::connection_handler::authenticated::RequestHandlingError;
+use coconut_interface::{Credential, SpendCredentialRequestBody, VerificationKey};
+use crypto::asymmetric::identity;
+use log::*;
+use std::sync::Arc;
+use url::Url;
+use validator_client::ApiClient;
+
+pub(crate) struct CoconutVerifier {
+    aggregated_verification_key: VerificationKey,
+
+    /// Used for proving to the validator APIs that the credentials are spent by a bonded gateway.
+    identity_keypair: Arc<identity::KeyPair>,
+
+    /// Validator APIs maintaining the shared set of spent credentials.
+    /// Empty if the gateway only relies on its own storage for detecting double spending.
+    shared_spent_set_apis: Vec<(Url, ApiClient)>,
+}
+
+impl CoconutVerifier {
+    pub fn new(
+        aggregated_verification_key: VerificationKey,
+        identity_keypair: Arc<identity::KeyPair>,
+        validator_api_urls: Vec<Url>,
+        check_shared_spent_credentials: bool,
+    ) -> Self {
+        let shared_spent_set_apis = if check_shared_spent_credentials {
+            validator_api_urls
+                .into_iter()
+                .map(|url| (url.clone(), ApiClient::new(url)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        CoconutVerifier {
+            aggregated_verification_key,
+            identity_keypair,
+            shared_spent_set_apis,
+        }
+    }
+
+    pub fn verify(&self, credential: &Credential) -> bool {
+        credential.verify(&self.aggregated_verification_key)
+    }
+
+    /// Marks the credential as spent in the shared set of spent credentials, if it is used.
+    /// Fails if any of the validator APIs reports the credential as already spent
+    /// or if none of them could be reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `credential`: the already verified credential that is being redeemed.
+    pub async fn spend_shared(&self, credential: &Credential) -> Result<(), RequestHandlingError> {
+        if self.shared_spent_set_apis.is_empty() {
+            return Ok(());
+        }
+
+        let signature = self
+            .identity_keypair
+            .private_key()
+            .sign(&SpendCredentialRequestBody::signed_message(credential))
+            .to_base58_string();
+        let request_body = SpendCredentialRequestBody::new(
+            credential.clone(),
+            self.identity_keypair.public_key().to_base58_string(),
+            signature,
+        );
+
+        let mut reached_any = false;
+        for (url, api) in &self.shared_spent_set_apis {
+            match api.spend_coconut_credential(&request_body).await {
+                Ok(response) => {
+                    if !response.newly_spent {
+                        return Err(RequestHandlingError::BandwidthCredentialAlreadySpent);
+                    }
+                    reached_any = true;
+                }
+                Err(err) => warn!(
+                    "Failed to mark the credential as spent in the shared set at {} - {}",
+                    url, err
+                ),
+            }
+        }
+
+        if reached_any {
+            Ok(())
+        } else {
+            Err(RequestHandlingError::SharedSpentSetUnavailable)
+        }
+    }
+}
@@ -2,9 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::client_handling::bandwidth::BandwidthUpdatePolicy;
+use crate::node::client_handling::connection_limiter::ConnectionPermit;
 use crate::node::client_handling::websocket::connection_handler::{
-    AuthenticatedHandler, ClientDetails, InitialAuthResult, SocketStream,
+    until_deadline, AuthenticatedHandler, ClientDetails, InitialAuthResult, SocketStream,
 };
+use crate::node::client_handling::websocket::listener::ClientTransport;
+use crate::node::statistics::GatewayStats;
 use crate::node::storage::error::StorageError;
 use crate::node::storage::PersistentStorage;
 use crypto::asymmetric::identity;
@@ -12,23 +16,28 @@ use futures::{channel::mpsc, SinkExt, StreamExt};
 use gateway_requests::authentication::encrypted_address::{
     EncryptedAddressBytes, EncryptedAddressConversionError,
 };
+use gateway_requests::bandwidth::received_message_cost;
+use gateway_requests::codec::GatewayCodec;
 use gateway_requests::iv::{IVConversionError, IV};
 use gateway_requests::registration::handshake::error::HandshakeError;
 use gateway_requests::registration::handshake::{gateway_handshake, SharedKeys};
-use gateway_requests::types::{ClientControlRequest, ServerResponse};
+use gateway_requests::registration::puzzle::RegistrationPuzzle;
+use gateway_requests::types::{ClientControlRequest, RegistrationHandshake, ServerResponse};
 use gateway_requests::BinaryResponse;
 use log::*;
 use mixnet_client::forwarder::MixForwardingSender;
 use nymsphinx::DestinationAddressBytes;
 use rand::{CryptoRng, Rng};
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
 use tokio_tungstenite::tungstenite::{protocol::Message, Error as WsError};
+use tokio_util::codec::Framed;
 
 #[cfg(feature = "coconut")]
-use coconut_interface::VerificationKey;
+use crate::node::client_handling::websocket::connection_handler::coconut::CoconutVerifier;
 
 #[cfg(not(feature = "coconut"))]
 use crate::node::client_handling::websocket::connection_handler::eth_events::ERC20Bridge;
@@ -57,6 +66,18 @@ enum InitialAuthenticationError {
     #[error("Only 'Register' or 'Authenticate' requests are allowed")]
     InvalidRequest,
 
+    #[error("Too many registration handshakes were started from this address recently")]
+    TooManyHandshakes,
+
+    #[error("The gateway requires solving a puzzle before registering")]
+    PuzzleRequired(RegistrationPuzzle),
+
+    #[error("The gateway is under load and requires solving a puzzle before registering, which this client does not support. Please update it or try again later")]
+    PuzzleUnsupported,
+
+    #[error("The client has not registered or authenticated in time")]
+    AuthenticationTimeout,
+
     #[error("Experienced connection error - {0}")]
     ConnectionError(#[from] WsError),
 }
@@ -64,7 +85,21 @@ enum InitialAuthenticationError {
 impl InitialAuthenticationError {
     /// Converts this Error into an appropriate websocket Message.
     fn into_error_message(self) -> Message {
-        ServerResponse::new_error(self.to_string()).into()
+        match self {
+            // this one is not really an error, but rather a request to retry the registration
+            InitialAuthenticationError::PuzzleRequired(puzzle) => {
+                let challenge = RegistrationHandshake::new_puzzle_challenge(&puzzle);
+                // the unwrap is fine as the message consists only of plain types
+                Message::Text(challenge.try_into().unwrap())
+            }
+            // the client is in the middle of the handshake, so it only understands handshake errors
+            InitialAuthenticationError::PuzzleUnsupported => {
+                let handshake_error = RegistrationHandshake::new_error(self.to_string());
+                // the unwrap is fine as the message consists only of plain types
+                Message::Text(handshake_error.try_into().unwrap())
+            }
+            _ => ServerResponse::new_error(self.to_string()).into(),
+        }
     }
 }
 
@@ -76,9 +111,15 @@ pub(crate) struct FreshHandler<R, S> {
     pub(crate) outbound_mix_sender: MixForwardingSender,
     pub(crate) socket_connection: SocketStream<S>,
     pub(crate) storage: PersistentStorage,
+    pub(crate) stats: GatewayStats,
+    pub(crate) connection_permit: ConnectionPermit,
+    /// Moment by which the client has to register or authenticate, if there's such a limit.
+    pub(crate) authentication_deadline: Option<Instant>,
+    pub(crate) bandwidth_updates: BandwidthUpdatePolicy,
+    pending_puzzle: Option<RegistrationPuzzle>,
 
     #[cfg(feature = "coconut")]
-    pub(crate) aggregated_verification_key: VerificationKey,
+    pub(crate) coconut_verifier: Arc<CoconutVerifier>,
 
     #[cfg(not(feature = "coconut"))]
     pub(crate) erc20_bridge: Arc<ERC20Bridge>,
@@ -96,31 +137,53 @@ where
     pub(crate) fn new(
         rng: R,
         conn: S,
+        transport: ClientTransport,
+        connection_permit: ConnectionPermit,
         testnet_mode: bool,
         outbound_mix_sender: MixForwardingSender,
         local_identity: Arc<identity::KeyPair>,
         storage: PersistentStorage,
         active_clients_store: ActiveClientsStore,
-        #[cfg(feature = "coconut")] aggregated_verification_key: VerificationKey,
+        stats: GatewayStats,
+        bandwidth_updates: BandwidthUpdatePolicy,
+        #[cfg(feature = "coconut")] coconut_verifier: Arc<CoconutVerifier>,
         #[cfg(not(feature = "coconut"))] erc20_bridge: Arc<ERC20Bridge>,
-    ) -> Self {
+    ) -> Self
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let socket_connection = match transport {
+            ClientTransport::WebSocket => SocketStream::RawTcp(conn),
+            // there's no upgrade to perform - the framing is used straight away
+            ClientTransport::Tcp => SocketStream::Framed(Framed::new(conn, GatewayCodec)),
+        };
+
+        let authentication_deadline = connection_permit
+            .authentication_timeout()
+            .map(|timeout| Instant::now() + timeout);
+
         FreshHandler {
             rng,
             active_clients_store,
             testnet_mode,
             outbound_mix_sender,
-            socket_connection: SocketStream::RawTcp(conn),
+            socket_connection,
             local_identity,
             storage,
+            stats,
+            connection_permit,
+            authentication_deadline,
+            bandwidth_updates,
+            pending_puzzle: None,
             #[cfg(feature = "coconut")]
-            aggregated_verification_key,
+            coconut_verifier,
             #[cfg(not(feature = "coconut"))]
             erc20_bridge,
         }
     }
 
     /// Attempts to perform websocket handshake with the remote and upgrades the raw TCP socket
-    /// to the framed WebSocket.
+    /// to the framed WebSocket. Connections using the raw TCP transport are left untouched.
     pub(crate) async fn perform_websocket_handshake(&mut self) -> Result<(), WsError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
@@ -144,14 +207,14 @@ where
     /// # Arguments
     ///
     /// * `init_msg`: a client handshake init message which should contain its identity public key as well as an ephemeral key.
-    async fn perform_registration_handshake(
+    pub(crate) async fn perform_registration_handshake(
         &mut self,
         init_msg: Vec<u8>,
     ) -> Result<SharedKeys, HandshakeError>
     where
         S: AsyncRead + AsyncWrite + Unpin + Send,
     {
-        debug_assert!(self.socket_connection.is_websocket());
+        debug_assert!(self.socket_connection.is_established());
         match &mut self.socket_connection {
             SocketStream::UpgradedWebSocket(ws_stream) => {
                 gateway_handshake(
@@ -162,6 +225,15 @@ where
                 )
                 .await
             }
+            SocketStream::Framed(framed_stream) => {
+                gateway_handshake(
+                    &mut self.rng,
+                    framed_stream,
+                    self.local_identity.as_ref(),
+                    init_msg,
+                )
+                .await
+            }
             _ => unreachable!(),
         }
     }
@@ -173,6 +245,7 @@ where
     {
         match self.socket_connection {
             SocketStream::UpgradedWebSocket(ref mut ws_stream) => ws_stream.next().await,
+            SocketStream::Framed(ref mut framed_stream) => framed_stream.next().await,
             _ => panic!("impossible state - websocket handshake was somehow reverted"),
         }
     }
@@ -191,6 +264,7 @@ where
             // it got something to do with batching and flushing - it might be important if it
             // turns out somehow we've got a bottleneck here
             SocketStream::UpgradedWebSocket(ref mut ws_stream) => ws_stream.send(msg).await,
+            SocketStream::Framed(ref mut framed_stream) => framed_stream.send(msg).await,
             _ => panic!("impossible state - websocket handshake was somehow reverted"),
         }
     }
@@ -224,6 +298,9 @@ where
             SocketStream::UpgradedWebSocket(ref mut ws_stream) => {
                 ws_stream.send_all(&mut send_stream).await
             }
+            SocketStream::Framed(ref mut framed_stream) => {
+                framed_stream.send_all(&mut send_stream).await
+            }
             _ => panic!("impossible state - websocket handshake was somehow reverted"),
         }
     }
@@ -252,18 +329,30 @@ where
     /// Attempts to retrieve all messages currently stored in the persistent database to the client,
     /// which was offline at the time of their receipt.
     ///
+    /// If the client acknowledges receiving stored messages, they are kept in the storage until
+    /// the acknowledgement arrives so that they could be redelivered if the connection drops.
+    /// Otherwise they are removed as soon as they are sent.
+    ///
+    /// Returns id of the last message pushed to the client.
+    ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client that is going to receive the messages.
     /// * `shared_keys`: shared keys derived between the client and the gateway used to encrypt and tag the messages.
+    /// * `acknowledges_stored_messages`: whether the client is going to acknowledge receiving the messages.
+    ///
+    /// The bandwidth of the client is decreased by the cost of the pushed messages. Messages that have
+    /// already been pushed before, but were never acknowledged, are not charged again.
     async fn push_stored_messages_to_client(
         &mut self,
         client_address: DestinationAddressBytes,
         shared_keys: SharedKeys,
-    ) -> Result<(), InitialAuthenticationError>
+        acknowledges_stored_messages: bool,
+    ) -> Result<Option<i64>, InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
+        let mut last_pushed_id = None;
         let mut start_next_after = None;
         loop {
             // retrieve some messages
@@ -272,7 +361,17 @@ where
                 .retrieve_messages(client_address, start_next_after)
                 .await?;
 
-            let (messages, ids) = messages
+            let consumed_bandwidth = messages
+                .iter()
+                .filter(|msg| !msg.delivered)
+                .map(|msg| received_message_cost(msg.content.len()))
+                .sum();
+            let first_delivery_ids: Vec<_> = messages
+                .iter()
+                .filter(|msg| !msg.delivered)
+                .map(|msg| msg.id)
+                .collect();
+            let (messages, ids): (Vec<_>, Vec<_>) = messages
                 .into_iter()
                 .map(|msg| (msg.content, msg.id))
                 .unzip();
@@ -284,6 +383,31 @@ where
                     err
                 );
                 return Err(InitialAuthenticationError::ConnectionError(err));
+            }
+            // the messages are delivered even if the client can't afford them, but its bandwidth
+            // should never go below zero because of it
+            let available_bandwidth = self
+                .storage
+                .get_available_bandwidth(client_address)
+                .await?
+                .unwrap_or_default();
+            self.storage
+                .consume_bandwidth(
+                    client_address,
+                    consumed_bandwidth.min(available_bandwidth.max(0)),
+                )
+                .await?;
+
+            if let Some(&last_id) = ids.last() {
+                last_pushed_id = Some(last_id);
+            }
+            if acknowledges_stored_messages {
+                // keep them until the client acknowledges them, but don't charge for them again
+                if !first_delivery_ids.is_empty() {
+                    self.storage
+                        .mark_messages_delivered(first_delivery_ids)
+                        .await?;
+                }
             } else {
                 // if it was successful - remove them from the store
                 self.storage.remove_messages(ids).await?;
@@ -297,7 +421,7 @@ where
             }
         }
 
-        Ok(())
+        Ok(last_pushed_id)
     }
 
     /// Checks whether the stored shared keys match the received data, i.e. whether the upon decryption
@@ -348,17 +472,22 @@ where
     ///
     /// Finally, upon completion, all previously stored messages are pushed back to the client.
     ///
+    /// Returns the shared keys alongside id of the last pushed stored message if the client
+    /// got authenticated.
+    ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client wishing to authenticate.
     /// * `encrypted_address`: ciphertext of the address of the client wishing to authenticate.
     /// * `iv`: fresh IV received with the request.
+    /// * `acknowledges_stored_messages`: whether the client is going to acknowledge receiving its stored messages.
     async fn authenticate_client(
         &mut self,
         client_address: DestinationAddressBytes,
         encrypted_address: EncryptedAddressBytes,
         iv: IV,
-    ) -> Result<Option<SharedKeys>, InitialAuthenticationError>
+        acknowledges_stored_messages: bool,
+    ) -> Result<Option<(SharedKeys, Option<i64>)>, InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
@@ -372,9 +501,14 @@ where
             .await?;
 
         if let Some(shared_keys) = shared_keys {
-            self.push_stored_messages_to_client(client_address, shared_keys)
+            let last_pushed_id = self
+                .push_stored_messages_to_client(
+                    client_address,
+                    shared_keys,
+                    acknowledges_stored_messages,
+                )
                 .await?;
-            Ok(Some(shared_keys))
+            Ok(Some((shared_keys, last_pushed_id)))
         } else {
             Ok(None)
         }
@@ -387,11 +521,15 @@ where
     /// * `client_address`: address of the client wishing to authenticate.
     /// * `encrypted_address`: ciphertext of the address of the client wishing to authenticate.
     /// * `iv`: fresh IV received with the request.
+    /// * `acknowledges_stored_messages`: whether the client is going to acknowledge receiving its stored messages.
+    /// * `accepts_bandwidth_updates`: whether the client accepts bandwidth updates pushed without a prior request.
     async fn handle_authenticate(
         &mut self,
         address: String,
         enc_address: String,
         iv: String,
+        acknowledges_stored_messages: bool,
+        accepts_bandwidth_updates: bool,
     ) -> Result<InitialAuthResult, InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
@@ -405,24 +543,36 @@ where
             return Err(InitialAuthenticationError::DuplicateConnection);
         }
 
-        let shared_keys = self
-            .authenticate_client(address, encrypted_address, iv)
+        let authenticated = self
+            .authenticate_client(address, encrypted_address, iv, acknowledges_stored_messages)
             .await?;
-        let status = shared_keys.is_some();
+        let status = authenticated.is_some();
         let bandwidth_remaining = self
             .storage
             .get_available_bandwidth(address)
             .await?
             .unwrap_or(0);
+        // only let the authenticated client know about its dropped messages
+        let dropped_messages = if status {
+            self.storage.take_dropped_messages_count(address).await?
+        } else {
+            0
+        };
+        let last_stored_message_id = authenticated
+            .and_then(|(_, last_pushed_id)| last_pushed_id)
+            .filter(|_| acknowledges_stored_messages);
         let client_details =
-            shared_keys.map(|shared_keys| ClientDetails::new(address, shared_keys));
+            authenticated.map(|(shared_keys, _)| ClientDetails::new(address, shared_keys));
 
         Ok(InitialAuthResult::new(
             client_details,
             ServerResponse::Authenticate {
                 status,
                 bandwidth_remaining,
+                dropped_messages,
+                last_stored_message_id,
             },
+            accepts_bandwidth_updates,
         ))
     }
 
@@ -460,21 +610,63 @@ where
             self.storage.create_bandwidth_entry(client.address).await?;
         }
 
-        self.push_stored_messages_to_client(client.address, client.shared_keys)
+        // registering clients do not acknowledge their stored messages
+        self.push_stored_messages_to_client(client.address, client.shared_keys, false)
             .await?;
 
         Ok(true)
     }
 
+    /// If the gateway is under load, checks whether the client has solved the puzzle it was given
+    /// for this registration attempt. If it hasn't, a new puzzle is issued instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `init_data`: init payload of the registration handshake.
+    /// * `puzzle_solution`: solution to the previously issued puzzle, if any.
+    /// * `supports_puzzle`: whether the client is able to solve the puzzle.
+    fn check_registration_puzzle(
+        &mut self,
+        init_data: &[u8],
+        puzzle_solution: Option<u64>,
+        supports_puzzle: bool,
+    ) -> Result<(), InitialAuthenticationError> {
+        let difficulty = match self.connection_permit.required_puzzle_difficulty() {
+            Some(difficulty) => difficulty,
+            None => return Ok(()),
+        };
+
+        // older clients would just hang upon receiving the puzzle, so tell them what's going on instead
+        if !supports_puzzle {
+            return Err(InitialAuthenticationError::PuzzleUnsupported);
+        }
+
+        if let (Some(puzzle), Some(solution)) = (self.pending_puzzle.take(), puzzle_solution) {
+            if puzzle.verify(init_data, solution) {
+                self.stats.record_solved_puzzle();
+                return Ok(());
+            }
+        }
+
+        let puzzle = RegistrationPuzzle::new(&mut self.rng, difficulty);
+        self.pending_puzzle = Some(puzzle.clone());
+        self.stats.record_issued_puzzle();
+        Err(InitialAuthenticationError::PuzzleRequired(puzzle))
+    }
+
     /// Tries to handle the received register request by checking attempting to complete registration
     /// handshake using the received data.
     ///
     /// # Arguments
     ///
     /// * `init_data`: init payload of the registration handshake.
+    /// * `puzzle_solution`: solution to the registration puzzle, if the client was given one.
+    /// * `supports_puzzle`: whether the client is able to solve the registration puzzle.
     async fn handle_register(
         &mut self,
         init_data: Vec<u8>,
+        puzzle_solution: Option<u64>,
+        supports_puzzle: bool,
     ) -> Result<InitialAuthResult, InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin + Send,
@@ -486,14 +678,32 @@ where
             return Err(InitialAuthenticationError::DuplicateConnection);
         }
 
-        let shared_keys = self.perform_registration_handshake(init_data).await?;
+        self.check_registration_puzzle(&init_data, puzzle_solution, supports_puzzle)?;
+        if !self.connection_permit.try_start_handshake() {
+            return Err(InitialAuthenticationError::TooManyHandshakes);
+        }
+
+        let deadline = self.authentication_deadline;
+        let shared_keys = until_deadline(deadline, self.perform_registration_handshake(init_data))
+            .await
+            .map_err(|_| InitialAuthenticationError::AuthenticationTimeout)??;
         let client_details = ClientDetails::new(remote_address, shared_keys);
 
         let status = self.register_client(client_details).await?;
+        let dropped_messages = self
+            .storage
+            .take_dropped_messages_count(remote_address)
+            .await?;
 
         Ok(InitialAuthResult::new(
             Some(client_details),
-            ServerResponse::Register { status },
+            ServerResponse::Register {
+                status,
+                dropped_messages,
+            },
+            // there's no way for the registering client to declare it, so to not break the older
+            // ones, the updates are only pushed once the client authenticates
+            false,
         ))
     }
 
@@ -516,9 +726,25 @@ where
                     address,
                     enc_address,
                     iv,
-                } => self.handle_authenticate(address, enc_address, iv).await,
-                ClientControlRequest::RegisterHandshakeInitRequest { data } => {
-                    self.handle_register(data).await
+                    acknowledges_stored_messages,
+                    accepts_bandwidth_updates,
+                } => {
+                    self.handle_authenticate(
+                        address,
+                        enc_address,
+                        iv,
+                        acknowledges_stored_messages,
+                        accepts_bandwidth_updates,
+                    )
+                    .await
+                }
+                ClientControlRequest::RegisterHandshakeInitRequest {
+                    data,
+                    puzzle_solution,
+                    supports_puzzle,
+                } => {
+                    self.handle_register(data, puzzle_solution, supports_puzzle)
+                        .await
                 }
                 // won't accept anything else (like bandwidth) without prior authentication
                 _ => Err(InitialAuthenticationError::InvalidRequest),
@@ -540,10 +766,17 @@ where
     {
         trace!("Started waiting for authenticate/register request...");
 
-        while let Some(msg) = self.read_websocket_message().await {
-            let msg = match msg {
-                Ok(msg) => msg,
-                Err(err) => {
+        loop {
+            let deadline = self.authentication_deadline;
+            let msg = match until_deadline(deadline, self.read_websocket_message()).await {
+                Ok(Some(Ok(msg))) => msg,
+                Ok(None) => break,
+                Err(_) => {
+                    // don't bother responding, the client might not even be reading anything
+                    debug!("The client has not registered or authenticated in time. Stopping the connection handler");
+                    break;
+                }
+                Ok(Some(Err(err))) => {
                     error!("failed to obtain message from websocket stream! stopping connection handler: {}", err);
                     break;
                 }
@@ -577,12 +810,14 @@ where
                             }
 
                             return if let Some(client_details) = auth_result.client_details {
+                                self.connection_permit.mark_authenticated();
                                 self.active_clients_store
                                     .insert(client_details.address, mix_sender);
                                 Some(AuthenticatedHandler::upgrade(
                                     self,
                                     client_details,
                                     mix_receiver,
+                                    auth_result.accepts_bandwidth_updates,
                                 ))
                             } else {
                                 None
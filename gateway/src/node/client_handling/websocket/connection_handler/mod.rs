@@ -1,18 +1,24 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use gateway_requests::codec::GatewayCodec;
 use gateway_requests::registration::handshake::SharedKeys;
 use gateway_requests::ServerResponse;
-use log::{trace, warn};
+use log::{debug, trace, warn};
 use nymsphinx::DestinationAddressBytes;
 use rand::{CryptoRng, Rng};
+use std::future::Future;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{error::Elapsed, Instant};
 use tokio_tungstenite::WebSocketStream;
+use tokio_util::codec::Framed;
 
 pub(crate) use self::authenticated::AuthenticatedHandler;
 pub(crate) use self::fresh::FreshHandler;
 
 mod authenticated;
+#[cfg(feature = "coconut")]
+pub(crate) mod coconut;
 #[cfg(not(feature = "coconut"))]
 pub(crate) mod eth_events;
 mod fresh;
@@ -26,12 +32,17 @@ mod fresh;
 pub(crate) enum SocketStream<S> {
     RawTcp(S),
     UpgradedWebSocket(WebSocketStream<S>),
+    /// Raw TCP socket using length-prefixed framing instead of the websocket protocol.
+    Framed(Framed<S, GatewayCodec>),
     Invalid,
 }
 
 impl<S> SocketStream<S> {
-    fn is_websocket(&self) -> bool {
-        matches!(self, SocketStream::UpgradedWebSocket(_))
+    fn is_established(&self) -> bool {
+        matches!(
+            self,
+            SocketStream::UpgradedWebSocket(_) | SocketStream::Framed(_)
+        )
     }
 }
 
@@ -53,31 +64,57 @@ impl ClientDetails {
 pub(crate) struct InitialAuthResult {
     pub(crate) client_details: Option<ClientDetails>,
     pub(crate) server_response: ServerResponse,
+    /// Indicates whether the client accepts bandwidth updates pushed without a prior request.
+    pub(crate) accepts_bandwidth_updates: bool,
 }
 
 impl InitialAuthResult {
-    fn new(client_details: Option<ClientDetails>, server_response: ServerResponse) -> Self {
+    fn new(
+        client_details: Option<ClientDetails>,
+        server_response: ServerResponse,
+        accepts_bandwidth_updates: bool,
+    ) -> Self {
         InitialAuthResult {
             client_details,
             server_response,
+            accepts_bandwidth_updates,
         }
     }
 }
 
+/// Waits for the provided future to resolve, unless the optional deadline passes first.
+async fn until_deadline<F: Future>(
+    deadline: Option<Instant>,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await,
+        None => Ok(future.await),
+    }
+}
+
 pub(crate) async fn handle_connection<R, S>(mut handle: FreshHandler<R, S>)
 where
     R: Rng + CryptoRng,
     S: AsyncRead + AsyncWrite + Unpin + Send,
 {
-    if let Err(err) = handle.perform_websocket_handshake().await {
-        warn!(
-            "Failed to complete WebSocket handshake - {}. Stopping the handler",
-            err
-        );
-        return;
+    let deadline = handle.authentication_deadline;
+    match until_deadline(deadline, handle.perform_websocket_handshake()).await {
+        Ok(Ok(())) => (),
+        Ok(Err(err)) => {
+            warn!(
+                "Failed to complete WebSocket handshake - {}. Stopping the handler",
+                err
+            );
+            return;
+        }
+        Err(_) => {
+            debug!("The client has not completed the WebSocket handshake in time. Stopping the handler");
+            return;
+        }
     }
 
-    trace!("Managed to perform websocket handshake!");
+    trace!("Managed to establish the client connection!");
 
     if let Some(auth_handle) = handle.perform_initial_authentication().await {
         auth_handle.listen_for_requests().await
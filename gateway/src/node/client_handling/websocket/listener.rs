@@ -2,51 +2,88 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::client_handling::bandwidth::BandwidthUpdatePolicy;
+use crate::node::client_handling::connection_limiter::ConnectionLimiter;
 use crate::node::client_handling::websocket::connection_handler::FreshHandler;
+use crate::node::statistics::GatewayStats;
 use crate::node::storage::PersistentStorage;
 use crypto::asymmetric::identity;
 use log::*;
 use mixnet_client::forwarder::MixForwardingSender;
 use rand::rngs::OsRng;
+use std::fmt::{self, Display, Formatter};
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
+use task::ShutdownListener;
 use tokio::task::JoinHandle;
 
 #[cfg(feature = "coconut")]
-use coconut_interface::VerificationKey;
+use crate::node::client_handling::websocket::connection_handler::coconut::CoconutVerifier;
 
 #[cfg(not(feature = "coconut"))]
 use crate::node::client_handling::websocket::connection_handler::eth_events::ERC20Bridge;
 
+/// Transport used by clients connecting to a listener.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ClientTransport {
+    /// Requests are exchanged as websocket messages after the HTTP upgrade.
+    WebSocket,
+
+    /// Requests are exchanged as length-prefixed frames directly over the TCP stream.
+    Tcp,
+}
+
+impl Display for ClientTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientTransport::WebSocket => write!(f, "websocket"),
+            ClientTransport::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
 pub(crate) struct Listener {
     address: SocketAddr,
+    transport: ClientTransport,
     local_identity: Arc<identity::KeyPair>,
     testnet_mode: bool,
+    stats: GatewayStats,
+    connection_limiter: ConnectionLimiter,
+    bandwidth_updates: BandwidthUpdatePolicy,
 
     #[cfg(feature = "coconut")]
-    aggregated_verification_key: VerificationKey,
+    coconut_verifier: Arc<CoconutVerifier>,
 
     #[cfg(not(feature = "coconut"))]
     erc20_bridge: Arc<ERC20Bridge>,
 }
 
 impl Listener {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         address: SocketAddr,
+        transport: ClientTransport,
         local_identity: Arc<identity::KeyPair>,
         testnet_mode: bool,
-        #[cfg(feature = "coconut")] aggregated_verification_key: VerificationKey,
-        #[cfg(not(feature = "coconut"))] erc20_bridge: ERC20Bridge,
+        stats: GatewayStats,
+        connection_limiter: ConnectionLimiter,
+        bandwidth_updates: BandwidthUpdatePolicy,
+        #[cfg(feature = "coconut")] coconut_verifier: Arc<CoconutVerifier>,
+        #[cfg(not(feature = "coconut"))] erc20_bridge: Arc<ERC20Bridge>,
     ) -> Self {
         Listener {
             address,
+            transport,
             local_identity,
             testnet_mode,
+            stats,
+            connection_limiter,
+            bandwidth_updates,
             #[cfg(feature = "coconut")]
-            aggregated_verification_key,
+            coconut_verifier,
             #[cfg(not(feature = "coconut"))]
-            erc20_bridge: Arc::new(erc20_bridge),
+            erc20_bridge,
         }
     }
 
@@ -57,32 +94,55 @@ impl Listener {
         outbound_mix_sender: MixForwardingSender,
         storage: PersistentStorage,
         active_clients_store: ActiveClientsStore,
+        mut shutdown: ShutdownListener,
     ) {
-        info!("Starting websocket listener at {}", self.address);
+        info!(
+            "Starting {} client listener at {}",
+            self.transport, self.address
+        );
         let tcp_listener = match tokio::net::TcpListener::bind(self.address).await {
             Ok(listener) => listener,
             Err(err) => {
-                error!("Failed to bind the websocket to {} - {}. Are you sure nothing else is running on the specified port and your user has sufficient permission to bind to the requested address?", self.address, err);
+                error!("Failed to bind the {} client listener to {} - {}. Are you sure nothing else is running on the specified port and your user has sufficient permission to bind to the requested address?", self.transport, self.address, err);
                 process::exit(1);
             }
         };
 
         loop {
-            match tcp_listener.accept().await {
+            let connection = tokio::select! {
+                connection = tcp_listener.accept() => connection,
+                _ = shutdown.recv() => {
+                    info!(
+                        "The {} client listener is no longer accepting new connections",
+                        self.transport
+                    );
+                    return;
+                }
+            };
+
+            match connection {
                 Ok((socket, remote_addr)) => {
                     trace!("received a socket connection from {}", remote_addr);
-                    // TODO: I think we *REALLY* need a mechanism for having a maximum number of connected
-                    // clients or spawned tokio tasks -> perhaps a worker system?
+                    // dropping the socket is enough to refuse the connection
+                    let connection_permit =
+                        match self.connection_limiter.try_accept(remote_addr.ip()) {
+                            Some(permit) => permit,
+                            None => continue,
+                        };
                     let handle = FreshHandler::new(
                         OsRng,
                         socket,
+                        self.transport,
+                        connection_permit,
                         self.testnet_mode,
                         outbound_mix_sender.clone(),
                         Arc::clone(&self.local_identity),
                         storage.clone(),
                         active_clients_store.clone(),
+                        self.stats.clone(),
+                        self.bandwidth_updates,
                         #[cfg(feature = "coconut")]
-                        self.aggregated_verification_key.clone(),
+                        Arc::clone(&self.coconut_verifier),
                         #[cfg(not(feature = "coconut"))]
                         Arc::clone(&self.erc20_bridge),
                     );
@@ -98,9 +158,10 @@ impl Listener {
         outbound_mix_sender: MixForwardingSender,
         storage: PersistentStorage,
         active_clients_store: ActiveClientsStore,
+        shutdown: ShutdownListener,
     ) -> JoinHandle<()> {
         tokio::spawn(async move {
-            self.run(outbound_mix_sender, storage, active_clients_store)
+            self.run(outbound_mix_sender, storage, active_clients_store, shutdown)
                 .await
         })
     }
@@ -1,7 +1,7 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-pub(crate) use listener::Listener;
+pub(crate) use listener::{ClientTransport, Listener};
 
 pub(crate) mod connection_handler;
 pub(crate) mod listener;
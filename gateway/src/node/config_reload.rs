@@ -0,0 +1,39 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::Config;
+use config::reload::{any_changed, LOG_LEVEL, PACKET_FORWARDING_FIELDS};
+use mixnet_client::PacketForwardingConfig;
+use tokio::sync::watch;
+
+// all the settings that can be changed without restarting the node
+pub(super) fn is_live(field: &str) -> bool {
+    field == LOG_LEVEL || PACKET_FORWARDING_FIELDS.contains(&field)
+}
+
+pub(super) fn apply_changes(
+    mixnet_client_config: watch::Sender<mixnet_client::Config>,
+) -> impl Fn(&Config, &[&String]) + Send {
+    move |new_config, changed_fields| {
+        if any_changed(changed_fields, &[LOG_LEVEL]) {
+            crate::set_log_level(new_config.get_log_level())
+        }
+        if any_changed(changed_fields, PACKET_FORWARDING_FIELDS) {
+            // the only possible error is the forwarder being gone, i.e. during the shutdown
+            let _ = mixnet_client_config.send(new_config.mixnet_client_config());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_safe_settings_are_applied_live() {
+        assert!(is_live("logging.level"));
+        assert!(is_live("debug.maximum_connection_buffer_size"));
+        assert!(!is_live("gateway.clients_port"));
+        assert!(!is_live("gateway.announce_address"));
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::storage::PersistentStorage;
+use log::*;
+use nymsphinx::DestinationAddressBytes;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::json::Json;
+use rocket::{Request, State};
+use serde::Serialize;
+
+/// Token that has to be presented (as `Authorization: Bearer <token>`) to access the admin routes.
+pub(crate) struct AdminApiToken(pub(crate) String);
+
+#[derive(Debug)]
+pub(crate) struct UnauthorizedAdminRequestError;
+
+/// Request guard that only allows requests presenting the configured admin token.
+pub(crate) struct AdminRequest;
+
+// compare the tokens in constant time so that the token could not be guessed byte by byte
+fn tokens_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminRequest {
+    type Error = UnauthorizedAdminRequestError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = match request.rocket().state::<AdminApiToken>() {
+            Some(token) => &token.0,
+            None => return Outcome::Failure((Status::NotFound, UnauthorizedAdminRequestError)),
+        };
+
+        let provided = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+
+        match provided {
+            Some(provided) if tokens_match(expected, provided) => Outcome::Success(AdminRequest),
+            _ => {
+                warn!(
+                    "Received an unauthorized request from {:?} for an admin route",
+                    request.client_ip()
+                );
+                Outcome::Failure((Status::Unauthorized, UnauthorizedAdminRequestError))
+            }
+        }
+    }
+}
+
+/// Returns addresses of all currently connected clients.
+#[get("/admin/clients")]
+pub(crate) fn clients(
+    _admin: AdminRequest,
+    active_clients: &State<ActiveClientsStore>,
+) -> Json<Vec<String>> {
+    Json(
+        active_clients
+            .connected_clients()
+            .into_iter()
+            .map(|client| client.as_base58_string())
+            .collect(),
+    )
+}
+
+#[derive(Serialize)]
+pub(crate) struct DisconnectResponse {
+    disconnected: bool,
+}
+
+/// Closes the connection of the specified client.
+#[post("/admin/clients/<address>/disconnect")]
+pub(crate) fn disconnect_client(
+    _admin: AdminRequest,
+    address: &str,
+    active_clients: &State<ActiveClientsStore>,
+) -> Result<Json<DisconnectResponse>, Status> {
+    let client =
+        DestinationAddressBytes::try_from_base58_string(address).map_err(|_| Status::BadRequest)?;
+
+    let disconnected = active_clients.kick(client);
+    if disconnected {
+        info!("{} got disconnected by the gateway operator", address);
+    }
+    Ok(Json(DisconnectResponse { disconnected }))
+}
+
+#[derive(Serialize)]
+pub(crate) struct ClientStorageUsage {
+    client: String,
+    messages: i64,
+    bytes: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct StorageUsage {
+    total_messages: i64,
+    total_bytes: i64,
+    clients: Vec<ClientStorageUsage>,
+}
+
+/// Returns the number and size of messages stored for offline clients.
+#[get("/admin/storage")]
+pub(crate) async fn storage(
+    _admin: AdminRequest,
+    storage: &State<PersistentStorage>,
+) -> Result<Json<StorageUsage>, Status> {
+    let usage = storage.inbox_usage().await.map_err(|err| {
+        error!("Failed to obtain storage usage - {}", err);
+        Status::InternalServerError
+    })?;
+
+    let clients: Vec<_> = usage
+        .into_iter()
+        .map(|usage| ClientStorageUsage {
+            client: usage.client_address_bs58,
+            messages: usage.messages,
+            bytes: usage.bytes,
+        })
+        .collect();
+
+    Ok(Json(StorageUsage {
+        total_messages: clients.iter().map(|usage| usage.messages).sum(),
+        total_bytes: clients.iter().map(|usage| usage.bytes).sum(),
+        clients,
+    }))
+}
@@ -0,0 +1,125 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::statistics::GatewayStats;
+use rocket::State;
+use std::fmt::Write;
+
+fn write_metric(output: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    // writing to a String can't fail
+    let _ = writeln!(output, "# HELP nym_gateway_{} {}", name, help);
+    let _ = writeln!(output, "# TYPE nym_gateway_{} {}", name, kind);
+    let _ = writeln!(output, "nym_gateway_{} {}", name, value);
+}
+
+/// Returns running stats of the gateway in the Prometheus text exposition format.
+#[get("/metrics")]
+pub(crate) fn metrics(
+    stats: &State<GatewayStats>,
+    active_clients: &State<ActiveClientsStore>,
+) -> String {
+    let snapshot = stats.snapshot();
+    let mut output = String::new();
+
+    write_metric(
+        &mut output,
+        "uptime_seconds",
+        "gauge",
+        "Time since the gateway has started up.",
+        snapshot.uptime_secs,
+    );
+    write_metric(
+        &mut output,
+        "connected_clients",
+        "gauge",
+        "Number of clients currently connected to the gateway.",
+        active_clients.connected_clients().len() as u64,
+    );
+    write_metric(
+        &mut output,
+        "mix_packets_received_total",
+        "counter",
+        "Packets received from the mix network for clients of the gateway.",
+        snapshot.mix_packets_received,
+    );
+    write_metric(
+        &mut output,
+        "mix_packets_pushed_to_clients_total",
+        "counter",
+        "Packets received from the mix network that got pushed directly to connected clients.",
+        snapshot.mix_packets_pushed_to_clients,
+    );
+    write_metric(
+        &mut output,
+        "mix_packets_stored_total",
+        "counter",
+        "Packets received from the mix network that got stored for offline clients.",
+        snapshot.mix_packets_stored,
+    );
+    write_metric(
+        &mut output,
+        "client_packets_forwarded_total",
+        "counter",
+        "Packets forwarded into the mix network on behalf of clients.",
+        snapshot.client_packets_forwarded,
+    );
+    write_metric(
+        &mut output,
+        "bandwidth_consumed_bytes_total",
+        "counter",
+        "Bandwidth consumed by clients sending packets into the mix network.",
+        snapshot.bandwidth_consumed,
+    );
+    write_metric(
+        &mut output,
+        "bandwidth_credentials_redeemed_total",
+        "counter",
+        "Bandwidth credentials redeemed by clients.",
+        snapshot.bandwidth_credentials_redeemed,
+    );
+    write_metric(
+        &mut output,
+        "unauthenticated_connections",
+        "gauge",
+        "Number of client connections that have not yet registered or authenticated.",
+        snapshot.unauthenticated_connections,
+    );
+    write_metric(
+        &mut output,
+        "connections_rate_limited_total",
+        "counter",
+        "Client connections refused as their address has opened too many connections recently.",
+        snapshot.connections_rate_limited,
+    );
+    write_metric(
+        &mut output,
+        "connections_over_capacity_total",
+        "counter",
+        "Client connections refused due to the limit of unauthenticated connections.",
+        snapshot.connections_over_capacity,
+    );
+    write_metric(
+        &mut output,
+        "handshakes_rate_limited_total",
+        "counter",
+        "Registration handshakes refused as their address has started too many handshakes recently.",
+        snapshot.handshakes_rate_limited,
+    );
+    write_metric(
+        &mut output,
+        "registration_puzzles_issued_total",
+        "counter",
+        "Puzzles registering clients were required to solve due to the gateway being under load.",
+        snapshot.registration_puzzles_issued,
+    );
+    write_metric(
+        &mut output,
+        "registration_puzzles_solved_total",
+        "counter",
+        "Registration puzzles correctly solved by clients.",
+        snapshot.registration_puzzles_solved,
+    );
+
+    output
+}
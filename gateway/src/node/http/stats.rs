@@ -0,0 +1,27 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::statistics::{GatewayStats, GatewayStatsSnapshot};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub(crate) struct GatewayStatsResponse {
+    connected_clients: usize,
+    #[serde(flatten)]
+    stats: GatewayStatsSnapshot,
+}
+
+/// Returns running stats of the gateway.
+#[get("/stats")]
+pub(crate) fn stats(
+    stats: &State<GatewayStats>,
+    active_clients: &State<ActiveClientsStore>,
+) -> Json<GatewayStatsResponse> {
+    Json(GatewayStatsResponse {
+        connected_clients: active_clients.connected_clients().len(),
+        stats: stats.snapshot(),
+    })
+}
@@ -4,6 +4,7 @@
 use crate::node::client_handling::active_clients::ActiveClientsStore;
 use crate::node::client_handling::websocket::message_receiver::MixMessageSender;
 use crate::node::mixnet_handling::receiver::packet_processing::PacketProcessor;
+use crate::node::statistics::GatewayStats;
 use crate::node::storage::error::StorageError;
 use crate::node::storage::PersistentStorage;
 use futures::StreamExt;
@@ -16,6 +17,7 @@ use nymsphinx::framing::packet::FramedSphinxPacket;
 use nymsphinx::DestinationAddressBytes;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use task::ShutdownListener;
 use tokio::net::TcpStream;
 use tokio_util::codec::Framed;
 
@@ -30,6 +32,7 @@ pub(crate) struct ConnectionHandler {
     active_clients_store: ActiveClientsStore,
     storage: PersistentStorage,
     ack_sender: MixForwardingSender,
+    stats: GatewayStats,
 }
 
 impl Clone for ConnectionHandler {
@@ -48,6 +51,7 @@ impl Clone for ConnectionHandler {
             active_clients_store: self.active_clients_store.clone(),
             storage: self.storage.clone(),
             ack_sender: self.ack_sender.clone(),
+            stats: self.stats.clone(),
         }
     }
 }
@@ -58,6 +62,7 @@ impl ConnectionHandler {
         storage: PersistentStorage,
         ack_sender: MixForwardingSender,
         active_clients_store: ActiveClientsStore,
+        stats: GatewayStats,
     ) -> Self {
         ConnectionHandler {
             packet_processor,
@@ -65,6 +70,7 @@ impl ConnectionHandler {
             storage,
             active_clients_store,
             ack_sender,
+            stats,
         }
     }
 
@@ -143,9 +149,15 @@ impl ConnectionHandler {
                 .await
             {
                 Err(err) => error!("Failed to store client data - {}", err),
-                Ok(_) => trace!("Stored packet for {}", client_address),
+                Ok(_) => {
+                    self.stats.record_mix_packet(true);
+                    trace!("Stored packet for {}", client_address)
+                }
             },
-            Ok(_) => trace!("Pushed received packet to {}", client_address),
+            Ok(_) => {
+                self.stats.record_mix_packet(false);
+                trace!("Pushed received packet to {}", client_address)
+            }
         }
 
         // if we managed to either push message directly to the [online] client or store it at
@@ -173,10 +185,24 @@ impl ConnectionHandler {
         self.handle_processed_packet(processed_final_hop).await
     }
 
-    pub(crate) async fn handle_connection(mut self, conn: TcpStream, remote: SocketAddr) {
+    pub(crate) async fn handle_connection(
+        mut self,
+        conn: TcpStream,
+        remote: SocketAddr,
+        mut shutdown: ShutdownListener,
+    ) {
         debug!("Starting connection handler for {:?}", remote);
         let mut framed_conn = Framed::new(conn, SphinxCodec);
-        while let Some(framed_sphinx_packet) = framed_conn.next().await {
+        loop {
+            let framed_sphinx_packet = tokio::select! {
+                framed_sphinx_packet = framed_conn.next() => match framed_sphinx_packet {
+                    Some(framed_sphinx_packet) => framed_sphinx_packet,
+                    None => break,
+                },
+                // stop reading new packets during the shutdown
+                _ = shutdown.recv() => break,
+            };
+
             match framed_sphinx_packet {
                 Ok(framed_sphinx_packet) => {
                     // TODO: benchmark spawning tokio task with full processing vs just processing it
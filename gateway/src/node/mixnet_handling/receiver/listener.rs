@@ -5,6 +5,7 @@ use crate::node::mixnet_handling::receiver::connection_handler::ConnectionHandle
 use log::*;
 use std::net::SocketAddr;
 use std::process;
+use task::ShutdownListener;
 use tokio::task::JoinHandle;
 
 pub(crate) struct Listener {
@@ -17,7 +18,11 @@ impl Listener {
         Listener { address }
     }
 
-    pub(crate) async fn run(&mut self, connection_handler: ConnectionHandler) {
+    pub(crate) async fn run(
+        &mut self,
+        connection_handler: ConnectionHandler,
+        mut shutdown: ShutdownListener,
+    ) {
         info!("Starting mixnet listener at {}", self.address);
         let tcp_listener = match tokio::net::TcpListener::bind(self.address).await {
             Ok(listener) => listener,
@@ -28,19 +33,33 @@ impl Listener {
         };
 
         loop {
-            match tcp_listener.accept().await {
-                Ok((socket, remote_addr)) => {
-                    let handler = connection_handler.clone();
-                    tokio::spawn(handler.handle_connection(socket, remote_addr));
+            tokio::select! {
+                connection = tcp_listener.accept() => match connection {
+                    Ok((socket, remote_addr)) => {
+                        let handler = connection_handler.clone();
+                        tokio::spawn(handler.handle_connection(
+                            socket,
+                            remote_addr,
+                            shutdown.clone(),
+                        ));
+                    }
+                    Err(e) => warn!("failed to get client: {:?}", e),
+                },
+                _ = shutdown.recv() => {
+                    info!("The mixnet listener is no longer accepting new connections");
+                    return;
                 }
-                Err(e) => warn!("failed to get client: {:?}", e),
             }
         }
     }
 
-    pub(crate) fn start(mut self, connection_handler: ConnectionHandler) -> JoinHandle<()> {
+    pub(crate) fn start(
+        mut self,
+        connection_handler: ConnectionHandler,
+        shutdown: ShutdownListener,
+    ) -> JoinHandle<()> {
         info!("Running mix listener on {:?}", self.address.to_string());
 
-        tokio::spawn(async move { self.run(connection_handler).await })
+        tokio::spawn(async move { self.run(connection_handler, shutdown).await })
     }
 }
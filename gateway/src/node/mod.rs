@@ -3,30 +3,49 @@
 
 use crate::commands::sign::load_identity_keys;
 use crate::commands::validate_bech32_address_or_exit;
-use crate::config::Config;
+use crate::config::{Config, StorageBackend};
 use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::client_handling::bandwidth::BandwidthUpdatePolicy;
+use crate::node::client_handling::connection_limiter::{ConnectionLimiter, ConnectionLimits};
 use crate::node::client_handling::websocket;
+use crate::node::client_handling::websocket::ClientTransport;
+use crate::node::http::{
+    admin::{self, AdminApiToken},
+    metrics::metrics,
+    not_found,
+    stats::stats,
+};
 use crate::node::mixnet_handling::receiver::connection_handler::ConnectionHandler;
-use crate::node::storage::PersistentStorage;
+use crate::node::statistics::GatewayStats;
+use crate::node::storage::purger::MessagesPurger;
+use crate::node::storage::{InboxLimits, PersistentStorage, PostgresStorage, SqliteStorage};
+use config::reload::ConfigReloader;
 use crypto::asymmetric::{encryption, identity};
 use log::*;
 use mixnet_client::forwarder::{MixForwardingSender, PacketForwarder};
+use mixnet_client::PacketForwardingConfig;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
+use task::{ShutdownListener, ShutdownNotifier};
+use tokio::sync::watch;
+use tokio::time::Instant;
 
 use crate::config::persistence::pathfinder::GatewayPathfinder;
+#[cfg(feature = "coconut")]
+use crate::node::client_handling::websocket::connection_handler::coconut::CoconutVerifier;
 #[cfg(not(feature = "coconut"))]
 use crate::node::client_handling::websocket::connection_handler::eth_events::ERC20Bridge;
 #[cfg(feature = "coconut")]
-use coconut_interface::VerificationKey;
-#[cfg(feature = "coconut")]
 use credentials::obtain_aggregate_verification_key;
 
 pub(crate) mod client_handling;
+mod config_reload;
+mod http;
 pub(crate) mod mixnet_handling;
+pub(crate) mod statistics;
 pub(crate) mod storage;
 
 pub struct Gateway {
@@ -36,6 +55,7 @@ pub struct Gateway {
     /// x25519 keypair used for Diffie-Hellman. Currently only used for sphinx key derivation.
     sphinx_keypair: Arc<encryption::KeyPair>,
     storage: PersistentStorage,
+    stats: GatewayStats,
 }
 
 impl Gateway {
@@ -48,13 +68,35 @@ impl Gateway {
             identity_keypair: Arc::new(Self::load_identity_keys(&pathfinder)),
             sphinx_keypair: Arc::new(Self::load_sphinx_keys(&pathfinder)),
             storage,
+            stats: GatewayStats::new(),
         }
     }
 
     async fn initialise_storage(config: &Config) -> PersistentStorage {
-        let path = config.get_persistent_store_path();
         let retrieval_limit = config.get_message_retrieval_limit();
-        match PersistentStorage::init(path, retrieval_limit).await {
+        // 0 means there's no limit
+        let limit = |value: u64| (value > 0).then(|| value as i64);
+        let inbox_limits = InboxLimits {
+            max_messages_per_client: limit(config.get_max_stored_messages_per_client()),
+            max_bytes_per_client: limit(config.get_max_stored_bytes_per_client()),
+        };
+        let storage: Result<PersistentStorage, _> = match config.get_storage_backend() {
+            StorageBackend::Sqlite => {
+                let path = config.get_persistent_store_path();
+                SqliteStorage::init(path, retrieval_limit, inbox_limits)
+                    .await
+                    .map(|storage| Arc::new(storage) as PersistentStorage)
+            }
+            StorageBackend::Postgres => PostgresStorage::init(
+                &config.get_postgres_url(),
+                config.get_postgres_max_connections(),
+                retrieval_limit,
+                inbox_limits,
+            )
+            .await
+            .map(|storage| Arc::new(storage) as PersistentStorage),
+        };
+        match storage {
             Err(err) => panic!("failed to initialise gateway storage - {}", err),
             Ok(storage) => storage,
         }
@@ -108,9 +150,14 @@ impl Gateway {
         );
         println!("Version: {}", self.config.get_version());
         println!(
-            "Mix Port: {}, Clients port: {}",
+            "Mix Port: {}, Clients port: {}, Clients TCP port: {}, Http Api port: {}",
             self.config.get_mix_port(),
-            self.config.get_clients_port()
+            self.config.get_clients_port(),
+            self.config
+                .get_clients_tcp_port()
+                .map(|port| port.to_string())
+                .unwrap_or_else(|| "disabled".to_string()),
+            self.config.get_http_api_port()
         );
 
         println!(
@@ -123,6 +170,7 @@ impl Gateway {
         &self,
         ack_sender: MixForwardingSender,
         active_clients_store: ActiveClientsStore,
+        shutdown: ShutdownListener,
     ) {
         info!("Starting mix socket listener...");
 
@@ -134,6 +182,7 @@ impl Gateway {
             self.storage.clone(),
             ack_sender,
             active_clients_store,
+            self.stats.clone(),
         );
 
         let listening_address = SocketAddr::new(
@@ -141,29 +190,69 @@ impl Gateway {
             self.config.get_mix_port(),
         );
 
-        mixnet_handling::Listener::new(listening_address).start(connection_handler);
+        mixnet_handling::Listener::new(listening_address).start(connection_handler, shutdown);
+    }
+
+    fn connection_limits(&self) -> ConnectionLimits {
+        // 0 means there's no limit
+        let limit = |value: u64| (value > 0).then(|| value as usize);
+        ConnectionLimits {
+            max_connections_per_ip_per_minute: limit(
+                self.config.get_max_client_connections_per_ip_per_minute(),
+            ),
+            max_handshakes_per_ip_per_minute: limit(
+                self.config
+                    .get_max_registration_handshakes_per_ip_per_minute(),
+            ),
+            max_unauthenticated_connections: limit(
+                self.config.get_max_unauthenticated_connections(),
+            ),
+            max_unauthenticated_connections_per_ip: limit(
+                self.config.get_max_unauthenticated_connections_per_ip(),
+            ),
+            authentication_timeout: Some(self.config.get_client_authentication_timeout())
+                .filter(|timeout| !timeout.is_zero()),
+            registration_puzzle_threshold: limit(self.config.get_registration_puzzle_threshold()),
+            registration_puzzle_difficulty: self.config.get_registration_puzzle_difficulty(),
+        }
     }
 
-    fn start_client_websocket_listener(
+    fn bandwidth_update_policy(&self) -> BandwidthUpdatePolicy {
+        let interval = self.config.get_bandwidth_update_interval();
+        let threshold = self.config.get_bandwidth_update_threshold();
+        // 0 means the updates are disabled
+        BandwidthUpdatePolicy {
+            interval: (!interval.is_zero()).then(|| interval),
+            threshold: (threshold > 0).then(|| threshold as i64),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn start_client_listener(
         &self,
+        port: u16,
+        transport: ClientTransport,
         forwarding_channel: MixForwardingSender,
         active_clients_store: ActiveClientsStore,
-        #[cfg(feature = "coconut")] verification_key: VerificationKey,
-        #[cfg(not(feature = "coconut"))] erc20_bridge: ERC20Bridge,
+        connection_limiter: ConnectionLimiter,
+        shutdown: ShutdownListener,
+        #[cfg(feature = "coconut")] coconut_verifier: Arc<CoconutVerifier>,
+        #[cfg(not(feature = "coconut"))] erc20_bridge: Arc<ERC20Bridge>,
     ) {
-        info!("Starting client [web]socket listener...");
+        info!("Starting client {} listener...", transport);
 
-        let listening_address = SocketAddr::new(
-            self.config.get_listening_address(),
-            self.config.get_clients_port(),
-        );
+        let listening_address = SocketAddr::new(self.config.get_listening_address(), port);
 
         websocket::Listener::new(
             listening_address,
+            transport,
             Arc::clone(&self.identity_keypair),
             self.config.get_testnet_mode(),
+            self.stats.clone(),
+            connection_limiter,
+            self.bandwidth_update_policy(),
             #[cfg(feature = "coconut")]
-            verification_key,
+            coconut_verifier,
             #[cfg(not(feature = "coconut"))]
             erc20_bridge,
         )
@@ -171,33 +260,160 @@ impl Gateway {
             forwarding_channel,
             self.storage.clone(),
             active_clients_store,
+            shutdown,
         );
     }
 
-    fn start_packet_forwarder(&self) -> MixForwardingSender {
-        info!("Starting mix packet forwarder...");
+    fn start_http_api(&self, active_clients_store: ActiveClientsStore) {
+        let mut config = rocket::config::Config::release_default();
 
-        let (mut packet_forwarder, packet_sender) = PacketForwarder::new(
-            self.config.get_packet_forwarding_initial_backoff(),
-            self.config.get_packet_forwarding_maximum_backoff(),
-            self.config.get_initial_connection_timeout(),
-            self.config.get_maximum_connection_buffer_size(),
+        // bind to the same address as we are using for mix and client traffic
+        config.address = self.config.get_listening_address();
+        config.port = self.config.get_http_api_port();
+        info!(
+            "Starting HTTP API on http://{}:{}",
+            config.address, config.port
         );
 
-        tokio::spawn(async move { packet_forwarder.run().await });
-        packet_sender
+        let server = rocket::build()
+            .configure(config)
+            .mount("/", routes![stats, metrics])
+            .register("/", catchers![not_found])
+            .manage(self.stats.clone())
+            .manage(active_clients_store.clone());
+
+        tokio::spawn(async move { server.launch().await });
+
+        // the admin routes are only exposed if the operator has set up the token,
+        // and they're served separately so that they wouldn't be reachable on the public address
+        match self.config.get_admin_api_token() {
+            Some(token) => self.start_admin_api(active_clients_store, token),
+            None => info!("Admin API token is not set - the admin routes are disabled"),
+        }
     }
 
-    async fn wait_for_interrupt(&self) {
-        if let Err(e) = tokio::signal::ctrl_c().await {
-            error!(
-                "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
-                e
-            );
+    fn start_admin_api(&self, active_clients_store: ActiveClientsStore, token: String) {
+        let mut config = rocket::config::Config::release_default();
+
+        let admin_address = self.config.get_admin_api_address();
+        config.address = admin_address.ip();
+        config.port = admin_address.port();
+        info!("Starting admin HTTP API on http://{}", admin_address);
+
+        let server = rocket::build()
+            .configure(config)
+            .mount(
+                "/",
+                routes![admin::clients, admin::disconnect_client, admin::storage],
+            )
+            .register("/", catchers![not_found])
+            .manage(AdminApiToken(token))
+            .manage(active_clients_store)
+            .manage(self.storage.clone());
+
+        tokio::spawn(async move { server.launch().await });
+    }
+
+    fn start_packet_forwarder(
+        &self,
+        shutdown: ShutdownListener,
+    ) -> (MixForwardingSender, watch::Sender<mixnet_client::Config>) {
+        info!("Starting mix packet forwarder...");
+
+        let (client_config_sender, client_config) =
+            watch::channel(self.config.mixnet_client_config());
+        let (mut packet_forwarder, packet_sender) =
+            PacketForwarder::new_with_config_updates(client_config);
+
+        tokio::spawn(async move { packet_forwarder.run_with_shutdown(shutdown).await });
+        (packet_sender, client_config_sender)
+    }
+
+    fn start_config_reloader(
+        &self,
+        mixnet_client_config: watch::Sender<mixnet_client::Config>,
+        shutdown: ShutdownListener,
+    ) {
+        let reloader = match ConfigReloader::<Config, _>::new(
+            "gateway",
+            self.config.get_id(),
+            config_reload::is_live,
+            config_reload::apply_changes(mixnet_client_config),
+        ) {
+            Ok(reloader) => reloader,
+            Err(err) => {
+                warn!(
+                    "Failed to load the config file - its changes are going to require a restart (Error was: {})",
+                    err
+                );
+                return;
+            }
+        };
+
+        info!("Starting config reloader - send SIGHUP to the gateway to apply the changes made to its config file");
+        tokio::spawn(async move { reloader.run(shutdown).await });
+    }
+
+    fn start_messages_purger(&self) {
+        let purge_interval = self.config.get_stored_messages_purge_interval();
+        if purge_interval.is_zero() {
+            warn!("Stored messages purging is disabled - neither expired nor oversized messages are going to be removed");
+            return;
         }
+
+        let max_age = self.config.get_stored_messages_max_age();
+        if max_age.is_zero() && self.config.get_max_stored_bytes_per_client() == 0 {
+            info!("Stored messages are going to be kept until they are retrieved");
+            return;
+        }
+
+        info!("Starting stored messages purger...");
+        MessagesPurger::new(
+            self.storage.clone(),
+            (!max_age.is_zero()).then(|| max_age),
+            purge_interval,
+        )
+        .start();
+    }
+
+    fn log_final_stats(&self) {
+        let stats = self.stats.snapshot();
+        info!(
+            "Since startup received {} mix packets ({} pushed to clients, {} stored) and forwarded {} client packets",
+            stats.mix_packets_received,
+            stats.mix_packets_pushed_to_clients,
+            stats.mix_packets_stored,
+            stats.client_packets_forwarded,
+        );
+    }
+
+    /// Waits for SIGTERM or SIGINT and shuts the gateway down in stages: first it stops accepting
+    /// new connections and reading from the mixnet ones, then it forwards whatever is still
+    /// waiting to be sent to the mix network and finally it closes the storage.
+    async fn wait_for_interrupt(
+        &self,
+        listeners_shutdown: ShutdownNotifier,
+        forwarder_shutdown: ShutdownNotifier,
+    ) {
+        task::wait_for_signal().await;
+
+        let drain_timeout = self.config.get_shutdown_drain_timeout();
         println!(
-            "Received SIGINT - the gateway will terminate now (threads are not yet nicely stopped, if you see stack traces that's alright)."
+            "The gateway is shutting down - handling already received packets for up to {:?}...",
+            drain_timeout
         );
+        let deadline = Instant::now() + drain_timeout;
+
+        let mut graceful = listeners_shutdown.shutdown(drain_timeout).await;
+        graceful &= forwarder_shutdown
+            .shutdown(deadline.saturating_duration_since(Instant::now()))
+            .await;
+
+        self.storage.close().await;
+        self.log_final_stats();
+        if graceful {
+            info!("The gateway has been shut down gracefully")
+        }
     }
 
     // TODO: ask DH whether this function still makes sense in ^0.10
@@ -245,32 +461,75 @@ impl Gateway {
                 .await
                 .expect("failed to contact validators to obtain their verification keys");
 
+        #[cfg(feature = "coconut")]
+        let coconut_verifier = Arc::new(CoconutVerifier::new(
+            validators_verification_key,
+            Arc::clone(&self.identity_keypair),
+            self.config.get_validator_api_endpoints(),
+            self.config.get_check_shared_spent_credentials(),
+        ));
+
         #[cfg(not(feature = "coconut"))]
-        let erc20_bridge = ERC20Bridge::new(
+        let erc20_bridge = Arc::new(ERC20Bridge::new(
             self.config.get_eth_endpoint(),
             self.config.get_validator_nymd_endpoints(),
             self.config.get_cosmos_mnemonic(),
-        );
+        ));
+
+        self.start_messages_purger();
 
-        let mix_forwarding_channel = self.start_packet_forwarder();
+        // the packet forwarder is only shut down once nothing new can be received from the mixnet
+        let listeners_shutdown = ShutdownNotifier::new();
+        let forwarder_shutdown = ShutdownNotifier::new();
+
+        let (mix_forwarding_channel, mixnet_client_config) =
+            self.start_packet_forwarder(forwarder_shutdown.subscribe());
+        self.start_config_reloader(mixnet_client_config, listeners_shutdown.subscribe());
 
         let active_clients_store = ActiveClientsStore::new();
         self.start_mix_socket_listener(
             mix_forwarding_channel.clone(),
             active_clients_store.clone(),
+            listeners_shutdown.subscribe(),
         );
 
-        self.start_client_websocket_listener(
+        self.start_http_api(active_clients_store.clone());
+
+        // the limits are shared between both client listeners
+        let connection_limiter =
+            ConnectionLimiter::new(self.connection_limits(), self.stats.clone());
+
+        if let Some(clients_tcp_port) = self.config.get_clients_tcp_port() {
+            self.start_client_listener(
+                clients_tcp_port,
+                ClientTransport::Tcp,
+                mix_forwarding_channel.clone(),
+                active_clients_store.clone(),
+                connection_limiter.clone(),
+                listeners_shutdown.subscribe(),
+                #[cfg(feature = "coconut")]
+                Arc::clone(&coconut_verifier),
+                #[cfg(not(feature = "coconut"))]
+                Arc::clone(&erc20_bridge),
+            );
+        }
+
+        self.start_client_listener(
+            self.config.get_clients_port(),
+            ClientTransport::WebSocket,
             mix_forwarding_channel,
             active_clients_store,
+            connection_limiter,
+            listeners_shutdown.subscribe(),
             #[cfg(feature = "coconut")]
-            validators_verification_key,
+            coconut_verifier,
             #[cfg(not(feature = "coconut"))]
             erc20_bridge,
         );
 
         info!("Finished nym gateway startup procedure - it should now be able to receive mix and client traffic!");
 
-        self.wait_for_interrupt().await
+        self.wait_for_interrupt(listeners_shutdown, forwarder_shutdown)
+            .await
     }
 }
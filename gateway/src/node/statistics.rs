@@ -0,0 +1,162 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Default)]
+struct GatewayStatsInner {
+    mix_packets_received: AtomicU64,
+    mix_packets_pushed_to_clients: AtomicU64,
+    mix_packets_stored: AtomicU64,
+    client_packets_forwarded: AtomicU64,
+    bandwidth_consumed: AtomicU64,
+    bandwidth_credentials_redeemed: AtomicU64,
+    unauthenticated_connections: AtomicU64,
+    connections_rate_limited: AtomicU64,
+    connections_over_capacity: AtomicU64,
+    handshakes_rate_limited: AtomicU64,
+    registration_puzzles_issued: AtomicU64,
+    registration_puzzles_solved: AtomicU64,
+}
+
+/// Counters of the traffic handled by the gateway since it has started up.
+// note that clone here is fine as upon cloning the same underlying counters will be used
+#[derive(Clone)]
+pub(crate) struct GatewayStats {
+    started_at: Instant,
+    inner: Arc<GatewayStatsInner>,
+}
+
+impl GatewayStats {
+    pub(crate) fn new() -> Self {
+        GatewayStats {
+            started_at: Instant::now(),
+            inner: Arc::new(GatewayStatsInner::default()),
+        }
+    }
+
+    /// Records a packet received from the mix network for one of our clients and whether it was
+    /// pushed to a connected client or stored for an offline one.
+    pub(crate) fn record_mix_packet(&self, stored: bool) {
+        self.inner
+            .mix_packets_received
+            .fetch_add(1, Ordering::Relaxed);
+        if stored {
+            self.inner
+                .mix_packets_stored
+                .fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.inner
+                .mix_packets_pushed_to_clients
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records a packet forwarded into the mix network on behalf of a client alongside the
+    /// bandwidth it has consumed.
+    pub(crate) fn record_forwarded_packet(&self, consumed_bandwidth: u64) {
+        self.inner
+            .client_packets_forwarded
+            .fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .bandwidth_consumed
+            .fetch_add(consumed_bandwidth, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_redeemed_credential(&self) {
+        self.inner
+            .bandwidth_credentials_redeemed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_unauthenticated_connections(&self, connections: u64) {
+        self.inner
+            .unauthenticated_connections
+            .store(connections, Ordering::Relaxed);
+    }
+
+    /// Records a client connection that got refused, either because its address has opened
+    /// too many connections recently or because there are too many unauthenticated connections.
+    pub(crate) fn record_rejected_connection(&self, over_capacity: bool) {
+        if over_capacity {
+            self.inner
+                .connections_over_capacity
+                .fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.inner
+                .connections_rate_limited
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_rate_limited_handshake(&self) {
+        self.inner
+            .handshakes_rate_limited
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_issued_puzzle(&self) {
+        self.inner
+            .registration_puzzles_issued
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_solved_puzzle(&self) {
+        self.inner
+            .registration_puzzles_solved
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> GatewayStatsSnapshot {
+        GatewayStatsSnapshot {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            mix_packets_received: self.inner.mix_packets_received.load(Ordering::Relaxed),
+            mix_packets_pushed_to_clients: self
+                .inner
+                .mix_packets_pushed_to_clients
+                .load(Ordering::Relaxed),
+            mix_packets_stored: self.inner.mix_packets_stored.load(Ordering::Relaxed),
+            client_packets_forwarded: self.inner.client_packets_forwarded.load(Ordering::Relaxed),
+            bandwidth_consumed: self.inner.bandwidth_consumed.load(Ordering::Relaxed),
+            bandwidth_credentials_redeemed: self
+                .inner
+                .bandwidth_credentials_redeemed
+                .load(Ordering::Relaxed),
+            unauthenticated_connections: self
+                .inner
+                .unauthenticated_connections
+                .load(Ordering::Relaxed),
+            connections_rate_limited: self.inner.connections_rate_limited.load(Ordering::Relaxed),
+            connections_over_capacity: self.inner.connections_over_capacity.load(Ordering::Relaxed),
+            handshakes_rate_limited: self.inner.handshakes_rate_limited.load(Ordering::Relaxed),
+            registration_puzzles_issued: self
+                .inner
+                .registration_puzzles_issued
+                .load(Ordering::Relaxed),
+            registration_puzzles_solved: self
+                .inner
+                .registration_puzzles_solved
+                .load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub(crate) struct GatewayStatsSnapshot {
+    pub(crate) uptime_secs: u64,
+    pub(crate) mix_packets_received: u64,
+    pub(crate) mix_packets_pushed_to_clients: u64,
+    pub(crate) mix_packets_stored: u64,
+    pub(crate) client_packets_forwarded: u64,
+    pub(crate) bandwidth_consumed: u64,
+    pub(crate) bandwidth_credentials_redeemed: u64,
+    pub(crate) unauthenticated_connections: u64,
+    pub(crate) connections_rate_limited: u64,
+    pub(crate) connections_over_capacity: u64,
+    pub(crate) handshakes_rate_limited: u64,
+    pub(crate) registration_puzzles_issued: u64,
+    pub(crate) registration_puzzles_solved: u64,
+}
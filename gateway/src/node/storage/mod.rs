@@ -1,78 +1,38 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::node::storage::bandwidth::BandwidthManager;
 use crate::node::storage::error::StorageError;
-use crate::node::storage::inboxes::InboxManager;
 use crate::node::storage::models::{PersistedSharedKeys, StoredMessage};
-use crate::node::storage::shared_keys::SharedKeysManager;
+use async_trait::async_trait;
 use gateway_requests::registration::handshake::SharedKeys;
-use log::{debug, error};
 use nymsphinx::DestinationAddressBytes;
-use sqlx::ConnectOptions;
-use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) use models::InboxUsage;
+pub(crate) use postgres::PostgresStorage;
+pub(crate) use sqlite::SqliteStorage;
 
-mod bandwidth;
 pub(crate) mod error;
-mod inboxes;
 mod models;
-mod shared_keys;
-
-// note that clone here is fine as upon cloning the same underlying pool will be used
-#[derive(Clone)]
-pub(crate) struct PersistentStorage {
-    shared_key_manager: SharedKeysManager,
-    inbox_manager: InboxManager,
-    bandwidth_manager: BandwidthManager,
+mod postgres;
+pub(crate) mod purger;
+mod sqlite;
+
+/// Storage of the gateway, shared between all of its tasks.
+// note that clone here is fine as upon cloning the same underlying storage will be used
+pub(crate) type PersistentStorage = Arc<dyn Storage>;
+
+/// Per-client limits on the messages kept in the storage. `None` means unlimited.
+/// Once a limit is exceeded, the oldest messages of the client get removed.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct InboxLimits {
+    pub(crate) max_messages_per_client: Option<i64>,
+    pub(crate) max_bytes_per_client: Option<i64>,
 }
 
-impl PersistentStorage {
-    /// Initialises `PersistentStorage` using the provided path.
-    ///
-    /// # Arguments
-    ///
-    /// * `database_path`: path to the database.
-    /// * `message_retrieval_limit`: maximum number of stored client messages that can be retrieved at once.
-    pub(crate) async fn init<P: AsRef<Path>>(
-        database_path: P,
-        message_retrieval_limit: i64,
-    ) -> Result<Self, StorageError> {
-        debug!(
-            "Attempting to connect to database {:?}",
-            database_path.as_ref().as_os_str()
-        );
-
-        // TODO: we can inject here more stuff based on our gateway global config
-        // struct. Maybe different pool size or timeout intervals?
-        let mut opts = sqlx::sqlite::SqliteConnectOptions::new()
-            .filename(database_path)
-            .create_if_missing(true);
-
-        // TODO: do we want auto_vacuum ?
-
-        opts.disable_statement_logging();
-
-        let connection_pool = match sqlx::SqlitePool::connect_with(opts).await {
-            Ok(db) => db,
-            Err(err) => {
-                error!("Failed to connect to SQLx database: {}", err);
-                return Err(err.into());
-            }
-        };
-
-        if let Err(err) = sqlx::migrate!("./migrations").run(&connection_pool).await {
-            error!("Failed to perform migration on the SQLx database: {}", err);
-            return Err(err.into());
-        }
-
-        // the cloning here are cheap as connection pool is stored behind an Arc
-        Ok(PersistentStorage {
-            shared_key_manager: SharedKeysManager::new(connection_pool.clone()),
-            inbox_manager: InboxManager::new(connection_pool.clone(), message_retrieval_limit),
-            bandwidth_manager: BandwidthManager::new(connection_pool),
-        })
-    }
-
+#[async_trait]
+pub(crate) trait Storage: Send + Sync {
     /// Inserts provided derived shared keys into the database.
     /// If keys previously existed for the provided client, they are overwritten with the new data.
     ///
@@ -80,71 +40,82 @@ impl PersistentStorage {
     ///
     /// * `client_address`: address of the client
     /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
-    pub(crate) async fn insert_shared_keys(
+    async fn insert_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
         shared_keys: SharedKeys,
-    ) -> Result<(), StorageError> {
-        let persisted_shared_keys = PersistedSharedKeys {
-            client_address_bs58: client_address.as_base58_string(),
-            derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys.to_base58_string(),
-        };
-        self.shared_key_manager
-            .insert_shared_keys(persisted_shared_keys)
-            .await?;
-        Ok(())
-    }
+    ) -> Result<(), StorageError>;
+
+    /// Replaces the shared keys of an already registered client.
+    /// Returns whether the keys got replaced, i.e. `false` if the client has no keys stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
+    async fn replace_shared_keys(
+        &self,
+        client_address: DestinationAddressBytes,
+        shared_keys: SharedKeys,
+    ) -> Result<bool, StorageError>;
 
     /// Tries to retrieve shared keys stored for the particular client.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
-    pub(crate) async fn get_shared_keys(
+    async fn get_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
-    ) -> Result<Option<PersistedSharedKeys>, StorageError> {
-        let keys = self
-            .shared_key_manager
-            .get_shared_keys(&client_address.as_base58_string())
-            .await?;
-        Ok(keys)
-    }
+    ) -> Result<Option<PersistedSharedKeys>, StorageError>;
 
-    /// Removes from the database shared keys derived with the particular client.
+    /// Atomically removes all data stored for the particular client, i.e. its shared keys, messages
+    /// and available bandwidth.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
-    // currently there is no code flow that causes removal (not overwriting)
-    // of the stored keys. However, retain the function for consistency and completion sake
-    #[allow(dead_code)]
-    pub(crate) async fn remove_shared_keys(
+    async fn remove_client(
         &self,
         client_address: DestinationAddressBytes,
-    ) -> Result<(), StorageError> {
-        self.shared_key_manager
-            .remove_shared_keys(&client_address.as_base58_string())
-            .await?;
-        Ok(())
-    }
+    ) -> Result<(), StorageError>;
 
     /// Inserts new message to the storage for an offline client for future retrieval.
+    /// If the client exceeds its limit on the number of stored messages, its oldest messages are removed.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
     /// * `message`: raw message to store.
-    pub(crate) async fn store_message(
+    async fn store_message(
         &self,
         client_address: DestinationAddressBytes,
         message: Vec<u8>,
-    ) -> Result<(), StorageError> {
-        self.inbox_manager
-            .insert_message(&client_address.as_base58_string(), message)
-            .await?;
-        Ok(())
-    }
+    ) -> Result<(), StorageError>;
+
+    /// Removes all stored messages that are older than the specified age.
+    /// Returns the number of messages that got removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age`: maximum age of messages that are allowed to remain in the storage.
+    async fn remove_expired_messages(&self, max_age: Duration) -> Result<u64, StorageError>;
+
+    /// Removes the oldest messages of all clients whose stored messages exceed the limit
+    /// on their total size, if there is such a limit.
+    /// Returns the number of messages that got removed.
+    async fn remove_messages_over_size_limit(&self) -> Result<u64, StorageError>;
+
+    /// Retrieves and resets the number of messages of the particular client that got removed
+    /// (due to either their age or the client exceeding its limits) before it managed to retrieve them.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    async fn take_dropped_messages_count(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<u64, StorageError>;
 
     /// Retrieves messages stored for the particular client specified by the provided address.
     ///
@@ -155,61 +126,61 @@ impl PersistentStorage {
     ///
     /// returns the retrieved messages alongside optional id of the last message retrieved if
     /// there are more messages to retrieve.
-    pub(crate) async fn retrieve_messages(
+    async fn retrieve_messages(
         &self,
         client_address: DestinationAddressBytes,
         start_after: Option<i64>,
-    ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError> {
-        let messages = self
-            .inbox_manager
-            .get_messages(&client_address.as_base58_string(), start_after)
-            .await?;
-        Ok(messages)
-    }
+    ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError>;
 
     /// Removes messages with the specified ids
     ///
     /// # Arguments
     ///
     /// * `ids`: ids of the messages to remove
-    pub(crate) async fn remove_messages(&self, ids: Vec<i64>) -> Result<(), StorageError> {
-        for id in ids {
-            self.inbox_manager.remove_message(id).await?;
-        }
-        Ok(())
-    }
+    async fn remove_messages(&self, ids: Vec<i64>) -> Result<(), StorageError>;
+
+    /// Marks messages with the specified ids as delivered to their recipient, so that it wouldn't
+    /// be charged again if they had to be redelivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids`: ids of the delivered messages
+    async fn mark_messages_delivered(&self, ids: Vec<i64>) -> Result<(), StorageError>;
+
+    /// Removes messages of the particular client that it has acknowledged receiving.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    /// * `up_to_id`: id of the last message acknowledged by the client
+    async fn remove_acknowledged_messages(
+        &self,
+        client_address: DestinationAddressBytes,
+        up_to_id: i64,
+    ) -> Result<(), StorageError>;
+
+    /// Retrieves the number and total size of the messages stored for each client.
+    async fn inbox_usage(&self) -> Result<Vec<InboxUsage>, StorageError>;
 
     /// Creates a new bandwidth entry for the particular client.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
-    pub(crate) async fn create_bandwidth_entry(
+    async fn create_bandwidth_entry(
         &self,
         client_address: DestinationAddressBytes,
-    ) -> Result<(), StorageError> {
-        self.bandwidth_manager
-            .insert_new_client(&client_address.as_base58_string())
-            .await?;
-        Ok(())
-    }
+    ) -> Result<(), StorageError>;
 
     /// Tries to retrieve available bandwidth for the particular client.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
-    pub(crate) async fn get_available_bandwidth(
+    async fn get_available_bandwidth(
         &self,
         client_address: DestinationAddressBytes,
-    ) -> Result<Option<i64>, StorageError> {
-        let res = self
-            .bandwidth_manager
-            .get_available_bandwidth(&client_address.as_base58_string())
-            .await
-            .map(|bandwidth_option| bandwidth_option.map(|bandwidth| bandwidth.available))?;
-        Ok(res)
-    }
+    ) -> Result<Option<i64>, StorageError>;
 
     /// Increases available bandwidth of the particular client by the specified amount.
     ///
@@ -217,16 +188,11 @@ impl PersistentStorage {
     ///
     /// * `client_address`: address of the client
     /// * `amount`: amount of available bandwidth to be added to the client.
-    pub(crate) async fn increase_bandwidth(
+    async fn increase_bandwidth(
         &self,
         client_address: DestinationAddressBytes,
         amount: i64,
-    ) -> Result<(), StorageError> {
-        self.bandwidth_manager
-            .increase_available_bandwidth(&client_address.as_base58_string(), amount)
-            .await?;
-        Ok(())
-    }
+    ) -> Result<(), StorageError>;
 
     /// Decreases available bandwidth of the particular client by the specified amount.
     ///
@@ -234,14 +200,46 @@ impl PersistentStorage {
     ///
     /// * `client_address`: address of the client
     /// * `amount`: amount of available bandwidth to be removed from the client.
-    pub(crate) async fn consume_bandwidth(
+    async fn consume_bandwidth(
         &self,
         client_address: DestinationAddressBytes,
         amount: i64,
-    ) -> Result<(), StorageError> {
-        self.bandwidth_manager
-            .decrease_available_bandwidth(&client_address.as_base58_string(), amount)
-            .await?;
-        Ok(())
-    }
+    ) -> Result<(), StorageError>;
+
+    /// Marks the coconut credential with the provided blinded serial number as spent by the client.
+    /// Returns whether the credential has not been spent before.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    /// * `blinded_serial_number`: base58-encoded blinded serial number of the credential.
+    #[cfg(feature = "coconut")]
+    async fn mark_credential_spent(
+        &self,
+        client_address: DestinationAddressBytes,
+        blinded_serial_number: &str,
+    ) -> Result<bool, StorageError>;
+
+    /// Removes the coconut credential with the provided blinded serial number from the spent set,
+    /// for example if it could not have been redeemed after all.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number`: base58-encoded blinded serial number of the credential.
+    #[cfg(feature = "coconut")]
+    async fn unmark_credential_spent(
+        &self,
+        blinded_serial_number: &str,
+    ) -> Result<(), StorageError>;
+
+    /// Waits for all pending queries to finish and closes all connections to the database.
+    /// Any further queries are going to fail.
+    async fn close(&self);
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the system clock is set before the unix epoch")
+        .as_secs() as i64
 }
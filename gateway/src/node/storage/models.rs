@@ -1,18 +1,32 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+#[derive(sqlx::FromRow)]
 pub(crate) struct PersistedSharedKeys {
     pub(crate) client_address_bs58: String,
     pub(crate) derived_aes128_ctr_blake3_hmac_keys_bs58: String,
 }
 
+#[derive(sqlx::FromRow)]
 pub(crate) struct StoredMessage {
     pub(crate) id: i64,
     #[allow(dead_code)]
     pub(crate) client_address_bs58: String,
     pub(crate) content: Vec<u8>,
+    #[allow(dead_code)]
+    pub(crate) timestamp: i64,
+    pub(crate) delivered: bool,
+}
+
+/// Number and total size of the messages stored for a particular client.
+#[derive(sqlx::FromRow)]
+pub(crate) struct InboxUsage {
+    pub(crate) client_address_bs58: String,
+    pub(crate) messages: i64,
+    pub(crate) bytes: i64,
 }
 
+#[derive(sqlx::FromRow)]
 pub(crate) struct PersistedBandwidth {
     #[allow(dead_code)]
     pub(crate) client_address_bs58: String,
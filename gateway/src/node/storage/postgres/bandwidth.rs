@@ -0,0 +1,121 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::models::PersistedBandwidth;
+
+#[derive(Clone)]
+pub(crate) struct BandwidthManager {
+    connection_pool: sqlx::PgPool,
+}
+
+impl BandwidthManager {
+    /// Creates new instance of the `BandwidthManager` with the provided postgres connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool`: database connection pool to use.
+    pub(crate) fn new(connection_pool: sqlx::PgPool) -> Self {
+        BandwidthManager { connection_pool }
+    }
+
+    /// Creates a new bandwidth entry for the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    pub(crate) async fn insert_new_client(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO available_bandwidth(client_address_bs58, available) VALUES ($1, 0)",
+        )
+        .bind(client_address_bs58)
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Tries to retrieve available bandwidth for the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    pub(crate) async fn get_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<Option<PersistedBandwidth>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM available_bandwidth WHERE client_address_bs58 = $1")
+            .bind(client_address_bs58)
+            .fetch_optional(&self.connection_pool)
+            .await
+    }
+
+    /// Increases available bandwidth of the particular client by the specified amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    /// * `amount`: amount of available bandwidth to be added to the client.
+    pub(crate) async fn increase_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+                UPDATE available_bandwidth
+                SET available = available + $1
+                WHERE client_address_bs58 = $2
+            "#,
+        )
+        .bind(amount)
+        .bind(client_address_bs58)
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Decreases available bandwidth of the particular client by the specified amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    /// * `amount`: amount of available bandwidth to be removed from the client.
+    pub(crate) async fn decrease_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+                UPDATE available_bandwidth
+                SET available = available - $1
+                WHERE client_address_bs58 = $2
+            "#,
+        )
+        .bind(amount)
+        .bind(client_address_bs58)
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the bandwidth entry of the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx`: transaction within which the removal should happen.
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    pub(crate) async fn remove_client(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        client_address_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM available_bandwidth WHERE client_address_bs58 = $1")
+            .bind(client_address_bs58)
+            .execute(&mut *tx)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::models::{InboxUsage, StoredMessage};
+use crate::node::storage::InboxLimits;
+
+#[derive(Clone)]
+pub(crate) struct InboxManager {
+    connection_pool: sqlx::PgPool,
+    /// Maximum number of messages that can be obtained from the database per operation.
+    /// It is used to prevent out of memory errors in the case of client receiving a lot of data while
+    /// offline and then loading it all at once when he comes back online.
+    retrieval_limit: i64,
+
+    /// Limits on the messages stored for each client.
+    limits: InboxLimits,
+}
+
+impl InboxManager {
+    /// Creates new instance of the `InboxManager` with the provided postgres connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool`: database connection pool to use.
+    /// * `retrieval_limit`: maximum number of messages that can be obtained per operation.
+    /// * `limits`: limits on the messages stored for each client.
+    pub(crate) fn new(
+        connection_pool: sqlx::PgPool,
+        retrieval_limit: i64,
+        limits: InboxLimits,
+    ) -> Self {
+        InboxManager {
+            connection_pool,
+            retrieval_limit,
+            limits,
+        }
+    }
+
+    /// Inserts new message to the storage for an offline client for future retrieval.
+    /// If it causes the client to exceed the maximum number of stored messages, its oldest
+    /// messages are removed. The limit on their total size is enforced separately by
+    /// `remove_messages_over_size_limit`, as it's too expensive to check on every insert.
+    ///
+    /// Returns the number of messages that got removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `content`: raw content of the message to store.
+    /// * `timestamp`: unix timestamp of when the message was received.
+    pub(crate) async fn insert_message(
+        &self,
+        client_address_bs58: &str,
+        content: Vec<u8>,
+        timestamp: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.connection_pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO message_store(client_address_bs58, content, timestamp) VALUES ($1, $2, $3)",
+        )
+        .bind(client_address_bs58)
+        .bind(content)
+        .bind(timestamp)
+        .execute(&mut tx)
+        .await?;
+
+        let mut dropped = 0;
+        if let Some(max_messages) = self.limits.max_messages_per_client {
+            // keep only the `max_messages` newest messages
+            dropped += sqlx::query(
+                r#"
+                    DELETE FROM message_store WHERE id IN (
+                        SELECT id FROM message_store
+                        WHERE client_address_bs58 = $1
+                        ORDER BY id DESC
+                        OFFSET $2
+                    )
+                "#,
+            )
+            .bind(client_address_bs58)
+            .bind(max_messages)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        }
+
+        if dropped > 0 {
+            sqlx::query(
+                r#"
+                    INSERT INTO dropped_messages(client_address_bs58, count) VALUES ($1, $2)
+                    ON CONFLICT(client_address_bs58) DO UPDATE
+                    SET count = dropped_messages.count + excluded.count
+                "#,
+            )
+            .bind(client_address_bs58)
+            .bind(dropped as i64)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(dropped)
+    }
+
+    /// Retrieves messages stored for the particular client specified by the provided address.
+    ///
+    /// It also respects the specified retrieval limit. If there are more messages stored than allowed
+    /// by the limit, it returns id of the last message retrieved to indicate start of the next query.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `start_after`: optional starting id of the messages to grab
+    ///
+    /// returns the retrieved messages alongside optional id of the last message retrieved if
+    /// there are more messages to retrieve.
+    pub(crate) async fn get_messages(
+        &self,
+        client_address_bs58: &str,
+        start_after: Option<i64>,
+    ) -> Result<(Vec<StoredMessage>, Option<i64>), sqlx::Error> {
+        // get 1 additional message to check whether there will be more to grab
+        // next time
+        let limit = self.retrieval_limit + 1;
+        let mut res: Vec<StoredMessage> = sqlx::query_as(
+            r#"
+                SELECT * FROM message_store
+                WHERE client_address_bs58 = $1 AND id > $2
+                ORDER BY id ASC
+                LIMIT $3
+            "#,
+        )
+        .bind(client_address_bs58)
+        // ids start at 1
+        .bind(start_after.unwrap_or_default())
+        .bind(limit)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        if res.len() > self.retrieval_limit as usize {
+            res.truncate(self.retrieval_limit as usize);
+            // assuming retrieval_limit > 0, unwrap will not fail
+            let start_after = res.last().unwrap().id;
+            Ok((res, Some(start_after)))
+        } else {
+            Ok((res, None))
+        }
+    }
+
+    /// Removes message with the specified id
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: id of the message to remove
+    pub(crate) async fn remove_message(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM message_store WHERE id = $1")
+            .bind(id)
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Marks messages with the specified ids as delivered to their recipient.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids`: ids of the delivered messages
+    pub(crate) async fn mark_messages_delivered(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.connection_pool.begin().await?;
+        for id in ids {
+            sqlx::query("UPDATE message_store SET delivered = TRUE WHERE id = $1")
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Removes all messages of the particular client with ids up to (and including) the specified one.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `up_to_id`: id of the last message to remove
+    pub(crate) async fn remove_messages_up_to(
+        &self,
+        client_address_bs58: &str,
+        up_to_id: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM message_store WHERE client_address_bs58 = $1 AND id <= $2")
+            .bind(client_address_bs58)
+            .bind(up_to_id)
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Removes all messages of the particular client alongside the record of its dropped messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx`: transaction within which the removal should happen.
+    /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn remove_client_messages(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        client_address_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM message_store WHERE client_address_bs58 = $1")
+            .bind(client_address_bs58)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM dropped_messages WHERE client_address_bs58 = $1")
+            .bind(client_address_bs58)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Removes all messages received before the specified timestamp, regardless of their recipient.
+    ///
+    /// Returns the number of messages that got removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `cutoff`: unix timestamp before which all messages should be removed.
+    pub(crate) async fn remove_messages_older_than(&self, cutoff: i64) -> Result<u64, sqlx::Error> {
+        let mut tx = self.connection_pool.begin().await?;
+
+        // remember how many messages each client is going to lose
+        sqlx::query(
+            r#"
+                INSERT INTO dropped_messages(client_address_bs58, count)
+                    SELECT client_address_bs58, COUNT(*) FROM message_store
+                    WHERE timestamp < $1
+                    GROUP BY client_address_bs58
+                ON CONFLICT(client_address_bs58) DO UPDATE
+                SET count = dropped_messages.count + excluded.count
+            "#,
+        )
+        .bind(cutoff)
+        .execute(&mut tx)
+        .await?;
+
+        let removed = sqlx::query("DELETE FROM message_store WHERE timestamp < $1")
+            .bind(cutoff)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    /// Removes the oldest messages of all clients whose stored messages exceed the maximum
+    /// total size, if there is such a limit.
+    ///
+    /// Returns the number of messages that got removed.
+    pub(crate) async fn remove_messages_over_size_limit(&self) -> Result<u64, sqlx::Error> {
+        let max_bytes = match self.limits.max_bytes_per_client {
+            Some(max_bytes) => max_bytes,
+            None => return Ok(0),
+        };
+
+        let mut tx = self.connection_pool.begin().await?;
+
+        // remember how many messages each client is going to lose
+        sqlx::query(
+            r#"
+                INSERT INTO dropped_messages(client_address_bs58, count)
+                    SELECT client_address_bs58, COUNT(*) FROM (
+                        SELECT client_address_bs58, SUM(LENGTH(content)) OVER (
+                            PARTITION BY client_address_bs58 ORDER BY id DESC
+                        ) AS total_size
+                        FROM message_store
+                    ) AS sizes
+                    WHERE total_size > $1
+                    GROUP BY client_address_bs58
+                ON CONFLICT(client_address_bs58) DO UPDATE
+                SET count = dropped_messages.count + excluded.count
+            "#,
+        )
+        .bind(max_bytes)
+        .execute(&mut tx)
+        .await?;
+
+        // keep only the newest messages of each client whose total size fits within `max_bytes`
+        let removed = sqlx::query(
+            r#"
+                DELETE FROM message_store WHERE id IN (
+                    SELECT id FROM (
+                        SELECT id, SUM(LENGTH(content)) OVER (
+                            PARTITION BY client_address_bs58 ORDER BY id DESC
+                        ) AS total_size
+                        FROM message_store
+                    ) AS sizes
+                    WHERE total_size > $1
+                )
+            "#,
+        )
+        .bind(max_bytes)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    /// Retrieves and resets the number of messages of the particular client that got removed
+    /// before it managed to retrieve them.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn take_dropped_messages_count(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar(
+            "DELETE FROM dropped_messages WHERE client_address_bs58 = $1 RETURNING count",
+        )
+        .bind(client_address_bs58)
+        .fetch_optional(&self.connection_pool)
+        .await?
+        .unwrap_or_default();
+        Ok(count)
+    }
+
+    /// Retrieves the number and total size of the messages stored for each client,
+    /// starting with the clients using the most space.
+    pub(crate) async fn get_usage(&self) -> Result<Vec<InboxUsage>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+                SELECT client_address_bs58, COUNT(*) AS messages, SUM(LENGTH(content))::BIGINT AS bytes
+                FROM message_store
+                GROUP BY client_address_bs58
+                ORDER BY bytes DESC
+            "#,
+        )
+        .fetch_all(&self.connection_pool)
+        .await
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::models::PersistedSharedKeys;
+
+#[derive(Clone)]
+pub(crate) struct SharedKeysManager {
+    connection_pool: sqlx::PgPool,
+}
+
+impl SharedKeysManager {
+    /// Creates new instance of the `SharedKeysManager` with the provided postgres connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool`: database connection pool to use.
+    pub(crate) fn new(connection_pool: sqlx::PgPool) -> Self {
+        SharedKeysManager { connection_pool }
+    }
+
+    /// Inserts provided derived shared keys into the database.
+    /// If keys previously existed for the provided client, they are overwritten with the new data.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
+    pub(crate) async fn insert_shared_keys(
+        &self,
+        shared_keys: PersistedSharedKeys,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+                INSERT INTO shared_keys(client_address_bs58, derived_aes128_ctr_blake3_hmac_keys_bs58) VALUES ($1, $2)
+                ON CONFLICT(client_address_bs58) DO UPDATE
+                SET derived_aes128_ctr_blake3_hmac_keys_bs58 = excluded.derived_aes128_ctr_blake3_hmac_keys_bs58
+            "#,
+        )
+        .bind(shared_keys.client_address_bs58)
+        .bind(shared_keys.derived_aes128_ctr_blake3_hmac_keys_bs58)
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Replaces the shared keys of the client, but only if it still has keys stored,
+    /// i.e. if it hasn't been removed in the meantime.
+    ///
+    /// Returns whether the keys got replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
+    pub(crate) async fn replace_shared_keys(
+        &self,
+        shared_keys: PersistedSharedKeys,
+    ) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query(
+            "UPDATE shared_keys SET derived_aes128_ctr_blake3_hmac_keys_bs58 = $1 WHERE client_address_bs58 = $2",
+        )
+        .bind(shared_keys.derived_aes128_ctr_blake3_hmac_keys_bs58)
+        .bind(shared_keys.client_address_bs58)
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+        Ok(updated > 0)
+    }
+
+    /// Tries to retrieve shared keys stored for the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn get_shared_keys(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<Option<PersistedSharedKeys>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM shared_keys WHERE client_address_bs58 = $1")
+            .bind(client_address_bs58)
+            .fetch_optional(&self.connection_pool)
+            .await
+    }
+
+    /// Removes from the database shared keys derived with the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx`: transaction within which the removal should happen.
+    /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn remove_shared_keys(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        client_address_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM shared_keys WHERE client_address_bs58 = $1")
+            .bind(client_address_bs58)
+            .execute(&mut *tx)
+            .await?;
+        Ok(())
+    }
+}
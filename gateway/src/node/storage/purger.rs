@@ -0,0 +1,59 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::PersistentStorage;
+use log::*;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Periodically removes messages that have been stored for offline clients for longer than allowed
+/// as well as the oldest messages of clients exceeding the limit on their total size.
+pub(crate) struct MessagesPurger {
+    storage: PersistentStorage,
+    max_age: Option<Duration>,
+    purge_interval: Duration,
+}
+
+impl MessagesPurger {
+    pub(crate) fn new(
+        storage: PersistentStorage,
+        max_age: Option<Duration>,
+        purge_interval: Duration,
+    ) -> Self {
+        MessagesPurger {
+            storage,
+            max_age,
+            purge_interval,
+        }
+    }
+
+    async fn run(&self) {
+        let mut interval = tokio::time::interval(self.purge_interval);
+        loop {
+            interval.tick().await;
+            if let Some(max_age) = self.max_age {
+                match self.storage.remove_expired_messages(max_age).await {
+                    Ok(0) => trace!("There were no expired messages to remove"),
+                    Ok(removed) => info!("Removed {} expired stored messages", removed),
+                    Err(err) => error!("Failed to remove expired stored messages - {}", err),
+                }
+            }
+
+            match self.storage.remove_messages_over_size_limit().await {
+                Ok(0) => trace!("There were no oversized inboxes to trim"),
+                Ok(removed) => info!(
+                    "Removed {} stored messages exceeding the size limit",
+                    removed
+                ),
+                Err(err) => error!(
+                    "Failed to remove stored messages exceeding the size limit - {}",
+                    err
+                ),
+            }
+        }
+    }
+
+    pub(crate) fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move { self.run().await })
+    }
+}
@@ -103,4 +103,24 @@ impl BandwidthManager {
         .await?;
         Ok(())
     }
+
+    /// Removes the bandwidth entry of the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx`: transaction within which the removal should happen.
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    pub(crate) async fn remove_client(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        client_address_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM available_bandwidth WHERE client_address_bs58 = ?",
+            client_address_bs58
+        )
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,472 @@
+// Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::error::StorageError;
+use crate::node::storage::models::{InboxUsage, PersistedSharedKeys, StoredMessage};
+use crate::node::storage::sqlite::bandwidth::BandwidthManager;
+use crate::node::storage::sqlite::inboxes::InboxManager;
+use crate::node::storage::sqlite::shared_keys::SharedKeysManager;
+#[cfg(feature = "coconut")]
+use crate::node::storage::sqlite::spent_credentials::SpentCredentialsManager;
+use crate::node::storage::{unix_now, InboxLimits, Storage};
+use async_trait::async_trait;
+use gateway_requests::registration::handshake::SharedKeys;
+use log::{debug, error};
+use nymsphinx::DestinationAddressBytes;
+use sqlx::ConnectOptions;
+use std::path::Path;
+use std::time::Duration;
+
+mod bandwidth;
+mod inboxes;
+mod shared_keys;
+#[cfg(feature = "coconut")]
+mod spent_credentials;
+
+/// Storage backed by a local SQLite database.
+pub(crate) struct SqliteStorage {
+    connection_pool: sqlx::SqlitePool,
+    shared_key_manager: SharedKeysManager,
+    inbox_manager: InboxManager,
+    bandwidth_manager: BandwidthManager,
+    #[cfg(feature = "coconut")]
+    spent_credentials_manager: SpentCredentialsManager,
+}
+
+impl SqliteStorage {
+    /// Initialises `SqliteStorage` using the provided path.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_path`: path to the database.
+    /// * `message_retrieval_limit`: maximum number of stored client messages that can be retrieved at once.
+    /// * `inbox_limits`: limits on the messages stored for each client.
+    pub(crate) async fn init<P: AsRef<Path>>(
+        database_path: P,
+        message_retrieval_limit: i64,
+        inbox_limits: InboxLimits,
+    ) -> Result<Self, StorageError> {
+        debug!(
+            "Attempting to connect to database {:?}",
+            database_path.as_ref().as_os_str()
+        );
+
+        // TODO: we can inject here more stuff based on our gateway global config
+        // struct. Maybe different pool size or timeout intervals?
+        let mut opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(database_path)
+            .create_if_missing(true);
+
+        // TODO: do we want auto_vacuum ?
+
+        opts.disable_statement_logging();
+
+        let connection_pool = match sqlx::SqlitePool::connect_with(opts).await {
+            Ok(db) => db,
+            Err(err) => {
+                error!("Failed to connect to SQLx database: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        if let Err(err) = sqlx::migrate!("./migrations/sqlite")
+            .run(&connection_pool)
+            .await
+        {
+            error!("Failed to perform migration on the SQLx database: {}", err);
+            return Err(err.into());
+        }
+
+        // the cloning here are cheap as connection pool is stored behind an Arc
+        Ok(SqliteStorage {
+            connection_pool: connection_pool.clone(),
+            shared_key_manager: SharedKeysManager::new(connection_pool.clone()),
+            inbox_manager: InboxManager::new(
+                connection_pool.clone(),
+                message_retrieval_limit,
+                inbox_limits,
+            ),
+            bandwidth_manager: BandwidthManager::new(connection_pool.clone()),
+            #[cfg(feature = "coconut")]
+            spent_credentials_manager: SpentCredentialsManager::new(connection_pool),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn insert_shared_keys(
+        &self,
+        client_address: DestinationAddressBytes,
+        shared_keys: SharedKeys,
+    ) -> Result<(), StorageError> {
+        let persisted_shared_keys = PersistedSharedKeys {
+            client_address_bs58: client_address.as_base58_string(),
+            derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys.to_base58_string(),
+        };
+        self.shared_key_manager
+            .insert_shared_keys(persisted_shared_keys)
+            .await?;
+        Ok(())
+    }
+
+    async fn replace_shared_keys(
+        &self,
+        client_address: DestinationAddressBytes,
+        shared_keys: SharedKeys,
+    ) -> Result<bool, StorageError> {
+        let persisted_shared_keys = PersistedSharedKeys {
+            client_address_bs58: client_address.as_base58_string(),
+            derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys.to_base58_string(),
+        };
+        let replaced = self
+            .shared_key_manager
+            .replace_shared_keys(persisted_shared_keys)
+            .await?;
+        Ok(replaced)
+    }
+
+    async fn get_shared_keys(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<Option<PersistedSharedKeys>, StorageError> {
+        let keys = self
+            .shared_key_manager
+            .get_shared_keys(&client_address.as_base58_string())
+            .await?;
+        Ok(keys)
+    }
+
+    async fn remove_client(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<(), StorageError> {
+        let client_address_bs58 = client_address.as_base58_string();
+
+        // remove everything at once so that the client would either be completely gone
+        // or could still authenticate and try again
+        let mut tx = self.connection_pool.begin().await?;
+        self.inbox_manager
+            .remove_client_messages(&mut tx, &client_address_bs58)
+            .await?;
+        self.bandwidth_manager
+            .remove_client(&mut tx, &client_address_bs58)
+            .await?;
+        self.shared_key_manager
+            .remove_shared_keys(&mut tx, &client_address_bs58)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn store_message(
+        &self,
+        client_address: DestinationAddressBytes,
+        message: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        let dropped = self
+            .inbox_manager
+            .insert_message(&client_address.as_base58_string(), message, unix_now())
+            .await?;
+        if dropped > 0 {
+            debug!(
+                "{} exceeded its message storage limits - {} oldest messages got removed",
+                client_address, dropped
+            );
+        }
+        Ok(())
+    }
+
+    async fn remove_expired_messages(&self, max_age: Duration) -> Result<u64, StorageError> {
+        let cutoff = unix_now() - max_age.as_secs() as i64;
+        let removed = self
+            .inbox_manager
+            .remove_messages_older_than(cutoff)
+            .await?;
+        Ok(removed)
+    }
+
+    async fn remove_messages_over_size_limit(&self) -> Result<u64, StorageError> {
+        let removed = self.inbox_manager.remove_messages_over_size_limit().await?;
+        Ok(removed)
+    }
+
+    async fn take_dropped_messages_count(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<u64, StorageError> {
+        let count = self
+            .inbox_manager
+            .take_dropped_messages_count(&client_address.as_base58_string())
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn retrieve_messages(
+        &self,
+        client_address: DestinationAddressBytes,
+        start_after: Option<i64>,
+    ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError> {
+        let messages = self
+            .inbox_manager
+            .get_messages(&client_address.as_base58_string(), start_after)
+            .await?;
+        Ok(messages)
+    }
+
+    async fn remove_messages(&self, ids: Vec<i64>) -> Result<(), StorageError> {
+        for id in ids {
+            self.inbox_manager.remove_message(id).await?;
+        }
+        Ok(())
+    }
+
+    async fn mark_messages_delivered(&self, ids: Vec<i64>) -> Result<(), StorageError> {
+        self.inbox_manager.mark_messages_delivered(&ids).await?;
+        Ok(())
+    }
+
+    async fn remove_acknowledged_messages(
+        &self,
+        client_address: DestinationAddressBytes,
+        up_to_id: i64,
+    ) -> Result<(), StorageError> {
+        self.inbox_manager
+            .remove_messages_up_to(&client_address.as_base58_string(), up_to_id)
+            .await?;
+        Ok(())
+    }
+
+    async fn inbox_usage(&self) -> Result<Vec<InboxUsage>, StorageError> {
+        let usage = self.inbox_manager.get_usage().await?;
+        Ok(usage)
+    }
+
+    async fn create_bandwidth_entry(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<(), StorageError> {
+        self.bandwidth_manager
+            .insert_new_client(&client_address.as_base58_string())
+            .await?;
+        Ok(())
+    }
+
+    async fn get_available_bandwidth(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<Option<i64>, StorageError> {
+        let res = self
+            .bandwidth_manager
+            .get_available_bandwidth(&client_address.as_base58_string())
+            .await
+            .map(|bandwidth_option| bandwidth_option.map(|bandwidth| bandwidth.available))?;
+        Ok(res)
+    }
+
+    async fn increase_bandwidth(
+        &self,
+        client_address: DestinationAddressBytes,
+        amount: i64,
+    ) -> Result<(), StorageError> {
+        self.bandwidth_manager
+            .increase_available_bandwidth(&client_address.as_base58_string(), amount)
+            .await?;
+        Ok(())
+    }
+
+    async fn consume_bandwidth(
+        &self,
+        client_address: DestinationAddressBytes,
+        amount: i64,
+    ) -> Result<(), StorageError> {
+        self.bandwidth_manager
+            .decrease_available_bandwidth(&client_address.as_base58_string(), amount)
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "coconut")]
+    async fn mark_credential_spent(
+        &self,
+        client_address: DestinationAddressBytes,
+        blinded_serial_number: &str,
+    ) -> Result<bool, StorageError> {
+        let newly_spent = self
+            .spent_credentials_manager
+            .insert_spent_credential(blinded_serial_number, &client_address.as_base58_string())
+            .await?;
+        Ok(newly_spent)
+    }
+
+    #[cfg(feature = "coconut")]
+    async fn unmark_credential_spent(
+        &self,
+        blinded_serial_number: &str,
+    ) -> Result<(), StorageError> {
+        self.spent_credentials_manager
+            .remove_spent_credential(blinded_serial_number)
+            .await?;
+        Ok(())
+    }
+
+    async fn close(&self) {
+        self.connection_pool.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+    use std::path::PathBuf;
+
+    struct TestStorage {
+        storage: SqliteStorage,
+        path: PathBuf,
+    }
+
+    impl Drop for TestStorage {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    async fn test_storage() -> TestStorage {
+        let path = std::env::temp_dir().join(format!(
+            "gateway-storage-test-{}.sqlite",
+            thread_rng().gen::<u64>()
+        ));
+        let limits = InboxLimits {
+            max_messages_per_client: Some(2),
+            max_bytes_per_client: None,
+        };
+        let storage = SqliteStorage::init(&path, 100, limits).await.unwrap();
+        TestStorage { storage, path }
+    }
+
+    fn client_address(byte: u8) -> DestinationAddressBytes {
+        DestinationAddressBytes::from_bytes([byte; 32])
+    }
+
+    fn shared_keys(byte: u8) -> SharedKeys {
+        // aes128 encryption key followed by the mac key
+        SharedKeys::try_from_bytes(&[byte; 32]).unwrap()
+    }
+
+    async fn register_client(storage: &SqliteStorage, client: DestinationAddressBytes) {
+        storage
+            .insert_shared_keys(client, shared_keys(1))
+            .await
+            .unwrap();
+        storage.create_bandwidth_entry(client).await.unwrap();
+        storage.increase_bandwidth(client, 1000).await.unwrap();
+        // the third message exceeds the limit and causes the first one to get dropped
+        for message in [vec![1], vec![2], vec![3]] {
+            storage.store_message(client, message).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn removing_client_removes_all_of_its_data() {
+        let test_storage = test_storage().await;
+        let storage = &test_storage.storage;
+        let client = client_address(1);
+        let other_client = client_address(2);
+        register_client(storage, client).await;
+        register_client(storage, other_client).await;
+
+        storage.remove_client(client).await.unwrap();
+
+        assert!(storage.get_shared_keys(client).await.unwrap().is_none());
+        assert!(storage
+            .get_available_bandwidth(client)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(storage
+            .retrieve_messages(client, None)
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+        assert_eq!(
+            storage.take_dropped_messages_count(client).await.unwrap(),
+            0
+        );
+
+        // and nothing of the other client is touched
+        assert!(storage
+            .get_shared_keys(other_client)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            storage.get_available_bandwidth(other_client).await.unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            storage
+                .retrieve_messages(other_client, None)
+                .await
+                .unwrap()
+                .0
+                .len(),
+            2
+        );
+        assert_eq!(
+            storage
+                .take_dropped_messages_count(other_client)
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn replacing_shared_keys_keeps_the_rest_of_client_data() {
+        let test_storage = test_storage().await;
+        let storage = &test_storage.storage;
+        let client = client_address(1);
+        register_client(storage, client).await;
+
+        assert!(storage
+            .replace_shared_keys(client, shared_keys(2))
+            .await
+            .unwrap());
+
+        let stored_keys = storage.get_shared_keys(client).await.unwrap().unwrap();
+        assert_eq!(
+            stored_keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
+            shared_keys(2).to_base58_string()
+        );
+        assert_eq!(
+            storage.get_available_bandwidth(client).await.unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            storage
+                .retrieve_messages(client, None)
+                .await
+                .unwrap()
+                .0
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn shared_keys_of_removed_client_are_not_replaced() {
+        let test_storage = test_storage().await;
+        let storage = &test_storage.storage;
+        let client = client_address(1);
+        register_client(storage, client).await;
+        storage.remove_client(client).await.unwrap();
+
+        assert!(!storage
+            .replace_shared_keys(client, shared_keys(2))
+            .await
+            .unwrap());
+        assert!(storage.get_shared_keys(client).await.unwrap().is_none());
+    }
+}
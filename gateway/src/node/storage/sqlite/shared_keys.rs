@@ -35,6 +35,29 @@ impl SharedKeysManager {
         Ok(())
     }
 
+    /// Replaces the shared keys of the client, but only if it still has keys stored,
+    /// i.e. if it hasn't been removed in the meantime.
+    ///
+    /// Returns whether the keys got replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
+    pub(crate) async fn replace_shared_keys(
+        &self,
+        shared_keys: PersistedSharedKeys,
+    ) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query!(
+            "UPDATE shared_keys SET derived_aes128_ctr_blake3_hmac_keys_bs58 = ? WHERE client_address_bs58 = ?",
+            shared_keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
+            shared_keys.client_address_bs58,
+        )
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+        Ok(updated > 0)
+    }
+
     /// Tries to retrieve shared keys stored for the particular client.
     ///
     /// # Arguments
@@ -57,19 +80,18 @@ impl SharedKeysManager {
     ///
     /// # Arguments
     ///
+    /// * `tx`: transaction within which the removal should happen.
     /// * `client_address_bs58`: base58-encoded address of the client
-    // currently there is no code flow that causes removal (not overwriting)
-    // of the stored keys. However, retain the function for consistency and completion sake
-    #[allow(dead_code)]
     pub(crate) async fn remove_shared_keys(
         &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
         client_address_bs58: &str,
     ) -> Result<(), sqlx::Error> {
         sqlx::query!(
             "DELETE FROM shared_keys WHERE client_address_bs58 = ?",
             client_address_bs58
         )
-        .execute(&self.connection_pool)
+        .execute(&mut *tx)
         .await?;
         Ok(())
     }
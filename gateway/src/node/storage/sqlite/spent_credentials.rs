@@ -0,0 +1,62 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+#[derive(Clone)]
+pub(crate) struct SpentCredentialsManager {
+    connection_pool: sqlx::SqlitePool,
+}
+
+impl SpentCredentialsManager {
+    /// Creates new instance of the `SpentCredentialsManager` with the provided sqlite connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool`: database connection pool to use.
+    pub(crate) fn new(connection_pool: sqlx::SqlitePool) -> Self {
+        SpentCredentialsManager { connection_pool }
+    }
+
+    /// Marks the credential with the provided blinded serial number as spent.
+    /// Returns whether the credential has not been spent before.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number_bs58`: base58-encoded blinded serial number of the credential.
+    /// * `client_address_bs58`: base58-encoded address of the client that has spent the credential.
+    pub(crate) async fn insert_spent_credential(
+        &self,
+        blinded_serial_number_bs58: &str,
+        client_address_bs58: &str,
+    ) -> Result<bool, sqlx::Error> {
+        // the check and the insertion have to happen atomically so that the same credential
+        // could not be concurrently spent on multiple connections
+        let rows_affected = sqlx::query!(
+            "INSERT OR IGNORE INTO spent_credentials(blinded_serial_number_bs58, client_address_bs58) VALUES (?, ?)",
+            blinded_serial_number_bs58,
+            client_address_bs58
+        )
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Removes the credential with the provided blinded serial number from the spent set.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number_bs58`: base58-encoded blinded serial number of the credential.
+    pub(crate) async fn remove_spent_credential(
+        &self,
+        blinded_serial_number_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM spent_credentials WHERE blinded_serial_number_bs58 = ?",
+            blinded_serial_number_bs58
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+}
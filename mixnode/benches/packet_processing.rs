@@ -0,0 +1,168 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+// Measures the throughput of the mixnode's packet processing pipeline: the processing pool
+// unwrapping the received packets, compared against unwrapping them on a single thread,
+// and the delay-forwarders, compared against a single, unsharded, one.
+// Run with `cargo bench --features benchmarking`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use crypto::asymmetric::encryption;
+use mixnode_common::packet_processor::processor::SphinxPacketProcessor;
+use nym_mixnode::benchmarking::{DelayForwarding, Processing};
+use nymsphinx::addressing::nodes::NymNodeRoutingAddress;
+use nymsphinx::forwarding::packet::MixPacket;
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use nymsphinx_params::packet_sizes::PacketSize;
+use nymsphinx_params::PacketMode;
+use nymsphinx_types::builder::SphinxPacketBuilder;
+use nymsphinx_types::crypto::{keygen, PublicKey};
+use nymsphinx_types::{
+    Delay, Destination, DestinationAddressBytes, Node, SphinxPacket, DESTINATION_ADDRESS_LENGTH,
+    IDENTIFIER_LENGTH,
+};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::time::Instant;
+
+const BATCH_SIZE: usize = 512;
+const WORKER_QUEUE_SIZE: usize = 1024;
+// the packets are spread between that many next hops, just like they would be in the network
+const NEXT_HOPS: usize = 64;
+
+// sphinx keys are not `Clone`, so pass around the raw bytes of the key instead
+fn make_sphinx_packet(first_hop_key: [u8; 32], next_hop: usize) -> SphinxPacket {
+    let route: Vec<_> = std::iter::once(PublicKey::from(first_hop_key))
+        .chain((0..2).map(|_| keygen().1))
+        .enumerate()
+        .map(|(i, key)| {
+            let address: SocketAddr = format!("1.2.3.{}:{}", i, 1789 + next_hop).parse().unwrap();
+            Node::new(
+                NymNodeRoutingAddress::from(address).try_into().unwrap(),
+                key,
+            )
+        })
+        .collect();
+    let destination = Destination::new(
+        DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+        [4u8; IDENTIFIER_LENGTH],
+    );
+    let delays: Vec<_> = route.iter().map(|_| Delay::new_from_nanos(42)).collect();
+
+    SphinxPacketBuilder::new()
+        .with_payload_size(PacketSize::default().payload_size())
+        .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
+        .unwrap()
+}
+
+fn make_received_batch(first_hop_key: [u8; 32]) -> Vec<FramedSphinxPacket> {
+    (0..BATCH_SIZE)
+        .map(|i| {
+            FramedSphinxPacket::new(
+                make_sphinx_packet(first_hop_key, i % NEXT_HOPS),
+                PacketMode::default(),
+            )
+        })
+        .collect()
+}
+
+fn make_unwrapped_batch() -> Vec<MixPacket> {
+    let first_hop_key = *keygen().1.as_bytes();
+    (0..BATCH_SIZE)
+        .map(|i| {
+            let next_hop: SocketAddr = format!("1.2.3.4:{}", 1789 + i % NEXT_HOPS).parse().unwrap();
+            MixPacket::new(
+                NymNodeRoutingAddress::from(next_hop),
+                make_sphinx_packet(first_hop_key, i % NEXT_HOPS),
+                PacketMode::default(),
+            )
+        })
+        .collect()
+}
+
+async fn wait_for_forwarded(forwarded: impl Fn() -> usize, target: usize) {
+    while forwarded() < target {
+        tokio::task::yield_now().await
+    }
+}
+
+fn bench_processing_pool(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let keypair = encryption::KeyPair::new(&mut rand::rngs::OsRng);
+    let public_key = *PublicKey::from(keypair.public_key()).as_bytes();
+    let workers = num_cpus::get();
+
+    let mut group = c.benchmark_group("sphinx_unwrapping");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.sample_size(20);
+
+    let processor = SphinxPacketProcessor::new(keypair.private_key().into());
+    group.bench_function("single_thread", |b| {
+        b.iter_batched(
+            || make_received_batch(public_key),
+            |packets| {
+                for packet in packets {
+                    processor.process_received(packet).unwrap();
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    let processing = runtime.block_on(async {
+        Processing::start(keypair.private_key(), workers, WORKER_QUEUE_SIZE, workers)
+    });
+    group.bench_function(format!("processing_pool_{}_workers", workers), |b| {
+        b.iter_batched(
+            || make_received_batch(public_key),
+            |packets| {
+                runtime.block_on(async {
+                    let target = processing.forwarded() + packets.len();
+                    for packet in packets {
+                        processing.process(packet).await
+                    }
+                    wait_for_forwarded(|| processing.forwarded(), target).await
+                })
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_delay_forwarding(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let shards = num_cpus::get();
+
+    let mut group = c.benchmark_group("delay_forwarding");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.sample_size(20);
+
+    for shards in [1, shards] {
+        let mut delay_forwarding = runtime.block_on(async { DelayForwarding::start(shards) });
+        group.bench_function(format!("{}_shards", shards), |b| {
+            b.iter_batched(
+                make_unwrapped_batch,
+                |packets| {
+                    runtime.block_on(async {
+                        let target = delay_forwarding.forwarded() + packets.len();
+                        // make the packets go through the delay queues
+                        let forward_instant = Instant::now() + Duration::from_micros(100);
+                        for packet in packets {
+                            delay_forwarding.send(packet, Some(forward_instant))
+                        }
+                        wait_for_forwarded(|| delay_forwarding.forwarded(), target).await
+                    })
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_processing_pool, bench_delay_forwarding);
+criterion_main!(benches);
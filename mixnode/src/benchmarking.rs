@@ -0,0 +1,151 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Entry points into the packet processing pipeline of the mixnode used by its benchmarks.
+//! The packets leaving the pipeline are not sent anywhere, they're only counted.
+
+use crate::node::listener::connection_handler::packet_processing::PacketProcessor;
+use crate::node::listener::connection_handler::processing_pool::{
+    ProcessingPool, ReceivedPacketHandler,
+};
+use crate::node::loop_cover::LoopTracker;
+use crate::node::metrics::NodeMetrics;
+use crate::node::node_statistics::UpdateSender;
+use crate::node::packet_delayforwarder::{
+    DelayForwarder, DelayQueueLimits, PacketDelayForwardSender,
+};
+use crypto::asymmetric::encryption;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use nymsphinx::addressing::nodes::NymNodeRoutingAddress;
+use nymsphinx::forwarding::packet::MixPacket;
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use nymsphinx::params::PacketMode;
+use nymsphinx::{DestinationAddressBytes, SphinxPacket};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use task::ShutdownNotifier;
+use tokio::time::Instant;
+
+// large enough for none of the benchmarked packets to ever get dropped
+const DELAY_FORWARDER_QUEUE_SIZE: usize = 8192;
+
+struct CountingClient {
+    forwarded: Arc<AtomicUsize>,
+}
+
+impl mixnet_client::SendWithoutResponse for CountingClient {
+    fn send_without_response(
+        &mut self,
+        _address: NymNodeRoutingAddress,
+        _packet: SphinxPacket,
+        _packet_mode: PacketMode,
+    ) -> io::Result<()> {
+        self.forwarded.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// The sharded delay-forwarders, as started by the mixnode, with their packet sender.
+/// Must be started from within a tokio runtime.
+pub struct DelayForwarding {
+    sender: PacketDelayForwardSender,
+    forwarded: Arc<AtomicUsize>,
+    node_stats_update_sender: UpdateSender,
+    // the forwarders stop once the notifier is gone
+    _shutdown: ShutdownNotifier,
+}
+
+impl DelayForwarding {
+    pub fn start(shards: usize) -> Self {
+        let (stats_sender, mut stats_receiver) = mpsc::unbounded();
+        tokio::spawn(async move { while stats_receiver.next().await.is_some() {} });
+        let node_stats_update_sender = UpdateSender::new(stats_sender);
+
+        let forwarded = Arc::new(AtomicUsize::new(0));
+        let shutdown = ShutdownNotifier::new();
+        let shard_senders = (0..shards)
+            .map(|_| {
+                let (mut delay_forwarder, shard_sender) = DelayForwarder::new(
+                    CountingClient {
+                        forwarded: Arc::clone(&forwarded),
+                    },
+                    node_stats_update_sender.clone(),
+                    NodeMetrics::new(),
+                    DelayQueueLimits::default(),
+                    DELAY_FORWARDER_QUEUE_SIZE,
+                );
+                let shutdown_listener = shutdown.subscribe();
+                tokio::spawn(async move { delay_forwarder.run(shutdown_listener).await });
+                shard_sender
+            })
+            .collect();
+
+        DelayForwarding {
+            sender: PacketDelayForwardSender::new(shard_senders, node_stats_update_sender.clone()),
+            forwarded,
+            node_stats_update_sender,
+            _shutdown: shutdown,
+        }
+    }
+
+    /// Hands the packet over to the delay-forwarder responsible for its next hop.
+    pub fn send(&mut self, packet: MixPacket, forward_instant: Option<Instant>) {
+        self.sender
+            .forward((packet, forward_instant))
+            .expect("the delay-forwarder has died!")
+    }
+
+    /// Total number of packets that have left the delay-forwarders.
+    pub fn forwarded(&self) -> usize {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+}
+
+/// The processing pool, as started by the mixnode, passing the unwrapped packets onto
+/// the delay-forwarders. Must be started from within a tokio runtime.
+pub struct Processing {
+    pool: ProcessingPool,
+    delay_forwarding: DelayForwarding,
+}
+
+impl Processing {
+    pub fn start(
+        encryption_key: &encryption::PrivateKey,
+        workers: usize,
+        worker_queue_size: usize,
+        delay_forwarder_shards: usize,
+    ) -> Self {
+        let delay_forwarding = DelayForwarding::start(delay_forwarder_shards);
+        let node_metrics = NodeMetrics::new();
+        let packet_handler = ReceivedPacketHandler::new(
+            PacketProcessor::new(
+                encryption_key,
+                delay_forwarding.node_stats_update_sender.clone(),
+            ),
+            delay_forwarding.sender.clone(),
+            node_metrics.clone(),
+            LoopTracker::new(node_metrics),
+            DestinationAddressBytes::from_bytes([0; 32]),
+        );
+
+        Processing {
+            pool: ProcessingPool::start(workers, worker_queue_size, packet_handler),
+            delay_forwarding,
+        }
+    }
+
+    /// Hands the received packet over to the processing pool.
+    pub async fn process(&self, packet: FramedSphinxPacket) {
+        self.pool
+            .process(packet)
+            .await
+            .expect("the processing pool has died!")
+    }
+
+    /// Total number of packets that have left the delay-forwarders.
+    pub fn forwarded(&self) -> usize {
+        self.delay_forwarding.forwarded()
+    }
+}
@@ -12,7 +12,9 @@ use url::Url;
 mod describe;
 mod init;
 mod node_details;
+mod rotate_identity;
 mod run;
+mod self_test;
 mod sign;
 mod upgrade;
 
@@ -24,9 +26,15 @@ pub(crate) enum Commands {
     /// Initialise the mixnode
     Init(init::Init),
 
+    /// Replace the identity key of the mixnode, keeping the old one until the change is submitted on chain
+    RotateIdentity(rotate_identity::RotateIdentity),
+
     /// Starts the mixnode
     Run(run::Run),
 
+    /// Check whether the running mixnode is reachable by the rest of the network
+    SelfTest(self_test::SelfTest),
+
     /// Sign text to prove ownership of this mixnode
     Sign(sign::Sign),
 
@@ -49,11 +57,13 @@ struct OverrideConfig {
     validators: Option<String>,
 }
 
-pub(crate) async fn execute(args: Cli) {
+pub async fn execute(args: Cli) {
     match &args.command {
         Commands::Describe(m) => describe::execute(m),
         Commands::Init(m) => init::execute(m).await,
+        Commands::RotateIdentity(m) => rotate_identity::execute(m),
         Commands::Run(m) => run::execute(m).await,
+        Commands::SelfTest(m) => self_test::execute(m).await,
         Commands::Sign(m) => sign::execute(m),
         Commands::Upgrade(m) => upgrade::execute(m),
         Commands::NodeDetails(m) => node_details::execute(m),
@@ -0,0 +1,129 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::commands::{validate_bech32_address_or_exit, version_check};
+use crate::config::{persistence::pathfinder::MixNodePathfinder, Config};
+use crate::node::MixNode;
+use clap::Args;
+use config::NymConfig;
+use crypto::asymmetric::identity;
+use log::error;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub(crate) struct RotateIdentity {
+    /// The id of the mixnode whose identity key you want to replace
+    #[clap(long)]
+    id: String,
+
+    /// Address of the wallet that owns the mixnode bond
+    #[clap(long)]
+    wallet_address: String,
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(".old");
+    backup.into()
+}
+
+// keeps the current keys around until the rotation is confirmed on chain. If the backup already
+// exists, a previous rotation might have not been submitted yet, so it must not be overwritten.
+fn backup_identity_keys(pathfinder: &MixNodePathfinder) -> io::Result<()> {
+    let key_paths = [
+        pathfinder.private_identity_key(),
+        pathfinder.public_identity_key(),
+    ];
+
+    for path in key_paths {
+        let backup = backup_path(path);
+        if backup.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{:?} already exists. If the previous rotation has already been submitted on chain, remove it and try again",
+                    backup
+                ),
+            ));
+        }
+    }
+
+    for path in key_paths {
+        fs::copy(path, backup_path(path))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn execute(args: &RotateIdentity) {
+    let config = match Config::load_from_file(Some(&args.id)) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!(
+                "Failed to load config for {}. Are you sure you have run `init` before? (Error was: {})",
+                args.id,
+                err,
+            );
+            return;
+        }
+    };
+
+    if !version_check(&config) {
+        error!("Failed the local version check");
+        return;
+    }
+
+    let wallet_address = args.wallet_address.trim();
+    validate_bech32_address_or_exit(wallet_address);
+
+    let pathfinder = MixNodePathfinder::new_from_config(&config);
+    let old_identity_keypair = MixNode::load_identity_keys(&pathfinder);
+
+    if let Err(err) = backup_identity_keys(&pathfinder) {
+        error!("Failed to back up the current identity keys - {}", err);
+        return;
+    }
+
+    let mut rng = rand::rngs::OsRng;
+    let new_identity_keypair = identity::KeyPair::new(&mut rng);
+    if let Err(err) = pemstore::store_keypair(
+        &new_identity_keypair,
+        &pemstore::KeyPairPath::new(
+            pathfinder.private_identity_key().to_owned(),
+            pathfinder.public_identity_key().to_owned(),
+        ),
+    ) {
+        error!(
+            "Failed to save the new identity keys - restore them from the '.old' backups before trying again (Error was: {})",
+            err
+        );
+        return;
+    }
+
+    // the old key vouches for the new one, while the new one proves it belongs to the bond owner
+    let identity_signature = old_identity_keypair
+        .private_key()
+        .sign(&new_identity_keypair.public_key().to_bytes())
+        .to_base58_string();
+    let owner_signature = new_identity_keypair.private_key().sign_text(wallet_address);
+
+    println!(
+        "Replaced the identity key {}. The previous keys were backed up alongside the new ones with the '.old' suffix.",
+        old_identity_keypair.public_key().to_base58_string(),
+    );
+    println!(
+        "New identity key: {}",
+        new_identity_keypair.public_key().to_base58_string()
+    );
+    println!("Owner signature: {}", owner_signature);
+    println!("Identity signature: {}", identity_signature);
+    println!(
+        "\nSubmit the above in the 'update_mixnode_identity' transaction from {} and restart the mixnode right after it gets included in a block. \
+        Until then, keep the backed up keys - if the transaction fails, restore them to go back to the previous identity. \
+        If the node has many delegations, keep submitting 'migrate_next_mixnode_delegations' for the new identity until the contract \
+        reports no further delegations to migrate - the node won't get rewarded until then.",
+        wallet_address
+    );
+}
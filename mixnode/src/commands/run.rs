@@ -90,6 +90,7 @@ pub(crate) async fn execute(args: &Run) {
     };
 
     config = override_config(config, OverrideConfig::from(args.clone()));
+    crate::set_log_level(config.get_log_level());
 
     if !version_check(&config) {
         error!("failed the local version check");
@@ -0,0 +1,106 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::commands::{override_config, OverrideConfig};
+use crate::config::Config;
+use crate::node::self_test::{CheckOutcome, TopologySource};
+use crate::node::MixNode;
+use clap::Args;
+use colored::Colorize;
+use config::NymConfig;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+#[derive(Args, Clone)]
+pub(crate) struct SelfTest {
+    /// Id of the running nym-mixnode we want to test
+    #[clap(long)]
+    id: String,
+
+    /// The host that will be tested instead of the one from the config
+    #[clap(long)]
+    announce_host: Option<String>,
+
+    /// Comma separated list of rest endpoints of the validators
+    #[clap(long)]
+    validators: Option<String>,
+
+    /// Path to a JSON file with `mixnodes` and `gateways` bonds, in the format returned by the
+    /// validator API, used for routing the test packets instead of the current active set
+    #[clap(long)]
+    topology: Option<PathBuf>,
+
+    /// The port on which the temporary client will be receiving the test packets.
+    /// It has to be reachable by other mixnodes. If not provided, a random port is used
+    #[clap(long, default_value = "0")]
+    client_port: u16,
+
+    /// Maximum number of seconds to wait for any single check to complete
+    #[clap(long, default_value = "30")]
+    timeout: u64,
+}
+
+impl From<SelfTest> for OverrideConfig {
+    fn from(self_test_config: SelfTest) -> Self {
+        OverrideConfig {
+            id: self_test_config.id,
+            host: None,
+            wallet_address: None,
+            mix_port: None,
+            verloc_port: None,
+            http_api_port: None,
+            announce_host: self_test_config.announce_host,
+            validators: self_test_config.validators,
+        }
+    }
+}
+
+pub(crate) async fn execute(args: &SelfTest) {
+    let mut config = match Config::load_from_file(Some(&args.id)) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!(
+                "Failed to load config for {}. Are you sure you have run `init` before? (Error was: {})",
+                args.id,
+                err
+            );
+            return;
+        }
+    };
+
+    config = override_config(config, OverrideConfig::from(args.clone()));
+
+    let topology_source = match &args.topology {
+        Some(path) => TopologySource::File(path.clone()),
+        None => TopologySource::ValidatorApi,
+    };
+
+    println!(
+        "Testing mixnode {}. Make sure it's running before continuing...\n",
+        args.id
+    );
+    let report = MixNode::new(config)
+        .self_test(
+            topology_source,
+            args.client_port,
+            Duration::from_secs(args.timeout),
+        )
+        .await;
+
+    for result in report.results() {
+        let (status, details) = match &result.outcome {
+            CheckOutcome::Pass(details) => ("PASS".green(), details),
+            CheckOutcome::Fail(details) => ("FAIL".red(), details),
+            CheckOutcome::Skipped(details) => ("SKIP".yellow(), details),
+        };
+        println!("[{}] {:<22}{}", status, result.name, details);
+    }
+
+    if report.passed() {
+        println!("\n{}", "All checks have passed".green());
+    } else {
+        println!("\n{}", "Some of the checks have failed".red());
+        process::exit(1)
+    }
+}
@@ -4,6 +4,7 @@
 use crate::config::template::config_template;
 use config::defaults::*;
 use config::NymConfig;
+use mixnet_client::PacketForwardingConfig;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
@@ -24,6 +25,10 @@ const DEFAULT_DELAY_BETWEEN_PACKETS: Duration = Duration::from_millis(50);
 const DEFAULT_BATCH_SIZE: usize = 50;
 const DEFAULT_TESTING_INTERVAL: Duration = Duration::from_secs(60 * 60 * 12);
 const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_secs(60 * 30);
+const DEFAULT_HISTORY_RETENTION: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+// 'LOGGING'
+const DEFAULT_LOG_LEVEL: &str = "info";
 
 // 'DEBUG'
 const DEFAULT_NODE_STATS_LOGGING_DELAY: Duration = Duration::from_millis(60_000);
@@ -32,11 +37,26 @@ const DEFAULT_PACKET_FORWARDING_INITIAL_BACKOFF: Duration = Duration::from_milli
 const DEFAULT_PACKET_FORWARDING_MAXIMUM_BACKOFF: Duration = Duration::from_millis(300_000);
 const DEFAULT_INITIAL_CONNECTION_TIMEOUT: Duration = Duration::from_millis(1_500);
 const DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE: usize = 128;
+const DEFAULT_AVERAGE_LOOP_COVER_DELAY: Duration = Duration::from_millis(10_000);
+const DEFAULT_AVERAGE_LOOP_PACKET_DELAY: Duration = Duration::from_millis(50);
+const DEFAULT_LOOP_COVER_TIMEOUT: Duration = Duration::from_millis(60_000);
+const DEFAULT_TOPOLOGY_REFRESH_RATE: Duration = Duration::from_millis(300_000);
+const DEFAULT_PACKET_PROCESSING_QUEUE_SIZE: usize = 256;
+const DEFAULT_DELAY_FORWARDER_QUEUE_SIZE: usize = 8192;
+const DEFAULT_MAXIMUM_DELAY_QUEUE_SIZE: usize = 250_000;
+const DEFAULT_MAXIMUM_DELAY_QUEUE_MEMORY: usize = 512 * 1024 * 1024;
+const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_millis(10_000);
+const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 32;
+const DEFAULT_MAX_PACKETS_PER_CONNECTION_PER_SECOND: usize = 10_000;
 
 pub fn missing_string_value<T: From<String>>() -> T {
     MISSING_VALUE.to_string().into()
 }
 
+fn default_log_level() -> String {
+    DEFAULT_LOG_LEVEL.to_string()
+}
+
 fn bind_all_address() -> IpAddr {
     "0.0.0.0".parse().unwrap()
 }
@@ -45,6 +65,10 @@ fn default_mix_port() -> u16 {
     DEFAULT_MIX_LISTENING_PORT
 }
 
+fn default_verloc_history_retention() -> Duration {
+    DEFAULT_HISTORY_RETENTION
+}
+
 fn default_verloc_port() -> u16 {
     DEFAULT_VERLOC_LISTENING_PORT
 }
@@ -113,6 +137,24 @@ impl NymConfig for Config {
     }
 }
 
+impl PacketForwardingConfig for Config {
+    fn get_packet_forwarding_initial_backoff(&self) -> Duration {
+        self.debug.packet_forwarding_initial_backoff
+    }
+
+    fn get_packet_forwarding_maximum_backoff(&self) -> Duration {
+        self.debug.packet_forwarding_maximum_backoff
+    }
+
+    fn get_initial_connection_timeout(&self) -> Duration {
+        self.debug.initial_connection_timeout
+    }
+
+    fn get_maximum_connection_buffer_size(&self) -> usize {
+        self.debug.maximum_connection_buffer_size
+    }
+}
+
 impl Config {
     pub fn new<S: Into<String>>(id: S) -> Self {
         Config::default().with_id(id)
@@ -258,20 +300,60 @@ impl Config {
         self.mixnode.http_api_port
     }
 
-    pub fn get_packet_forwarding_initial_backoff(&self) -> Duration {
-        self.debug.packet_forwarding_initial_backoff
+    pub fn get_average_loop_cover_delay(&self) -> Duration {
+        self.debug.average_loop_cover_delay
     }
 
-    pub fn get_packet_forwarding_maximum_backoff(&self) -> Duration {
-        self.debug.packet_forwarding_maximum_backoff
+    pub fn get_average_loop_packet_delay(&self) -> Duration {
+        self.debug.average_loop_packet_delay
     }
 
-    pub fn get_initial_connection_timeout(&self) -> Duration {
-        self.debug.initial_connection_timeout
+    pub fn get_loop_cover_timeout(&self) -> Duration {
+        self.debug.loop_cover_timeout
     }
 
-    pub fn get_maximum_connection_buffer_size(&self) -> usize {
-        self.debug.maximum_connection_buffer_size
+    pub fn get_topology_refresh_rate(&self) -> Duration {
+        self.debug.topology_refresh_rate
+    }
+
+    pub fn get_packet_processing_workers(&self) -> usize {
+        self.debug.packet_processing_workers
+    }
+
+    pub fn get_packet_processing_queue_size(&self) -> usize {
+        self.debug.packet_processing_queue_size
+    }
+
+    pub fn get_delay_forwarder_shards(&self) -> usize {
+        self.debug.delay_forwarder_shards
+    }
+
+    pub fn get_delay_forwarder_queue_size(&self) -> usize {
+        self.debug.delay_forwarder_queue_size
+    }
+
+    pub fn get_maximum_delay_queue_size(&self) -> usize {
+        self.debug.maximum_delay_queue_size
+    }
+
+    pub fn get_maximum_delay_queue_memory(&self) -> usize {
+        self.debug.maximum_delay_queue_memory
+    }
+
+    pub fn get_shutdown_drain_timeout(&self) -> Duration {
+        self.debug.shutdown_drain_timeout
+    }
+
+    pub fn get_max_connections_per_ip(&self) -> usize {
+        self.debug.max_connections_per_ip
+    }
+
+    pub fn get_max_packets_per_connection_per_second(&self) -> usize {
+        self.debug.max_packets_per_connection_per_second
+    }
+
+    pub fn get_accept_topology_peers_only(&self) -> bool {
+        self.debug.accept_topology_peers_only
     }
 
     pub fn get_version(&self) -> &str {
@@ -306,9 +388,17 @@ impl Config {
         self.verloc.retry_timeout
     }
 
+    pub fn get_measurement_history_retention(&self) -> Duration {
+        self.verloc.history_retention
+    }
+
     pub fn get_wallet_address(&self) -> &str {
         &self.mixnode.wallet_address
     }
+
+    pub fn get_log_level(&self) -> &str {
+        &self.logging.level
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -408,9 +498,21 @@ impl Default for MixNode {
     }
 }
 
-#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
-struct Logging {}
+struct Logging {
+    /// Maximum level of the logged messages. Ignored if the `RUST_LOG` environment variable is set.
+    #[serde(default = "default_log_level")]
+    level: String,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Logging {
+            level: default_log_level(),
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -436,6 +538,10 @@ struct Verloc {
     /// Specifies delay between attempting to run the measurement again if the previous run failed
     /// due to being unable to get the list of nodes.
     retry_timeout: Duration,
+
+    /// Specifies for how long the results of past measurements are kept around.
+    #[serde(default = "default_verloc_history_retention")]
+    history_retention: Duration,
 }
 
 impl Default for Verloc {
@@ -448,6 +554,7 @@ impl Default for Verloc {
             tested_nodes_batch_size: DEFAULT_BATCH_SIZE,
             testing_interval: DEFAULT_TESTING_INTERVAL,
             retry_timeout: DEFAULT_RETRY_TIMEOUT,
+            history_retention: DEFAULT_HISTORY_RETENTION,
         }
     }
 }
@@ -479,6 +586,69 @@ struct Debug {
 
     /// Maximum number of packets that can be stored waiting to get sent to a particular connection.
     maximum_connection_buffer_size: usize,
+
+    /// The parameter of Poisson distribution determining how long, on average,
+    /// the mixnode is going to wait before sending another loop cover packet to itself.
+    /// Setting it to 0 disables the loop cover traffic.
+    #[serde(with = "humantime_serde")]
+    average_loop_cover_delay: Duration,
+
+    /// The average delay a loop cover packet is going to get delayed at a single mixnode.
+    #[serde(with = "humantime_serde")]
+    average_loop_packet_delay: Duration,
+
+    /// Time after which a loop cover packet that hasn't come back is considered lost.
+    #[serde(with = "humantime_serde")]
+    loop_cover_timeout: Duration,
+
+    /// Delay between each subsequent refresh of the network topology used for
+    /// constructing the routes of the loop cover packets and determining the expected peers.
+    #[serde(with = "humantime_serde")]
+    topology_refresh_rate: Duration,
+
+    /// Number of threads unwrapping received sphinx packets.
+    /// Setting it to 0 uses as many threads as there are available CPU cores.
+    packet_processing_workers: usize,
+
+    /// Maximum number of received packets that can be waiting for a single processing thread.
+    /// Once all of the queues are full, the mixnode stops reading from its connections.
+    packet_processing_queue_size: usize,
+
+    /// Number of independent delay queues, each forwarding packets to a distinct subset of
+    /// the next hops. Setting it to 0 uses as many queues as there are available CPU cores.
+    delay_forwarder_shards: usize,
+
+    /// Maximum number of unwrapped packets that can be waiting for a single delay queue to
+    /// pick them up. Any packets above it are dropped until the queue catches up.
+    delay_forwarder_queue_size: usize,
+
+    /// Maximum number of packets that can be delayed at the same time, shared between all of
+    /// the delay queues. Once they get close to full, new packets start being randomly dropped.
+    /// Setting it to 0 removes the limit.
+    maximum_delay_queue_size: usize,
+
+    /// Maximum total size, in bytes, of all packets delayed at the same time, shared between all
+    /// of the delay queues. Setting it to 0 removes the limit.
+    maximum_delay_queue_memory: usize,
+
+    /// Maximum time the mixnode is going to spend, after receiving SIGTERM or SIGINT, on
+    /// forwarding the packets it has already received before exiting.
+    #[serde(with = "humantime_serde")]
+    shutdown_drain_timeout: Duration,
+
+    /// Maximum number of connections a single IP address can have open at the same time.
+    /// Setting it to 0 removes the limit.
+    max_connections_per_ip: usize,
+
+    /// Maximum average number of packets per second that can be received on a single connection,
+    /// any packets above it are dropped. Setting it to 0 removes the limit.
+    max_packets_per_connection_per_second: usize,
+
+    /// Specifies whether connections should only be accepted from the nodes that are expected to
+    /// send packets to this mixnode according to the current network topology, i.e. the mixnodes
+    /// on the preceding layer and, for the first layer, the gateways. Note that the peers are
+    /// recognised by the ip addresses they have announced.
+    accept_topology_peers_only: bool,
 }
 
 impl Default for Debug {
@@ -490,6 +660,20 @@ impl Default for Debug {
             packet_forwarding_maximum_backoff: DEFAULT_PACKET_FORWARDING_MAXIMUM_BACKOFF,
             initial_connection_timeout: DEFAULT_INITIAL_CONNECTION_TIMEOUT,
             maximum_connection_buffer_size: DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE,
+            average_loop_cover_delay: DEFAULT_AVERAGE_LOOP_COVER_DELAY,
+            average_loop_packet_delay: DEFAULT_AVERAGE_LOOP_PACKET_DELAY,
+            loop_cover_timeout: DEFAULT_LOOP_COVER_TIMEOUT,
+            topology_refresh_rate: DEFAULT_TOPOLOGY_REFRESH_RATE,
+            packet_processing_workers: 0,
+            packet_processing_queue_size: DEFAULT_PACKET_PROCESSING_QUEUE_SIZE,
+            delay_forwarder_shards: 0,
+            delay_forwarder_queue_size: DEFAULT_DELAY_FORWARDER_QUEUE_SIZE,
+            maximum_delay_queue_size: DEFAULT_MAXIMUM_DELAY_QUEUE_SIZE,
+            maximum_delay_queue_memory: DEFAULT_MAXIMUM_DELAY_QUEUE_MEMORY,
+            shutdown_drain_timeout: DEFAULT_SHUTDOWN_DRAIN_TIMEOUT,
+            max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+            max_packets_per_connection_per_second: DEFAULT_MAX_PACKETS_PER_CONNECTION_PER_SECOND,
+            accept_topology_peers_only: false,
         }
     }
 }
@@ -73,7 +73,9 @@ nym_root_directory = '{{ mixnode.nym_root_directory }}'
 
 [logging]
 
-# TODO
+# Maximum level of the logged messages, one of 'off', 'error', 'warn', 'info', 'debug' or 'trace'.
+# It is ignored if the RUST_LOG environment variable is set.
+level = '{{ logging.level }}'
 
 "#
 }
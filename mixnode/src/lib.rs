@@ -0,0 +1,80 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+#[macro_use]
+extern crate rocket;
+
+use clap::Parser;
+use lazy_static::lazy_static;
+
+// only exposed to the benchmarks, so that it's never part of the regular builds
+#[cfg(feature = "benchmarking")]
+#[doc(hidden)]
+pub mod benchmarking;
+mod commands;
+mod config;
+mod node;
+
+pub use commands::execute;
+
+lazy_static! {
+    pub static ref LONG_ABOUT: String = long_version();
+}
+
+// Helper for passing LONG_ABOUT to clap
+fn long_about() -> &'static str {
+    &LONG_ABOUT
+}
+
+#[derive(Parser)]
+#[clap(author = "Nymtech", version, about, long_about = Some(long_about()))]
+pub struct Cli {
+    #[clap(subcommand)]
+    command: commands::Commands,
+}
+
+fn long_version() -> String {
+    format!(
+        r#"
+{:<20}{}
+{:<20}{}
+{:<20}{}
+{:<20}{}
+{:<20}{}
+{:<20}{}
+{:<20}{}
+{:<20}{}
+"#,
+        "Build Timestamp:",
+        env!("VERGEN_BUILD_TIMESTAMP"),
+        "Build Version:",
+        env!("VERGEN_BUILD_SEMVER"),
+        "Commit SHA:",
+        env!("VERGEN_GIT_SHA"),
+        "Commit Date:",
+        env!("VERGEN_GIT_COMMIT_TIMESTAMP"),
+        "Commit Branch:",
+        env!("VERGEN_GIT_BRANCH"),
+        "rustc Version:",
+        env!("VERGEN_RUSTC_SEMVER"),
+        "rustc Channel:",
+        env!("VERGEN_RUSTC_CHANNEL"),
+        "cargo Profile:",
+        env!("VERGEN_CARGO_PROFILE"),
+    )
+}
+
+/// Sets the maximum level of the logged messages, unless it has been specified with `RUST_LOG`.
+pub(crate) fn set_log_level(level: &str) {
+    if ::std::env::var("RUST_LOG").is_ok() {
+        return;
+    }
+
+    match level.parse() {
+        Ok(level) => log::set_max_level(level),
+        Err(_) => log::warn!(
+            "'{}' is not a valid log level - the current one is going to be kept",
+            level
+        ),
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::Config;
+use crate::node::node_statistics::IntervalsUpdater;
+use config::reload::{any_changed, LOG_LEVEL, PACKET_FORWARDING_FIELDS};
+use mixnet_client::PacketForwardingConfig;
+use tokio::sync::watch;
+
+const NODE_STATS_FIELDS: &[&str] = &[
+    "debug.node_stats_logging_delay",
+    "debug.node_stats_updating_delay",
+];
+
+// all the settings that can be changed without restarting the node
+pub(super) fn is_live(field: &str) -> bool {
+    field == LOG_LEVEL
+        || NODE_STATS_FIELDS.contains(&field)
+        || PACKET_FORWARDING_FIELDS.contains(&field)
+}
+
+pub(super) fn apply_changes(
+    mixnet_client_config: watch::Sender<mixnet_client::Config>,
+    node_stats_intervals: IntervalsUpdater,
+) -> impl Fn(&Config, &[&String]) + Send {
+    move |new_config, changed_fields| {
+        if any_changed(changed_fields, &[LOG_LEVEL]) {
+            crate::set_log_level(new_config.get_log_level())
+        }
+        if any_changed(changed_fields, NODE_STATS_FIELDS) {
+            node_stats_intervals.set_logging_delay(new_config.get_node_stats_logging_delay());
+            node_stats_intervals.set_updating_delay(new_config.get_node_stats_updating_delay());
+        }
+        if any_changed(changed_fields, PACKET_FORWARDING_FIELDS) {
+            // the only possible error is all of the forwarders being gone, i.e. during the shutdown
+            let _ = mixnet_client_config.send(new_config.mixnet_client_config());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_safe_settings_are_applied_live() {
+        assert!(is_live("logging.level"));
+        assert!(is_live("debug.packet_forwarding_initial_backoff"));
+        assert!(is_live("debug.node_stats_updating_delay"));
+        assert!(!is_live("mixnode.mix_port"));
+        assert!(!is_live("mixnode.announce_address"));
+        assert!(!is_live("debug.maximum_delay_queue_size"));
+        assert!(!is_live("verloc.packets_per_node"));
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::http::verloc::VerlocState;
+use crate::node::metrics::{HistogramSnapshot, NodeMetrics, VERLOC_RTT_BUCKETS};
+use crate::node::node_statistics::SharedNodeStats;
+use rocket::State;
+use std::fmt::Write;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn write_header(output: &mut String, name: &str, kind: &str, help: &str) {
+    // writing to a String can't fail
+    let _ = writeln!(output, "# HELP nym_mixnode_{} {}", name, help);
+    let _ = writeln!(output, "# TYPE nym_mixnode_{} {}", name, kind);
+}
+
+fn write_metric(output: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    write_header(output, name, kind, help);
+    let _ = writeln!(output, "nym_mixnode_{} {}", name, value);
+}
+
+fn write_histogram(output: &mut String, name: &str, help: &str, histogram: &HistogramSnapshot) {
+    write_header(output, name, "histogram", help);
+    for (bound, count) in &histogram.buckets {
+        let _ = writeln!(
+            output,
+            "nym_mixnode_{}_bucket{{le=\"{}\"}} {}",
+            name, bound, count
+        );
+    }
+    let _ = writeln!(
+        output,
+        "nym_mixnode_{}_bucket{{le=\"+Inf\"}} {}",
+        name, histogram.count
+    );
+    let _ = writeln!(
+        output,
+        "nym_mixnode_{}_sum {}",
+        name,
+        histogram.sum.as_secs_f64()
+    );
+    let _ = writeln!(output, "nym_mixnode_{}_count {}", name, histogram.count);
+}
+
+/// Returns running stats of the mixnode in the Prometheus text exposition format.
+#[get("/metrics")]
+pub(crate) async fn metrics(
+    metrics: &State<NodeMetrics>,
+    stats: &State<SharedNodeStats>,
+    verloc: &State<VerlocState>,
+) -> String {
+    let snapshot = metrics.snapshot();
+    // note: the packet counts are only as recent as the last stats update
+    let stats = stats.clone_data().await;
+    let verloc = verloc.clone_data().await;
+    let mut output = String::new();
+
+    write_metric(
+        &mut output,
+        "uptime_seconds",
+        "gauge",
+        "Time since the mixnode has started up.",
+        snapshot.uptime_secs,
+    );
+    write_metric(
+        &mut output,
+        "packets_received_total",
+        "counter",
+        "Sphinx packets received from the mix network.",
+        stats.total_packets_received(),
+    );
+    write_metric(
+        &mut output,
+        "packets_sent_total",
+        "counter",
+        "Sphinx packets sent to the next hop. Note that it doesn't imply they got delivered.",
+        stats.total_packets_sent(),
+    );
+    write_metric(
+        &mut output,
+        "packets_dropped_total",
+        "counter",
+        "Sphinx packets explicitly dropped by the mixnode instead of getting forwarded.",
+        stats.total_packets_explicitly_dropped(),
+    );
+    write_header(
+        &mut output,
+        "packets_dropped_by_reason_total",
+        "counter",
+        "Sphinx packets explicitly dropped by the mixnode, grouped by the reason of the drop.",
+    );
+    for (reason, count) in stats.total_packets_dropped_by_reason() {
+        let _ = writeln!(
+            output,
+            "nym_mixnode_packets_dropped_by_reason_total{{reason=\"{}\"}} {}",
+            reason.as_str(),
+            count
+        );
+    }
+    write_metric(
+        &mut output,
+        "active_connections",
+        "gauge",
+        "Number of currently open connections from other nodes and clients.",
+        snapshot.active_connections,
+    );
+    write_metric(
+        &mut output,
+        "delay_queue_depth",
+        "gauge",
+        "Number of packets currently being delayed before getting forwarded.",
+        snapshot.delay_queue_depth,
+    );
+    write_metric(
+        &mut output,
+        "delay_queue_high_water_mark",
+        "gauge",
+        "The largest number of packets that were being delayed at the same time since startup.",
+        snapshot.delay_queue_high_water_mark,
+    );
+    write_header(
+        &mut output,
+        "connections_rejected_total",
+        "counter",
+        "Incoming connections refused by the mixnode, grouped by the reason of the refusal.",
+    );
+    for (reason, count) in [
+        ("per_ip_limit", snapshot.connections_rejected_over_ip_limit),
+        ("unknown_peer", snapshot.connections_rejected_unknown_peer),
+    ] {
+        let _ = writeln!(
+            output,
+            "nym_mixnode_connections_rejected_total{{reason=\"{}\"}} {}",
+            reason, count
+        );
+    }
+    write_metric(
+        &mut output,
+        "packets_rate_limited_total",
+        "counter",
+        "Sphinx packets dropped as their connection has exceeded the allowed packet rate.",
+        snapshot.packets_rate_limited,
+    );
+    write_metric(
+        &mut output,
+        "malformed_frames_total",
+        "counter",
+        "Connections closed after receiving data that could not be decoded into a sphinx packet.",
+        snapshot.malformed_frames,
+    );
+    write_histogram(
+        &mut output,
+        "packet_processing_seconds",
+        "Time it took to unwrap received sphinx packets.",
+        &snapshot.processing_latency,
+    );
+    write_metric(
+        &mut output,
+        "loops_sent_total",
+        "counter",
+        "Loop cover packets sent by the mixnode to itself.",
+        snapshot.loops_sent,
+    );
+    write_metric(
+        &mut output,
+        "loops_received_total",
+        "counter",
+        "Loop cover packets that came back to the mixnode in time.",
+        snapshot.loops_received,
+    );
+    write_metric(
+        &mut output,
+        "loops_lost_total",
+        "counter",
+        "Loop cover packets that did not come back to the mixnode in time.",
+        snapshot.loops_lost,
+    );
+
+    let measurements = verloc
+        .results()
+        .iter()
+        .filter_map(|result| result.latest_measurement)
+        .collect::<Vec<_>>();
+    let measured_rtts = measurements
+        .iter()
+        .map(|measurement| measurement.mean)
+        .collect::<Vec<Duration>>();
+    let measured_jitters = measurements
+        .iter()
+        .map(|measurement| measurement.jitter)
+        .collect::<Vec<Duration>>();
+    write_metric(
+        &mut output,
+        "verloc_tested_nodes",
+        "gauge",
+        "Number of nodes tested during the current verloc run.",
+        verloc.total_tested() as u64,
+    );
+    write_metric(
+        &mut output,
+        "verloc_measured_nodes",
+        "gauge",
+        "Number of nodes that responded to the verloc measurements.",
+        measured_rtts.len() as u64,
+    );
+    if let Some(run_finished) = verloc.run_finished() {
+        write_metric(
+            &mut output,
+            "verloc_last_run_finished_timestamp_seconds",
+            "gauge",
+            "Unix timestamp of the time at which the last verloc run has finished.",
+            run_finished
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+    }
+    write_histogram(
+        &mut output,
+        "verloc_rtt_seconds",
+        "Mean round-trip times to the nodes measured during the current verloc run.",
+        &HistogramSnapshot::from_values(VERLOC_RTT_BUCKETS, &measured_rtts),
+    );
+    write_histogram(
+        &mut output,
+        "verloc_jitter_seconds",
+        "Mean differences between subsequent round-trip times to the nodes measured during the current verloc run.",
+        &HistogramSnapshot::from_values(VERLOC_RTT_BUCKETS, &measured_jitters),
+    );
+    write_metric(
+        &mut output,
+        "verloc_echo_packets_sent",
+        "gauge",
+        "Echo packets sent to the nodes that responded to the verloc measurements during the current run.",
+        measurements
+            .iter()
+            .map(|measurement| measurement.packets_sent as u64)
+            .sum(),
+    );
+    write_metric(
+        &mut output,
+        "verloc_echo_packets_lost",
+        "gauge",
+        "Echo packets sent during the current verloc run that did not get a reply in time.",
+        measurements
+            .iter()
+            .map(|measurement| measurement.packets_lost as u64)
+            .sum(),
+    );
+
+    output
+}
@@ -1,4 +1,5 @@
 pub(crate) mod description;
+pub(crate) mod metrics;
 pub(crate) mod stats;
 pub(crate) mod verloc;
 
@@ -1,6 +1,8 @@
-use mixnode_common::verloc::{AtomicVerlocResult, VerlocResult};
+use mixnode_common::verloc::{AtomicVerlocResult, NodeVerlocHistory, VerlocResult};
 use rocket::serde::json::Json;
 use rocket::State;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub(crate) struct VerlocState {
     shared: AtomicVerlocResult,
@@ -12,12 +14,53 @@ impl VerlocState {
             shared: atomic_verloc_result,
         }
     }
+
+    pub(crate) async fn clone_data(&self) -> VerlocResult {
+        self.shared.clone_data().await
+    }
+
+    pub(crate) async fn history(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Vec<NodeVerlocHistory> {
+        self.shared.history(since, until).await
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum VerlocResponse {
+    Latest(VerlocResult),
+    History(Vec<NodeVerlocHistory>),
+}
+
+// timestamps too far in the future to be represented are treated as not having been specified
+fn from_unix_timestamp(timestamp: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs(timestamp))
 }
 
 /// Provides verifiable location (verloc) measurements for this mixnode - a list of the
 /// round-trip times, in milliseconds, for all other mixnodes that this node knows about.
-#[get("/verloc")]
-pub(crate) async fn verloc(state: &State<VerlocState>) -> Json<VerlocResult> {
+///
+/// If either `since` or `until` (unix timestamps, in seconds) is specified, it instead returns
+/// the history of all measurements performed within that time range, grouped by the measured node.
+#[get("/verloc?<since>&<until>")]
+pub(crate) async fn verloc(
+    state: &State<VerlocState>,
+    since: Option<u64>,
+    until: Option<u64>,
+) -> Json<VerlocResponse> {
+    if since.is_some() || until.is_some() {
+        let history = state
+            .history(
+                since.and_then(from_unix_timestamp),
+                until.and_then(from_unix_timestamp),
+            )
+            .await;
+        return Json(VerlocResponse::History(history));
+    }
+
     // since it's impossible to get a mutable reference to the state, we can't cache any results outside the lock : (
-    Json(state.shared.clone_data().await)
+    Json(VerlocResponse::Latest(state.clone_data().await))
 }
@@ -0,0 +1,117 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::listener::connection_limiter::ConnectionLimiter;
+use crypto::asymmetric::identity;
+use log::*;
+use nymsphinx::params::DEFAULT_NUM_MIX_HOPS;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time;
+use topology::{nym_topology_from_bonds, MixLayer};
+use url::Url;
+
+/// Periodically determines, based on the current network topology, which nodes are expected to
+/// send packets to this mixnode, i.e. the ones on the preceding layer, and only allows them
+/// to connect. If this mixnode is not part of the active set, everyone is allowed.
+pub(crate) struct AllowedPeersRefresher {
+    /// Identity of this mixnode used to find it in the network topology.
+    identity_key: identity::PublicKey,
+
+    /// Version of this mixnode used to filter out incompatible nodes out of the topology.
+    version: String,
+
+    validator_api_urls: Vec<Url>,
+
+    /// Delay between subsequent refreshes of the network topology.
+    refresh_rate: Duration,
+
+    connection_limiter: ConnectionLimiter,
+}
+
+impl AllowedPeersRefresher {
+    pub(crate) fn new(
+        identity_key: identity::PublicKey,
+        version: String,
+        validator_api_urls: Vec<Url>,
+        refresh_rate: Duration,
+        connection_limiter: ConnectionLimiter,
+    ) -> Self {
+        AllowedPeersRefresher {
+            identity_key,
+            version,
+            validator_api_urls,
+            refresh_rate,
+            connection_limiter,
+        }
+    }
+
+    async fn refresh(&self) {
+        let validator_api = self
+            .validator_api_urls
+            .choose(&mut OsRng)
+            .expect("The list of validator apis is empty");
+        let validator_client = validator_client::ApiClient::new(validator_api.clone());
+
+        let mixnodes = match validator_client.get_cached_active_mixnodes().await {
+            Ok(mixnodes) => mixnodes,
+            Err(err) => {
+                warn!("failed to get network mixnodes - {}. We're going to keep using the old set of allowed peers", err);
+                return;
+            }
+        };
+        let gateways = match validator_client.get_cached_gateways().await {
+            Ok(gateways) => gateways,
+            Err(err) => {
+                warn!("failed to get network gateways - {}. We're going to keep using the old set of allowed peers", err);
+                return;
+            }
+        };
+
+        let topology =
+            nym_topology_from_bonds(mixnodes, gateways).filter_system_version(&self.version);
+        let own_layer = topology
+            .mixes_as_vec()
+            .into_iter()
+            .find(|node| node.identity_key == self.identity_key)
+            .map(|node| node.layer as MixLayer);
+
+        let allowed_peers = match own_layer {
+            Some(layer) => match topology.previous_hops(layer, DEFAULT_NUM_MIX_HOPS) {
+                Ok(previous_hops) => Some(
+                    previous_hops
+                        .into_iter()
+                        .map(|address| address.ip())
+                        .collect::<HashSet<IpAddr>>(),
+                ),
+                Err(err) => {
+                    warn!("failed to determine nodes preceding us in the topology - {:?}. We're going to keep using the old set of allowed peers", err);
+                    return;
+                }
+            },
+            None => {
+                debug!("we're not part of the active set - accepting connections from everyone");
+                None
+            }
+        };
+
+        if let Some(allowed_peers) = &allowed_peers {
+            debug!("accepting connections from {} peers", allowed_peers.len());
+        }
+        self.connection_limiter.set_allowed_peers(allowed_peers);
+    }
+
+    pub(crate) async fn run(&self, mut shutdown: ShutdownListener) {
+        let mut refresh = time::interval(self.refresh_rate);
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => self.refresh().await,
+                _ = shutdown.recv() => return,
+            }
+        }
+    }
+}
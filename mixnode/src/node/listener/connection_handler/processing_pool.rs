@@ -0,0 +1,161 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::listener::connection_handler::packet_processing::{
+    MixProcessingResult, PacketProcessor,
+};
+use crate::node::loop_cover::LoopTracker;
+use crate::node::metrics::NodeMetrics;
+use crate::node::packet_delayforwarder::PacketDelayForwardSender;
+use log::*;
+use nymsphinx::cover::recover_mix_loop_id;
+use nymsphinx::forwarding::packet::MixPacket;
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use nymsphinx::{Delay as SphinxDelay, DestinationAddressBytes};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::Instant;
+
+/// Returned when the packet could not be handed over to the processing pool as all of its
+/// workers are gone.
+#[derive(Debug)]
+pub(crate) struct PoolClosedError;
+
+/// Unwraps received sphinx packets and passes the results onto the further stages.
+#[derive(Clone)]
+pub(crate) struct ReceivedPacketHandler {
+    packet_processor: PacketProcessor,
+    delay_forwarding_channel: PacketDelayForwardSender,
+    node_metrics: NodeMetrics,
+    loop_tracker: LoopTracker,
+    /// Sphinx destination address of this mixnode used by its own loop cover packets.
+    own_address: DestinationAddressBytes,
+}
+
+impl ReceivedPacketHandler {
+    pub(crate) fn new(
+        packet_processor: PacketProcessor,
+        delay_forwarding_channel: PacketDelayForwardSender,
+        node_metrics: NodeMetrics,
+        loop_tracker: LoopTracker,
+        own_address: DestinationAddressBytes,
+    ) -> Self {
+        ReceivedPacketHandler {
+            packet_processor,
+            delay_forwarding_channel,
+            node_metrics,
+            loop_tracker,
+            own_address,
+        }
+    }
+
+    fn delay_and_forward_packet(&mut self, mix_packet: MixPacket, delay: Option<SphinxDelay>) {
+        // determine instant at which packet should get forwarded. this way we minimise effect of
+        // being stuck in the queue [of the channel] to get inserted into the delay queue
+        let forward_instant = delay.map(|delay| Instant::now() + delay.to_duration());
+
+        // if forward() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.delay_forwarding_channel
+            .forward((mix_packet, forward_instant))
+            .expect("the delay-forwarder has died!");
+    }
+
+    fn handle_received_packet(&mut self, framed_sphinx_packet: FramedSphinxPacket) {
+        //
+        // TODO: here be replay attack detection - it will require similar key cache to the one in
+        // packet processor for vpn packets,
+        // question: can it also be per connection vs global?
+        //
+
+        // all processing such, key caching, etc. was done.
+        // however, if it was a forward hop, we still need to delay it
+        let processing_start = std::time::Instant::now();
+        let processing_result = self.packet_processor.process_received(framed_sphinx_packet);
+        self.node_metrics
+            .record_processing_latency(processing_start.elapsed());
+
+        match processing_result {
+            Err(e) => debug!("We failed to process received sphinx packet - {:?}", e),
+            Ok(res) => match res {
+                MixProcessingResult::ForwardHop(forward_packet, delay) => {
+                    self.delay_and_forward_packet(forward_packet, delay)
+                }
+                MixProcessingResult::FinalHop(final_hop) => {
+                    // the ack of our own loop is a dummy, so it's just dropped
+                    match recover_mix_loop_id(&final_hop.message) {
+                        Some(loop_id) if final_hop.destination == self.own_address => {
+                            self.loop_tracker.register_received(loop_id)
+                        }
+                        _ => {
+                            warn!("Received a final hop packet that is not our loop cover message!")
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Pool of worker threads performing the CPU-heavy sphinx unwrapping of packets received
+/// on all connections. Cloning it is cheap as it only clones the handles to the workers.
+#[derive(Clone)]
+pub(crate) struct ProcessingPool {
+    workers: Arc<Vec<mpsc::Sender<FramedSphinxPacket>>>,
+    next_worker: Arc<AtomicUsize>,
+}
+
+impl ProcessingPool {
+    /// Spawns `num_workers` threads each of which can have up to `worker_queue_size` packets
+    /// waiting to get processed.
+    pub(crate) fn start(
+        num_workers: usize,
+        worker_queue_size: usize,
+        packet_handler: ReceivedPacketHandler,
+    ) -> Self {
+        let workers = (0..num_workers)
+            .map(|id| {
+                let (sender, mut receiver) = mpsc::channel(worker_queue_size);
+                let mut handler = packet_handler.clone();
+                thread::Builder::new()
+                    .name(format!("sphinx-worker-{}", id))
+                    .spawn(move || {
+                        while let Some(packet) = receiver.blocking_recv() {
+                            handler.handle_received_packet(packet)
+                        }
+                    })
+                    .expect("failed to spawn sphinx processing worker");
+                sender
+            })
+            .collect();
+
+        ProcessingPool {
+            workers: Arc::new(workers),
+            next_worker: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Hands the packet over to the first worker, starting from the next one in the round robin,
+    /// that has some space left in its queue. If all of them are busy, waits until the queue
+    /// of the next worker frees up, so that the slow down propagates back to the connection.
+    pub(crate) async fn process(&self, packet: FramedSphinxPacket) -> Result<(), PoolClosedError> {
+        let num_workers = self.workers.len();
+        let start = self.next_worker.fetch_add(1, Ordering::Relaxed);
+
+        let mut packet = packet;
+        for offset in 0..num_workers {
+            match self.workers[(start + offset) % num_workers].try_send(packet) {
+                Ok(_) => return Ok(()),
+                Err(TrySendError::Full(returned)) => packet = returned,
+                Err(TrySendError::Closed(_)) => return Err(PoolClosedError),
+            }
+        }
+
+        self.workers[start % num_workers]
+            .send(packet)
+            .await
+            .map_err(|_| PoolClosedError)
+    }
+}
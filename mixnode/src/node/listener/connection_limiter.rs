@@ -0,0 +1,241 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::metrics::NodeMetrics;
+use log::*;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+/// Limits protecting the mixnode from peers opening lots of connections or flooding it with
+/// packets, all of which have to go through the expensive sphinx processing. `None` means unlimited.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ConnectionLimits {
+    pub(crate) max_connections_per_ip: Option<usize>,
+    pub(crate) max_packets_per_connection_per_second: Option<usize>,
+}
+
+/// Keeps track of connections of all peers of the mixnode and decides whether new ones
+/// are still within the configured `ConnectionLimits` and come from one of the expected peers.
+// note that clone here is fine as upon cloning the same underlying state will be used
+#[derive(Clone)]
+pub(crate) struct ConnectionLimiter {
+    limits: ConnectionLimits,
+    open_connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    /// Addresses of nodes that are allowed to connect. `None` means everyone is allowed.
+    allowed_peers: Arc<RwLock<Option<HashSet<IpAddr>>>>,
+    node_metrics: NodeMetrics,
+}
+
+impl ConnectionLimiter {
+    pub(crate) fn new(limits: ConnectionLimits, node_metrics: NodeMetrics) -> Self {
+        ConnectionLimiter {
+            limits,
+            open_connections: Arc::new(Mutex::new(HashMap::new())),
+            allowed_peers: Arc::new(RwLock::new(None)),
+            node_metrics,
+        }
+    }
+
+    /// Replaces the set of addresses that are allowed to connect. `None` allows everyone.
+    pub(crate) fn set_allowed_peers(&self, allowed_peers: Option<HashSet<IpAddr>>) {
+        *self.allowed_peers.write().unwrap() = allowed_peers;
+    }
+
+    fn is_allowed_peer(&self, remote_ip: IpAddr) -> bool {
+        match self.allowed_peers.read().unwrap().as_ref() {
+            Some(allowed_peers) => allowed_peers.contains(&remote_ip),
+            None => true,
+        }
+    }
+
+    /// Checks whether a new connection from the specified address can be accepted and if so,
+    /// returns a permit that counts towards the connections of that address until it's dropped.
+    pub(crate) fn try_accept(&self, remote_ip: IpAddr) -> Option<ConnectionPermit> {
+        if !self.is_allowed_peer(remote_ip) {
+            debug!(
+                "Refusing connection from {} - it's not a node in the current topology",
+                remote_ip
+            );
+            self.node_metrics.connection_rejected(true);
+            return None;
+        }
+
+        let mut open_connections = self.open_connections.lock().unwrap();
+        let peer_connections = open_connections.entry(remote_ip).or_insert(0);
+        if let Some(max_connections) = self.limits.max_connections_per_ip {
+            if *peer_connections >= max_connections {
+                debug!(
+                    "Refusing connection from {} - it has too many connections open already",
+                    remote_ip
+                );
+                self.node_metrics.connection_rejected(false);
+                return None;
+            }
+        }
+        *peer_connections += 1;
+
+        Some(ConnectionPermit {
+            limiter: self.clone(),
+            remote_ip,
+            rate_limiter: self
+                .limits
+                .max_packets_per_connection_per_second
+                .map(PacketRateLimiter::new),
+        })
+    }
+
+    fn release(&self, remote_ip: IpAddr) {
+        let mut open_connections = self.open_connections.lock().unwrap();
+        if let Some(peer_connections) = open_connections.get_mut(&remote_ip) {
+            *peer_connections -= 1;
+            if *peer_connections == 0 {
+                open_connections.remove(&remote_ip);
+            }
+        }
+    }
+}
+
+/// Permit of an accepted connection.
+pub(crate) struct ConnectionPermit {
+    limiter: ConnectionLimiter,
+    remote_ip: IpAddr,
+    rate_limiter: Option<PacketRateLimiter>,
+}
+
+impl ConnectionPermit {
+    /// Checks whether another packet received on the connection is still within the rate limit.
+    pub(crate) fn try_accept_packet(&mut self) -> bool {
+        match self.rate_limiter.as_mut() {
+            Some(rate_limiter) => rate_limiter.try_consume(),
+            None => true,
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.limiter.release(self.remote_ip)
+    }
+}
+
+/// Token bucket allowing on average up to the specified number of packets per second, with bursts
+/// of up to a second worth of packets.
+struct PacketRateLimiter {
+    packets_per_second: f64,
+    available: f64,
+    last_refill: Instant,
+}
+
+impl PacketRateLimiter {
+    fn new(packets_per_second: usize) -> Self {
+        PacketRateLimiter {
+            packets_per_second: packets_per_second as f64,
+            available: packets_per_second as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_consume_at(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.available =
+            (self.available + elapsed * self.packets_per_second).min(self.packets_per_second);
+        self.last_refill = now;
+
+        if self.available >= 1.0 {
+            self.available -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn try_consume(&mut self) -> bool {
+        self.try_consume_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter(max_connections_per_ip: Option<usize>) -> ConnectionLimiter {
+        ConnectionLimiter::new(
+            ConnectionLimits {
+                max_connections_per_ip,
+                max_packets_per_connection_per_second: None,
+            },
+            NodeMetrics::new(),
+        )
+    }
+
+    #[test]
+    fn connections_per_ip_are_limited_until_permits_are_dropped() {
+        let limiter = limiter(Some(2));
+        let peer = "1.2.3.4".parse().unwrap();
+        let other_peer = "4.3.2.1".parse().unwrap();
+
+        let first = limiter.try_accept(peer).unwrap();
+        let _second = limiter.try_accept(peer).unwrap();
+        assert!(limiter.try_accept(peer).is_none());
+        assert!(limiter.try_accept(other_peer).is_some());
+
+        drop(first);
+        assert!(limiter.try_accept(peer).is_some());
+        assert_eq!(
+            1,
+            limiter
+                .node_metrics
+                .snapshot()
+                .connections_rejected_over_ip_limit
+        );
+    }
+
+    #[test]
+    fn only_allowed_peers_are_accepted_if_they_are_known() {
+        let limiter = limiter(None);
+        let peer = "1.2.3.4".parse().unwrap();
+        let other_peer = "4.3.2.1".parse().unwrap();
+        assert!(limiter.try_accept(other_peer).is_some());
+
+        limiter.set_allowed_peers(Some(vec![peer].into_iter().collect()));
+        assert!(limiter.try_accept(peer).is_some());
+        assert!(limiter.try_accept(other_peer).is_none());
+        assert_eq!(
+            1,
+            limiter
+                .node_metrics
+                .snapshot()
+                .connections_rejected_unknown_peer
+        );
+    }
+
+    #[test]
+    fn packet_rate_limiter_refills_over_time() {
+        let mut rate_limiter = PacketRateLimiter::new(10);
+        let start = rate_limiter.last_refill;
+
+        for _ in 0..10 {
+            assert!(rate_limiter.try_consume_at(start));
+        }
+        assert!(!rate_limiter.try_consume_at(start));
+
+        // half a second refills half of the bucket
+        let later = start + Duration::from_millis(500);
+        for _ in 0..5 {
+            assert!(rate_limiter.try_consume_at(later));
+        }
+        assert!(!rate_limiter.try_consume_at(later));
+
+        // but never above its capacity
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..10 {
+            assert!(rate_limiter.try_consume_at(much_later));
+        }
+        assert!(!rate_limiter.try_consume_at(much_later));
+    }
+}
@@ -2,24 +2,32 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::node::listener::connection_handler::ConnectionHandler;
+use crate::node::listener::connection_limiter::ConnectionLimiter;
 use log::error;
 use std::net::SocketAddr;
 use std::process;
+use task::ShutdownListener;
 use tokio::net::TcpListener;
 use tokio::task::JoinHandle;
 
+pub(crate) mod allowed_peers;
 pub(crate) mod connection_handler;
+pub(crate) mod connection_limiter;
 
 pub(crate) struct Listener {
     address: SocketAddr,
+    connection_limiter: ConnectionLimiter,
 }
 
 impl Listener {
-    pub(crate) fn new(address: SocketAddr) -> Self {
-        Listener { address }
+    pub(crate) fn new(address: SocketAddr, connection_limiter: ConnectionLimiter) -> Self {
+        Listener {
+            address,
+            connection_limiter,
+        }
     }
 
-    async fn run(&mut self, connection_handler: ConnectionHandler) {
+    async fn run(&mut self, connection_handler: ConnectionHandler, mut shutdown: ShutdownListener) {
         let listener = match TcpListener::bind(self.address).await {
             Ok(listener) => listener,
             Err(err) => {
@@ -29,19 +37,40 @@ impl Listener {
         };
 
         loop {
-            match listener.accept().await {
-                Ok((socket, remote_addr)) => {
-                    let handler = connection_handler.clone();
-                    tokio::spawn(handler.handle_connection(socket, remote_addr));
+            tokio::select! {
+                connection = listener.accept() => match connection {
+                    Ok((socket, remote_addr)) => {
+                        // dropping the socket is enough to refuse the connection
+                        let connection_permit =
+                            match self.connection_limiter.try_accept(remote_addr.ip()) {
+                                Some(permit) => permit,
+                                None => continue,
+                            };
+                        let handler = connection_handler.clone();
+                        tokio::spawn(handler.handle_connection(
+                            socket,
+                            remote_addr,
+                            connection_permit,
+                            shutdown.clone(),
+                        ));
+                    }
+                    Err(err) => warn!("Failed to accept incoming connection - {:?}", err),
+                },
+                _ = shutdown.recv() => {
+                    info!("The mix listener is no longer accepting new connections");
+                    return;
                 }
-                Err(err) => warn!("Failed to accept incoming connection - {:?}", err),
             }
         }
     }
 
-    pub(crate) fn start(mut self, connection_handler: ConnectionHandler) -> JoinHandle<()> {
+    pub(crate) fn start(
+        mut self,
+        connection_handler: ConnectionHandler,
+        shutdown: ShutdownListener,
+    ) -> JoinHandle<()> {
         info!("Running mix listener on {:?}", self.address.to_string());
 
-        tokio::spawn(async move { self.run(connection_handler).await })
+        tokio::spawn(async move { self.run(connection_handler, shutdown).await })
     }
 }
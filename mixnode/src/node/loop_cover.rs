@@ -0,0 +1,257 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::metrics::NodeMetrics;
+use crate::node::packet_delayforwarder::PacketDelayForwardSender;
+use crypto::asymmetric::identity;
+use log::*;
+use nymsphinx::cover::generate_mix_loop_cover_packet;
+use nymsphinx::utils::sample_poisson_duration;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time::{self, Instant};
+use topology::{mix, nym_topology_from_bonds, NymTopology};
+use url::Url;
+
+/// Keeps track of the loop cover packets sent by this mixnode that haven't come back yet.
+#[derive(Clone)]
+pub(crate) struct LoopTracker {
+    pending: Arc<Mutex<HashMap<u64, Instant>>>,
+    node_metrics: NodeMetrics,
+}
+
+impl LoopTracker {
+    pub(crate) fn new(node_metrics: NodeMetrics) -> Self {
+        LoopTracker {
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            node_metrics,
+        }
+    }
+
+    fn register_sent(&self, loop_id: u64) {
+        self.pending
+            .lock()
+            .expect("loop tracker mutex got poisoned")
+            .insert(loop_id, Instant::now());
+        self.node_metrics.loop_sent();
+    }
+
+    /// Marks the loop with the specified id as completed.
+    pub(crate) fn register_received(&self, loop_id: u64) {
+        let sent_at = self
+            .pending
+            .lock()
+            .expect("loop tracker mutex got poisoned")
+            .remove(&loop_id);
+
+        match sent_at {
+            Some(sent_at) => {
+                trace!("loop {} came back after {:?}", loop_id, sent_at.elapsed());
+                self.node_metrics.loop_received();
+            }
+            None => debug!(
+                "received loop {} that we either haven't sent or have already considered lost",
+                loop_id
+            ),
+        }
+    }
+
+    /// Removes all loops that haven't come back within the specified timeout and marks them as lost.
+    fn expire_lost(&self, timeout: Duration) -> usize {
+        let mut pending = self
+            .pending
+            .lock()
+            .expect("loop tracker mutex got poisoned");
+        let before = pending.len();
+        pending.retain(|_, sent_at| sent_at.elapsed() < timeout);
+        let lost = before - pending.len();
+        drop(pending);
+
+        if lost > 0 {
+            debug!("{} loop cover packets did not come back in time", lost);
+            self.node_metrics.loops_lost(lost);
+        }
+        lost
+    }
+}
+
+/// Sends Poisson-timed loop cover packets through the network back to this mixnode, so that
+/// the ones that never come back could be used as a signal of its (or its peers') health.
+pub(crate) struct LoopCoverTrafficStream {
+    /// Identity of this mixnode used to find it in the network topology.
+    identity_key: identity::PublicKey,
+
+    /// Version of this mixnode used to filter out incompatible nodes out of the topology.
+    version: String,
+
+    validator_api_urls: Vec<Url>,
+
+    /// Average delay between sending subsequent loop cover packets.
+    average_loop_cover_delay: Duration,
+
+    /// Average delay a loop cover packet is going to get delayed at a single mixnode.
+    average_packet_delay: Duration,
+
+    /// Time after which a loop cover packet that hasn't come back is considered lost.
+    loop_timeout: Duration,
+
+    /// Delay between subsequent refreshes of the network topology.
+    topology_refresh_rate: Duration,
+
+    /// Channel used for sending the loop cover packets to the first hop without any further delays.
+    delay_forwarding_channel: PacketDelayForwardSender,
+
+    loop_tracker: LoopTracker,
+
+    /// The most recent network topology alongside the entry of this mixnode in it.
+    topology: Option<(NymTopology, mix::Node)>,
+}
+
+impl LoopCoverTrafficStream {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        identity_key: identity::PublicKey,
+        version: String,
+        validator_api_urls: Vec<Url>,
+        average_loop_cover_delay: Duration,
+        average_packet_delay: Duration,
+        loop_timeout: Duration,
+        topology_refresh_rate: Duration,
+        delay_forwarding_channel: PacketDelayForwardSender,
+        loop_tracker: LoopTracker,
+    ) -> Self {
+        LoopCoverTrafficStream {
+            identity_key,
+            version,
+            validator_api_urls,
+            average_loop_cover_delay,
+            average_packet_delay,
+            loop_timeout,
+            topology_refresh_rate,
+            delay_forwarding_channel,
+            loop_tracker,
+            topology: None,
+        }
+    }
+
+    async fn refresh_topology(&mut self) {
+        let validator_api = self
+            .validator_api_urls
+            .choose(&mut OsRng)
+            .expect("The list of validator apis is empty");
+        let validator_client = validator_client::ApiClient::new(validator_api.clone());
+
+        let mixnodes = match validator_client.get_cached_active_mixnodes().await {
+            Ok(mixnodes) => mixnodes,
+            Err(err) => {
+                warn!("failed to get network mixnodes for the loop cover traffic - {}. We're going to keep using the old topology", err);
+                return;
+            }
+        };
+
+        // gateways don't relay packets back into the network, so the loops only go through mixnodes
+        let topology =
+            nym_topology_from_bonds(mixnodes, Vec::new()).filter_system_version(&self.version);
+        let own_node = topology
+            .mixes_as_vec()
+            .into_iter()
+            .find(|node| node.identity_key == self.identity_key);
+
+        if own_node.is_none() {
+            debug!("we're not part of the active set - we won't be sending any loop cover traffic");
+        }
+        self.topology = own_node.map(|node| (topology, node));
+    }
+
+    fn send_loop(&mut self) {
+        let (topology, own_node) = match self.topology.as_ref() {
+            Some(topology) => topology,
+            None => return,
+        };
+
+        // the ids are random so that nobody else could forge loops that we'd consider as received
+        let loop_id = OsRng.gen();
+        let loop_packet = match generate_mix_loop_cover_packet(
+            &mut OsRng,
+            topology,
+            own_node,
+            loop_id,
+            self.average_packet_delay,
+        ) {
+            Ok(packet) => packet,
+            Err(err) => {
+                warn!("failed to create loop cover packet - {:?}", err);
+                return;
+            }
+        };
+        self.loop_tracker.register_sent(loop_id);
+        // if forward() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.delay_forwarding_channel
+            .forward((loop_packet, None))
+            .expect("the delay-forwarder has died!");
+    }
+
+    pub(crate) async fn run(&mut self, mut shutdown: ShutdownListener) {
+        let mut topology_refresh = time::interval(self.topology_refresh_rate);
+        let mut lost_loops_check = time::interval(self.loop_timeout);
+        let next_loop = time::sleep(sample_poisson_duration(
+            &mut OsRng,
+            self.average_loop_cover_delay,
+        ));
+        tokio::pin!(next_loop);
+
+        loop {
+            tokio::select! {
+                _ = topology_refresh.tick() => self.refresh_topology().await,
+                _ = lost_loops_check.tick() => {
+                    self.loop_tracker.expire_lost(self.loop_timeout);
+                }
+                _ = &mut next_loop => {
+                    self.send_loop();
+                    let next_delay = sample_poisson_duration(&mut OsRng, self.average_loop_cover_delay);
+                    next_loop.as_mut().reset(Instant::now() + next_delay);
+                }
+                _ = shutdown.recv() => {
+                    trace!("the loop cover traffic stream is finished");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn loops_that_did_not_come_back_in_time_are_lost() {
+        time::pause();
+        let metrics = NodeMetrics::new();
+        let tracker = LoopTracker::new(metrics.clone());
+
+        tracker.register_sent(1);
+        tracker.register_sent(2);
+        tracker.register_sent(3);
+        tracker.register_received(1);
+        time::advance(Duration::from_secs(10)).await;
+        tracker.register_sent(4);
+        time::advance(Duration::from_secs(5)).await;
+
+        assert_eq!(2, tracker.expire_lost(Duration::from_secs(12)));
+        // it arrived too late
+        tracker.register_received(2);
+        tracker.register_received(4);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(4, snapshot.loops_sent);
+        assert_eq!(2, snapshot.loops_received);
+        assert_eq!(2, snapshot.loops_lost);
+    }
+}
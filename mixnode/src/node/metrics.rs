@@ -0,0 +1,295 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Upper bounds, in seconds, of the buckets of the sphinx packet processing latency histogram.
+pub(crate) const PROCESSING_LATENCY_BUCKETS: &[f64] = &[
+    0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.025, 0.1,
+];
+
+/// Upper bounds, in seconds, of the buckets of the verloc round-trip time histogram.
+pub(crate) const VERLOC_RTT_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.2, 0.3, 0.5, 1.0, 2.5];
+
+/// Histogram with fixed buckets that can be updated concurrently.
+struct Histogram {
+    bounds: &'static [f64],
+    // the last bucket holds all values larger than the last bound
+    buckets: Vec<AtomicU64>,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: Duration) {
+        let bucket = bucket_index(self.bounds, value);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(value.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let counts = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        HistogramSnapshot::from_counts(
+            self.bounds,
+            counts,
+            Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+fn bucket_index(bounds: &[f64], value: Duration) -> usize {
+    let value = value.as_secs_f64();
+    bounds
+        .iter()
+        .position(|&bound| value <= bound)
+        .unwrap_or(bounds.len())
+}
+
+/// Point-in-time state of a histogram, with cumulative bucket counts as expected by Prometheus.
+pub(crate) struct HistogramSnapshot {
+    /// Upper bound of each bucket alongside the number of values not larger than it.
+    pub(crate) buckets: Vec<(f64, u64)>,
+    pub(crate) count: u64,
+    pub(crate) sum: Duration,
+}
+
+impl HistogramSnapshot {
+    fn from_counts(bounds: &[f64], counts: Vec<u64>, sum: Duration) -> Self {
+        let mut cumulative = 0;
+        let mut buckets = Vec::with_capacity(bounds.len());
+        for (&bound, count) in bounds.iter().zip(&counts) {
+            cumulative += count;
+            buckets.push((bound, cumulative));
+        }
+
+        HistogramSnapshot {
+            buckets,
+            count: counts.iter().sum(),
+            sum,
+        }
+    }
+
+    /// Builds the histogram out of already collected values.
+    pub(crate) fn from_values(bounds: &[f64], values: &[Duration]) -> Self {
+        let mut counts = vec![0; bounds.len() + 1];
+        for value in values {
+            counts[bucket_index(bounds, *value)] += 1;
+        }
+        HistogramSnapshot::from_counts(bounds, counts, values.iter().sum())
+    }
+}
+
+struct NodeMetricsInner {
+    active_connections: AtomicU64,
+    delay_queue_depth: AtomicU64,
+    delay_queue_high_water_mark: AtomicU64,
+    processing_latency: Histogram,
+    loops_sent: AtomicU64,
+    loops_received: AtomicU64,
+    loops_lost: AtomicU64,
+    connections_rejected_over_ip_limit: AtomicU64,
+    connections_rejected_unknown_peer: AtomicU64,
+    packets_rate_limited: AtomicU64,
+    malformed_frames: AtomicU64,
+}
+
+/// Metrics of the mixnode, other than the packet counts kept by the node stats, that get
+/// exposed to the monitoring systems. Cloning it is cheap as the underlying data is shared.
+#[derive(Clone)]
+pub(crate) struct NodeMetrics {
+    started_at: Instant,
+    inner: Arc<NodeMetricsInner>,
+}
+
+impl NodeMetrics {
+    pub(crate) fn new() -> Self {
+        NodeMetrics {
+            started_at: Instant::now(),
+            inner: Arc::new(NodeMetricsInner {
+                active_connections: AtomicU64::new(0),
+                delay_queue_depth: AtomicU64::new(0),
+                delay_queue_high_water_mark: AtomicU64::new(0),
+                processing_latency: Histogram::new(PROCESSING_LATENCY_BUCKETS),
+                loops_sent: AtomicU64::new(0),
+                loops_received: AtomicU64::new(0),
+                loops_lost: AtomicU64::new(0),
+                connections_rejected_over_ip_limit: AtomicU64::new(0),
+                connections_rejected_unknown_peer: AtomicU64::new(0),
+                packets_rate_limited: AtomicU64::new(0),
+                malformed_frames: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub(crate) fn connection_opened(&self) {
+        self.inner
+            .active_connections
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_closed(&self) {
+        self.inner
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Updates the total number of packets currently waiting in the delay queues
+    /// with the change of the length of one of them.
+    pub(crate) fn update_delay_queue_depth(&self, previous: usize, current: usize) {
+        if current >= previous {
+            let increase = (current - previous) as u64;
+            let total = self
+                .inner
+                .delay_queue_depth
+                .fetch_add(increase, Ordering::Relaxed)
+                + increase;
+            self.inner
+                .delay_queue_high_water_mark
+                .fetch_max(total, Ordering::Relaxed);
+        } else {
+            self.inner
+                .delay_queue_depth
+                .fetch_sub((previous - current) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Records time it took to unwrap a single received sphinx packet.
+    pub(crate) fn record_processing_latency(&self, latency: Duration) {
+        self.inner.processing_latency.observe(latency)
+    }
+
+    pub(crate) fn loop_sent(&self) {
+        self.inner.loops_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn loop_received(&self) {
+        self.inner.loops_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn loops_lost(&self, count: usize) {
+        self.inner
+            .loops_lost
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Records a connection that got refused, either because its address has too many
+    /// connections open already or because it's not one of the expected peers.
+    pub(crate) fn connection_rejected(&self, unknown_peer: bool) {
+        if unknown_peer {
+            self.inner
+                .connections_rejected_unknown_peer
+                .fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.inner
+                .connections_rejected_over_ip_limit
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn packet_rate_limited(&self) {
+        self.inner
+            .packets_rate_limited
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn malformed_frame(&self) {
+        self.inner.malformed_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> NodeMetricsSnapshot {
+        NodeMetricsSnapshot {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            active_connections: self.inner.active_connections.load(Ordering::Relaxed),
+            delay_queue_depth: self.inner.delay_queue_depth.load(Ordering::Relaxed),
+            delay_queue_high_water_mark: self
+                .inner
+                .delay_queue_high_water_mark
+                .load(Ordering::Relaxed),
+            processing_latency: self.inner.processing_latency.snapshot(),
+            loops_sent: self.inner.loops_sent.load(Ordering::Relaxed),
+            loops_received: self.inner.loops_received.load(Ordering::Relaxed),
+            loops_lost: self.inner.loops_lost.load(Ordering::Relaxed),
+            connections_rejected_over_ip_limit: self
+                .inner
+                .connections_rejected_over_ip_limit
+                .load(Ordering::Relaxed),
+            connections_rejected_unknown_peer: self
+                .inner
+                .connections_rejected_unknown_peer
+                .load(Ordering::Relaxed),
+            packets_rate_limited: self.inner.packets_rate_limited.load(Ordering::Relaxed),
+            malformed_frames: self.inner.malformed_frames.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) struct NodeMetricsSnapshot {
+    pub(crate) uptime_secs: u64,
+    pub(crate) active_connections: u64,
+    pub(crate) delay_queue_depth: u64,
+    pub(crate) delay_queue_high_water_mark: u64,
+    pub(crate) processing_latency: HistogramSnapshot,
+    pub(crate) loops_sent: u64,
+    pub(crate) loops_received: u64,
+    pub(crate) loops_lost: u64,
+    pub(crate) connections_rejected_over_ip_limit: u64,
+    pub(crate) connections_rejected_unknown_peer: u64,
+    pub(crate) packets_rate_limited: u64,
+    pub(crate) malformed_frames: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[0.001, 0.01]);
+        histogram.observe(Duration::from_micros(500));
+        histogram.observe(Duration::from_millis(1));
+        histogram.observe(Duration::from_millis(5));
+        histogram.observe(Duration::from_secs(1));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(vec![(0.001, 2), (0.01, 3)], snapshot.buckets);
+        assert_eq!(4, snapshot.count);
+        assert_eq!(Duration::from_micros(1_006_500), snapshot.sum);
+    }
+
+    #[test]
+    fn histogram_can_be_built_from_values() {
+        let values = [Duration::from_millis(20), Duration::from_millis(300)];
+        let snapshot = HistogramSnapshot::from_values(&[0.1, 1.0], &values);
+
+        assert_eq!(vec![(0.1, 1), (1.0, 2)], snapshot.buckets);
+        assert_eq!(2, snapshot.count);
+        assert_eq!(Duration::from_millis(320), snapshot.sum);
+    }
+
+    #[test]
+    fn delay_queue_high_water_mark_is_kept_across_shards() {
+        let metrics = NodeMetrics::new();
+        metrics.update_delay_queue_depth(0, 5);
+        metrics.update_delay_queue_depth(0, 3);
+        metrics.update_delay_queue_depth(5, 1);
+        metrics.update_delay_queue_depth(3, 4);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(5, snapshot.delay_queue_depth);
+        assert_eq!(8, snapshot.delay_queue_high_water_mark);
+    }
+}
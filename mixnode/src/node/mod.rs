@@ -6,32 +6,79 @@ use crate::config::persistence::pathfinder::MixNodePathfinder;
 use crate::config::Config;
 use crate::node::http::{
     description::description,
+    metrics::metrics,
     not_found,
     stats::stats,
     verloc::{verloc as verlocRoute, VerlocState},
 };
+use crate::node::listener::allowed_peers::AllowedPeersRefresher;
 use crate::node::listener::connection_handler::packet_processing::PacketProcessor;
+use crate::node::listener::connection_handler::processing_pool::{
+    ProcessingPool, ReceivedPacketHandler,
+};
 use crate::node::listener::connection_handler::ConnectionHandler;
+use crate::node::listener::connection_limiter::{ConnectionLimiter, ConnectionLimits};
 use crate::node::listener::Listener;
+use crate::node::loop_cover::{LoopCoverTrafficStream, LoopTracker};
+use crate::node::metrics::NodeMetrics;
 use crate::node::node_description::NodeDescription;
 use crate::node::node_statistics::SharedNodeStats;
-use crate::node::packet_delayforwarder::{DelayForwarder, PacketDelayForwardSender};
+use crate::node::packet_delayforwarder::{
+    DelayForwarder, DelayQueueLimits, PacketDelayForwardSender,
+};
+use crate::node::self_test::{SelfTest, SelfTestReport, TopologySource};
 use ::crypto::asymmetric::{encryption, identity};
+use config::reload::ConfigReloader;
 use config::NymConfig;
 use log::{error, info, warn};
+use mixnet_client::PacketForwardingConfig;
 use mixnode_common::verloc::{self, AtomicVerlocResult, VerlocMeasurer};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
+use std::time::Duration;
+use task::{ShutdownListener, ShutdownNotifier};
+use tokio::sync::watch;
 use version_checker::parse_version;
 
+mod config_reload;
 mod http;
-mod listener;
+pub(crate) mod listener;
+pub(crate) mod loop_cover;
+pub(crate) mod metrics;
 pub(crate) mod node_description;
-mod node_statistics;
-mod packet_delayforwarder;
+pub(crate) mod node_statistics;
+pub(crate) mod packet_delayforwarder;
+pub(crate) mod self_test;
+
+fn available_cores() -> usize {
+    num_cpus::get()
+}
+
+// 0 means "use all available cores"
+fn worker_count(configured: usize) -> usize {
+    if configured == 0 {
+        available_cores()
+    } else {
+        configured
+    }
+}
+
+// splits the total limit between the shards, 0 means "no limit"
+fn shard_limit(total: usize, shards: usize) -> Option<usize> {
+    if total == 0 {
+        None
+    } else {
+        Some((total + shards - 1) / shards)
+    }
+}
+
+// 0 means "no limit"
+fn limit(value: usize) -> Option<usize> {
+    (value > 0).then(|| value)
+}
 
 // the MixNode will live for whole duration of this program
 pub struct MixNode {
@@ -91,6 +138,26 @@ impl MixNode {
         verification_code
     }
 
+    /// Checks whether this, already running, mixnode is reachable under the addresses from its config
+    /// and whether sphinx packets can get routed through it.
+    pub(crate) async fn self_test(
+        &self,
+        topology_source: TopologySource,
+        client_port: u16,
+        timeout: Duration,
+    ) -> SelfTestReport {
+        SelfTest::new(
+            &self.config,
+            *self.identity_keypair.public_key(),
+            *self.sphinx_keypair.public_key(),
+            topology_source,
+            client_port,
+            timeout,
+        )
+        .run()
+        .await
+    }
+
     /// Prints relevant node details to the console
     pub(crate) fn print_node_details(&self) {
         println!(
@@ -124,6 +191,7 @@ impl MixNode {
         &self,
         atomic_verloc_result: AtomicVerlocResult,
         node_stats_pointer: SharedNodeStats,
+        node_metrics: NodeMetrics,
     ) {
         info!("Starting HTTP API on http://localhost:8000");
 
@@ -139,70 +207,200 @@ impl MixNode {
         tokio::spawn(async move {
             rocket::build()
                 .configure(config)
-                .mount("/", routes![verlocRoute, description, stats])
+                .mount("/", routes![verlocRoute, description, stats, metrics])
                 .register("/", catchers![not_found])
                 .manage(verloc_state)
                 .manage(descriptor)
                 .manage(node_stats_pointer)
+                .manage(node_metrics)
                 .launch()
                 .await
         });
     }
 
-    fn start_node_stats_controller(&self) -> (SharedNodeStats, node_statistics::UpdateSender) {
+    fn start_node_stats_controller(
+        &self,
+        shutdown: ShutdownListener,
+    ) -> (
+        SharedNodeStats,
+        node_statistics::UpdateSender,
+        node_statistics::IntervalsUpdater,
+    ) {
         info!("Starting node stats controller...");
         let controller = node_statistics::Controller::new(
             self.config.get_node_stats_logging_delay(),
             self.config.get_node_stats_updating_delay(),
         );
         let node_stats_pointer = controller.get_node_stats_data_pointer();
-        let update_sender = controller.start();
+        let intervals_updater = controller.get_intervals_updater();
+        let update_sender = controller.start(shutdown);
 
-        (node_stats_pointer, update_sender)
+        (node_stats_pointer, update_sender, intervals_updater)
     }
 
     fn start_socket_listener(
         &self,
         node_stats_update_sender: node_statistics::UpdateSender,
         delay_forwarding_channel: PacketDelayForwardSender,
+        node_metrics: NodeMetrics,
+        loop_tracker: LoopTracker,
+        shutdown: ShutdownListener,
     ) {
         info!("Starting socket listener...");
 
         let packet_processor =
             PacketProcessor::new(self.sphinx_keypair.private_key(), node_stats_update_sender);
 
-        let connection_handler = ConnectionHandler::new(packet_processor, delay_forwarding_channel);
+        let packet_handler = ReceivedPacketHandler::new(
+            packet_processor,
+            delay_forwarding_channel,
+            node_metrics.clone(),
+            loop_tracker,
+            self.identity_keypair
+                .public_key()
+                .derive_destination_address(),
+        );
+        let processing_workers = worker_count(self.config.get_packet_processing_workers());
+        info!("Using {} sphinx processing workers", processing_workers);
+        let processing_pool = ProcessingPool::start(
+            processing_workers,
+            self.config.get_packet_processing_queue_size(),
+            packet_handler,
+        );
+
+        let connection_handler = ConnectionHandler::new(processing_pool, node_metrics.clone());
+        let connection_limiter = self.start_connection_limiter(node_metrics, shutdown.clone());
 
         let listening_address = SocketAddr::new(
             self.config.get_listening_address(),
             self.config.get_mix_port(),
         );
 
-        Listener::new(listening_address).start(connection_handler);
+        Listener::new(listening_address, connection_limiter).start(connection_handler, shutdown);
+    }
+
+    fn start_connection_limiter(
+        &self,
+        node_metrics: NodeMetrics,
+        shutdown: ShutdownListener,
+    ) -> ConnectionLimiter {
+        let limits = ConnectionLimits {
+            max_connections_per_ip: limit(self.config.get_max_connections_per_ip()),
+            max_packets_per_connection_per_second: limit(
+                self.config.get_max_packets_per_connection_per_second(),
+            ),
+        };
+        let connection_limiter = ConnectionLimiter::new(limits, node_metrics);
+
+        if self.config.get_accept_topology_peers_only() {
+            info!("Only the nodes preceding us in the network topology are going to be allowed to connect");
+            let refresher = AllowedPeersRefresher::new(
+                *self.identity_keypair.public_key(),
+                self.config.get_version().to_owned(),
+                self.config.get_validator_api_endpoints(),
+                self.config.get_topology_refresh_rate(),
+                connection_limiter.clone(),
+            );
+            tokio::spawn(async move { refresher.run(shutdown).await });
+        }
+
+        connection_limiter
     }
 
     fn start_packet_delay_forwarder(
         &mut self,
         node_stats_update_sender: node_statistics::UpdateSender,
-    ) -> PacketDelayForwardSender {
-        info!("Starting packet delay-forwarder...");
-
-        let client_config = mixnet_client::Config::new(
-            self.config.get_packet_forwarding_initial_backoff(),
-            self.config.get_packet_forwarding_maximum_backoff(),
-            self.config.get_initial_connection_timeout(),
-            self.config.get_maximum_connection_buffer_size(),
-        );
+        node_metrics: NodeMetrics,
+        shutdown: &ShutdownNotifier,
+    ) -> (
+        PacketDelayForwardSender,
+        watch::Sender<mixnet_client::Config>,
+    ) {
+        let shards = worker_count(self.config.get_delay_forwarder_shards());
+        info!("Starting {} packet delay-forwarders...", shards);
 
-        let mut packet_forwarder = DelayForwarder::new(
-            mixnet_client::Client::new(client_config),
-            node_stats_update_sender,
-        );
+        let (client_config_sender, client_config) =
+            watch::channel(self.config.mixnet_client_config());
 
-        let packet_sender = packet_forwarder.sender();
+        let limits = DelayQueueLimits {
+            max_packets: shard_limit(self.config.get_maximum_delay_queue_size(), shards),
+            max_bytes: shard_limit(self.config.get_maximum_delay_queue_memory(), shards),
+        };
 
-        tokio::spawn(async move { packet_forwarder.run().await });
-        packet_sender
+        let shard_senders = (0..shards)
+            .map(|_| {
+                let mut mixnet_client =
+                    mixnet_client::Client::new_with_config_updates(client_config.clone());
+                mixnet_client.with_shutdown(shutdown.subscribe());
+                let (mut packet_forwarder, shard_sender) = DelayForwarder::new(
+                    mixnet_client,
+                    node_stats_update_sender.clone(),
+                    node_metrics.clone(),
+                    limits,
+                    self.config.get_delay_forwarder_queue_size(),
+                );
+                let shutdown_listener = shutdown.subscribe();
+                tokio::spawn(async move { packet_forwarder.run(shutdown_listener).await });
+                shard_sender
+            })
+            .collect();
+
+        (
+            PacketDelayForwardSender::new(shard_senders, node_stats_update_sender),
+            client_config_sender,
+        )
+    }
+
+    fn start_config_reloader(
+        &self,
+        mixnet_client_config: watch::Sender<mixnet_client::Config>,
+        node_stats_intervals: node_statistics::IntervalsUpdater,
+        shutdown: ShutdownListener,
+    ) {
+        let reloader = match ConfigReloader::<Config, _>::new(
+            "mixnode",
+            self.config.get_id(),
+            config_reload::is_live,
+            config_reload::apply_changes(mixnet_client_config, node_stats_intervals),
+        ) {
+            Ok(reloader) => reloader,
+            Err(err) => {
+                warn!(
+                    "Failed to load the config file - its changes are going to require a restart (Error was: {})",
+                    err
+                );
+                return;
+            }
+        };
+
+        info!("Starting config reloader - send SIGHUP to the mixnode to apply the changes made to its config file");
+        tokio::spawn(async move { reloader.run(shutdown).await });
+    }
+
+    fn start_loop_cover_traffic_stream(
+        &self,
+        delay_forwarding_channel: PacketDelayForwardSender,
+        loop_tracker: LoopTracker,
+        shutdown: ShutdownListener,
+    ) {
+        if self.config.get_average_loop_cover_delay().is_zero() {
+            info!("Loop cover traffic is disabled");
+            return;
+        }
+        info!("Starting loop cover traffic stream...");
+
+        let mut stream = LoopCoverTrafficStream::new(
+            *self.identity_keypair.public_key(),
+            self.config.get_version().to_owned(),
+            self.config.get_validator_api_endpoints(),
+            self.config.get_average_loop_cover_delay(),
+            self.config.get_average_loop_packet_delay(),
+            self.config.get_loop_cover_timeout(),
+            self.config.get_topology_refresh_rate(),
+            delay_forwarding_channel,
+            loop_tracker,
+        );
+        tokio::spawn(async move { stream.run(shutdown).await });
     }
 
     fn start_verloc_measurements(&self) -> AtomicVerlocResult {
@@ -235,6 +433,7 @@ impl MixNode {
             .tested_nodes_batch_size(self.config.get_measurement_tested_nodes_batch_size())
             .testing_interval(self.config.get_measurement_testing_interval())
             .retry_timeout(self.config.get_measurement_retry_timeout())
+            .history_retention(self.config.get_measurement_history_retention())
             .validator_api_urls(self.config.get_validator_api_endpoints())
             .build();
 
@@ -268,16 +467,17 @@ impl MixNode {
             .map(|node| node.mix_node.identity_key.clone())
     }
 
-    async fn wait_for_interrupt(&self) {
-        if let Err(e) = tokio::signal::ctrl_c().await {
-            error!(
-                "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
-                e
-            );
-        }
+    async fn wait_for_interrupt(&self, shutdown: ShutdownNotifier) {
+        task::wait_for_signal().await;
+
+        let drain_timeout = self.config.get_shutdown_drain_timeout();
         println!(
-            "Received SIGINT - the mixnode will terminate now (threads are not yet nicely stopped, if you see stack traces that's alright)."
+            "The mixnode is shutting down - forwarding already received packets for up to {:?}...",
+            drain_timeout
         );
+        if shutdown.shutdown(drain_timeout).await {
+            info!("The mixnode has been shut down gracefully")
+        }
     }
 
     pub async fn run(&mut self) {
@@ -295,15 +495,38 @@ impl MixNode {
             }
         }
 
-        let (node_stats_pointer, node_stats_update_sender) = self.start_node_stats_controller();
-        let delay_forwarding_channel =
-            self.start_packet_delay_forwarder(node_stats_update_sender.clone());
-        self.start_socket_listener(node_stats_update_sender, delay_forwarding_channel);
+        let shutdown = ShutdownNotifier::new();
+        let node_metrics = NodeMetrics::new();
+        let loop_tracker = LoopTracker::new(node_metrics.clone());
+        let (node_stats_pointer, node_stats_update_sender, node_stats_intervals) =
+            self.start_node_stats_controller(shutdown.subscribe());
+        let (delay_forwarding_channel, mixnet_client_config) = self.start_packet_delay_forwarder(
+            node_stats_update_sender.clone(),
+            node_metrics.clone(),
+            &shutdown,
+        );
+        self.start_config_reloader(
+            mixnet_client_config,
+            node_stats_intervals,
+            shutdown.subscribe(),
+        );
+        self.start_socket_listener(
+            node_stats_update_sender,
+            delay_forwarding_channel.clone(),
+            node_metrics.clone(),
+            loop_tracker.clone(),
+            shutdown.subscribe(),
+        );
+        self.start_loop_cover_traffic_stream(
+            delay_forwarding_channel,
+            loop_tracker,
+            shutdown.subscribe(),
+        );
 
         let atomic_verloc_results = self.start_verloc_measurements();
-        self.start_http_api(atomic_verloc_results, node_stats_pointer);
+        self.start_http_api(atomic_verloc_results, node_stats_pointer, node_metrics);
 
         info!("Finished nym mixnode startup procedure - it should now be able to receive mix traffic!");
-        self.wait_for_interrupt().await
+        self.wait_for_interrupt(shutdown).await
     }
 }
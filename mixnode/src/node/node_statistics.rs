@@ -7,10 +7,12 @@ use std::ops::DerefMut;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use tokio::sync::{RwLock, RwLockReadGuard};
+use task::ShutdownListener;
+use tokio::sync::{watch, RwLock, RwLockReadGuard};
 
 // convenience aliases
 type PacketsMap = HashMap<String, u64>;
+type DropReasonsMap = HashMap<DropReason, u64>;
 type PacketDataReceiver = mpsc::UnboundedReceiver<PacketEvent>;
 type PacketDataSender = mpsc::UnboundedSender<PacketEvent>;
 
@@ -29,9 +31,11 @@ impl SharedNodeStats {
                 packets_received_since_startup: 0,
                 packets_sent_since_startup: HashMap::new(),
                 packets_explicitly_dropped_since_startup: HashMap::new(),
+                packets_dropped_by_reason_since_startup: HashMap::new(),
                 packets_received_since_last_update: 0,
                 packets_sent_since_last_update: HashMap::new(),
                 packets_explicitly_dropped_since_last_update: HashMap::new(),
+                packets_dropped_by_reason_since_last_update: HashMap::new(),
             })),
         }
     }
@@ -41,6 +45,7 @@ impl SharedNodeStats {
         new_received: u64,
         new_sent: PacketsMap,
         new_dropped: PacketsMap,
+        new_dropped_by_reason: DropReasonsMap,
     ) {
         let mut guard = self.inner.write().await;
         let snapshot_time = SystemTime::now();
@@ -58,14 +63,22 @@ impl SharedNodeStats {
 
         for (mix, count) in new_dropped.iter() {
             *guard
-                .packets_explicitly_dropped_since_last_update
+                .packets_explicitly_dropped_since_startup
                 .entry(mix.clone())
                 .or_insert(0) += *count;
         }
 
+        for (reason, count) in new_dropped_by_reason.iter() {
+            *guard
+                .packets_dropped_by_reason_since_startup
+                .entry(*reason)
+                .or_insert(0) += *count;
+        }
+
         guard.packets_received_since_last_update = new_received;
         guard.packets_sent_since_last_update = new_sent;
         guard.packets_explicitly_dropped_since_last_update = new_dropped;
+        guard.packets_dropped_by_reason_since_last_update = new_dropped_by_reason;
     }
 
     pub(crate) async fn clone_data(&self) -> NodeStats {
@@ -93,6 +106,9 @@ pub(crate) struct NodeStats {
     // we know for sure we dropped packets to those destinations
     packets_explicitly_dropped_since_startup: PacketsMap,
 
+    // the same drops, but grouped by why they happened
+    packets_dropped_by_reason_since_startup: DropReasonsMap,
+
     packets_received_since_last_update: u64,
 
     // note: sent does not imply forwarded. We don't know if it was delivered successfully
@@ -100,9 +116,28 @@ pub(crate) struct NodeStats {
 
     // we know for sure we dropped packets to those destinations
     packets_explicitly_dropped_since_last_update: PacketsMap,
+
+    // the same drops, but grouped by why they happened
+    packets_dropped_by_reason_since_last_update: DropReasonsMap,
 }
 
 impl NodeStats {
+    pub(crate) fn total_packets_received(&self) -> u64 {
+        self.packets_received_since_startup
+    }
+
+    pub(crate) fn total_packets_sent(&self) -> u64 {
+        self.packets_sent_since_startup.values().sum()
+    }
+
+    pub(crate) fn total_packets_explicitly_dropped(&self) -> u64 {
+        self.packets_explicitly_dropped_since_startup.values().sum()
+    }
+
+    pub(crate) fn total_packets_dropped_by_reason(&self) -> &DropReasonsMap {
+        &self.packets_dropped_by_reason_since_startup
+    }
+
     pub(crate) fn simplify(&self) -> NodeStatsSimple {
         NodeStatsSimple {
             update_time: self.update_time,
@@ -148,10 +183,38 @@ pub(crate) struct NodeStatsSimple {
     packets_explicitly_dropped_since_last_update: u64,
 }
 
+/// Reason for which the mixnode has explicitly dropped a packet instead of forwarding it.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DropReason {
+    /// The buffer of the connection to the next hop was full.
+    ConnectionBufferFull,
+
+    /// The delay queue has reached either its maximum size or its memory budget.
+    DelayQueueFull,
+
+    /// The delay queue got close to its limits and the packet got randomly chosen to be dropped early.
+    DelayQueueCongested,
+
+    /// The delay-forwarder has fallen behind and its queue of packets waiting to get delayed was full.
+    DelayForwarderFull,
+}
+
+impl DropReason {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            DropReason::ConnectionBufferFull => "connection_buffer_full",
+            DropReason::DelayQueueFull => "delay_queue_full",
+            DropReason::DelayQueueCongested => "delay_queue_congested",
+            DropReason::DelayForwarderFull => "delay_forwarder_full",
+        }
+    }
+}
+
 pub(crate) enum PacketEvent {
     Sent(String),
     Received,
-    Dropped(String),
+    Dropped(String, DropReason),
 }
 
 #[derive(Debug, Clone)]
@@ -164,6 +227,7 @@ struct PacketDataInner {
     received: AtomicU64,
     sent: Mutex<PacketsMap>,
     dropped: Mutex<PacketsMap>,
+    dropped_by_reason: Mutex<DropReasonsMap>,
 }
 
 impl CurrentPacketData {
@@ -173,6 +237,7 @@ impl CurrentPacketData {
                 received: AtomicU64::new(0),
                 sent: Mutex::new(HashMap::new()),
                 dropped: Mutex::new(HashMap::new()),
+                dropped_by_reason: Mutex::new(HashMap::new()),
             }),
         }
     }
@@ -187,21 +252,26 @@ impl CurrentPacketData {
         *receiver_count += 1;
     }
 
-    async fn increment_dropped(&self, destination: String) {
+    async fn increment_dropped(&self, destination: String, reason: DropReason) {
         let mut unlocked = self.inner.dropped.lock().await;
         let dropped_count = unlocked.entry(destination).or_insert(0);
         *dropped_count += 1;
+
+        let mut unlocked_reasons = self.inner.dropped_by_reason.lock().await;
+        *unlocked_reasons.entry(reason).or_insert(0) += 1;
     }
 
-    async fn acquire_and_reset(&self) -> (u64, PacketsMap, PacketsMap) {
+    async fn acquire_and_reset(&self) -> (u64, PacketsMap, PacketsMap, DropReasonsMap) {
         let mut unlocked_sent = self.inner.sent.lock().await;
         let mut unlocked_dropped = self.inner.dropped.lock().await;
+        let mut unlocked_dropped_by_reason = self.inner.dropped_by_reason.lock().await;
         let received = self.inner.received.swap(0, Ordering::SeqCst);
 
         let sent = std::mem::take(unlocked_sent.deref_mut());
         let dropped = std::mem::take(unlocked_dropped.deref_mut());
+        let dropped_by_reason = std::mem::take(unlocked_dropped_by_reason.deref_mut());
 
-        (received, sent, dropped)
+        (received, sent, dropped, dropped_by_reason)
     }
 }
 
@@ -226,8 +296,10 @@ impl UpdateHandler {
                 PacketEvent::Sent(destination) => {
                     self.current_data.increment_sent(destination).await
                 }
-                PacketEvent::Dropped(destination) => {
-                    self.current_data.increment_dropped(destination).await
+                PacketEvent::Dropped(destination, reason) => {
+                    self.current_data
+                        .increment_dropped(destination, reason)
+                        .await
                 }
             }
         }
@@ -259,26 +331,38 @@ impl UpdateSender {
         self.0.unbounded_send(PacketEvent::Received).unwrap()
     }
 
-    pub(crate) fn report_dropped(&self, destination: String) {
+    pub(crate) fn report_dropped(&self, destination: String, reason: DropReason) {
         // in unbounded_send() failed it means that the receiver channel was disconnected
         // and hence something weird must have happened without a way of recovering
         self.0
-            .unbounded_send(PacketEvent::Dropped(destination))
+            .unbounded_send(PacketEvent::Dropped(destination, reason))
             .unwrap()
     }
 }
 
+/// Waits for the current value of `delay`. Returns `false` if the delay got changed in the meantime,
+/// in which case the caller should start waiting for the new value instead.
+async fn wait_for_delay(delay: &mut watch::Receiver<Duration>) -> bool {
+    let current_delay = *delay.borrow();
+    tokio::select! {
+        _ = tokio::time::sleep(current_delay) => true,
+        // if the sender is gone, the delay can't change anymore, so just keep sleeping
+        Ok(_) = delay.changed() => false,
+    }
+}
+
 // Worker that periodically updates the shared node stats from the current packet data buffer that
 // the `UpdateHandler` updates.
+#[derive(Clone)]
 struct StatsUpdater {
-    updating_delay: Duration,
+    updating_delay: watch::Receiver<Duration>,
     current_packet_data: CurrentPacketData,
     current_stats: SharedNodeStats,
 }
 
 impl StatsUpdater {
     fn new(
-        updating_delay: Duration,
+        updating_delay: watch::Receiver<Duration>,
         current_packet_data: CurrentPacketData,
         current_stats: SharedNodeStats,
     ) -> Self {
@@ -291,27 +375,32 @@ impl StatsUpdater {
 
     async fn update_stats(&self) {
         // grab new data since last update
-        let (received, sent, dropped) = self.current_packet_data.acquire_and_reset().await;
-        self.current_stats.update(received, sent, dropped).await;
+        let (received, sent, dropped, dropped_by_reason) =
+            self.current_packet_data.acquire_and_reset().await;
+        self.current_stats
+            .update(received, sent, dropped, dropped_by_reason)
+            .await;
     }
 
-    async fn run(&self) {
+    async fn run(&mut self) {
         loop {
-            tokio::time::sleep(self.updating_delay).await;
-            self.update_stats().await
+            if wait_for_delay(&mut self.updating_delay).await {
+                self.update_stats().await
+            }
         }
     }
 }
 
 // TODO: question: should this data still be logged to the console or should we perhaps remove it
 // since we have the http endpoint now?
+#[derive(Clone)]
 struct PacketStatsConsoleLogger {
-    logging_delay: Duration,
+    logging_delay: watch::Receiver<Duration>,
     stats: SharedNodeStats,
 }
 
 impl PacketStatsConsoleLogger {
-    fn new(logging_delay: Duration, stats: SharedNodeStats) -> Self {
+    fn new(logging_delay: watch::Receiver<Duration>, stats: SharedNodeStats) -> Self {
         PacketStatsConsoleLogger {
             logging_delay,
             stats,
@@ -388,12 +477,31 @@ impl PacketStatsConsoleLogger {
 
     async fn run(&mut self) {
         loop {
-            tokio::time::sleep(self.logging_delay).await;
-            self.log_running_stats().await;
+            if wait_for_delay(&mut self.logging_delay).await {
+                self.log_running_stats().await;
+            }
         }
     }
 }
 
+/// Allows changing the intervals of the already running stats controller.
+#[derive(Clone)]
+pub(crate) struct IntervalsUpdater {
+    logging_delay: Arc<watch::Sender<Duration>>,
+    updating_delay: Arc<watch::Sender<Duration>>,
+}
+
+impl IntervalsUpdater {
+    pub(crate) fn set_logging_delay(&self, logging_delay: Duration) {
+        // the only possible error is all receivers being gone, i.e. the controller not running
+        let _ = self.logging_delay.send(logging_delay);
+    }
+
+    pub(crate) fn set_updating_delay(&self, updating_delay: Duration) {
+        let _ = self.updating_delay.send(updating_delay);
+    }
+}
+
 // basically an easy single entry point to start all of the required tasks
 pub struct Controller {
     /// Responsible for handling data coming from UpdateSender
@@ -410,6 +518,9 @@ pub struct Controller {
 
     /// Pointer to the current node stats
     node_stats: SharedNodeStats,
+
+    /// Allows changing the logging and updating intervals at runtime
+    intervals_updater: IntervalsUpdater,
 }
 
 impl Controller {
@@ -417,20 +528,30 @@ impl Controller {
         let (sender, receiver) = mpsc::unbounded();
         let shared_packet_data = CurrentPacketData::new();
         let shared_node_stats = SharedNodeStats::new();
+        let (logging_delay_sender, logging_delay) = watch::channel(logging_delay);
+        let (updating_delay_sender, updating_delay) = watch::channel(stats_updating_delay);
 
         Controller {
             update_handler: UpdateHandler::new(shared_packet_data.clone(), receiver),
             update_sender: UpdateSender::new(sender),
             console_logger: PacketStatsConsoleLogger::new(logging_delay, shared_node_stats.clone()),
             stats_updater: StatsUpdater::new(
-                stats_updating_delay,
+                updating_delay,
                 shared_packet_data,
                 shared_node_stats.clone(),
             ),
             node_stats: shared_node_stats,
+            intervals_updater: IntervalsUpdater {
+                logging_delay: Arc::new(logging_delay_sender),
+                updating_delay: Arc::new(updating_delay_sender),
+            },
         }
     }
 
+    pub(crate) fn get_intervals_updater(&self) -> IntervalsUpdater {
+        self.intervals_updater.clone()
+    }
+
     pub(crate) fn get_node_stats_data_pointer(&self) -> SharedNodeStats {
         SharedNodeStats {
             inner: Arc::clone(&self.node_stats.inner),
@@ -438,13 +559,22 @@ impl Controller {
     }
 
     // reporter is how node is going to be accessing the metrics data
-    pub(crate) fn start(self) -> UpdateSender {
+    pub(crate) fn start(self, shutdown: ShutdownListener) -> UpdateSender {
         // move out of self
         let mut update_handler = self.update_handler;
-        let stats_updater = self.stats_updater;
+        let mut stats_updater = self.stats_updater;
         let mut console_logger = self.console_logger;
 
-        tokio::spawn(async move { update_handler.run().await });
+        let final_stats_updater = stats_updater.clone();
+        let mut final_console_logger = console_logger.clone();
+        tokio::spawn(async move {
+            // the handler only finishes once all of the senders are gone, i.e. during the shutdown
+            // once nothing else can be reported. Flush whatever is left to the shared stats.
+            update_handler.run().await;
+            final_stats_updater.update_stats().await;
+            final_console_logger.log_running_stats().await;
+            drop(shutdown)
+        });
         tokio::spawn(async move { stats_updater.run().await });
         tokio::spawn(async move { console_logger.run().await });
 
@@ -455,6 +585,7 @@ impl Controller {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use task::ShutdownNotifier;
 
     #[tokio::test]
     async fn node_stats_reported_are_received() {
@@ -463,7 +594,8 @@ mod tests {
         let node_stats_controller = Controller::new(logging_delay, stats_updating_delay);
 
         let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
-        let update_sender = node_stats_controller.start();
+        let shutdown = ShutdownNotifier::new();
+        let update_sender = node_stats_controller.start(shutdown.subscribe());
         tokio::time::pause();
 
         // Pass input
@@ -486,4 +618,90 @@ mod tests {
         assert_eq!(&stats.packets_received_since_startup, &0u64);
         assert!(&stats.packets_explicitly_dropped_since_startup.is_empty());
     }
+
+    #[tokio::test]
+    async fn node_stats_track_drop_reasons() {
+        let node_stats_controller =
+            Controller::new(Duration::from_millis(20), Duration::from_millis(10));
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let shutdown = ShutdownNotifier::new();
+        let update_sender = node_stats_controller.start(shutdown.subscribe());
+        tokio::time::pause();
+
+        update_sender.report_dropped("foo".to_string(), DropReason::DelayQueueFull);
+        update_sender.report_dropped("bar".to_string(), DropReason::DelayQueueFull);
+        update_sender.report_dropped("foo".to_string(), DropReason::ConnectionBufferFull);
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(3, stats.total_packets_explicitly_dropped());
+        assert_eq!(
+            Some(&2),
+            stats
+                .total_packets_dropped_by_reason()
+                .get(&DropReason::DelayQueueFull)
+        );
+        assert_eq!(
+            Some(&1),
+            stats
+                .total_packets_dropped_by_reason()
+                .get(&DropReason::ConnectionBufferFull)
+        );
+        assert_eq!(
+            None,
+            stats
+                .total_packets_dropped_by_reason()
+                .get(&DropReason::DelayQueueCongested)
+        );
+    }
+
+    #[tokio::test]
+    async fn node_stats_are_flushed_on_shutdown() {
+        // neither of the periodic tasks is going to run during the test
+        let node_stats_controller =
+            Controller::new(Duration::from_secs(3600), Duration::from_secs(3600));
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let shutdown = ShutdownNotifier::new();
+        let update_sender = node_stats_controller.start(shutdown.subscribe());
+
+        update_sender.report_received();
+        update_sender.report_sent("foo".to_string());
+        drop(update_sender);
+
+        assert!(shutdown.shutdown(Duration::from_secs(5)).await);
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(1, stats.total_packets_received());
+        assert_eq!(1, stats.total_packets_sent());
+    }
+
+    #[tokio::test]
+    async fn updating_delay_can_be_changed_while_running() {
+        let node_stats_controller =
+            Controller::new(Duration::from_secs(3600), Duration::from_secs(3600));
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let intervals_updater = node_stats_controller.get_intervals_updater();
+        let shutdown = ShutdownNotifier::new();
+        let update_sender = node_stats_controller.start(shutdown.subscribe());
+        tokio::time::pause();
+
+        update_sender.report_sent("foo".to_string());
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(0, node_stats_pointer.read().await.total_packets_sent());
+
+        intervals_updater.set_updating_delay(Duration::from_millis(10));
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(1, node_stats_pointer.read().await.total_packets_sent());
+    }
 }
@@ -1,20 +1,139 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::node::node_statistics::UpdateSender;
+use crate::node::metrics::NodeMetrics;
+use crate::node::node_statistics::{DropReason, UpdateSender};
 use futures::channel::mpsc;
 use futures::StreamExt;
+use log::{info, trace};
 use nonexhaustive_delayqueue::{Expired, NonExhaustiveDelayQueue, TimerError};
 use nymsphinx::forwarding::packet::MixPacket;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::io;
+use task::ShutdownListener;
 use tokio::time::Instant;
 
 // Delay + MixPacket vs Instant + MixPacket
 
 // rather than using Duration directly, we use an Instant, this way we minimise skew due to
 // time packet spent waiting in the queue to get delayed
-pub(crate) type PacketDelayForwardSender = mpsc::UnboundedSender<(MixPacket, Option<Instant>)>;
-type PacketDelayForwardReceiver = mpsc::UnboundedReceiver<(MixPacket, Option<Instant>)>;
+type DelayedPacket = (MixPacket, Option<Instant>);
+pub(crate) type PacketDelayForwardShardSender = mpsc::Sender<DelayedPacket>;
+type PacketDelayForwardReceiver = mpsc::Receiver<DelayedPacket>;
+
+/// Fraction of the delay queue limits above which the packets start being randomly dropped.
+const EARLY_DROP_THRESHOLD: f64 = 0.8;
+
+/// Limits on the contents of a single delay queue. Once the queue fills beyond
+/// `EARLY_DROP_THRESHOLD` of either of them, new packets get dropped with the probability
+/// growing linearly up to 1 at the limit itself (i.e. random early drop).
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DelayQueueLimits {
+    /// Maximum number of packets in the queue.
+    pub(crate) max_packets: Option<usize>,
+
+    /// Maximum total size, in bytes, of all packets in the queue.
+    pub(crate) max_bytes: Option<usize>,
+}
+
+impl DelayQueueLimits {
+    /// Determines whether a new packet of the specified size should be dropped rather than
+    /// inserted into the queue. `random_sample` is expected to be uniformly drawn from [0, 1).
+    fn drop_reason(
+        &self,
+        queued_packets: usize,
+        queued_bytes: usize,
+        packet_len: usize,
+        random_sample: f64,
+    ) -> Option<DropReason> {
+        let packets_fill = self
+            .max_packets
+            .map(|max| (queued_packets + 1) as f64 / max as f64)
+            .unwrap_or_default();
+        let bytes_fill = self
+            .max_bytes
+            .map(|max| (queued_bytes + packet_len) as f64 / max as f64)
+            .unwrap_or_default();
+        let fill = packets_fill.max(bytes_fill);
+
+        if fill > 1.0 {
+            Some(DropReason::DelayQueueFull)
+        } else if fill > EARLY_DROP_THRESHOLD {
+            let drop_probability = (fill - EARLY_DROP_THRESHOLD) / (1.0 - EARLY_DROP_THRESHOLD);
+            if random_sample < drop_probability {
+                Some(DropReason::DelayQueueCongested)
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// Error returned when the delay-forwarder a packet was meant for is no longer running.
+#[derive(Debug)]
+pub(crate) struct DelayForwarderClosedError;
+
+/// Distributes packets between multiple instances of `DelayForwarder`. All packets destined for
+/// the same next hop always go to the same shard, so that each connection is only ever
+/// maintained by a single shard.
+#[derive(Clone)]
+pub(crate) struct PacketDelayForwardSender {
+    shards: Vec<PacketDelayForwardShardSender>,
+    node_stats_update_sender: UpdateSender,
+}
+
+impl PacketDelayForwardSender {
+    pub(crate) fn new(
+        shards: Vec<PacketDelayForwardShardSender>,
+        node_stats_update_sender: UpdateSender,
+    ) -> Self {
+        assert!(
+            !shards.is_empty(),
+            "there must exist at least a single delay-forwarder"
+        );
+        PacketDelayForwardSender {
+            shards,
+            node_stats_update_sender,
+        }
+    }
+
+    fn shard_index(&self, packet: &MixPacket) -> usize {
+        if self.shards.len() == 1 {
+            return 0;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        packet.next_hop().hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    /// Hands the packet over to the delay-forwarder responsible for its next hop. If that
+    /// forwarder has fallen behind and its queue is full, the packet gets dropped rather than
+    /// buffered without any limit.
+    pub(crate) fn forward(
+        &mut self,
+        packet: DelayedPacket,
+    ) -> Result<(), DelayForwarderClosedError> {
+        let shard = self.shard_index(&packet.0);
+        match self.shards[shard].try_send(packet) {
+            Ok(_) => Ok(()),
+            Err(err) if err.is_full() => {
+                let next_hop = err.into_inner().0.next_hop();
+                trace!(
+                    "dropping packet to {} - the delay-forwarder is full",
+                    next_hop
+                );
+                self.node_stats_update_sender
+                    .report_dropped(next_hop.to_string(), DropReason::DelayForwarderFull);
+                Ok(())
+            }
+            Err(_) => Err(DelayForwarderClosedError),
+        }
+    }
+}
 
 /// Entity responsible for delaying received sphinx packet and forwarding it to next node.
 pub(crate) struct DelayForwarder<C>
@@ -23,29 +142,49 @@ where
 {
     delay_queue: NonExhaustiveDelayQueue<MixPacket>,
     mixnet_client: C,
-    packet_sender: PacketDelayForwardSender,
     packet_receiver: PacketDelayForwardReceiver,
     node_stats_update_sender: UpdateSender,
+    node_metrics: NodeMetrics,
+    limits: DelayQueueLimits,
+    /// Total size of all packets currently in the delay queue.
+    queued_bytes: usize,
+    /// Length of the delay queue as last reported to the node metrics.
+    reported_queue_depth: usize,
 }
 
 impl<C> DelayForwarder<C>
 where
     C: mixnet_client::SendWithoutResponse,
 {
-    pub(crate) fn new(client: C, node_stats_update_sender: UpdateSender) -> DelayForwarder<C> {
-        let (packet_sender, packet_receiver) = mpsc::unbounded();
+    pub(crate) fn new(
+        client: C,
+        node_stats_update_sender: UpdateSender,
+        node_metrics: NodeMetrics,
+        limits: DelayQueueLimits,
+        queue_size: usize,
+    ) -> (DelayForwarder<C>, PacketDelayForwardShardSender) {
+        let (packet_sender, packet_receiver) = mpsc::channel(queue_size);
 
-        DelayForwarder::<C> {
-            delay_queue: NonExhaustiveDelayQueue::new(),
-            mixnet_client: client,
+        (
+            DelayForwarder::<C> {
+                delay_queue: NonExhaustiveDelayQueue::new(),
+                mixnet_client: client,
+                packet_receiver,
+                node_stats_update_sender,
+                node_metrics,
+                limits,
+                queued_bytes: 0,
+                reported_queue_depth: 0,
+            },
             packet_sender,
-            packet_receiver,
-            node_stats_update_sender,
-        }
+        )
     }
 
-    pub(crate) fn sender(&self) -> PacketDelayForwardSender {
-        self.packet_sender.clone()
+    fn report_queue_depth(&mut self) {
+        let queue_depth = self.delay_queue.len();
+        self.node_metrics
+            .update_delay_queue_depth(self.reported_queue_depth, queue_depth);
+        self.reported_queue_depth = queue_depth;
     }
 
     fn forward_packet(&mut self, packet: MixPacket) {
@@ -62,7 +201,7 @@ where
                 // in any other case the connection might still be re-established (or created for the first time)
                 // and the packet might get sent, but we won't know about it
                 self.node_stats_update_sender
-                    .report_dropped(next_hop.to_string())
+                    .report_dropped(next_hop.to_string(), DropReason::ConnectionBufferFull)
             } else if err.kind() == io::ErrorKind::NotConnected {
                 // let's give the benefit of the doubt and assume we manage to establish connection
                 self.node_stats_update_sender
@@ -82,10 +221,31 @@ where
             .expect("Encountered timer issue within the runtime!")
             .into_inner();
 
+        self.queued_bytes -= delayed_packet.sphinx_packet().len();
+        self.report_queue_depth();
         self.forward_packet(delayed_packet)
     }
 
-    fn handle_new_packet(&mut self, new_packet: (MixPacket, Option<Instant>)) {
+    fn delay_packet(&mut self, packet: MixPacket, forward_instant: Instant) {
+        let packet_len = packet.sphinx_packet().len();
+        if let Some(reason) = self.limits.drop_reason(
+            self.delay_queue.len(),
+            self.queued_bytes,
+            packet_len,
+            rand::random(),
+        ) {
+            trace!("dropping packet to {} - {:?}", packet.next_hop(), reason);
+            self.node_stats_update_sender
+                .report_dropped(packet.next_hop().to_string(), reason);
+            return;
+        }
+
+        self.delay_queue.insert_at(packet, forward_instant);
+        self.queued_bytes += packet_len;
+        self.report_queue_depth();
+    }
+
+    fn handle_new_packet(&mut self, new_packet: DelayedPacket) {
         // in case of a zero delay packet, don't bother putting it in the delay queue,
         // just forward it immediately
         if let Some(instant) = new_packet.1 {
@@ -94,33 +254,51 @@ where
             if instant.checked_duration_since(Instant::now()).is_none() {
                 self.forward_packet(new_packet.0)
             } else {
-                self.delay_queue.insert_at(new_packet.0, instant);
+                self.delay_packet(new_packet.0, instant)
             }
         } else {
             self.forward_packet(new_packet.0)
         }
     }
 
-    pub(crate) async fn run(&mut self) {
+    /// Delays and forwards packets until the shutdown. Afterwards it keeps going until all of the
+    /// senders are gone, i.e. the rest of the node has stopped producing packets, and until all of
+    /// the packets that are still being delayed have been forwarded.
+    pub(crate) async fn run(&mut self, mut shutdown: ShutdownListener) {
+        let mut senders_dropped = false;
         loop {
             tokio::select! {
                 delayed = self.delay_queue.next() => {
                     self.handle_done_delaying(delayed);
                 }
-                new_packet = self.packet_receiver.next() => {
-                    // this one is impossible to ever panic - the object itself contains a sender
-                    // and hence it can't happen that ALL senders are dropped
-                    self.handle_new_packet(new_packet.unwrap())
+                new_packet = self.packet_receiver.next(), if !senders_dropped => {
+                    match new_packet {
+                        Some(new_packet) => self.handle_new_packet(new_packet),
+                        None => {
+                            // all senders are only ever dropped during the shutdown
+                            senders_dropped = true;
+                        }
+                    }
+                }
+                _ = shutdown.recv(), if !shutdown.is_shutdown() => {
+                    info!("Draining the delay queue with {} packets...", self.delay_queue.len());
                 }
             }
+
+            if senders_dropped && self.delay_queue.is_empty() {
+                break;
+            }
         }
+        trace!("the delay queue has been drained");
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::node::node_statistics::PacketEvent;
 
+    use std::collections::HashSet;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
@@ -133,6 +311,7 @@ mod tests {
         crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node, NodeAddressBytes,
         SphinxPacket, DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH, NODE_ADDRESS_LENGTH,
     };
+    use task::ShutdownNotifier;
 
     #[derive(Default)]
     struct TestClient {
@@ -194,11 +373,18 @@ mod tests {
         let node_stats_update_sender = UpdateSender::new(stats_sender);
         let client = TestClient::default();
         let client_packets_sent = client.packets_sent.clone();
-        let mut delay_forwarder = DelayForwarder::new(client, node_stats_update_sender);
-        let packet_sender = delay_forwarder.sender();
+        let (mut delay_forwarder, mut packet_sender) = DelayForwarder::new(
+            client,
+            node_stats_update_sender,
+            NodeMetrics::new(),
+            DelayQueueLimits::default(),
+            16,
+        );
+        let shutdown = ShutdownNotifier::new();
 
         // Spawn the worker, listening on packet_sender channel
-        tokio::spawn(async move { delay_forwarder.run().await });
+        let shutdown_listener = shutdown.subscribe();
+        tokio::spawn(async move { delay_forwarder.run(shutdown_listener).await });
 
         // Send a `MixPacket` down the channel without any delay attached.
         let next_hop =
@@ -210,7 +396,7 @@ mod tests {
         );
         let forward_instant = None;
         packet_sender
-            .unbounded_send((mix_packet, forward_instant))
+            .try_send((mix_packet, forward_instant))
             .unwrap();
 
         // Give the the worker a chance to act
@@ -227,4 +413,163 @@ mod tests {
             vec![next_hop]
         );
     }
+
+    #[tokio::test]
+    async fn delayed_packets_are_forwarded_before_the_shutdown_completes() {
+        let (stats_sender, _stats_receiver) = mpsc::unbounded();
+        let node_stats_update_sender = UpdateSender::new(stats_sender);
+        let client = TestClient::default();
+        let client_packets_sent = client.packets_sent.clone();
+        let (mut delay_forwarder, mut packet_sender) = DelayForwarder::new(
+            client,
+            node_stats_update_sender,
+            NodeMetrics::new(),
+            DelayQueueLimits::default(),
+            16,
+        );
+        let shutdown = ShutdownNotifier::new();
+        let shutdown_listener = shutdown.subscribe();
+        tokio::spawn(async move { delay_forwarder.run(shutdown_listener).await });
+
+        let next_hop =
+            NymNodeRoutingAddress::from(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 42));
+        for _ in 0..3 {
+            let mix_packet = MixPacket::new(
+                next_hop,
+                make_valid_sphinx_packet(PacketSize::default()),
+                PacketMode::default(),
+            );
+            let forward_instant = Some(Instant::now() + Duration::from_millis(50));
+            packet_sender
+                .try_send((mix_packet, forward_instant))
+                .unwrap();
+        }
+        drop(packet_sender);
+
+        assert!(shutdown.shutdown(Duration::from_secs(5)).await);
+        assert_eq!(3, client_packets_sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn packets_for_the_same_hop_go_through_the_same_shard() {
+        let (senders, mut receivers): (Vec<_>, Vec<_>) = (0..4).map(|_| mpsc::channel(32)).unzip();
+        let (stats_sender, _stats_receiver) = mpsc::unbounded();
+        let mut sharded_sender =
+            PacketDelayForwardSender::new(senders, UpdateSender::new(stats_sender));
+
+        let hops = (0..10)
+            .map(|port| {
+                NymNodeRoutingAddress::from(SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
+                    port,
+                ))
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..3 {
+            for hop in &hops {
+                let mix_packet = MixPacket::new(
+                    *hop,
+                    make_valid_sphinx_packet(PacketSize::default()),
+                    PacketMode::default(),
+                );
+                sharded_sender.forward((mix_packet, None)).unwrap();
+            }
+        }
+
+        let mut received = 0;
+        let mut seen_hops = HashSet::new();
+        for receiver in receivers.iter_mut() {
+            let mut shard_hops = HashSet::new();
+            while let Ok(Some((packet, _))) = receiver.try_next() {
+                shard_hops.insert(packet.next_hop());
+                received += 1;
+            }
+            // none of the hops could have been handled by another shard
+            assert!(seen_hops.is_disjoint(&shard_hops));
+            seen_hops.extend(shard_hops);
+        }
+        assert_eq!(30, received);
+        assert_eq!(10, seen_hops.len());
+    }
+
+    #[test]
+    fn packets_are_dropped_when_delay_forwarder_falls_behind() {
+        let (sender, mut receiver) = mpsc::channel(2);
+        let (stats_sender, mut stats_receiver) = mpsc::unbounded();
+        let mut sharded_sender =
+            PacketDelayForwardSender::new(vec![sender], UpdateSender::new(stats_sender));
+
+        let next_hop =
+            NymNodeRoutingAddress::from(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 42));
+        for _ in 0..10 {
+            let mix_packet = MixPacket::new(
+                next_hop,
+                make_valid_sphinx_packet(PacketSize::default()),
+                PacketMode::default(),
+            );
+            sharded_sender.forward((mix_packet, None)).unwrap();
+        }
+
+        // the channel has space for its buffer plus one packet for every sender
+        let mut queued = 0;
+        while let Ok(Some(_)) = receiver.try_next() {
+            queued += 1;
+        }
+        assert_eq!(3, queued);
+
+        let mut dropped = 0;
+        while let Ok(Some(event)) = stats_receiver.try_next() {
+            match event {
+                PacketEvent::Dropped(destination, DropReason::DelayForwarderFull) => {
+                    assert_eq!(next_hop.to_string(), destination);
+                    dropped += 1;
+                }
+                _ => panic!("unexpected packet event"),
+            }
+        }
+        assert_eq!(7, dropped);
+
+        // and once the forwarder is gone, the sender should tell about it
+        drop(receiver);
+        let mix_packet = MixPacket::new(
+            next_hop,
+            make_valid_sphinx_packet(PacketSize::default()),
+            PacketMode::default(),
+        );
+        assert!(sharded_sender.forward((mix_packet, None)).is_err());
+    }
+
+    #[test]
+    fn packets_are_dropped_when_delay_queue_gets_full() {
+        let limits = DelayQueueLimits {
+            max_packets: Some(100),
+            max_bytes: Some(10_000),
+        };
+
+        // there's plenty of space
+        assert_eq!(None, limits.drop_reason(10, 1000, 10, 0.0));
+        // both limits have been reached
+        assert_eq!(
+            Some(DropReason::DelayQueueFull),
+            limits.drop_reason(100, 1000, 10, 0.99)
+        );
+        assert_eq!(
+            Some(DropReason::DelayQueueFull),
+            limits.drop_reason(10, 9995, 10, 0.99)
+        );
+        // 90% full: half of the packets should get dropped
+        assert_eq!(
+            Some(DropReason::DelayQueueCongested),
+            limits.drop_reason(89, 1000, 10, 0.4)
+        );
+        assert_eq!(None, limits.drop_reason(89, 1000, 10, 0.6));
+        assert_eq!(
+            Some(DropReason::DelayQueueCongested),
+            limits.drop_reason(10, 8990, 10, 0.4)
+        );
+
+        // no limits
+        let limits = DelayQueueLimits::default();
+        assert_eq!(None, limits.drop_reason(usize::MAX - 1, 0, 10, 0.0));
+    }
 }
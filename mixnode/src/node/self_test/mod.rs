@@ -0,0 +1,527 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checks whether the mixnode, while it's running, is reachable by the rest of the network under
+//! the addresses it's announcing and whether it correctly processes sphinx packets going through it.
+
+use crate::config::Config;
+use crate::node::self_test::test_client::{sphinx_node, TestClient};
+use crypto::asymmetric::{encryption, identity};
+use mixnet_contract_common::{GatewayBond, MixNodeBond};
+use mixnode_common::verloc;
+use nymsphinx::Node as SphinxNode;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use std::fmt::{self, Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use topology::{mix, nym_topology_from_bonds, MixLayer, NymTopology};
+
+mod test_client;
+
+/// Number of echo packets sent to the verloc port of this mixnode.
+const VERLOC_TEST_PACKETS: usize = 3;
+
+/// Layer this mixnode is assumed to be on when it's not part of the topology.
+const DEFAULT_LAYER: MixLayer = 2;
+
+/// Where the network topology used for routing the test packets comes from.
+pub(crate) enum TopologySource {
+    /// The current active set as reported by the validator API.
+    ValidatorApi,
+
+    /// A JSON file in the same format as returned by the validator API,
+    /// i.e. `{"mixnodes": [...], "gateways": [...]}`.
+    File(PathBuf),
+}
+
+impl Display for TopologySource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologySource::ValidatorApi => write!(f, "the active set"),
+            TopologySource::File(path) => write!(f, "the topology in {}", path.display()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TopologyFile {
+    mixnodes: Vec<MixNodeBond>,
+    #[serde(default)]
+    gateways: Vec<GatewayBond>,
+}
+
+pub(crate) enum CheckOutcome {
+    Pass(String),
+    Fail(String),
+    Skipped(String),
+}
+
+pub(crate) struct CheckResult {
+    pub(crate) name: &'static str,
+    pub(crate) outcome: CheckOutcome,
+}
+
+#[derive(Default)]
+pub(crate) struct SelfTestReport {
+    results: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    fn push(&mut self, name: &'static str, outcome: CheckOutcome) {
+        self.results.push(CheckResult { name, outcome })
+    }
+
+    pub(crate) fn results(&self) -> &[CheckResult] {
+        &self.results
+    }
+
+    /// Checks whether none of the performed checks has failed.
+    pub(crate) fn passed(&self) -> bool {
+        !self
+            .results
+            .iter()
+            .any(|result| matches!(result.outcome, CheckOutcome::Fail(_)))
+    }
+}
+
+pub(crate) struct SelfTest<'a> {
+    config: &'a Config,
+    identity_key: identity::PublicKey,
+    sphinx_key: encryption::PublicKey,
+    topology_source: TopologySource,
+    /// Port on which the temporary client receiving the test packets is listening.
+    client_port: u16,
+    /// Maximum amount of time to wait for any single check to complete.
+    timeout: Duration,
+}
+
+impl<'a> SelfTest<'a> {
+    pub(crate) fn new(
+        config: &'a Config,
+        identity_key: identity::PublicKey,
+        sphinx_key: encryption::PublicKey,
+        topology_source: TopologySource,
+        client_port: u16,
+        timeout: Duration,
+    ) -> Self {
+        SelfTest {
+            config,
+            identity_key,
+            sphinx_key,
+            topology_source,
+            client_port,
+            timeout,
+        }
+    }
+
+    pub(crate) async fn run(&self) -> SelfTestReport {
+        let mut report = SelfTestReport::default();
+
+        let announced_ip = match self.resolve_announce_address().await {
+            Ok(ip) => {
+                report.push("announce address", Self::check_announced_ip(ip));
+                Some(ip)
+            }
+            Err(outcome) => {
+                report.push("announce address", outcome);
+                None
+            }
+        };
+
+        let (topology, own_node) = match self.load_topology().await {
+            Ok(topology) => {
+                let own_node = topology
+                    .mixes_as_vec()
+                    .into_iter()
+                    .find(|node| node.identity_key == self.identity_key);
+                report.push(
+                    "topology",
+                    self.check_topology(own_node.as_ref(), announced_ip),
+                );
+                (Some(topology), own_node)
+            }
+            Err(err) => {
+                report.push(
+                    "topology",
+                    CheckOutcome::Fail(format!(
+                        "could not obtain {} - {}",
+                        self.topology_source, err
+                    )),
+                );
+                (None, None)
+            }
+        };
+
+        let announced_ip = match announced_ip {
+            Some(ip) => ip,
+            None => {
+                let reason = "the announce address could not be resolved".to_string();
+                for name in [
+                    "mix port",
+                    "verloc port",
+                    "http api port",
+                    "direct sphinx route",
+                    "network sphinx route",
+                ] {
+                    report.push(name, CheckOutcome::Skipped(reason.clone()))
+                }
+                return report;
+            }
+        };
+        let mix_address = SocketAddr::new(announced_ip, self.config.get_mix_port());
+
+        report.push("mix port", self.check_mix_port(mix_address).await);
+        report.push("verloc port", self.check_verloc_port(announced_ip).await);
+        report.push("http api port", self.check_http_api(announced_ip).await);
+
+        let listening_address =
+            SocketAddr::new(self.config.get_listening_address(), self.client_port);
+        let announced_client_address = SocketAddr::new(announced_ip, self.client_port);
+        let mut client = match TestClient::start(listening_address, announced_client_address).await
+        {
+            Ok(client) => client,
+            Err(err) => {
+                let reason = format!(
+                    "could not start the temporary client on {} - {}",
+                    listening_address, err
+                );
+                report.push("direct sphinx route", CheckOutcome::Fail(reason.clone()));
+                report.push("network sphinx route", CheckOutcome::Fail(reason));
+                return report;
+            }
+        };
+
+        report.push(
+            "direct sphinx route",
+            self.check_direct_route(&mut client, mix_address).await,
+        );
+        report.push(
+            "network sphinx route",
+            self.check_network_route(&mut client, mix_address, topology, own_node)
+                .await,
+        );
+
+        report
+    }
+
+    async fn resolve_announce_address(&self) -> Result<IpAddr, CheckOutcome> {
+        let announce_address = self.config.get_announce_address();
+        let mut resolved =
+            tokio::net::lookup_host((&*announce_address, self.config.get_mix_port()))
+                .await
+                .map_err(|err| {
+                    CheckOutcome::Fail(format!(
+                        "could not resolve the announce address '{}' - {}",
+                        announce_address, err
+                    ))
+                })?;
+
+        resolved.next().map(|address| address.ip()).ok_or_else(|| {
+            CheckOutcome::Fail(format!(
+                "the announce address '{}' does not resolve to any ip address",
+                announce_address
+            ))
+        })
+    }
+
+    fn check_announced_ip(ip: IpAddr) -> CheckOutcome {
+        let is_local = match ip {
+            IpAddr::V4(ip) => {
+                ip.is_loopback() || ip.is_unspecified() || ip.is_private() || ip.is_link_local()
+            }
+            IpAddr::V6(ip) => ip.is_loopback() || ip.is_unspecified(),
+        };
+
+        if is_local {
+            CheckOutcome::Fail(format!(
+                "{} is not a public address - nodes outside of your local network won't be able to reach you. Set `announce_address` in the config (or use `--announce-host`) to your public ip or hostname",
+                ip
+            ))
+        } else {
+            CheckOutcome::Pass(format!("announcing {}", ip))
+        }
+    }
+
+    async fn load_topology(&self) -> Result<NymTopology, String> {
+        let (mixnodes, gateways) = match &self.topology_source {
+            TopologySource::ValidatorApi => {
+                let validator_api = self
+                    .config
+                    .get_validator_api_endpoints()
+                    .choose(&mut OsRng)
+                    .cloned()
+                    .ok_or_else(|| "no validator api is specified in the config".to_string())?;
+                let validator_client = validator_client::ApiClient::new(validator_api);
+                let mixnodes = validator_client
+                    .get_cached_active_mixnodes()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                let gateways = validator_client
+                    .get_cached_gateways()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                (mixnodes, gateways)
+            }
+            TopologySource::File(path) => {
+                let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+                let topology: TopologyFile =
+                    serde_json::from_str(&content).map_err(|err| err.to_string())?;
+                (topology.mixnodes, topology.gateways)
+            }
+        };
+
+        Ok(nym_topology_from_bonds(mixnodes, gateways)
+            .filter_system_version(self.config.get_version()))
+    }
+
+    fn check_topology(
+        &self,
+        own_node: Option<&mix::Node>,
+        announced_ip: Option<IpAddr>,
+    ) -> CheckOutcome {
+        let own_node = match own_node {
+            Some(node) => node,
+            None => {
+                return CheckOutcome::Skipped(format!(
+                    "this mixnode is not part of {} - it's going to be treated as a layer {} node",
+                    self.topology_source, DEFAULT_LAYER
+                ))
+            }
+        };
+
+        if own_node.sphinx_key != self.sphinx_key {
+            return CheckOutcome::Fail(format!(
+                "the network knows this mixnode under sphinx key {}, but it's using {}. Did you re-initialise it without bonding it again?",
+                own_node.sphinx_key.to_base58_string(),
+                self.sphinx_key.to_base58_string()
+            ));
+        }
+
+        if let Some(announced_ip) = announced_ip {
+            let announced_address = SocketAddr::new(announced_ip, self.config.get_mix_port());
+            if own_node.mix_host != announced_address {
+                return CheckOutcome::Fail(format!(
+                    "the network expects this mixnode on {}, but it's announcing {}. Make sure the host and ports you've bonded with match the config",
+                    own_node.mix_host, announced_address
+                ));
+            }
+        }
+
+        CheckOutcome::Pass(format!("bonded on layer {}", own_node.layer as MixLayer))
+    }
+
+    async fn check_mix_port(&self, mix_address: SocketAddr) -> CheckOutcome {
+        match tokio::time::timeout(self.timeout, TcpStream::connect(mix_address)).await {
+            Ok(Ok(_)) => CheckOutcome::Pass(format!("accepting connections on {}", mix_address)),
+            Ok(Err(err)) => CheckOutcome::Fail(format!(
+                "could not connect to {} - {}. Make sure the mixnode is running and the port is open in your firewall",
+                mix_address, err
+            )),
+            Err(_timeout) => CheckOutcome::Fail(format!(
+                "could not connect to {} within {:?}. Make sure the port is open in your firewall",
+                mix_address, self.timeout
+            )),
+        }
+    }
+
+    async fn check_verloc_port(&self, announced_ip: IpAddr) -> CheckOutcome {
+        let verloc_address = SocketAddr::new(announced_ip, self.config.get_verloc_port());
+        let verloc_config = verloc::ConfigBuilder::new()
+            .packets_per_node(VERLOC_TEST_PACKETS)
+            .packet_timeout(self.config.get_measurement_packet_timeout())
+            .connection_timeout(self.timeout)
+            .delay_between_packets(self.config.get_measurement_delay_between_packets())
+            .validator_api_urls(self.config.get_validator_api_endpoints())
+            .build();
+        let identity = Arc::new(identity::KeyPair::new(&mut OsRng));
+
+        match verloc::measure_node(&verloc_config, identity, verloc_address, self.identity_key)
+            .await
+        {
+            Ok(measurement) => CheckOutcome::Pass(format!(
+                "replying to echo packets on {} with {}",
+                verloc_address, measurement
+            )),
+            Err(err) => CheckOutcome::Fail(format!(
+                "failed to measure {} - {}. Make sure the port is open in your firewall",
+                verloc_address, err
+            )),
+        }
+    }
+
+    async fn check_http_api(&self, announced_ip: IpAddr) -> CheckOutcome {
+        let http_address = SocketAddr::new(announced_ip, self.config.get_http_api_port());
+        let url = format!("http://{}/description", http_address);
+
+        let client = match reqwest::Client::builder().timeout(self.timeout).build() {
+            Ok(client) => client,
+            Err(err) => {
+                return CheckOutcome::Fail(format!("could not create the http client - {}", err))
+            }
+        };
+
+        match client.get(&url).send().await {
+            Ok(response) if response.status().is_success() => {
+                CheckOutcome::Pass(format!("serving requests on {}", http_address))
+            }
+            Ok(response) => {
+                CheckOutcome::Fail(format!("{} responded with {}", url, response.status()))
+            }
+            Err(err) => CheckOutcome::Fail(format!(
+                "could not query {} - {}. Make sure the port is open in your firewall",
+                url, err
+            )),
+        }
+    }
+
+    /// Sends the packet to the first node on the route and waits for it to arrive at the client.
+    async fn send_through_route(
+        &self,
+        client: &mut TestClient,
+        route: &[SphinxNode],
+        first_hop: SocketAddr,
+    ) -> Result<Duration, String> {
+        let nonce = rand::random();
+        let packet = client
+            .create_packet(route, nonce)
+            .map_err(|err| err.to_string())?;
+
+        let start = Instant::now();
+        client
+            .send_packet(first_hop, packet, self.timeout)
+            .await
+            .map_err(|err| err.to_string())?;
+
+        if client.wait_for_packet(nonce, self.timeout).await {
+            Ok(start.elapsed())
+        } else {
+            Err(format!(
+                "the packet did not arrive at the temporary client on {} within {:?}",
+                client.announced_address(),
+                self.timeout
+            ))
+        }
+    }
+
+    async fn check_direct_route(
+        &self,
+        client: &mut TestClient,
+        mix_address: SocketAddr,
+    ) -> CheckOutcome {
+        let own_node = match sphinx_node(mix_address, &self.sphinx_key) {
+            Ok(node) => node,
+            Err(err) => return CheckOutcome::Fail(err.to_string()),
+        };
+
+        match self
+            .send_through_route(client, &[own_node], mix_address)
+            .await
+        {
+            Ok(elapsed) => {
+                CheckOutcome::Pass(format!("this node -> temporary client took {:?}", elapsed))
+            }
+            Err(err) => {
+                let mut diagnosis = format!(
+                    "{}. Either the mixnode could not process the packet, which happens when it's running with a different sphinx key, or it could not reach the temporary client (is port {} open?)",
+                    err,
+                    client.announced_address().port()
+                );
+                if self.config.get_accept_topology_peers_only() {
+                    diagnosis.push_str(". Note that with `accept_topology_peers_only` enabled the mixnode may refuse packets sent from its own machine")
+                }
+                CheckOutcome::Fail(diagnosis)
+            }
+        }
+    }
+
+    fn random_other_node(&self, topology: &NymTopology, layer: MixLayer) -> Option<mix::Node> {
+        topology
+            .mixes_in_layer(layer)
+            .into_iter()
+            .filter(|node| node.identity_key != self.identity_key)
+            .collect::<Vec<_>>()
+            .choose(&mut OsRng)
+            .cloned()
+    }
+
+    /// Sends the packet through a node on the preceding layer, this node and a node on the
+    /// following layer, so that it has to both reach this node and leave it over the network.
+    async fn check_network_route(
+        &self,
+        client: &mut TestClient,
+        mix_address: SocketAddr,
+        topology: Option<NymTopology>,
+        own_node: Option<mix::Node>,
+    ) -> CheckOutcome {
+        let topology = match topology {
+            Some(topology) => topology,
+            None => return CheckOutcome::Skipped("the topology is not available".to_string()),
+        };
+        let own_layer = own_node
+            .map(|node| node.layer as MixLayer)
+            .unwrap_or(DEFAULT_LAYER);
+        let previous_layer = if own_layer == 1 { 3 } else { own_layer - 1 };
+        let next_layer = if own_layer == 3 { 1 } else { own_layer + 1 };
+
+        // layer 1 nodes only expect packets from the gateways, which can't be used here
+        let previous_hop = if own_layer == 1 && self.config.get_accept_topology_peers_only() {
+            None
+        } else {
+            match self.random_other_node(&topology, previous_layer) {
+                Some(node) => Some(node),
+                None => {
+                    return CheckOutcome::Skipped(format!(
+                        "there are no other mixnodes on layer {}",
+                        previous_layer
+                    ))
+                }
+            }
+        };
+        let next_hop = match self.random_other_node(&topology, next_layer) {
+            Some(node) => node,
+            None => {
+                return CheckOutcome::Skipped(format!(
+                    "there are no other mixnodes on layer {}",
+                    next_layer
+                ))
+            }
+        };
+
+        let own_sphinx_node = match sphinx_node(mix_address, &self.sphinx_key) {
+            Ok(node) => node,
+            Err(err) => return CheckOutcome::Fail(err.to_string()),
+        };
+        let mut route = Vec::with_capacity(3);
+        let mut description = Vec::with_capacity(3);
+        let first_hop = match &previous_hop {
+            Some(previous_hop) => {
+                route.push(SphinxNode::from(previous_hop));
+                description.push(previous_hop.mix_host.to_string());
+                previous_hop.mix_host
+            }
+            None => mix_address,
+        };
+        route.push(own_sphinx_node);
+        description.push("this node".to_string());
+        route.push(SphinxNode::from(&next_hop));
+        description.push(next_hop.mix_host.to_string());
+        description.push("temporary client".to_string());
+        let description = description.join(" -> ");
+
+        match self.send_through_route(client, &route, first_hop).await {
+            Ok(elapsed) => CheckOutcome::Pass(format!("{} took {:?}", description, elapsed)),
+            Err(err) => CheckOutcome::Fail(format!(
+                "{} on {}. If the direct sphinx route works, either the other nodes can't reach this one (check the firewall and the announce address), this node can't reach them, or {} can't reach the temporary client on port {}. Try running the test again to go through different nodes",
+                err,
+                description,
+                next_hop.mix_host,
+                client.announced_address().port()
+            )),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::{encryption, identity};
+use futures::{SinkExt, StreamExt};
+use log::*;
+use nymsphinx::addressing::nodes::{NymNodeRoutingAddress, NymNodeRoutingAddressError};
+use nymsphinx::framing::codec::SphinxCodec;
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use nymsphinx::params::packet_sizes::PacketSize;
+use nymsphinx::params::PacketMode;
+use nymsphinx::{
+    builder::SphinxPacketBuilder, Delay, Destination, Error as SphinxError, Node as SphinxNode,
+    PrivateKey, ProcessedPacket, SphinxPacket,
+};
+use rand::rngs::OsRng;
+use std::convert::TryInto;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::codec::Framed;
+
+/// Prefix of the plaintext of all self-test packets, followed by the big-endian test nonce.
+const SELF_TEST_PAYLOAD: &[u8] = b"nym-mixnode self-test";
+
+#[derive(Debug)]
+pub(crate) enum TestClientError {
+    InvalidAddress(NymNodeRoutingAddressError),
+    PacketConstruction(SphinxError),
+    Sending(SocketAddr, io::Error),
+}
+
+impl Display for TestClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TestClientError::InvalidAddress(err) => {
+                write!(f, "a node on the route has an invalid address - {:?}", err)
+            }
+            TestClientError::PacketConstruction(err) => {
+                write!(f, "failed to construct the sphinx packet - {:?}", err)
+            }
+            TestClientError::Sending(address, err) => {
+                write!(f, "failed to send the packet to {} - {}", address, err)
+            }
+        }
+    }
+}
+
+/// Creates a sphinx route entry for the node listening on the specified address.
+pub(crate) fn sphinx_node(
+    address: SocketAddr,
+    sphinx_key: &encryption::PublicKey,
+) -> Result<SphinxNode, TestClientError> {
+    let address = NymNodeRoutingAddress::from(address)
+        .try_into()
+        .map_err(TestClientError::InvalidAddress)?;
+    Ok(SphinxNode::new(address, sphinx_key.into()))
+}
+
+/// Short-lived client acting as the final hop of the self-test packets. It listens for packets
+/// sent over the mix network to its own, ephemeral, sphinx key and reports the nonces they carried.
+pub(crate) struct TestClient {
+    sphinx_keypair: Arc<encryption::KeyPair>,
+    identity_keypair: identity::KeyPair,
+    /// Address under which other nodes can reach this client.
+    announced_address: SocketAddr,
+    received_nonces: mpsc::UnboundedReceiver<u64>,
+    listener_handle: JoinHandle<()>,
+}
+
+impl TestClient {
+    /// Starts listening on `listening_address`. If its port is 0, a random one gets chosen and
+    /// announced alongside the ip of `announced_address`.
+    pub(crate) async fn start(
+        listening_address: SocketAddr,
+        mut announced_address: SocketAddr,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(listening_address).await?;
+        if announced_address.port() == 0 {
+            announced_address.set_port(listener.local_addr()?.port());
+        }
+
+        let mut rng = OsRng;
+        let sphinx_keypair = Arc::new(encryption::KeyPair::new(&mut rng));
+        let (received_sender, received_nonces) = mpsc::unbounded_channel();
+        let listener_handle = tokio::spawn(Self::listen(
+            listener,
+            Arc::clone(&sphinx_keypair),
+            received_sender,
+        ));
+
+        Ok(TestClient {
+            sphinx_keypair,
+            identity_keypair: identity::KeyPair::new(&mut rng),
+            announced_address,
+            received_nonces,
+            listener_handle,
+        })
+    }
+
+    pub(crate) fn announced_address(&self) -> SocketAddr {
+        self.announced_address
+    }
+
+    async fn listen(
+        listener: TcpListener,
+        sphinx_keypair: Arc<encryption::KeyPair>,
+        received_sender: mpsc::UnboundedSender<u64>,
+    ) {
+        loop {
+            let (conn, remote) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!(
+                        "the self-test client failed to accept a connection - {}",
+                        err
+                    );
+                    continue;
+                }
+            };
+            trace!("the self-test client received a connection from {}", remote);
+
+            let sphinx_keypair = Arc::clone(&sphinx_keypair);
+            let received_sender = received_sender.clone();
+            tokio::spawn(async move {
+                let sphinx_key: PrivateKey = sphinx_keypair.private_key().into();
+                let mut framed_conn = Framed::new(conn, SphinxCodec);
+                while let Some(Ok(framed_packet)) = framed_conn.next().await {
+                    match Self::recover_nonce(framed_packet, &sphinx_key) {
+                        Some(nonce) => {
+                            if received_sender.send(nonce).is_err() {
+                                return;
+                            }
+                        }
+                        None => debug!(
+                            "the self-test client received a packet from {} that is not a self-test packet",
+                            remote
+                        ),
+                    }
+                }
+            });
+        }
+    }
+
+    fn recover_nonce(framed_packet: FramedSphinxPacket, sphinx_key: &PrivateKey) -> Option<u64> {
+        let payload = match framed_packet.into_inner().process(sphinx_key).ok()? {
+            ProcessedPacket::FinalHop(_, _, payload) => payload,
+            ProcessedPacket::ForwardHop(..) => return None,
+        };
+        let plaintext = payload.recover_plaintext().ok()?;
+        let nonce_bytes = plaintext
+            .strip_prefix(SELF_TEST_PAYLOAD)?
+            .get(..8)?
+            .try_into()
+            .ok()?;
+        Some(u64::from_be_bytes(nonce_bytes))
+    }
+
+    /// Creates a packet carrying the nonce that, after going through all nodes on the route,
+    /// comes back to this client. No delays are put on any of the hops.
+    pub(crate) fn create_packet(
+        &self,
+        route: &[SphinxNode],
+        nonce: u64,
+    ) -> Result<SphinxPacket, TestClientError> {
+        let mut full_route = route.to_vec();
+        full_route.push(sphinx_node(
+            self.announced_address,
+            self.sphinx_keypair.public_key(),
+        )?);
+
+        let destination = Destination::new(
+            self.identity_keypair
+                .public_key()
+                .derive_destination_address(),
+            Default::default(),
+        );
+        let delays = full_route
+            .iter()
+            .map(|_| Delay::new_from_nanos(0))
+            .collect::<Vec<_>>();
+
+        let payload = SELF_TEST_PAYLOAD
+            .iter()
+            .cloned()
+            .chain(nonce.to_be_bytes().iter().cloned())
+            .chain(std::iter::repeat(0))
+            .take(PacketSize::default().plaintext_size())
+            .collect();
+
+        SphinxPacketBuilder::new()
+            .with_payload_size(PacketSize::default().payload_size())
+            .build_packet(payload, &full_route, &destination, &delays)
+            .map_err(TestClientError::PacketConstruction)
+    }
+
+    /// Sends the packet to the first hop on its route.
+    pub(crate) async fn send_packet(
+        &self,
+        first_hop: SocketAddr,
+        packet: SphinxPacket,
+        connection_timeout: Duration,
+    ) -> Result<(), TestClientError> {
+        let conn =
+            match tokio::time::timeout(connection_timeout, TcpStream::connect(first_hop)).await {
+                Err(_timeout) => {
+                    return Err(TestClientError::Sending(
+                        first_hop,
+                        io::ErrorKind::TimedOut.into(),
+                    ))
+                }
+                Ok(Err(err)) => return Err(TestClientError::Sending(first_hop, err)),
+                Ok(Ok(conn)) => conn,
+            };
+
+        let mut framed_conn = Framed::new(conn, SphinxCodec);
+        framed_conn
+            .send(FramedSphinxPacket::new(packet, PacketMode::Mix))
+            .await
+            .map_err(|err| TestClientError::Sending(first_hop, err.into()))
+    }
+
+    /// Waits until a packet carrying the specified nonce arrives. Returns whether it happened
+    /// within the timeout.
+    pub(crate) async fn wait_for_packet(&mut self, nonce: u64, timeout: Duration) -> bool {
+        let received_nonces = &mut self.received_nonces;
+        let wait = async {
+            while let Some(received) = received_nonces.recv().await {
+                if received == nonce {
+                    return true;
+                }
+                debug!("received a late self-test packet with nonce {}", received);
+            }
+            false
+        };
+
+        tokio::time::timeout(timeout, wait).await.unwrap_or(false)
+    }
+}
+
+impl Drop for TestClient {
+    fn drop(&mut self) {
+        self.listener_handle.abort()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn packets_addressed_to_the_client_are_received() {
+        let local_address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut client = TestClient::start(local_address, local_address)
+            .await
+            .unwrap();
+
+        // with an empty route, the client is also the first hop
+        let packet = client.create_packet(&[], 42).unwrap();
+        client
+            .send_packet(client.announced_address(), packet, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(client.wait_for_packet(42, Duration::from_secs(5)).await);
+        assert!(!client.wait_for_packet(42, Duration::from_millis(50)).await);
+    }
+}
@@ -9,6 +9,8 @@ export type Operation =
   | "UnbondMixnode"
   | "UnbondMixnodeOnBehalf"
   | "UpdateMixnodeConfig"
+  | "UpdateMixnodeIdentity"
+  | "MigrateMixnodeDelegations"
   | "DelegateToMixnode"
   | "DelegateToMixnodeOnBehalf"
   | "UndelegateFromMixnode"
@@ -17,6 +19,7 @@ export type Operation =
   | "BondGatewayOnBehalf"
   | "UnbondGateway"
   | "UnbondGatewayOnBehalf"
+  | "UpdateGatewayIdentity"
   | "UpdateContractSettings"
   | "BeginMixnodeRewarding"
   | "FinishMixnodeRewarding"
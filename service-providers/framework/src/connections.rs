@@ -0,0 +1,16 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Management of long-lived connections proxied over the mix network. A service provider
+//! registers every connection with the [`Controller`], which puts the messages received for it
+//! back in order and passes them to the [`ProxyRunner`] of that connection.
+//!
+//! The same machinery is used on the other end by the socks5 client, hence it is only
+//! re-exported here rather than duplicated.
+
+pub use proxy_helpers::connection_controller::{
+    ConnectionMessage, ConnectionReceiver, ConnectionSender, Controller, ControllerCommand,
+    ControllerSender,
+};
+pub use proxy_helpers::flow_control::FlowControl;
+pub use proxy_helpers::proxy_runner::ProxyRunner;
@@ -0,0 +1,31 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Common plumbing of services running on top of the mix network. A service provider
+//! receives requests through the websocket of a native client, handles them via the
+//! [`ServiceProvider`] trait and sends the responses back either to the revealed
+//! address of the sender or with the attached reply SURB. Providers proxying long-lived
+//! connections can additionally use the [`connections`] machinery and keep track of their
+//! clients with [`Sessions`].
+
+use nymsphinx::receiver::ReconstructedMessage;
+
+pub mod connections;
+pub mod reply;
+mod runner;
+pub mod sessions;
+pub mod websocket;
+
+pub use reply::{Reply, ReplyListenerStopped, ReplySender, ReturnAddress};
+pub use runner::run;
+pub use sessions::{Session, Sessions};
+
+pub trait ServiceProvider {
+    /// Called once, after the connection to the native client got established,
+    /// but before any request is handled.
+    fn on_start(&mut self, _reply_sender: &ReplySender) {}
+
+    /// Handles a single message received from the mix network. Any long-running work
+    /// should be spawned as a separate task as no further requests are received in the meantime.
+    fn handle_request(&mut self, request: ReconstructedMessage, reply_sender: &ReplySender);
+}
@@ -0,0 +1,94 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use futures::channel::mpsc;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::ReplySurb;
+use std::fmt;
+use websocket_requests::requests::ClientRequest;
+
+pub(crate) type ReplyReceiver = mpsc::UnboundedReceiver<Reply>;
+
+/// Destination of a message sent back to the mix network.
+#[derive(Debug)]
+pub enum ReturnAddress {
+    /// Address of the sender that has explicitly revealed itself.
+    Known(Recipient),
+
+    /// Single-use reply block attached to the received message by an anonymous sender.
+    Anonymous(ReplySurb),
+}
+
+impl From<Recipient> for ReturnAddress {
+    fn from(recipient: Recipient) -> Self {
+        ReturnAddress::Known(recipient)
+    }
+}
+
+impl From<ReplySurb> for ReturnAddress {
+    fn from(reply_surb: ReplySurb) -> Self {
+        ReturnAddress::Anonymous(reply_surb)
+    }
+}
+
+#[derive(Debug)]
+pub struct Reply {
+    pub message: Vec<u8>,
+    pub return_address: ReturnAddress,
+}
+
+impl Reply {
+    pub(crate) fn into_client_request(self) -> ClientRequest {
+        match self.return_address {
+            ReturnAddress::Known(recipient) => ClientRequest::Send {
+                recipient,
+                message: self.message,
+                with_reply_surb: false,
+            },
+            ReturnAddress::Anonymous(reply_surb) => ClientRequest::Reply {
+                message: self.message,
+                reply_surb,
+            },
+        }
+    }
+}
+
+/// Returned when a reply can no longer be sent, as the connection to the native client
+/// has been closed.
+#[derive(Debug)]
+pub struct ReplyListenerStopped;
+
+impl fmt::Display for ReplyListenerStopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the mixnet reply listener has stopped")
+    }
+}
+
+impl std::error::Error for ReplyListenerStopped {}
+
+/// Handle used by the service providers for sending messages back to the mix network.
+/// It can be freely cloned and moved to any task that handles the requests.
+#[derive(Clone, Debug)]
+pub struct ReplySender(mpsc::UnboundedSender<Reply>);
+
+impl ReplySender {
+    pub(crate) fn new() -> (Self, ReplyReceiver) {
+        let (sender, receiver) = mpsc::unbounded();
+        (ReplySender(sender), receiver)
+    }
+
+    /// Sends the message to the specified return address. It fails once the connection
+    /// to the native client is gone, for example during shutdown.
+    pub fn send_reply<R: Into<ReturnAddress>>(
+        &self,
+        return_address: R,
+        message: Vec<u8>,
+    ) -> Result<(), ReplyListenerStopped> {
+        self.0
+            .unbounded_send(Reply {
+                message,
+                return_address: return_address.into(),
+            })
+            .map_err(|_| ReplyListenerStopped)
+    }
+}
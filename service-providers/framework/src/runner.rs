@@ -0,0 +1,94 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::reply::{ReplyReceiver, ReplySender};
+use crate::websocket::{self, TSWebsocketStream, WebsocketConnectionError};
+use crate::ServiceProvider;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use log::*;
+use nymsphinx::receiver::ReconstructedMessage;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use websocket_requests::responses::ServerResponse;
+
+/// Listens for any replies of the service provider and writes them back to the mix network
+/// via the `websocket_writer`.
+async fn reply_listener(
+    mut websocket_writer: SplitSink<TSWebsocketStream, Message>,
+    mut reply_receiver: ReplyReceiver,
+) {
+    while let Some(reply) = reply_receiver.next().await {
+        // make 'request' to native-websocket client
+        let message = Message::Binary(reply.into_client_request().serialize());
+        if let Err(err) = websocket_writer.send(message).await {
+            error!("Failed to send reply to the native client - {}", err);
+            return;
+        }
+    }
+}
+
+/// Reads the next message received from the mix network. Returns `Ok(None)` once the native
+/// client has closed the websocket.
+async fn read_websocket_message(
+    websocket_reader: &mut SplitStream<TSWebsocketStream>,
+) -> Result<Option<ReconstructedMessage>, WebsocketConnectionError> {
+    while let Some(msg) = websocket_reader.next().await {
+        let data = match msg {
+            Ok(msg) => msg.into_data(),
+            Err(err) => {
+                error!("Failed to read from the websocket - {}", err);
+                return Err(WebsocketConnectionError::ConnectionClosed);
+            }
+        };
+
+        // try to recover the actual message from the mix network...
+        let deserialized_message = match ServerResponse::deserialize(&data) {
+            Ok(deserialized) => deserialized,
+            Err(err) => {
+                error!(
+                    "Failed to deserialize received websocket message! - {}",
+                    err
+                );
+                continue;
+            }
+        };
+
+        match deserialized_message {
+            ServerResponse::Received(received) => return Ok(Some(received)),
+            ServerResponse::Error(err) => error!("Received error from native client! - {}", err),
+            ServerResponse::SelfAddress(_) => warn!("Received unrequested self address"),
+        }
+    }
+    Ok(None)
+}
+
+/// Connects to the native client listening at `native_client_uri` and passes every message it
+/// receives from the mix network to the service provider. Returns `Ok` once the native client
+/// closes the websocket and an error if the connection could not be established or has failed.
+pub async fn run<P: ServiceProvider>(
+    service_provider: &mut P,
+    native_client_uri: &str,
+) -> Result<(), WebsocketConnectionError> {
+    let websocket_stream = websocket::Connection::new(native_client_uri)
+        .connect()
+        .await?;
+    info!(
+        "* connected to local websocket server at {}",
+        native_client_uri
+    );
+
+    // split the websocket so that we could read and write from separate threads
+    let (websocket_writer, mut websocket_reader) = websocket_stream.split();
+
+    let (reply_sender, reply_receiver) = ReplySender::new();
+    tokio::spawn(reply_listener(websocket_writer, reply_receiver));
+
+    service_provider.on_start(&reply_sender);
+
+    // for each incoming message from the websocket... (which in 99.99% cases is going to be a mix message)
+    while let Some(received) = read_websocket_message(&mut websocket_reader).await? {
+        service_provider.handle_request(received, &reply_sender)
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx::addressing::clients::Recipient;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// State kept by a service provider for a series of related requests of a single client,
+/// for example a proxied connection.
+#[derive(Debug)]
+pub struct Session<S> {
+    pub return_address: Recipient,
+    pub state: S,
+    last_active: Instant,
+}
+
+impl<S> Session<S> {
+    pub fn last_active(&self) -> Instant {
+        self.last_active
+    }
+}
+
+/// Sessions of all clients of a service provider. Sessions that have not been used for longer
+/// than the idle timeout (if any) can be removed with `remove_idle`.
+#[derive(Debug)]
+pub struct Sessions<K, S> {
+    sessions: HashMap<K, Session<S>>,
+    idle_timeout: Option<Duration>,
+}
+
+impl<K, S> Sessions<K, S>
+where
+    K: Hash + Eq + Clone,
+{
+    pub fn new(idle_timeout: Option<Duration>) -> Self {
+        Sessions {
+            sessions: HashMap::new(),
+            idle_timeout,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Starts a new session, replacing (and returning) any existing one with the same key.
+    pub fn open(&mut self, key: K, return_address: Recipient, state: S) -> Option<Session<S>> {
+        self.sessions.insert(
+            key,
+            Session {
+                return_address,
+                state,
+                last_active: Instant::now(),
+            },
+        )
+    }
+
+    pub fn get(&self, key: &K) -> Option<&Session<S>> {
+        self.sessions.get(key)
+    }
+
+    /// Gets the session for further processing and marks it as active.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut Session<S>> {
+        let session = self.sessions.get_mut(key)?;
+        session.last_active = Instant::now();
+        Some(session)
+    }
+
+    pub fn close(&mut self, key: &K) -> Option<Session<S>> {
+        self.sessions.remove(key)
+    }
+
+    /// Counts sessions opened by the specified client.
+    pub fn client_sessions(&self, client: &Recipient) -> usize {
+        let client = client.to_bytes();
+        self.sessions
+            .values()
+            .filter(|session| session.return_address.to_bytes() == client)
+            .count()
+    }
+
+    /// Removes and returns all sessions that have exceeded the idle timeout.
+    pub fn remove_idle(&mut self, now: Instant) -> Vec<(K, Session<S>)> {
+        let idle_timeout = match self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return Vec::new(),
+        };
+
+        let idle_keys = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                now.saturating_duration_since(session.last_active) >= idle_timeout
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        idle_keys
+            .into_iter()
+            .filter_map(|key| self.sessions.remove(&key).map(|session| (key, session)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+
+    fn client() -> Recipient {
+        let mut rng = rand::thread_rng();
+
+        let client_id_pair = identity::KeyPair::new(&mut rng);
+        let client_enc_pair = encryption::KeyPair::new(&mut rng);
+        let gateway_id_pair = identity::KeyPair::new(&mut rng);
+
+        Recipient::new(
+            *client_id_pair.public_key(),
+            *client_enc_pair.public_key(),
+            *gateway_id_pair.public_key(),
+        )
+    }
+
+    #[test]
+    fn sessions_can_be_opened_and_closed() {
+        let (alice, bob) = (client(), client());
+        let mut sessions = Sessions::new(None);
+
+        assert!(sessions.open(1, alice, "foo").is_none());
+        assert!(sessions.open(2, alice, "bar").is_none());
+        assert!(sessions.open(3, bob, "baz").is_none());
+        assert_eq!(3, sessions.len());
+        assert_eq!(2, sessions.client_sessions(&alice));
+
+        sessions.get_mut(&2).unwrap().state = "quux";
+        assert_eq!("quux", sessions.get(&2).unwrap().state);
+
+        assert_eq!("foo", sessions.close(&1).unwrap().state);
+        assert!(sessions.get(&1).is_none());
+        assert_eq!(1, sessions.client_sessions(&alice));
+    }
+
+    #[test]
+    fn idle_sessions_are_removed() {
+        let alice = client();
+        let idle_timeout = Duration::from_secs(60);
+        let mut sessions = Sessions::new(Some(idle_timeout));
+        sessions.open(1, alice, ());
+        sessions.open(2, alice, ());
+
+        assert!(sessions.remove_idle(Instant::now()).is_empty());
+
+        let later = sessions.get(&1).unwrap().last_active() + idle_timeout;
+        sessions.get_mut(&2).unwrap().last_active = later;
+        let removed = sessions.remove_idle(later);
+        assert_eq!(1, removed.len());
+        assert_eq!(1, removed[0].0);
+        assert!(sessions.get(&2).is_some());
+    }
+
+    #[test]
+    fn sessions_never_expire_without_timeout() {
+        let mut sessions = Sessions::new(None);
+        sessions.open(1, client(), ());
+
+        let much_later = Instant::now() + Duration::from_secs(365 * 24 * 60 * 60);
+        assert!(sessions.remove_idle(much_later).is_empty());
+        assert_eq!(1, sessions.len());
+    }
+}
@@ -1,12 +1,13 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use std::fmt::{self, Display, Formatter};
 use tokio::net::TcpStream;
 use tokio_tungstenite::WebSocketStream;
 use tokio_tungstenite::{connect_async, MaybeTlsStream};
 
 #[allow(clippy::upper_case_acronyms)]
-pub(crate) type TSWebsocketStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+pub type TSWebsocketStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
 pub struct Connection {
     uri: String,
@@ -30,4 +31,21 @@ impl Connection {
 #[derive(Debug)]
 pub enum WebsocketConnectionError {
     ConnectionNotEstablished,
+    ConnectionClosed,
 }
+
+impl Display for WebsocketConnectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WebsocketConnectionError::ConnectionNotEstablished => write!(
+                f,
+                "websocket connection attempt failed, is the Nym client running?"
+            ),
+            WebsocketConnectionError::ConnectionClosed => {
+                write!(f, "the websocket stream has failed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WebsocketConnectionError {}
@@ -3,9 +3,8 @@
 
 use fs::OpenOptions;
 use io::BufReader;
-use ipnetwork::IpNetwork;
 use publicsuffix::{errors, List};
-use std::collections::HashSet;
+use rules::{Destination, HostPattern, HostRule};
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -13,8 +12,27 @@ use std::io::BufRead;
 use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use watcher::HostsFileWatcher;
 
-/// Filters outbound requests based on what's in an `allowed_hosts` list.
+mod rules;
+mod watcher;
+
+/// Outcome of checking a host against the `allowed_hosts` list.
+#[derive(Debug)]
+pub(crate) enum FilterDecision<'a> {
+    Allowed(&'a HostRule),
+    Denied(&'a HostRule),
+    NoMatchingRule,
+    InvalidHost,
+}
+
+/// Filters outbound requests based on the rules in an `allowed_hosts` list.
+///
+/// Each rule has the form of `host[:port] [allow|deny]`, for example `*.example.com:443` or
+/// `10.0.0.0/8 deny`. Deny rules always take precedence over allow rules and requests
+/// not matching any rule are denied.
 ///
 /// Requests to unknown hosts are automatically written to an `unknown_hosts`
 /// list so that they can be copy/pasted into the `allowed_hosts` list if desired.
@@ -26,10 +44,13 @@ use std::path::PathBuf;
 /// domain is for a given request. This allows us to distinguish all the rules for e.g.
 /// .com, .co.uk, .co.jp, uk.com, etc, so that we can distinguish correct root-ish
 /// domains as allowed. That list is loaded once at startup from the network.
+///
+/// The `allowed_hosts` list is reloaded whenever its file changes or the process receives a SIGHUP.
 pub(crate) struct OutboundRequestFilter {
     allowed_hosts: HostsStore,
     domain_list: publicsuffix::List,
     unknown_hosts: HostsStore,
+    reload_requested: Arc<AtomicBool>,
 }
 
 impl OutboundRequestFilter {
@@ -45,47 +66,65 @@ impl OutboundRequestFilter {
             allowed_hosts,
             domain_list,
             unknown_hosts,
+            reload_requested: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Creates the filter using the `allowed.list` and `unknown.list` files
+    /// from the default storage directory.
+    pub(crate) fn new_with_default_storefiles() -> OutboundRequestFilter {
+        let allowed_hosts = HostsStore::new(
+            HostsStore::default_base_dir(),
+            PathBuf::from("allowed.list"),
+        );
+
+        let unknown_hosts = HostsStore::new(
+            HostsStore::default_base_dir(),
+            PathBuf::from("unknown.list"),
+        );
+        OutboundRequestFilter::new(allowed_hosts, unknown_hosts)
+    }
+
     fn fetch_domain_list() -> Result<List, errors::Error> {
         publicsuffix::List::fetch()
     }
 
-    /// Returns `true` if a host's root domain is in the `allowed_hosts` list.
+    /// Returns path to the file containing the `allowed_hosts` list.
+    pub(crate) fn allowed_hosts_storefile(&self) -> &Path {
+        &self.allowed_hosts.storefile
+    }
+
+    /// Creates a watcher that will cause the `allowed_hosts` list to get reloaded
+    /// on any changes to its storefile.
+    pub(crate) fn hosts_file_watcher(&self) -> HostsFileWatcher {
+        HostsFileWatcher::new(
+            self.allowed_hosts.storefile.clone(),
+            Arc::clone(&self.reload_requested),
+        )
+    }
+
+    fn maybe_reload(&mut self) {
+        if self.reload_requested.swap(false, Ordering::SeqCst) {
+            self.allowed_hosts.reload()
+        }
+    }
+
+    /// Returns `true` if a host is allowed by the rules in the `allowed_hosts` list.
     ///
-    /// If it's not in the list, return `false` and write it to the `unknown_hosts` storefile.
+    /// If it does not match any rule, return `false` and write it to the `unknown_hosts` storefile.
     pub(crate) fn check(&mut self, host: &str) -> bool {
-        // first check if it's a socket address (ip:port)
-        // (this check is performed to not incorrectly strip what we think might be a port
-        // from ipv6 address, as for example ::1 contains colons but has no port
-        let allowed = if let Ok(socketaddr) = host.parse::<SocketAddr>() {
-            if !self.allowed_hosts.contains_ip_address(socketaddr.ip()) {
-                self.unknown_hosts.maybe_add_ip(socketaddr.ip());
-                return false;
-            }
-            true
-        } else if let Ok(ipaddr) = host.parse::<IpAddr>() {
-            // then check if it was an ip address
-            if !self.allowed_hosts.contains_ip_address(ipaddr) {
-                self.unknown_hosts.maybe_add_ip(ipaddr);
-                return false;
-            }
-            true
-        } else {
-            // finally, then assume it might be a domain
-            let trimmed = Self::trim_port(host);
-            if let Some(domain_root) = self.get_domain_root(&trimmed) {
-                // it's a domain
-                if !self.allowed_hosts.contains_domain(&domain_root) {
-                    self.unknown_hosts.maybe_add_domain(&trimmed);
-                    return false;
+        self.maybe_reload();
+
+        let allowed = match self.parse_destination(host) {
+            Some(destination) => match self.allowed_hosts.matching_rule(&destination) {
+                Some(rule) => rule.is_allow(),
+                None => {
+                    self.unknown_hosts.maybe_add(&destination);
+                    false
                 }
-                true
-            } else {
-                // it's something else, no idea what, probably some nonsense
-                false
-            }
+            },
+            // it's something else, no idea what, probably some nonsense
+            None => false,
         };
 
         if !allowed {
@@ -98,6 +137,42 @@ impl OutboundRequestFilter {
         allowed
     }
 
+    /// Determines which rule of the `allowed_hosts` list, if any, applies to the host.
+    /// Unlike `check`, it does not record unknown hosts.
+    pub(crate) fn evaluate(&self, host: &str) -> FilterDecision<'_> {
+        let destination = match self.parse_destination(host) {
+            Some(destination) => destination,
+            None => return FilterDecision::InvalidHost,
+        };
+
+        match self.allowed_hosts.matching_rule(&destination) {
+            Some(rule) if rule.is_allow() => FilterDecision::Allowed(rule),
+            Some(rule) => FilterDecision::Denied(rule),
+            None => FilterDecision::NoMatchingRule,
+        }
+    }
+
+    fn parse_destination(&self, host: &str) -> Option<Destination> {
+        // first check if it's a socket address (ip:port)
+        // (this check is performed to not incorrectly strip what we think might be a port
+        // from ipv6 address, as for example ::1 contains colons but has no port
+        if let Ok(socketaddr) = host.parse::<SocketAddr>() {
+            Some(Destination::Ip(socketaddr.ip(), Some(socketaddr.port())))
+        } else if let Ok(ipaddr) = host.parse::<IpAddr>() {
+            // then check if it was an ip address
+            Some(Destination::Ip(ipaddr, None))
+        } else {
+            // finally, then assume it might be a domain
+            let trimmed = Self::trim_port(host).to_lowercase();
+            let root = self.get_domain_root(&trimmed)?;
+            Some(Destination::Domain {
+                name: trimmed,
+                root,
+                port: Self::extract_port(host),
+            })
+        }
+    }
+
     fn trim_port(host: &str) -> String {
         let mut tmp: Vec<_> = host.split(':').collect();
         if tmp.len() > 1 {
@@ -108,6 +183,11 @@ impl OutboundRequestFilter {
         }
     }
 
+    fn extract_port(host: &str) -> Option<u16> {
+        host.rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+    }
+
     /// Attempts to get the root domain, shorn of subdomains, using publicsuffix.
     fn get_domain_root(&self, host: &str) -> Option<String> {
         match self.domain_list.parse_domain(host) {
@@ -120,71 +200,39 @@ impl OutboundRequestFilter {
     }
 }
 
-// used for parsing file content
-enum Host {
-    Domain(String),
-    IpNetwork(IpNetwork),
-}
-
-// TODO: perphaps in the future it should do some domain validation?
-// so for example if somebody put some nonsense in the whitelist file like "foomp", it would get
-// rejected?
-impl From<String> for Host {
-    fn from(raw: String) -> Self {
-        if let Ok(ipnet) = raw.parse() {
-            Host::IpNetwork(ipnet)
-        } else {
-            Host::Domain(raw)
-        }
-    }
-}
-
-impl Host {
-    fn is_domain(&self) -> bool {
-        matches!(self, Host::Domain(..))
-    }
-
-    fn extract_domain(self) -> String {
-        match self {
-            Host::Domain(domain) => domain,
-            _ => panic!("called extract domain on an ipnet!"),
-        }
-    }
-
-    fn extract_ipnetwork(self) -> IpNetwork {
-        match self {
-            Host::IpNetwork(ipnet) => ipnet,
-            _ => panic!("called extract ipnet on a domain!"),
-        }
-    }
-}
-
 /// A simple file-based store for information about allowed / unknown hosts.
-/// Currently it completely ignores any port information.
-// TODO: in the future allow filtering by port, so for example 1.1.1.1:80 would be a valid filter,
-// which would allow connections to the port :80 while any requests to say 1.1.1.1:1234 would be denied.
 #[derive(Debug)]
 pub(crate) struct HostsStore {
     storefile: PathBuf,
 
-    domains: HashSet<String>,
-    ip_nets: Vec<IpNetwork>,
+    rules: Vec<HostRule>,
 }
 
 impl HostsStore {
     /// Constructs a new HostsStore
     pub(crate) fn new(base_dir: PathBuf, filename: PathBuf) -> HostsStore {
         let storefile = HostsStore::setup_storefile(base_dir, filename);
-        let hosts = HostsStore::load_from_storefile(&storefile)
+        let rules = HostsStore::load_from_storefile(&storefile)
             .unwrap_or_else(|_| panic!("Could not load hosts from storefile at {:?}", storefile));
 
-        let (domains, ip_nets): (Vec<_>, Vec<_>) =
-            hosts.into_iter().partition(|host| host.is_domain());
+        HostsStore { storefile, rules }
+    }
 
-        HostsStore {
-            storefile,
-            domains: domains.into_iter().map(Host::extract_domain).collect(),
-            ip_nets: ip_nets.into_iter().map(Host::extract_ipnetwork).collect(),
+    fn reload(&mut self) {
+        match HostsStore::load_from_storefile(&self.storefile) {
+            Ok(rules) => {
+                log::info!(
+                    "Reloaded {} rules from {:?}",
+                    rules.len(),
+                    self.storefile
+                );
+                self.rules = rules;
+            }
+            Err(err) => log::error!(
+                "Could not reload hosts from storefile at {:?} - {}. The previous rules are going to be kept",
+                self.storefile,
+                err
+            ),
         }
     }
 
@@ -205,21 +253,30 @@ impl HostsStore {
         HostsStore::append(&self.storefile, host);
     }
 
+    /// Returns the rule applying to the destination. Deny rules take precedence over allow rules,
+    /// otherwise the first matching rule is returned.
+    fn matching_rule(&self, destination: &Destination) -> Option<&HostRule> {
+        let mut matching = self.rules.iter().filter(|rule| rule.matches(destination));
+        let first_match = matching.next()?;
+        if !first_match.is_allow() {
+            return Some(first_match);
+        }
+        matching.find(|rule| !rule.is_allow()).or(Some(first_match))
+    }
+
     fn contains_domain(&self, host: &str) -> bool {
-        self.domains.contains(&host.to_string())
+        self.rules
+            .iter()
+            .any(|rule| matches!(&rule.pattern, HostPattern::Domain(domain) if domain == host))
     }
 
     fn contains_ip_address(&self, address: IpAddr) -> bool {
         // I'm not sure it's possible to achieve the same functionality without iterating through
         // the whole thing. Maybe by some clever usage of tries? But I doubt we're going to have
         // so many filtering rules that it's going to matter at this point.
-        for ip_net in &self.ip_nets {
-            if ip_net.contains(address) {
-                return true;
-            }
-        }
-
-        false
+        self.rules.iter().any(
+            |rule| matches!(rule.pattern, HostPattern::IpNetwork(ip_net) if ip_net.contains(address)),
+        )
     }
 
     /// Returns the default base directory for the storefile.
@@ -231,17 +288,14 @@ impl HostsStore {
             .join(".nym")
     }
 
-    fn maybe_add_ip(&mut self, ip: IpAddr) {
-        if !self.contains_ip_address(ip) {
-            self.ip_nets.push(ip.into());
-            self.append_to_file(&ip.to_string());
-        }
-    }
-
-    fn maybe_add_domain(&mut self, domain: &str) {
-        if !self.contains_domain(domain) {
-            self.domains.insert(domain.to_string());
-            self.append_to_file(domain);
+    fn maybe_add(&mut self, destination: &Destination) {
+        let exists = match destination {
+            Destination::Ip(ip, _) => self.contains_ip_address(*ip),
+            Destination::Domain { name, .. } => self.contains_domain(name),
+        };
+        if !exists {
+            self.rules.push(destination.into());
+            self.append_to_file(&destination.to_string());
         }
     }
 
@@ -257,17 +311,36 @@ impl HostsStore {
         storefile
     }
 
-    /// Loads the storefile contents into memory.
-    fn load_from_storefile<P>(filename: P) -> io::Result<Vec<Host>>
+    /// Loads the storefile contents into memory. Empty lines, comments (starting with `#` or `//`)
+    /// and malformed rules are skipped.
+    fn load_from_storefile<P>(filename: P) -> io::Result<Vec<HostRule>>
     where
         P: AsRef<Path>,
     {
-        let file = File::open(filename)?;
+        let file = File::open(&filename)?;
         let reader = BufReader::new(&file);
-        Ok(reader
-            .lines()
-            .map(|line| Host::from(line.expect("failed to read input file line!")))
-            .collect())
+        let mut rules = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            match line.parse::<HostRule>() {
+                Ok(mut rule) => {
+                    rule.line = Some(i + 1);
+                    rules.push(rule)
+                }
+                Err(err) => log::warn!(
+                    "Ignoring malformed rule '{}' at line {} of {:?} - {}",
+                    line,
+                    i + 1,
+                    filename.as_ref(),
+                    err
+                ),
+            }
+        }
+        Ok(rules)
     }
 }
 
@@ -379,11 +452,11 @@ mod tests {
             let host = "unknown.com";
             let mut filter = setup();
             filter.check(host);
-            assert_eq!(1, filter.unknown_hosts.domains.len());
-            assert!(filter.unknown_hosts.domains.contains("unknown.com"));
+            assert_eq!(1, filter.unknown_hosts.rules.len());
+            assert!(filter.unknown_hosts.contains_domain("unknown.com"));
             filter.check(host);
-            assert_eq!(1, filter.unknown_hosts.domains.len());
-            assert!(filter.unknown_hosts.domains.contains("unknown.com"));
+            assert_eq!(1, filter.unknown_hosts.rules.len());
+            assert!(filter.unknown_hosts.contains_domain("unknown.com"));
         }
     }
 
@@ -512,6 +585,87 @@ mod tests {
         }
     }
 
+    #[cfg(test)]
+    mod requests_matching_explicit_rules {
+        use super::*;
+
+        fn setup(rules: &[&str]) -> OutboundRequestFilter {
+            let (allowed_storefile, base_dir1, allowed_filename) = create_test_storefile();
+            let (_, base_dir2, unknown_filename) = create_test_storefile();
+
+            for rule in rules {
+                HostsStore::append(&allowed_storefile, rule)
+            }
+
+            let allowed = HostsStore::new(base_dir1, allowed_filename);
+            let unknown = HostsStore::new(base_dir2, unknown_filename);
+            OutboundRequestFilter::new(allowed, unknown)
+        }
+
+        #[test]
+        fn are_denied_if_any_deny_rule_matches() {
+            let mut filter = setup(&[
+                "0.0.0.0/0",
+                "10.0.0.0/8 deny",
+                "nymtech.net",
+                "*.nymtech.net deny",
+            ]);
+
+            assert!(filter.check("1.1.1.1:443"));
+            assert!(!filter.check("10.1.2.3:443"));
+            assert!(filter.check("nymtech.net:443"));
+            assert!(!filter.check("foomp.nymtech.net:443"));
+
+            // denied hosts are not unknown
+            assert!(filter.unknown_hosts.rules.is_empty());
+        }
+
+        #[test]
+        fn respect_ports() {
+            let mut filter = setup(&["*.example.com:443", "1.1.1.1:53"]);
+
+            assert!(filter.check("api.example.com:443"));
+            assert!(!filter.check("api.example.com:80"));
+            assert!(!filter.check("example.com:443"));
+            assert!(filter.check("1.1.1.1:53"));
+            assert!(!filter.check("1.1.1.1:54"));
+        }
+
+        #[test]
+        fn report_the_matching_rule() {
+            let filter = setup(&["nymtech.net", "*.nymtech.net:22 deny"]);
+
+            match filter.evaluate("foomp.nymtech.net:443") {
+                FilterDecision::Allowed(rule) => assert_eq!(Some(1), rule.line),
+                _ => unreachable!(),
+            }
+            match filter.evaluate("foomp.nymtech.net:22") {
+                FilterDecision::Denied(rule) => assert_eq!(Some(2), rule.line),
+                _ => unreachable!(),
+            }
+            assert!(matches!(
+                filter.evaluate("unknown.com:443"),
+                FilterDecision::NoMatchingRule
+            ));
+            assert!(matches!(
+                filter.evaluate("flappappa"),
+                FilterDecision::InvalidHost
+            ));
+        }
+
+        #[test]
+        fn are_reloaded_when_requested() {
+            let mut filter = setup(&["nymtech.net"]);
+            assert!(!filter.check("example.com:443"));
+
+            HostsStore::append(filter.allowed_hosts_storefile(), "example.com");
+            assert!(!filter.check("example.com:443"));
+
+            filter.reload_requested.store(true, Ordering::SeqCst);
+            assert!(filter.check("example.com:443"));
+        }
+    }
+
     fn random_string() -> String {
         format!("{:?}", rand::random::<u32>())
     }
@@ -546,13 +700,36 @@ mod tests {
             HostsStore::append(&storefile, "5:6:7::/48");
 
             let host_store = HostsStore::new(base_dir, filename);
-            assert!(host_store.domains.contains("nymtech.net"));
-            assert!(host_store.domains.contains("edwardsnowden.com"));
+            assert!(host_store.contains_domain("nymtech.net"));
+            assert!(host_store.contains_domain("edwardsnowden.com"));
+
+            assert!(contains_network(&host_store, "1.2.3.4"));
+            assert!(contains_network(&host_store, "5.6.7.8/16"));
+            assert!(contains_network(&host_store, "1:2:3::"));
+            assert!(contains_network(&host_store, "5:6:7::/48"));
+        }
+
+        #[test]
+        fn skips_comments_and_malformed_rules() {
+            let (storefile, base_dir, filename) = create_test_storefile();
+            HostsStore::append(&storefile, "# some comment");
+            HostsStore::append(&storefile, "");
+            HostsStore::append(&storefile, "nymtech.net");
+            HostsStore::append(&storefile, "nymtech.net:foomp");
+            HostsStore::append(&storefile, "10.0.0.0/8 deny");
+
+            let host_store = HostsStore::new(base_dir, filename);
+            assert_eq!(2, host_store.rules.len());
+            assert_eq!(Some(3), host_store.rules[0].line);
+            assert_eq!(Some(5), host_store.rules[1].line);
+        }
 
-            assert!(host_store.ip_nets.contains(&"1.2.3.4".parse().unwrap()));
-            assert!(host_store.ip_nets.contains(&"5.6.7.8/16".parse().unwrap()));
-            assert!(host_store.ip_nets.contains(&"1:2:3::".parse().unwrap()));
-            assert!(host_store.ip_nets.contains(&"5:6:7::/48".parse().unwrap()));
+        fn contains_network(host_store: &HostsStore, network: &str) -> bool {
+            let network = network.parse().unwrap();
+            host_store
+                .rules
+                .iter()
+                .any(|rule| rule.pattern == HostPattern::IpNetwork(network))
         }
     }
 }
@@ -0,0 +1,377 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use ipnetwork::IpNetwork;
+use std::fmt::{self, Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Destination of an outbound request, as extracted from the requested remote address.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Destination {
+    Ip(IpAddr, Option<u16>),
+    Domain {
+        name: String,
+        root: String,
+        port: Option<u16>,
+    },
+}
+
+impl Display for Destination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Destination::Ip(ip, _) => write!(f, "{}", ip),
+            Destination::Domain { name, .. } => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Destination {
+    fn port(&self) -> Option<u16> {
+        match self {
+            Destination::Ip(_, port) => *port,
+            Destination::Domain { port, .. } => *port,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RuleAction {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum HostPattern {
+    /// Matches exactly the specified domain. If it is a root domain, it also matches
+    /// all of its subdomains.
+    Domain(String),
+
+    /// Specified as `*.domain`, matches all subdomains of the domain, but not the domain itself.
+    Wildcard(String),
+
+    /// Matches all addresses within the network.
+    IpNetwork(IpNetwork),
+}
+
+#[derive(Debug)]
+pub(crate) enum RuleParseError {
+    UnknownAction(String),
+    TrailingData(String),
+    MalformedPort(String),
+    MalformedIpNetwork(String),
+    UnterminatedBracket,
+}
+
+impl Display for RuleParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleParseError::UnknownAction(action) => write!(
+                f,
+                "unknown rule action '{}' (expected 'allow' or 'deny')",
+                action
+            ),
+            RuleParseError::TrailingData(data) => write!(f, "unexpected trailing data '{}'", data),
+            RuleParseError::MalformedPort(port) => write!(f, "malformed port '{}'", port),
+            RuleParseError::MalformedIpNetwork(network) => {
+                write!(f, "malformed ip network '{}'", network)
+            }
+            RuleParseError::UnterminatedBracket => write!(f, "missing closing ']'"),
+        }
+    }
+}
+
+impl std::error::Error for RuleParseError {}
+
+/// A single filtering rule of the form `host[:port] [allow|deny]`, where `host` is either
+/// a domain, a `*.domain` wildcard or an ip network (IPv6 networks have to be put in brackets
+/// when followed by a port, e.g. `[2001:db8::/32]:443`). Rules without a port apply to every port
+/// and rules without an explicit action allow the traffic.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HostRule {
+    pub(crate) pattern: HostPattern,
+    pub(crate) port: Option<u16>,
+    pub(crate) action: RuleAction,
+
+    /// The line of the storefile this rule was loaded from, if any.
+    pub(crate) line: Option<usize>,
+}
+
+impl Display for HostRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.pattern, self.port) {
+            (HostPattern::Domain(domain), None) => write!(f, "{}", domain)?,
+            (HostPattern::Domain(domain), Some(port)) => write!(f, "{}:{}", domain, port)?,
+            (HostPattern::Wildcard(domain), None) => write!(f, "*.{}", domain)?,
+            (HostPattern::Wildcard(domain), Some(port)) => write!(f, "*.{}:{}", domain, port)?,
+            (HostPattern::IpNetwork(network), None) => write!(f, "{}", network)?,
+            (HostPattern::IpNetwork(IpNetwork::V4(network)), Some(port)) => {
+                write!(f, "{}:{}", network, port)?
+            }
+            (HostPattern::IpNetwork(IpNetwork::V6(network)), Some(port)) => {
+                write!(f, "[{}]:{}", network, port)?
+            }
+        }
+        if self.action == RuleAction::Deny {
+            write!(f, " deny")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&Destination> for HostRule {
+    fn from(destination: &Destination) -> Self {
+        let pattern = match destination {
+            Destination::Ip(ip, _) => HostPattern::IpNetwork((*ip).into()),
+            Destination::Domain { name, .. } => HostPattern::Domain(name.clone()),
+        };
+        HostRule {
+            pattern,
+            port: None,
+            action: RuleAction::Allow,
+            line: None,
+        }
+    }
+}
+
+impl FromStr for HostRule {
+    type Err = RuleParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut tokens = raw.split_whitespace();
+        let host = tokens.next().unwrap_or_default();
+        let action = match tokens.next() {
+            None | Some("allow") => RuleAction::Allow,
+            Some("deny") => RuleAction::Deny,
+            Some(other) => return Err(RuleParseError::UnknownAction(other.to_string())),
+        };
+        let trailing: Vec<_> = tokens.collect();
+        if !trailing.is_empty() {
+            return Err(RuleParseError::TrailingData(trailing.join(" ")));
+        }
+
+        let (host, port) = split_host_port(host)?;
+        let pattern = if let Some(domain) = host.strip_prefix("*.") {
+            HostPattern::Wildcard(domain.to_lowercase())
+        } else if let Ok(network) = host.parse() {
+            HostPattern::IpNetwork(network)
+        } else if host.contains('/') || host.contains(':') {
+            return Err(RuleParseError::MalformedIpNetwork(host.to_string()));
+        } else {
+            // TODO: perhaps in the future it should do some domain validation?
+            // so for example if somebody put some nonsense in the allowed hosts file like "foomp",
+            // it would get rejected?
+            HostPattern::Domain(host.to_lowercase())
+        };
+
+        Ok(HostRule {
+            pattern,
+            port,
+            action,
+            line: None,
+        })
+    }
+}
+
+fn parse_port(raw: &str) -> Result<Option<u16>, RuleParseError> {
+    if raw == "*" {
+        return Ok(None);
+    }
+    raw.parse()
+        .map(Some)
+        .map_err(|_| RuleParseError::MalformedPort(raw.to_string()))
+}
+
+fn split_host_port(raw: &str) -> Result<(&str, Option<u16>), RuleParseError> {
+    if let Some(bracketed) = raw.strip_prefix('[') {
+        let (host, remainder) = bracketed
+            .split_once(']')
+            .ok_or(RuleParseError::UnterminatedBracket)?;
+        if remainder.is_empty() {
+            return Ok((host, None));
+        }
+        return match remainder.strip_prefix(':') {
+            Some(port) => Ok((host, parse_port(port)?)),
+            None => Err(RuleParseError::TrailingData(remainder.to_string())),
+        };
+    }
+
+    // a colon in the host part implies an unbracketed IPv6 address, which can't have a port
+    match raw.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => Ok((host, parse_port(port)?)),
+        _ => Ok((raw, None)),
+    }
+}
+
+impl HostRule {
+    pub(crate) fn is_allow(&self) -> bool {
+        self.action == RuleAction::Allow
+    }
+
+    pub(crate) fn matches(&self, destination: &Destination) -> bool {
+        let host_matches = match (&self.pattern, destination) {
+            (HostPattern::IpNetwork(network), Destination::Ip(ip, _)) => network.contains(*ip),
+            (HostPattern::Domain(domain), Destination::Domain { name, root, .. }) => {
+                domain == name || domain == root
+            }
+            (HostPattern::Wildcard(domain), Destination::Domain { name, .. }) => name
+                .strip_suffix(domain.as_str())
+                .map(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.'))
+                .unwrap_or_default(),
+            _ => false,
+        };
+
+        match self.port {
+            Some(port) => host_matches && destination.port() == Some(port),
+            None => host_matches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(name: &str, root: &str, port: Option<u16>) -> Destination {
+        Destination::Domain {
+            name: name.to_string(),
+            root: root.to_string(),
+            port,
+        }
+    }
+
+    #[cfg(test)]
+    mod parsing_rules {
+        use super::*;
+
+        #[test]
+        fn works_for_plain_hosts() {
+            let rule: HostRule = "nymtech.net".parse().unwrap();
+            assert_eq!(rule.pattern, HostPattern::Domain("nymtech.net".into()));
+            assert_eq!(rule.port, None);
+            assert_eq!(rule.action, RuleAction::Allow);
+
+            let rule: HostRule = "1.2.3.4/24".parse().unwrap();
+            assert_eq!(
+                rule.pattern,
+                HostPattern::IpNetwork("1.2.3.4/24".parse().unwrap())
+            );
+
+            let rule: HostRule = "2001:db8::1".parse().unwrap();
+            assert_eq!(
+                rule.pattern,
+                HostPattern::IpNetwork("2001:db8::1".parse().unwrap())
+            );
+            assert_eq!(rule.port, None);
+        }
+
+        #[test]
+        fn works_with_ports() {
+            let rule: HostRule = "*.example.com:443".parse().unwrap();
+            assert_eq!(rule.pattern, HostPattern::Wildcard("example.com".into()));
+            assert_eq!(rule.port, Some(443));
+
+            let rule: HostRule = "10.0.0.0/8:22 deny".parse().unwrap();
+            assert_eq!(
+                rule.pattern,
+                HostPattern::IpNetwork("10.0.0.0/8".parse().unwrap())
+            );
+            assert_eq!(rule.port, Some(22));
+            assert_eq!(rule.action, RuleAction::Deny);
+
+            let rule: HostRule = "[2001:db8::/32]:443".parse().unwrap();
+            assert_eq!(
+                rule.pattern,
+                HostPattern::IpNetwork("2001:db8::/32".parse().unwrap())
+            );
+            assert_eq!(rule.port, Some(443));
+
+            let rule: HostRule = "nymtech.net:*".parse().unwrap();
+            assert_eq!(rule.port, None);
+        }
+
+        #[test]
+        fn works_with_actions() {
+            let rule: HostRule = "10.0.0.0/8 deny".parse().unwrap();
+            assert_eq!(rule.action, RuleAction::Deny);
+
+            let rule: HostRule = "nymtech.net   allow".parse().unwrap();
+            assert_eq!(rule.action, RuleAction::Allow);
+        }
+
+        #[test]
+        fn fails_for_malformed_rules() {
+            assert!("nymtech.net block".parse::<HostRule>().is_err());
+            assert!("nymtech.net deny please".parse::<HostRule>().is_err());
+            assert!("nymtech.net:https".parse::<HostRule>().is_err());
+            assert!("nymtech.net:99999".parse::<HostRule>().is_err());
+            assert!("[2001:db8::/32:443".parse::<HostRule>().is_err());
+            assert!("10.0.0.0/33".parse::<HostRule>().is_err());
+        }
+
+        #[test]
+        fn can_be_displayed_back() {
+            for raw in [
+                "nymtech.net",
+                "*.example.com:443",
+                "10.0.0.0/8 deny",
+                "[2001:db8::/32]:443 deny",
+            ] {
+                assert_eq!(raw, raw.parse::<HostRule>().unwrap().to_string())
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod matching_rules {
+        use super::*;
+
+        #[test]
+        fn domains_match_subdomains_only_if_root() {
+            let root_rule: HostRule = "example.com".parse().unwrap();
+            let subdomain_rule: HostRule = "api.example.com".parse().unwrap();
+
+            let apex = domain("example.com", "example.com", Some(443));
+            let api = domain("api.example.com", "example.com", Some(443));
+            let other = domain("www.example.com", "example.com", Some(443));
+
+            assert!(root_rule.matches(&apex));
+            assert!(root_rule.matches(&api));
+            assert!(root_rule.matches(&other));
+
+            assert!(!subdomain_rule.matches(&apex));
+            assert!(subdomain_rule.matches(&api));
+            assert!(!subdomain_rule.matches(&other));
+        }
+
+        #[test]
+        fn wildcards_match_subdomains_but_not_the_domain_itself() {
+            let rule: HostRule = "*.example.com".parse().unwrap();
+
+            assert!(!rule.matches(&domain("example.com", "example.com", None)));
+            assert!(rule.matches(&domain("api.example.com", "example.com", None)));
+            assert!(rule.matches(&domain("foo.api.example.com", "example.com", None)));
+            assert!(!rule.matches(&domain("notexample.com", "notexample.com", None)));
+        }
+
+        #[test]
+        fn ports_are_respected() {
+            let rule: HostRule = "*.example.com:443".parse().unwrap();
+
+            assert!(rule.matches(&domain("api.example.com", "example.com", Some(443))));
+            assert!(!rule.matches(&domain("api.example.com", "example.com", Some(80))));
+            assert!(!rule.matches(&domain("api.example.com", "example.com", None)));
+
+            let rule: HostRule = "1.1.1.1:53".parse().unwrap();
+            assert!(rule.matches(&Destination::Ip("1.1.1.1".parse().unwrap(), Some(53))));
+            assert!(!rule.matches(&Destination::Ip("1.1.1.1".parse().unwrap(), Some(80))));
+        }
+
+        #[test]
+        fn networks_do_not_match_domains() {
+            let rule: HostRule = "0.0.0.0/0".parse().unwrap();
+            assert!(!rule.matches(&domain("example.com", "example.com", None)));
+            assert!(rule.matches(&Destination::Ip("8.8.8.8".parse().unwrap(), None)));
+        }
+    }
+}
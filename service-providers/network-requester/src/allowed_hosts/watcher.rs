@@ -0,0 +1,79 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use log::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const POLLING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Requests the allowed hosts to be reloaded whenever their storefile gets modified
+/// or the process receives a SIGHUP. The actual reload happens during the next filter check.
+pub(crate) struct HostsFileWatcher {
+    storefile: PathBuf,
+    last_modified: Option<SystemTime>,
+    reload_requested: Arc<AtomicBool>,
+}
+
+impl HostsFileWatcher {
+    pub(crate) fn new(storefile: PathBuf, reload_requested: Arc<AtomicBool>) -> Self {
+        HostsFileWatcher {
+            last_modified: Self::modification_time(&storefile),
+            storefile,
+            reload_requested,
+        }
+    }
+
+    fn modification_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn request_reload(&self) {
+        self.reload_requested.store(true, Ordering::SeqCst)
+    }
+
+    fn check_for_modification(&mut self) {
+        let modified = Self::modification_time(&self.storefile);
+        if modified != self.last_modified {
+            info!(
+                "{:?} got modified - the allowed hosts are going to be reloaded",
+                self.storefile
+            );
+            self.last_modified = modified;
+            self.request_reload();
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) async fn run(mut self) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = signal(SignalKind::hangup()).expect("failed to setup SIGHUP handler");
+        let mut polling_interval = tokio::time::interval(POLLING_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => {
+                    info!("Received SIGHUP - the allowed hosts are going to be reloaded");
+                    self.request_reload();
+                }
+                _ = polling_interval.tick() => self.check_for_modification(),
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) async fn run(mut self) {
+        let mut polling_interval = tokio::time::interval(POLLING_INTERVAL);
+
+        loop {
+            polling_interval.tick().await;
+            self.check_for_modification();
+        }
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx::addressing::clients::Recipient;
+use service_provider_framework::Sessions;
+use socks5_requests::{ConnectionId, ErrorKind, Response, ResponseMessage};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+const CONNECTION_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits imposed on each client (identified by its return address). `None` means unlimited.
+#[derive(Clone, Debug)]
+pub(crate) struct Limits {
+    pub(crate) max_concurrent_connections: Option<usize>,
+    pub(crate) max_new_connections_per_minute: Option<usize>,
+    pub(crate) max_bandwidth: Option<u64>,
+    pub(crate) bandwidth_window: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_concurrent_connections: None,
+            max_new_connections_per_minute: None,
+            max_bandwidth: None,
+            bandwidth_window: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ClientUsage {
+    active_connections: usize,
+    recent_connections: VecDeque<Instant>,
+    bandwidth_window_start: Instant,
+    bandwidth_used: u64,
+}
+
+impl ClientUsage {
+    fn new(now: Instant) -> Self {
+        ClientUsage {
+            active_connections: 0,
+            recent_connections: VecDeque::new(),
+            bandwidth_window_start: now,
+            bandwidth_used: 0,
+        }
+    }
+
+    fn prune_recent_connections(&mut self, now: Instant) {
+        while let Some(opened) = self.recent_connections.front() {
+            if now.duration_since(*opened) < CONNECTION_RATE_WINDOW {
+                break;
+            }
+            self.recent_connections.pop_front();
+        }
+    }
+
+    fn maybe_reset_bandwidth_window(&mut self, now: Instant, window: Duration) {
+        if now.duration_since(self.bandwidth_window_start) >= window {
+            self.bandwidth_window_start = now;
+            self.bandwidth_used = 0;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.active_connections == 0
+            && self.recent_connections.is_empty()
+            && self.bandwidth_used == 0
+    }
+}
+
+/// Reasons for refusing to account for more data on a connection.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Refusal {
+    /// The limit got exceeded just now. The client should be notified and the connection closed.
+    LimitExceeded(ErrorKind),
+
+    /// The connection is being closed due to a limit that was exceeded before. The client has
+    /// already been notified, so any further data should be dropped silently.
+    AlreadyRefused,
+}
+
+/// Builds the message informing the client that its request on the connection got refused.
+/// Older clients would read an error response as a closing data response and write the error
+/// into the application's socket, so unless they have advertised support for error responses,
+/// they are only told that the connection got closed.
+pub(crate) fn refusal_response(
+    connection_id: ConnectionId,
+    kind: ErrorKind,
+    error_responses: bool,
+) -> ResponseMessage {
+    if error_responses {
+        ResponseMessage::new_error(connection_id, kind)
+    } else {
+        Response::new(connection_id, Vec::new(), true).into()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ConnectionState {
+    /// Indicates whether the connection has exceeded a limit and is being closed.
+    refused: bool,
+
+    /// Indicates whether the client has advertised support for error responses.
+    error_responses: bool,
+}
+
+/// Keeps track of connections and bandwidth used by each client of the network requester
+/// and decides whether their requests are still within the configured `Limits`.
+#[derive(Debug)]
+pub(crate) struct ClientLimiter {
+    limits: Limits,
+    clients: HashMap<[u8; Recipient::LEN], ClientUsage>,
+    connections: Sessions<ConnectionId, ConnectionState>,
+}
+
+impl ClientLimiter {
+    pub(crate) fn new(limits: Limits) -> Self {
+        ClientLimiter {
+            limits,
+            clients: HashMap::new(),
+            // connections are closed by their proxies, even if they are idle
+            connections: Sessions::new(None),
+        }
+    }
+
+    /// Returns the client that has opened the specified connection.
+    pub(crate) fn connection_owner(&self, connection_id: ConnectionId) -> Option<Recipient> {
+        self.connections
+            .get(&connection_id)
+            .map(|connection| connection.return_address)
+    }
+
+    /// Returns whether the client that has opened the specified connection understands
+    /// error responses.
+    pub(crate) fn accepts_error_responses(&self, connection_id: ConnectionId) -> bool {
+        self.connections
+            .get(&connection_id)
+            .map(|connection| connection.state.error_responses)
+            .unwrap_or_default()
+    }
+
+    /// Checks whether the client is allowed to open another connection and if so, registers it.
+    pub(crate) fn try_open_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        client: Recipient,
+        error_responses: bool,
+        now: Instant,
+    ) -> Result<(), ErrorKind> {
+        let usage = self
+            .clients
+            .entry(client.to_bytes())
+            .or_insert_with(|| ClientUsage::new(now));
+        usage.prune_recent_connections(now);
+
+        if let Some(max_connections) = self.limits.max_concurrent_connections {
+            if usage.active_connections >= max_connections {
+                return Err(ErrorKind::TooManyConnections);
+            }
+        }
+        if let Some(max_new_connections) = self.limits.max_new_connections_per_minute {
+            if usage.recent_connections.len() >= max_new_connections {
+                return Err(ErrorKind::ConnectionRateExceeded);
+            }
+        }
+
+        usage.active_connections += 1;
+        usage.recent_connections.push_back(now);
+        let state = ConnectionState {
+            refused: false,
+            error_responses,
+        };
+        self.connections.open(connection_id, client, state);
+        Ok(())
+    }
+
+    /// Unregisters the connection, regardless of whether it got established successfully.
+    pub(crate) fn close_connection(&mut self, connection_id: ConnectionId, now: Instant) {
+        let client = match self.connections.close(&connection_id) {
+            Some(connection) => connection.return_address.to_bytes(),
+            None => return,
+        };
+
+        if let Some(usage) = self.clients.get_mut(&client) {
+            usage.active_connections = usage.active_connections.saturating_sub(1);
+        }
+        self.forget_idle_clients(now);
+    }
+
+    /// Removes clients that no longer affect any of the limits.
+    fn forget_idle_clients(&mut self, now: Instant) {
+        let bandwidth_window = self.limits.bandwidth_window;
+        self.clients.retain(|_, usage| {
+            usage.prune_recent_connections(now);
+            usage.maybe_reset_bandwidth_window(now, bandwidth_window);
+            !usage.is_idle()
+        })
+    }
+
+    /// Accounts for data sent or received on the specified connection, unless it would exceed
+    /// the bandwidth limit of its client. Once that happens, the connection is considered refused
+    /// and all of its subsequent data is rejected until it gets closed.
+    pub(crate) fn try_use_bandwidth(
+        &mut self,
+        connection_id: ConnectionId,
+        bytes: usize,
+        now: Instant,
+    ) -> Result<(), Refusal> {
+        let connection = match self.connections.get_mut(&connection_id) {
+            Some(connection) => connection,
+            // the connection is no longer there - there's nothing to account for
+            None => return Ok(()),
+        };
+        if connection.state.refused {
+            return Err(Refusal::AlreadyRefused);
+        }
+        let client = connection.return_address.to_bytes();
+        let usage = match self.clients.get_mut(&client) {
+            Some(usage) => usage,
+            None => return Ok(()),
+        };
+
+        usage.maybe_reset_bandwidth_window(now, self.limits.bandwidth_window);
+        let used = usage.bandwidth_used + bytes as u64;
+        if let Some(max_bandwidth) = self.limits.max_bandwidth {
+            if bytes > 0 && used > max_bandwidth {
+                connection.state.refused = true;
+                return Err(Refusal::LimitExceeded(ErrorKind::BandwidthExceeded));
+            }
+        }
+        usage.bandwidth_used = used;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+    use socks5_requests::ResponseFlag;
+
+    fn client() -> Recipient {
+        let mut rng = rand::thread_rng();
+
+        let client_id_pair = identity::KeyPair::new(&mut rng);
+        let client_enc_pair = encryption::KeyPair::new(&mut rng);
+        let gateway_id_pair = identity::KeyPair::new(&mut rng);
+
+        Recipient::new(
+            *client_id_pair.public_key(),
+            *client_enc_pair.public_key(),
+            *gateway_id_pair.public_key(),
+        )
+    }
+
+    #[test]
+    fn limits_concurrent_connections() {
+        let now = Instant::now();
+        let (alice, bob) = (client(), client());
+        let mut limiter = ClientLimiter::new(Limits {
+            max_concurrent_connections: Some(2),
+            ..Default::default()
+        });
+
+        assert!(limiter.try_open_connection(1, alice, true, now).is_ok());
+        assert!(limiter.try_open_connection(2, alice, true, now).is_ok());
+        assert_eq!(
+            Err(ErrorKind::TooManyConnections),
+            limiter.try_open_connection(3, alice, true, now)
+        );
+
+        // other clients are not affected
+        assert!(limiter.try_open_connection(4, bob, true, now).is_ok());
+
+        limiter.close_connection(1, now);
+        assert!(limiter.try_open_connection(3, alice, true, now).is_ok());
+    }
+
+    #[test]
+    fn limits_new_connections_per_minute() {
+        let now = Instant::now();
+        let alice = client();
+        let mut limiter = ClientLimiter::new(Limits {
+            max_new_connections_per_minute: Some(2),
+            ..Default::default()
+        });
+
+        assert!(limiter.try_open_connection(1, alice, true, now).is_ok());
+        limiter.close_connection(1, now);
+        assert!(limiter.try_open_connection(2, alice, true, now).is_ok());
+        limiter.close_connection(2, now);
+        assert_eq!(
+            Err(ErrorKind::ConnectionRateExceeded),
+            limiter.try_open_connection(3, alice, true, now)
+        );
+
+        let later = now + CONNECTION_RATE_WINDOW;
+        assert!(limiter.try_open_connection(3, alice, true, later).is_ok());
+    }
+
+    #[test]
+    fn limits_bandwidth_per_window() {
+        let now = Instant::now();
+        let alice = client();
+        let window = Duration::from_secs(10);
+        let mut limiter = ClientLimiter::new(Limits {
+            max_bandwidth: Some(1000),
+            bandwidth_window: window,
+            ..Default::default()
+        });
+
+        limiter.try_open_connection(1, alice, true, now).unwrap();
+        limiter.try_open_connection(2, alice, true, now).unwrap();
+        limiter.try_open_connection(3, alice, true, now).unwrap();
+        assert!(limiter.try_use_bandwidth(1, 600, now).is_ok());
+        assert_eq!(
+            Err(Refusal::LimitExceeded(ErrorKind::BandwidthExceeded)),
+            limiter.try_use_bandwidth(2, 600, now)
+        );
+        assert!(limiter.try_use_bandwidth(3, 400, now).is_ok());
+
+        // closing the connection does not allow to bypass the limit
+        limiter.close_connection(1, now);
+        limiter.close_connection(2, now);
+        limiter.close_connection(3, now);
+        limiter.try_open_connection(4, alice, true, now).unwrap();
+        assert_eq!(
+            Err(Refusal::LimitExceeded(ErrorKind::BandwidthExceeded)),
+            limiter.try_use_bandwidth(4, 1, now)
+        );
+
+        limiter.try_open_connection(5, alice, true, now).unwrap();
+        assert!(limiter.try_use_bandwidth(5, 1000, now + window).is_ok());
+    }
+
+    #[test]
+    fn refused_connections_are_only_reported_once() {
+        let now = Instant::now();
+        let alice = client();
+        let mut limiter = ClientLimiter::new(Limits {
+            max_bandwidth: Some(1000),
+            ..Default::default()
+        });
+
+        limiter.try_open_connection(1, alice, true, now).unwrap();
+        assert_eq!(
+            Err(Refusal::LimitExceeded(ErrorKind::BandwidthExceeded)),
+            limiter.try_use_bandwidth(1, 1001, now)
+        );
+
+        // even if the data would have fit within the limit, the connection is being closed
+        assert_eq!(
+            Err(Refusal::AlreadyRefused),
+            limiter.try_use_bandwidth(1, 1, now)
+        );
+        assert_eq!(
+            Err(Refusal::AlreadyRefused),
+            limiter.try_use_bandwidth(1, 2000, now)
+        );
+    }
+
+    #[test]
+    fn legacy_clients_never_receive_error_responses() {
+        let now = Instant::now();
+        let (alice, bob) = (client(), client());
+        let mut limiter = ClientLimiter::new(Limits {
+            max_concurrent_connections: Some(1),
+            max_bandwidth: Some(1000),
+            ..Default::default()
+        });
+
+        limiter.try_open_connection(1, alice, false, now).unwrap();
+        limiter.try_open_connection(2, bob, true, now).unwrap();
+        assert!(!limiter.accepts_error_responses(1));
+        assert!(limiter.accepts_error_responses(2));
+
+        let refused = limiter
+            .try_open_connection(3, alice, false, now)
+            .unwrap_err();
+        let refused_bandwidth = match limiter.try_use_bandwidth(1, 1001, now) {
+            Err(Refusal::LimitExceeded(kind)) => kind,
+            other => panic!("unexpected bandwidth usage result {:?}", other),
+        };
+        for (connection_id, kind) in [(3, refused), (1, refused_bandwidth)] {
+            let bytes = refusal_response(connection_id, kind, limiter.accepts_error_responses(1))
+                .into_bytes();
+            assert_ne!(ResponseFlag::Error as u8, bytes[0]);
+            match ResponseMessage::try_from_bytes(&bytes).unwrap() {
+                ResponseMessage::Data(response) => {
+                    assert_eq!(connection_id, response.connection_id);
+                    assert!(response.data.is_empty());
+                    assert!(response.is_closed);
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+        }
+
+        let bytes = refusal_response(2, ErrorKind::BandwidthExceeded, true).into_bytes();
+        assert_eq!(ResponseFlag::Error as u8, bytes[0]);
+    }
+
+    #[test]
+    fn forgets_idle_clients() {
+        let now = Instant::now();
+        let alice = client();
+        let mut limiter = ClientLimiter::new(Limits::default());
+
+        limiter.try_open_connection(1, alice, true, now).unwrap();
+        assert_eq!(
+            alice.to_bytes(),
+            limiter.connection_owner(1).unwrap().to_bytes()
+        );
+        limiter.close_connection(1, now + CONNECTION_RATE_WINDOW);
+
+        assert!(limiter.connection_owner(1).is_none());
+        assert!(limiter.clients.is_empty());
+    }
+}
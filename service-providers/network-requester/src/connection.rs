@@ -1,13 +1,14 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::restricted_networks::RestrictedNetworks;
 use futures::channel::mpsc;
+use log::*;
 use nymsphinx::addressing::clients::Recipient;
-use proxy_helpers::connection_controller::ConnectionReceiver;
-use proxy_helpers::proxy_runner::ProxyRunner;
-use socks5_requests::{ConnectionId, RemoteAddress, Response};
+use service_provider_framework::connections::{ConnectionReceiver, FlowControl, ProxyRunner};
+use socks5_requests::{ConnectionId, RemoteAddress, Response, ResponseMessage};
 use std::io;
-use tokio::net::TcpStream;
+use tokio::net::{self, TcpStream};
 
 /// A TCP connection between the Socks5 service provider, which makes
 /// outbound requests on behalf of users and returns the responses through
@@ -25,8 +26,9 @@ impl Connection {
         id: ConnectionId,
         address: RemoteAddress,
         return_address: Recipient,
+        restricted_networks: &RestrictedNetworks,
     ) -> io::Result<Self> {
-        let conn = TcpStream::connect(&address).await?;
+        let conn = Self::connect_unrestricted(&address, restricted_networks).await?;
 
         Ok(Connection {
             id,
@@ -36,10 +38,46 @@ impl Connection {
         })
     }
 
+    /// Resolves the address and attempts to connect to each of the resulting socket addresses
+    /// that are not within a restricted network. Note that we're connecting to the exact addresses
+    /// we have checked so that the remote can't rebind its domain in the meantime.
+    async fn connect_unrestricted(
+        address: &str,
+        restricted_networks: &RestrictedNetworks,
+    ) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for socket_address in net::lookup_host(address).await? {
+            if restricted_networks.is_restricted(socket_address.ip()) {
+                warn!(
+                    "Refusing to connect to {} ({}) as it's within a restricted network",
+                    address, socket_address
+                );
+                last_err = Some(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} is within a restricted network", socket_address),
+                ));
+                continue;
+            }
+
+            match TcpStream::connect(socket_address).await {
+                Ok(conn) => return Ok(conn),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any address",
+            )
+        }))
+    }
+
     pub(crate) async fn run_proxy(
         &mut self,
         mix_receiver: ConnectionReceiver,
-        mix_sender: mpsc::UnboundedSender<(Response, Recipient)>,
+        mix_sender: mpsc::UnboundedSender<(ResponseMessage, Recipient)>,
+        flow_control: FlowControl,
     ) {
         let stream = self.conn.take().unwrap();
         let remote_source_address = "???".to_string(); // we don't know ip address of requester
@@ -52,10 +90,22 @@ impl Connection {
             mix_receiver,
             mix_sender,
             connection_id,
+            flow_control,
+        )
+        .run(
+            move |conn_id, read_data, socket_closed| {
+                (
+                    Response::new(conn_id, read_data, socket_closed).into(),
+                    recipient,
+                )
+            },
+            move |conn_id, credit| {
+                (
+                    ResponseMessage::new_window_update(conn_id, credit),
+                    recipient,
+                )
+            },
         )
-        .run(move |conn_id, read_data, socket_closed| {
-            (Response::new(conn_id, read_data, socket_closed), recipient)
-        })
         .await
         .into_inner();
         self.conn = Some(stream);
@@ -1,23 +1,24 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::allowed_hosts::{HostsStore, OutboundRequestFilter};
+use crate::allowed_hosts::OutboundRequestFilter;
+use crate::client_limits::{refusal_response, ClientLimiter, Limits, Refusal};
 use crate::connection::Connection;
-use crate::websocket;
-use crate::websocket::TSWebsocketStream;
+use crate::restricted_networks::RestrictedNetworks;
 use futures::channel::mpsc;
-use futures::stream::{SplitSink, SplitStream};
-use futures::{SinkExt, StreamExt};
+use futures::StreamExt;
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::receiver::ReconstructedMessage;
-use proxy_helpers::connection_controller::{Controller, ControllerCommand, ControllerSender};
-use socks5_requests::{ConnectionId, Request, Response};
-use std::path::PathBuf;
+use service_provider_framework::connections::{
+    Controller, ControllerCommand, ControllerSender, FlowControl,
+};
+use service_provider_framework::websocket::WebsocketConnectionError;
+use service_provider_framework::ReplySender;
+use socks5_requests::{ConnectionId, Request, Response, ResponseMessage};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use tokio_tungstenite::tungstenite::protocol::Message;
-use websocket::WebsocketConnectionError;
-use websocket_requests::{requests::ClientRequest, responses::ServerResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 // Since it's an atomic, it's safe to be kept static and shared across threads
 static ACTIVE_PROXIES: AtomicUsize = AtomicUsize::new(0);
@@ -25,88 +26,113 @@ static ACTIVE_PROXIES: AtomicUsize = AtomicUsize::new(0);
 pub struct ServiceProvider {
     listening_address: String,
     outbound_request_filter: OutboundRequestFilter,
+    restricted_networks: Arc<RestrictedNetworks>,
+    client_limiter: Arc<Mutex<ClientLimiter>>,
     open_proxy: bool,
+
+    controller_sender: ControllerSender,
+    mix_input_sender: mpsc::UnboundedSender<(ResponseMessage, Recipient)>,
+
+    // those get started once we're connected to the native client
+    active_connections_controller: Option<Controller>,
+    mix_input_receiver: Option<mpsc::UnboundedReceiver<(ResponseMessage, Recipient)>>,
 }
 
 impl ServiceProvider {
-    pub fn new(listening_address: String, open_proxy: bool) -> ServiceProvider {
-        let allowed_hosts = HostsStore::new(
-            HostsStore::default_base_dir(),
-            PathBuf::from("allowed.list"),
-        );
+    pub(crate) fn new(
+        listening_address: String,
+        open_proxy: bool,
+        restricted_networks: RestrictedNetworks,
+        limits: Limits,
+    ) -> ServiceProvider {
+        let outbound_request_filter = OutboundRequestFilter::new_with_default_storefiles();
+
+        // channels responsible for managing messages that are to be sent to the mix network. The receiver is
+        // going to be used by `mixnet_response_listener`
+        let (mix_input_sender, mix_input_receiver) =
+            mpsc::unbounded::<(ResponseMessage, Recipient)>();
+
+        // controller for managing all active connections
+        let (active_connections_controller, controller_sender) = Controller::new();
 
-        let unknown_hosts = HostsStore::new(
-            HostsStore::default_base_dir(),
-            PathBuf::from("unknown.list"),
-        );
-        let outbound_request_filter = OutboundRequestFilter::new(allowed_hosts, unknown_hosts);
         ServiceProvider {
             listening_address,
             outbound_request_filter,
+            restricted_networks: Arc::new(restricted_networks),
+            client_limiter: Arc::new(Mutex::new(ClientLimiter::new(limits))),
             open_proxy,
+            controller_sender,
+            mix_input_sender,
+            active_connections_controller: Some(active_connections_controller),
+            mix_input_receiver: Some(mix_input_receiver),
         }
     }
 
     /// Listens for any messages from `mix_reader` that should be written back to the mix network
-    /// via the `websocket_writer`.
+    /// via the `reply_sender`.
     async fn mixnet_response_listener(
-        mut websocket_writer: SplitSink<TSWebsocketStream, Message>,
-        mut mix_reader: mpsc::UnboundedReceiver<(Response, Recipient)>,
+        reply_sender: ReplySender,
+        mut mix_reader: mpsc::UnboundedReceiver<(ResponseMessage, Recipient)>,
+        controller_sender: ControllerSender,
+        client_limiter: Arc<Mutex<ClientLimiter>>,
     ) {
-        // TODO: wire SURBs in here once they're available
-        while let Some((response, return_address)) = mix_reader.next().await {
-            // make 'request' to native-websocket client
-            let response_message = ClientRequest::Send {
-                recipient: return_address,
-                message: response.into_bytes(),
-                with_reply_surb: false,
-            };
-
-            let message = Message::Binary(response_message.serialize());
-            websocket_writer.send(message).await.unwrap();
-        }
-    }
-
-    async fn read_websocket_message(
-        websocket_reader: &mut SplitStream<TSWebsocketStream>,
-    ) -> Option<ReconstructedMessage> {
-        while let Some(msg) = websocket_reader.next().await {
-            let data = msg
-                .expect("we failed to read from the websocket!")
-                .into_data();
-
-            // try to recover the actual message from the mix network...
-            let deserialized_message = match ServerResponse::deserialize(&data) {
-                Ok(deserialized) => deserialized,
-                Err(err) => {
-                    error!(
-                        "Failed to deserialize received websocket message! - {}",
-                        err
-                    );
-                    continue;
+        // the responses can't be sent with reply SURBs as each connection might need an unbounded
+        // number of them, so the clients always reveal their addresses in the `Connect` requests
+        while let Some((mut response, return_address)) = mix_reader.next().await {
+            if let ResponseMessage::Data(data_response) = &response {
+                let conn_id = data_response.connection_id;
+                let used_bandwidth = client_limiter.lock().unwrap().try_use_bandwidth(
+                    conn_id,
+                    data_response.data.len(),
+                    Instant::now(),
+                );
+                match used_bandwidth {
+                    Ok(_) => (),
+                    Err(Refusal::LimitExceeded(err)) => {
+                        warn!(
+                            "Closing connection {} of {} - {}",
+                            conn_id, return_address, err
+                        );
+                        let error_responses = client_limiter
+                            .lock()
+                            .unwrap()
+                            .accepts_error_responses(conn_id);
+                        response = refusal_response(conn_id, err, error_responses);
+                        controller_sender
+                            .unbounded_send(ControllerCommand::Send(conn_id, Vec::new(), true))
+                            .unwrap();
+                    }
+                    // the client already knows the connection is getting closed
+                    Err(Refusal::AlreadyRefused) => continue,
                 }
-            };
+            }
 
-            let received = match deserialized_message {
-                ServerResponse::Received(received) => received,
-                ServerResponse::Error(err) => {
-                    panic!("received error from native client! - {}", err)
-                }
-                _ => unimplemented!("probably should never be reached?"),
-            };
-            return Some(received);
+            if let Err(err) = reply_sender.send_reply(return_address, response.into_bytes()) {
+                warn!("Stopping the mixnet response listener - {}", err);
+                break;
+            }
         }
-        None
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn start_proxy(
         conn_id: ConnectionId,
         remote_addr: String,
         return_address: Recipient,
+        flow_control: bool,
         controller_sender: ControllerSender,
-        mix_input_sender: mpsc::UnboundedSender<(Response, Recipient)>,
+        mix_input_sender: mpsc::UnboundedSender<(ResponseMessage, Recipient)>,
+        restricted_networks: Arc<RestrictedNetworks>,
+        client_limiter: Arc<Mutex<ClientLimiter>>,
     ) {
-        let mut conn = match Connection::new(conn_id, remote_addr.clone(), return_address).await {
+        let mut conn = match Connection::new(
+            conn_id,
+            remote_addr.clone(),
+            return_address,
+            &restricted_networks,
+        )
+        .await
+        {
             Ok(conn) => conn,
             Err(err) => {
                 error!(
@@ -117,17 +143,30 @@ impl ServiceProvider {
 
                 // inform the remote that the connection is closed before it even was established
                 mix_input_sender
-                    .unbounded_send((Response::new(conn_id, Vec::new(), true), return_address))
+                    .unbounded_send((
+                        Response::new(conn_id, Vec::new(), true).into(),
+                        return_address,
+                    ))
                     .unwrap();
 
+                client_limiter
+                    .lock()
+                    .unwrap()
+                    .close_connection(conn_id, Instant::now());
                 return;
             }
         };
 
         // Connect implies it's a fresh connection - register it with our controller
         let (mix_sender, mix_receiver) = mpsc::unbounded();
+        // older clients don't understand window updates, so they get the unbounded behaviour
+        let flow_control = FlowControl::negotiated(flow_control);
         controller_sender
-            .unbounded_send(ControllerCommand::Insert(conn_id, mix_sender))
+            .unbounded_send(ControllerCommand::Insert(
+                conn_id,
+                mix_sender,
+                flow_control.clone(),
+            ))
             .unwrap();
 
         let old_count = ACTIVE_PROXIES.fetch_add(1, Ordering::SeqCst);
@@ -138,12 +177,17 @@ impl ServiceProvider {
         );
 
         // run the proxy on the connection
-        conn.run_proxy(mix_receiver, mix_input_sender).await;
+        conn.run_proxy(mix_receiver, mix_input_sender, flow_control)
+            .await;
 
         // proxy is done - remove the access channel from the controller
         controller_sender
             .unbounded_send(ControllerCommand::Remove(conn_id))
             .unwrap();
+        client_limiter
+            .lock()
+            .unwrap()
+            .close_connection(conn_id, Instant::now());
 
         let old_count = ACTIVE_PROXIES.fetch_sub(1, Ordering::SeqCst);
         info!(
@@ -155,19 +199,39 @@ impl ServiceProvider {
 
     fn handle_proxy_connect(
         &mut self,
-        controller_sender: &mut ControllerSender,
-        mix_input_sender: &mpsc::UnboundedSender<(Response, Recipient)>,
         conn_id: ConnectionId,
         remote_addr: String,
         return_address: Recipient,
+        flow_control: bool,
+        error_responses: bool,
     ) {
         if !self.open_proxy && !self.outbound_request_filter.check(&remote_addr) {
             log::info!("Domain {:?} failed filter check", remote_addr);
             return;
         }
 
-        let controller_sender_clone = controller_sender.clone();
-        let mix_input_sender_clone = mix_input_sender.clone();
+        let opened_connection = self.client_limiter.lock().unwrap().try_open_connection(
+            conn_id,
+            return_address,
+            error_responses,
+            Instant::now(),
+        );
+        if let Err(err) = opened_connection {
+            warn!(
+                "Refusing to connect to {:?} on behalf of {} - {}",
+                remote_addr, return_address, err
+            );
+            let response = refusal_response(conn_id, err, error_responses);
+            self.mix_input_sender
+                .unbounded_send((response, return_address))
+                .unwrap();
+            return;
+        }
+
+        let controller_sender_clone = self.controller_sender.clone();
+        let mix_input_sender_clone = self.mix_input_sender.clone();
+        let restricted_networks = Arc::clone(&self.restricted_networks);
+        let client_limiter = Arc::clone(&self.client_limiter);
 
         // and start the proxy for this connection
         tokio::spawn(async move {
@@ -175,31 +239,54 @@ impl ServiceProvider {
                 conn_id,
                 remote_addr,
                 return_address,
+                flow_control,
                 controller_sender_clone,
                 mix_input_sender_clone,
+                restricted_networks,
+                client_limiter,
             )
             .await
         });
     }
 
-    fn handle_proxy_send(
-        &self,
-        controller_sender: &mut ControllerSender,
-        conn_id: ConnectionId,
-        data: Vec<u8>,
-        closed: bool,
-    ) {
-        controller_sender
+    fn handle_proxy_send(&self, conn_id: ConnectionId, data: Vec<u8>, closed: bool) {
+        let mut client_limiter = self.client_limiter.lock().unwrap();
+        match client_limiter.try_use_bandwidth(conn_id, data.len(), Instant::now()) {
+            Ok(_) => (),
+            Err(Refusal::LimitExceeded(err)) => {
+                // the connection must exist, otherwise we wouldn't have been able to exceed the limit
+                let return_address = client_limiter.connection_owner(conn_id).unwrap();
+                warn!(
+                    "Closing connection {} of {} - {}",
+                    conn_id, return_address, err
+                );
+                let error_responses = client_limiter.accepts_error_responses(conn_id);
+                let response = refusal_response(conn_id, err, error_responses);
+                self.mix_input_sender
+                    .unbounded_send((response, return_address))
+                    .unwrap();
+                self.controller_sender
+                    .unbounded_send(ControllerCommand::Send(conn_id, Vec::new(), true))
+                    .unwrap();
+                return;
+            }
+            // the client has already been told about it, so just drop the data
+            Err(Refusal::AlreadyRefused) => return,
+        }
+        drop(client_limiter);
+
+        self.controller_sender
             .unbounded_send(ControllerCommand::Send(conn_id, data, closed))
             .unwrap()
     }
 
-    fn handle_proxy_request(
-        &mut self,
-        raw_request: &[u8],
-        controller_sender: &mut ControllerSender,
-        mix_input_sender: &mpsc::UnboundedSender<(Response, Recipient)>,
-    ) {
+    fn handle_proxy_window_update(&self, conn_id: ConnectionId, credit: u32) {
+        self.controller_sender
+            .unbounded_send(ControllerCommand::WindowUpdate(conn_id, credit))
+            .unwrap()
+    }
+
+    fn handle_proxy_request(&mut self, raw_request: &[u8]) {
         // try to treat each received mix message as a service provider request
         let deserialized_request = match Request::try_from_bytes(raw_request) {
             Ok(request) => request,
@@ -211,70 +298,73 @@ impl ServiceProvider {
 
         match deserialized_request {
             Request::Connect(req) => self.handle_proxy_connect(
-                controller_sender,
-                mix_input_sender,
                 req.conn_id,
                 req.remote_addr,
                 req.return_address,
+                req.flow_control,
+                req.error_responses,
             ),
-            Request::Send(conn_id, data, closed) => {
-                self.handle_proxy_send(controller_sender, conn_id, data, closed)
+            Request::Send(conn_id, data, closed) => self.handle_proxy_send(conn_id, data, closed),
+            Request::WindowUpdate(conn_id, credit) => {
+                self.handle_proxy_window_update(conn_id, credit)
             }
         }
     }
 
     /// Start all subsystems
     pub async fn run(&mut self) {
-        let websocket_stream = self.connect_websocket(&self.listening_address).await;
-
-        // split the websocket so that we could read and write from separate threads
-        let (websocket_writer, mut websocket_reader) = websocket_stream.split();
-
-        // channels responsible for managing messages that are to be sent to the mix network. The receiver is
-        // going to be used by `mixnet_response_listener`
-        let (mix_input_sender, mix_input_receiver) = mpsc::unbounded::<(Response, Recipient)>();
+        let listening_address = self.listening_address.clone();
+        match service_provider_framework::run(self, &listening_address).await {
+            Err(WebsocketConnectionError::ConnectionNotEstablished) => {
+                panic!("Error: websocket connection attempt failed, is the Nym client running?")
+            }
+            Err(WebsocketConnectionError::ConnectionClosed) => {
+                error!("The websocket connection to the native client has failed!")
+            }
+            Ok(_) => error!("The websocket stream has finished!"),
+        }
+    }
+}
 
-        // controller for managing all active connections
-        let (mut active_connections_controller, mut controller_sender) = Controller::new();
+impl service_provider_framework::ServiceProvider for ServiceProvider {
+    fn on_start(&mut self, reply_sender: &ReplySender) {
+        let mut active_connections_controller = self
+            .active_connections_controller
+            .take()
+            .expect("the service provider has already been started");
         tokio::spawn(async move {
             active_connections_controller.run().await;
         });
 
         // start the listener for mix messages
+        let mix_input_receiver = self
+            .mix_input_receiver
+            .take()
+            .expect("the service provider has already been started");
+        let reply_sender = reply_sender.clone();
+        let controller_sender = self.controller_sender.clone();
+        let client_limiter = Arc::clone(&self.client_limiter);
         tokio::spawn(async move {
-            Self::mixnet_response_listener(websocket_writer, mix_input_receiver).await;
+            Self::mixnet_response_listener(
+                reply_sender,
+                mix_input_receiver,
+                controller_sender,
+                client_limiter,
+            )
+            .await;
         });
 
-        println!("\nAll systems go. Press CTRL-C to stop the server.");
-
-        // for each incoming message from the websocket... (which in 99.99% cases is going to be a mix message)
-        loop {
-            let received = match Self::read_websocket_message(&mut websocket_reader).await {
-                Some(msg) => msg,
-                None => {
-                    error!("The websocket stream has finished!");
-                    return;
-                }
-            };
-
-            let raw_message = received.message;
-            // TODO: here be potential SURB (i.e. received.reply_SURB)
+        // reload the allowed hosts whenever they get modified
+        let hosts_file_watcher = self.outbound_request_filter.hosts_file_watcher();
+        tokio::spawn(hosts_file_watcher.run());
 
-            self.handle_proxy_request(&raw_message, &mut controller_sender, &mix_input_sender)
-        }
+        println!("\nAll systems go. Press CTRL-C to stop the server.");
     }
 
-    // Make the websocket connection so we can receive incoming Mixnet messages.
-    async fn connect_websocket(&self, uri: &str) -> TSWebsocketStream {
-        let ws_stream = match websocket::Connection::new(uri).connect().await {
-            Ok(ws_stream) => {
-                info!("* connected to local websocket server at {}", uri);
-                ws_stream
-            }
-            Err(WebsocketConnectionError::ConnectionNotEstablished) => {
-                panic!("Error: websocket connection attempt failed, is the Nym client running?")
-            }
-        };
-        ws_stream
+    fn handle_request(&mut self, request: ReconstructedMessage, _reply_sender: &ReplySender) {
+        if request.reply_surb.is_some() {
+            warn!("this request had a surb - it is going to be ignored as all responses are sent to the return address of the connection");
+        }
+        self.handle_proxy_request(&request.message)
     }
 }
@@ -1,14 +1,27 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use clap::{App, Arg, ArgMatches};
+use allowed_hosts::{FilterDecision, OutboundRequestFilter};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use client_limits::Limits;
+use ipnetwork::IpNetwork;
+use restricted_networks::RestrictedNetworks;
+use std::time::Duration;
 
 mod allowed_hosts;
+mod client_limits;
 mod connection;
 mod core;
-mod websocket;
+mod restricted_networks;
 
 const OPEN_PROXY_ARG: &str = "open-proxy";
+const ALLOW_LOCAL_NETWORK_ARG: &str = "allow-local-network";
+const MAX_CONNECTIONS_ARG: &str = "max-connections";
+const MAX_CONNECTIONS_PER_MINUTE_ARG: &str = "max-connections-per-minute";
+const MAX_BANDWIDTH_ARG: &str = "max-bandwidth";
+const BANDWIDTH_WINDOW_ARG: &str = "bandwidth-window";
+const CHECK_COMMAND: &str = "check";
+const HOST_ARG: &str = "host";
 
 fn parse_args<'a>() -> ArgMatches<'a> {
     App::new("Nym Network Requester")
@@ -20,21 +33,150 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                 .long(OPEN_PROXY_ARG)
                 .short("o"),
         )
+        .arg(
+            Arg::with_name(ALLOW_LOCAL_NETWORK_ARG)
+                .help("allows connections to the specified network (for example '10.0.0.0/8') even though it's a loopback, private, link-local or otherwise local network that is blocked by default. Can be specified multiple times")
+                .long(ALLOW_LOCAL_NETWORK_ARG)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name(MAX_CONNECTIONS_ARG)
+                .help("maximum number of concurrent connections a single client can have open. 0 means unlimited")
+                .long(MAX_CONNECTIONS_ARG)
+                .takes_value(true)
+                .default_value("128"),
+        )
+        .arg(
+            Arg::with_name(MAX_CONNECTIONS_PER_MINUTE_ARG)
+                .help("maximum number of new connections a single client can open per minute. 0 means unlimited")
+                .long(MAX_CONNECTIONS_PER_MINUTE_ARG)
+                .takes_value(true)
+                .default_value("512"),
+        )
+        .arg(
+            Arg::with_name(MAX_BANDWIDTH_ARG)
+                .help("maximum number of bytes a single client can send and receive within the bandwidth window. 0 means unlimited")
+                .long(MAX_BANDWIDTH_ARG)
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name(BANDWIDTH_WINDOW_ARG)
+                .help("length, in seconds, of the window within which the bandwidth of each client is limited")
+                .long(BANDWIDTH_WINDOW_ARG)
+                .takes_value(true)
+                .default_value("60"),
+        )
+        .subcommand(
+            SubCommand::with_name(CHECK_COMMAND)
+                .about("Reports which rule of the allowed hosts list applies to the specified host")
+                .arg(
+                    Arg::with_name(HOST_ARG)
+                        .help("host to check, for example 'nymtech.net:443'")
+                        .required(true),
+                ),
+        )
         .get_matches()
 }
 
+fn check_host(host: &str) {
+    let filter = OutboundRequestFilter::new_with_default_storefiles();
+    let storefile = filter.allowed_hosts_storefile().display().to_string();
+    match filter.evaluate(host) {
+        FilterDecision::Allowed(rule) => println!(
+            "{} is ALLOWED by rule '{}' (line {} of {})",
+            host,
+            rule,
+            rule.line.unwrap_or_default(),
+            storefile
+        ),
+        FilterDecision::Denied(rule) => println!(
+            "{} is DENIED by rule '{}' (line {} of {})",
+            host,
+            rule,
+            rule.line.unwrap_or_default(),
+            storefile
+        ),
+        FilterDecision::NoMatchingRule => println!(
+            "{} does not match any rule in {} and is going to be DENIED",
+            host, storefile
+        ),
+        FilterDecision::InvalidHost => {
+            println!("{} is not a valid host and is going to be DENIED", host)
+        }
+    }
+}
+
+fn parse_limit<T>(matches: &ArgMatches, arg: &str) -> Option<T>
+where
+    T: std::str::FromStr + PartialEq + Default,
+    <T as std::str::FromStr>::Err: std::fmt::Display,
+{
+    let value = matches.value_of(arg).unwrap();
+    let limit = value
+        .parse()
+        .unwrap_or_else(|err| panic!("'{}' is not a valid value for --{} - {}", value, arg, err));
+    if limit == T::default() {
+        None
+    } else {
+        Some(limit)
+    }
+}
+
+fn parse_limits(matches: &ArgMatches) -> Limits {
+    let bandwidth_window = parse_limit(matches, BANDWIDTH_WINDOW_ARG)
+        .map(Duration::from_secs)
+        .expect("the bandwidth window can't be empty");
+
+    Limits {
+        max_concurrent_connections: parse_limit(matches, MAX_CONNECTIONS_ARG),
+        max_new_connections_per_minute: parse_limit(matches, MAX_CONNECTIONS_PER_MINUTE_ARG),
+        max_bandwidth: parse_limit(matches, MAX_BANDWIDTH_ARG),
+        bandwidth_window,
+    }
+}
+
 #[tokio::main]
 async fn main() {
     setup_logging();
     let matches = parse_args();
+    if let Some(check_matches) = matches.subcommand_matches(CHECK_COMMAND) {
+        check_host(check_matches.value_of(HOST_ARG).unwrap());
+        return;
+    }
+
     let open_proxy = matches.is_present(OPEN_PROXY_ARG);
     if open_proxy {
         println!("\n\nYOU HAVE STARTED IN 'OPEN PROXY' MODE. ANYONE WITH YOUR CLIENT ADDRESS CAN MAKE REQUESTS FROM YOUR MACHINE. PLEASE QUIT IF YOU DON'T UNDERSTAND WHAT YOU'RE DOING.\n\n");
     }
 
+    let allowed_local_networks: Vec<IpNetwork> = matches
+        .values_of(ALLOW_LOCAL_NETWORK_ARG)
+        .map(|networks| {
+            networks
+                .map(|network| {
+                    network.parse().unwrap_or_else(|err| {
+                        panic!("'{}' is not a valid network - {}", network, err)
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    for network in &allowed_local_networks {
+        println!(
+            "Connections to {} are allowed even if it's a local network",
+            network
+        );
+    }
+    let restricted_networks = RestrictedNetworks::new(allowed_local_networks);
+    let limits = parse_limits(&matches);
+
     let uri = "ws://localhost:1977";
     println!("Starting socks5 service provider:");
-    let mut server = core::ServiceProvider::new(uri.into(), open_proxy);
+    let mut server =
+        core::ServiceProvider::new(uri.into(), open_proxy, restricted_networks, limits);
     server.run().await;
 }
 
@@ -0,0 +1,179 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Loopback, private, link-local and otherwise special-purpose networks. Allowing connections
+/// to any of them would let clients reach services on the operator's machine or its local network
+/// (including cloud metadata endpoints, such as 169.254.169.254).
+const DEFAULT_RESTRICTED_NETWORKS: &[&str] = &[
+    // IPv4
+    "0.0.0.0/8",          // "this" network
+    "10.0.0.0/8",         // private-use
+    "100.64.0.0/10",      // shared address space (carrier-grade NAT)
+    "127.0.0.0/8",        // loopback
+    "169.254.0.0/16",     // link-local (includes most cloud metadata endpoints)
+    "172.16.0.0/12",      // private-use
+    "192.0.0.0/24",       // IETF protocol assignments
+    "192.0.2.0/24",       // documentation (TEST-NET-1)
+    "192.168.0.0/16",     // private-use
+    "198.18.0.0/15",      // benchmarking
+    "198.51.100.0/24",    // documentation (TEST-NET-2)
+    "203.0.113.0/24",     // documentation (TEST-NET-3)
+    "224.0.0.0/4",        // multicast
+    "240.0.0.0/4",        // reserved
+    "255.255.255.255/32", // limited broadcast
+    // IPv6
+    "::/128",         // unspecified
+    "::1/128",        // loopback
+    "64:ff9b::/96",   // IPv4-IPv6 translation
+    "64:ff9b:1::/48", // local-use IPv4-IPv6 translation
+    "2002::/16",      // 6to4 (can wrap any IPv4 address, including the private ones)
+    "fc00::/7",       // unique-local (includes the AWS metadata endpoint, fd00:ec2::254)
+    "fe80::/10",      // link-local
+    "ff00::/8",       // multicast
+];
+
+/// Networks the network requester refuses to connect to, regardless of whether the destination
+/// was allowed by the `OutboundRequestFilter` or the requester runs in the 'open proxy' mode.
+/// The check is performed on resolved addresses, right before connecting, so that it can't
+/// be bypassed with a domain (re)binding to a restricted address.
+#[derive(Debug)]
+pub(crate) struct RestrictedNetworks {
+    restricted: Vec<IpNetwork>,
+    exceptions: Vec<IpNetwork>,
+}
+
+impl Default for RestrictedNetworks {
+    fn default() -> Self {
+        RestrictedNetworks::new(Vec::new())
+    }
+}
+
+impl RestrictedNetworks {
+    /// Creates the default restrictions, except for the networks explicitly allowed by the operator.
+    pub(crate) fn new(exceptions: Vec<IpNetwork>) -> Self {
+        RestrictedNetworks {
+            restricted: DEFAULT_RESTRICTED_NETWORKS
+                .iter()
+                .map(|network| network.parse().unwrap())
+                .collect(),
+            exceptions,
+        }
+    }
+
+    /// Returns the IPv4 address embedded in an IPv4-mapped IPv6 address (`::ffff:a.b.c.d`),
+    /// so that it could be checked against IPv4 networks.
+    fn canonical(address: IpAddr) -> IpAddr {
+        match address {
+            IpAddr::V6(v6) => match v6.segments() {
+                [0, 0, 0, 0, 0, 0xffff, high, low] => IpAddr::V4(Ipv4Addr::new(
+                    (high >> 8) as u8,
+                    high as u8,
+                    (low >> 8) as u8,
+                    low as u8,
+                )),
+                _ => address,
+            },
+            _ => address,
+        }
+    }
+
+    pub(crate) fn is_restricted(&self, address: IpAddr) -> bool {
+        let address = Self::canonical(address);
+        if self
+            .exceptions
+            .iter()
+            .any(|network| network.contains(address))
+        {
+            return false;
+        }
+        self.restricted
+            .iter()
+            .any(|network| network.contains(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_networks_are_valid() {
+        let restricted_networks = RestrictedNetworks::default();
+        assert_eq!(
+            DEFAULT_RESTRICTED_NETWORKS.len(),
+            restricted_networks.restricted.len()
+        );
+    }
+
+    #[test]
+    fn local_addresses_are_restricted() {
+        let restricted_networks = RestrictedNetworks::default();
+        for address in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00:ec2::254",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(
+                restricted_networks.is_restricted(address.parse().unwrap()),
+                "{} should be restricted",
+                address
+            )
+        }
+    }
+
+    #[test]
+    fn documentation_and_6to4_addresses_are_restricted() {
+        let restricted_networks = RestrictedNetworks::default();
+        for address in [
+            "192.0.2.1",
+            "198.51.100.1",
+            "203.0.113.1",
+            // 6to4 wrapping 10.0.0.1 and 1.1.1.1
+            "2002:a00:1::1",
+            "2002:101:101::1",
+        ] {
+            assert!(
+                restricted_networks.is_restricted(address.parse().unwrap()),
+                "{} should be restricted",
+                address
+            )
+        }
+    }
+
+    #[test]
+    fn public_addresses_are_not_restricted() {
+        let restricted_networks = RestrictedNetworks::default();
+        for address in [
+            "1.1.1.1",
+            "8.8.8.8",
+            "172.32.0.1",
+            "2606:4700:4700::1111",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(
+                !restricted_networks.is_restricted(address.parse().unwrap()),
+                "{} should not be restricted",
+                address
+            )
+        }
+    }
+
+    #[test]
+    fn exceptions_take_precedence() {
+        let restricted_networks = RestrictedNetworks::new(vec!["10.1.0.0/16".parse().unwrap()]);
+        assert!(!restricted_networks.is_restricted("10.1.2.3".parse().unwrap()));
+        assert!(restricted_networks.is_restricted("10.2.2.3".parse().unwrap()));
+    }
+}
@@ -0,0 +1,11 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- shared set of coconut credentials that got spent at any of the gateways
+CREATE TABLE spent_credentials
+(
+    blinded_serial_number_bs58 TEXT    NOT NULL PRIMARY KEY UNIQUE,
+    timestamp                  INTEGER NOT NULL
+);
@@ -1,15 +1,24 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::contract_cache::ValidatorCache;
+use crate::node_status_api::models::ErrorResponse;
+use crate::storage::ValidatorApiStorage;
 use coconut_interface::{
     elgamal::PublicKey, Attribute, BlindSignRequest, BlindSignRequestBody, BlindedSignature,
-    BlindedSignatureResponse, KeyPair, Parameters, VerificationKeyResponse,
+    BlindedSignatureResponse, KeyPair, Parameters, SpendCredentialRequestBody,
+    SpendCredentialResponse, VerificationKey, VerificationKeyResponse,
 };
 use config::defaults::VALIDATOR_API_VERSION;
+use credentials::obtain_aggregate_verification_key;
+use crypto::asymmetric::identity;
 use getset::{CopyGetters, Getters};
 use rocket::fairing::AdHoc;
+use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::State;
+use tokio::sync::RwLock;
+use url::Url;
 
 #[derive(Getters, CopyGetters, Debug)]
 pub(crate) struct InternalSignRequest {
@@ -50,6 +59,97 @@ impl InternalSignRequest {
     }
 }
 
+/// Shared set of spent credentials, so that the same credential could not be used at multiple
+/// gateways. Only valid credentials redeemed by bonded gateways are accepted into it.
+pub(crate) struct SharedSpentSet {
+    validator_api_urls: Vec<Url>,
+
+    /// Obtained on the first use, as the validator APIs (including this one)
+    /// might not be available yet when we're starting up.
+    aggregated_verification_key: RwLock<Option<VerificationKey>>,
+}
+
+impl SharedSpentSet {
+    pub(crate) fn stage(validator_api_urls: Vec<Url>) -> AdHoc {
+        AdHoc::on_ignite("Shared Spent Set Stage", |rocket| async {
+            rocket
+                .manage(SharedSpentSet {
+                    validator_api_urls,
+                    aggregated_verification_key: RwLock::new(None),
+                })
+                .mount(
+                    format!("/{}", VALIDATOR_API_VERSION),
+                    routes![post_spend_credential],
+                )
+        })
+    }
+
+    async fn aggregated_verification_key(&self) -> Result<VerificationKey, ErrorResponse> {
+        if let Some(verification_key) = self.aggregated_verification_key.read().await.as_ref() {
+            return Ok(verification_key.clone());
+        }
+
+        let mut aggregated_verification_key = self.aggregated_verification_key.write().await;
+        if let Some(verification_key) = aggregated_verification_key.as_ref() {
+            return Ok(verification_key.clone());
+        }
+
+        let verification_key = obtain_aggregate_verification_key(&self.validator_api_urls)
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    format!("failed to obtain the aggregated verification key - {}", err),
+                    Status::ServiceUnavailable,
+                )
+            })?;
+        *aggregated_verification_key = Some(verification_key.clone());
+        Ok(verification_key)
+    }
+
+    /// Checks whether the request comes from a bonded gateway and the credential is valid.
+    async fn verify_request(
+        &self,
+        request: &SpendCredentialRequestBody,
+        validator_cache: &ValidatorCache,
+    ) -> Result<(), ErrorResponse> {
+        let is_bonded = validator_cache
+            .gateways()
+            .await
+            .into_inner()
+            .iter()
+            .any(|bond| bond.gateway.identity_key == request.gateway_identity);
+        if !is_bonded {
+            return Err(ErrorResponse::new(
+                "the credential can only be spent by a bonded gateway",
+                Status::Forbidden,
+            ));
+        }
+
+        let gateway_identity =
+            identity::PublicKey::from_base58_string(&request.gateway_identity)
+                .map_err(|err| ErrorResponse::new(err.to_string(), Status::BadRequest))?;
+        let signature = identity::Signature::from_base58_string(&request.signature)
+            .map_err(|err| ErrorResponse::new(err.to_string(), Status::BadRequest))?;
+        let message = SpendCredentialRequestBody::signed_message(&request.credential);
+        if gateway_identity.verify(&message, &signature).is_err() {
+            return Err(ErrorResponse::new(
+                "invalid gateway signature",
+                Status::Forbidden,
+            ));
+        }
+
+        let verification_key = self.aggregated_verification_key().await?;
+        if !request.credential.verify(&verification_key) {
+            return Err(ErrorResponse::new(
+                "invalid bandwidth credential",
+                Status::BadRequest,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 fn blind_sign(request: InternalSignRequest, key_pair: &KeyPair) -> BlindedSignature {
     let params = Parameters::new(request.total_params()).unwrap();
     coconut_interface::blind_sign(
@@ -83,3 +183,25 @@ pub async fn post_blind_sign(
 pub async fn get_verification_key(key_pair: &State<KeyPair>) -> Json<VerificationKeyResponse> {
     Json(VerificationKeyResponse::new(key_pair.verification_key()))
 }
+
+#[post("/spend-credential", data = "<spend_credential_request_body>")]
+pub(crate) async fn post_spend_credential(
+    spend_credential_request_body: Json<SpendCredentialRequestBody>,
+    shared_spent_set: &State<SharedSpentSet>,
+    validator_cache: &State<ValidatorCache>,
+    storage: &State<ValidatorApiStorage>,
+) -> Result<Json<SpendCredentialResponse>, ErrorResponse> {
+    shared_spent_set
+        .verify_request(&spend_credential_request_body, validator_cache)
+        .await?;
+
+    storage
+        .mark_credential_spent(
+            &spend_credential_request_body
+                .credential
+                .blinded_serial_number(),
+        )
+        .await
+        .map(|newly_spent| Json(SpendCredentialResponse::new(newly_spent)))
+        .map_err(|err| ErrorResponse::new(err.to_string(), Status::InternalServerError))
+}
@@ -7,6 +7,7 @@ extern crate rocket;
 use crate::config::Config;
 use crate::contract_cache::ValidatorCacheRefresher;
 use crate::network_monitor::NetworkMonitorBuilder;
+use crate::node_status_api::identity_rotation_updater::IdentityRotationUpdater;
 use crate::node_status_api::uptime_updater::HistoricalUptimeUpdater;
 use crate::nymd_client::Client;
 use crate::rewarding::Rewarder;
@@ -30,7 +31,7 @@ use validator_client::ValidatorClientError;
 
 use crate::rewarded_set_updater::RewardedSetUpdater;
 #[cfg(feature = "coconut")]
-use coconut::InternalSignRequest;
+use coconut::{InternalSignRequest, SharedSpentSet};
 
 pub(crate) mod config;
 pub(crate) mod contract_cache;
@@ -399,15 +400,27 @@ async fn setup_rocket(config: &Config, liftoff_notify: Arc<Notify>) -> Result<Ro
         .attach(setup_liftoff_notify(liftoff_notify))
         .attach(ValidatorCache::stage());
 
+    // spending credentials requires knowing which gateways are bonded, so unlike
+    // the rest of the coconut routes, it's not available in the coconut-only mode
     #[cfg(feature = "coconut")]
-    let rocket = rocket.attach(InternalSignRequest::stage(config.keypair()));
+    let rocket = rocket
+        .attach(InternalSignRequest::stage(config.keypair()))
+        .attach(SharedSpentSet::stage(
+            config.get_all_validator_api_endpoints(),
+        ));
+
+    // the storage is required by both the network monitor and the shared set of spent credentials
+    let rocket = if config.get_network_monitor_enabled() || cfg!(feature = "coconut") {
+        rocket.attach(storage::ValidatorApiStorage::stage(
+            config.get_node_status_api_database_path(),
+        ))
+    } else {
+        rocket
+    };
 
     // see if we should start up network monitor and if so, attach the node status api
     if config.get_network_monitor_enabled() {
         Ok(rocket
-            .attach(storage::ValidatorApiStorage::stage(
-                config.get_node_status_api_database_path(),
-            ))
             .attach(node_status_api::stage_full())
             .ignite()
             .await?)
@@ -482,9 +495,13 @@ async fn run_validator_api(matches: ArgMatches<'static>) -> Result<()> {
         // setup our daily uptime updater. Note that if network monitor is disabled, then we have
         // no data for the updates and hence we don't need to start it up
         let storage = rocket.state::<ValidatorApiStorage>().unwrap().clone();
-        let uptime_updater = HistoricalUptimeUpdater::new(storage);
+        let uptime_updater = HistoricalUptimeUpdater::new(storage.clone());
         tokio::spawn(async move { uptime_updater.run().await });
 
+        // nodes that have rotated their identity keys keep their history under the new keys
+        let identity_rotation_updater = IdentityRotationUpdater::new(nymd_client.clone(), storage);
+        tokio::spawn(async move { identity_rotation_updater.run().await });
+
         if let Some(rewarder) = setup_rewarder(&config, &rocket, &nymd_client).await? {
             info!("Periodic rewarding is starting...");
 
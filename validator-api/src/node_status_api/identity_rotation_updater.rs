@@ -0,0 +1,139 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node_status_api::models::ValidatorApiStorageError;
+use crate::node_status_api::FIFTEEN_MINUTES;
+use crate::nymd_client::Client;
+use crate::storage::ValidatorApiStorage;
+use log::{error, info, warn};
+use mixnet_contract_common::{IdentityKey, IdentityRotation};
+use std::collections::{HashMap, HashSet};
+use tokio::time::sleep;
+use validator_client::nymd::CosmWasmClient;
+
+/// Resolves the identity each of the rotated away keys ends up with, i.e. if a node has rotated
+/// its key multiple times, all of its previous keys point to the most recent one.
+fn final_identities(rotations: &[IdentityRotation]) -> Vec<(&IdentityKey, &IdentityKey)> {
+    let next = rotations
+        .iter()
+        .map(|rotation| (&rotation.previous_identity, &rotation.new_identity))
+        .collect::<HashMap<_, _>>();
+
+    rotations
+        .iter()
+        .map(|rotation| {
+            let mut current = &rotation.new_identity;
+            // the contract never lets a node rotate back to any of its old keys,
+            // but let's not loop forever if that assumption ever breaks
+            let mut visited = HashSet::new();
+            while let Some(newer) = next.get(current) {
+                if !visited.insert(current) {
+                    break;
+                }
+                current = newer;
+            }
+            (&rotation.previous_identity, current)
+        })
+        .collect()
+}
+
+/// Periodically carries over the uptime history of nodes that have rotated their identity keys
+/// on chain, so that they wouldn't start from scratch under their new keys.
+pub(crate) struct IdentityRotationUpdater<C> {
+    nymd_client: Client<C>,
+    storage: ValidatorApiStorage,
+}
+
+impl<C> IdentityRotationUpdater<C>
+where
+    C: CosmWasmClient + Sync,
+{
+    pub(crate) fn new(nymd_client: Client<C>, storage: ValidatorApiStorage) -> Self {
+        IdentityRotationUpdater {
+            nymd_client,
+            storage,
+        }
+    }
+
+    async fn apply_mixnode_rotations(
+        &self,
+        rotations: &[IdentityRotation],
+    ) -> Result<(), ValidatorApiStorageError> {
+        for (old_identity, new_identity) in final_identities(rotations) {
+            self.storage
+                .rotate_mixnode_identity(old_identity, new_identity)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_gateway_rotations(
+        &self,
+        rotations: &[IdentityRotation],
+    ) -> Result<(), ValidatorApiStorageError> {
+        for (old_identity, new_identity) in final_identities(rotations) {
+            self.storage
+                .rotate_gateway_identity(old_identity, new_identity)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn update(&self) {
+        match self.nymd_client.get_mixnode_identity_rotations().await {
+            Ok(rotations) => {
+                if let Err(err) = self.apply_mixnode_rotations(&rotations).await {
+                    error!(
+                        "We failed to carry over the history of mixnodes with rotated identities - {}",
+                        err
+                    )
+                }
+            }
+            Err(err) => warn!("Failed to obtain mixnode identity rotations - {}", err),
+        }
+
+        match self.nymd_client.get_gateway_identity_rotations().await {
+            Ok(rotations) => {
+                if let Err(err) = self.apply_gateway_rotations(&rotations).await {
+                    error!(
+                        "We failed to carry over the history of gateways with rotated identities - {}",
+                        err
+                    )
+                }
+            }
+            Err(err) => warn!("Failed to obtain gateway identity rotations - {}", err),
+        }
+    }
+
+    pub(crate) async fn run(&self) {
+        info!("Starting the identity rotation updater");
+        loop {
+            self.update().await;
+            sleep(FIFTEEN_MINUTES).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotation(previous: &str, new: &str) -> IdentityRotation {
+        IdentityRotation {
+            previous_identity: previous.to_string(),
+            new_identity: new.to_string(),
+            block_height: 42,
+        }
+    }
+
+    #[test]
+    fn all_previous_keys_point_to_the_most_recent_one() {
+        let rotations = vec![rotation("b", "c"), rotation("a", "b"), rotation("x", "y")];
+        let resolved = final_identities(&rotations)
+            .into_iter()
+            .map(|(old, new)| (old.as_str(), new.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![("b", "c"), ("a", "c"), ("x", "y")], resolved);
+    }
+}
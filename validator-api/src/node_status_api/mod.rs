@@ -4,6 +4,7 @@
 use rocket::fairing::AdHoc;
 use std::time::Duration;
 
+pub(crate) mod identity_rotation_updater;
 pub(crate) mod local_guard;
 pub(crate) mod models;
 pub(crate) mod routes;
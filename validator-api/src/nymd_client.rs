@@ -5,9 +5,9 @@ use crate::config::Config;
 use crate::rewarding::{error::RewardingError, IntervalRewardParams, MixnodeToReward};
 use config::defaults::{default_network, DEFAULT_VALIDATOR_API_PORT};
 use mixnet_contract_common::{
-    ContractStateParams, Delegation, ExecuteMsg, GatewayBond, IdentityKey, Interval, MixNodeBond,
-    MixnodeRewardingStatusResponse, RewardedSetNodeStatus, RewardedSetUpdateDetails,
-    MIXNODE_DELEGATORS_PAGE_LIMIT,
+    ContractStateParams, Delegation, ExecuteMsg, GatewayBond, IdentityKey, IdentityRotation,
+    Interval, MixNodeBond, MixnodeRewardingStatusResponse, RewardedSetNodeStatus,
+    RewardedSetUpdateDetails, MIXNODE_DELEGATORS_PAGE_LIMIT,
 };
 use serde::Serialize;
 use std::sync::Arc;
@@ -121,6 +121,32 @@ impl<C> Client<C> {
         self.0.read().await.get_all_nymd_gateways().await
     }
 
+    pub(crate) async fn get_mixnode_identity_rotations(
+        &self,
+    ) -> Result<Vec<IdentityRotation>, ValidatorClientError>
+    where
+        C: CosmWasmClient + Sync,
+    {
+        self.0
+            .read()
+            .await
+            .get_all_nymd_mixnode_identity_rotations()
+            .await
+    }
+
+    pub(crate) async fn get_gateway_identity_rotations(
+        &self,
+    ) -> Result<Vec<IdentityRotation>, ValidatorClientError>
+    where
+        C: CosmWasmClient + Sync,
+    {
+        self.0
+            .read()
+            .await
+            .get_all_nymd_gateway_identity_rotations()
+            .await
+    }
+
     #[allow(dead_code)]
     // I've got a feeling we will need this again very soon, so I'd rather not remove this
     // (and all subcalls in the various clients) just yet
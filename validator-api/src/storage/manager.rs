@@ -51,6 +51,190 @@ impl StorageManager {
         Ok(id)
     }
 
+    /// Moves all data of the mixnode stored under its previous identity over to its new one.
+    /// If the mixnode has already been tested under the new identity, both histories are merged,
+    /// with the old entries taking precedence for the days present in both.
+    /// Does nothing if there's no data stored under the previous identity.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_identity`: identity (base58-encoded public key) the mixnode has rotated away from.
+    /// * `new_identity`: current identity (base58-encoded public key) of the mixnode.
+    pub(super) async fn rotate_mixnode_identity(
+        &self,
+        old_identity: &str,
+        new_identity: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.connection_pool.begin().await?;
+
+        let old_id = match sqlx::query!(
+            "SELECT id FROM mixnode_details WHERE identity = ?",
+            old_identity
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        {
+            Some(row) => row.id,
+            None => return tx.commit().await,
+        };
+
+        let new_id = sqlx::query!(
+            "SELECT id FROM mixnode_details WHERE identity = ?",
+            new_identity
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .map(|row| row.id);
+
+        if let Some(new_id) = new_id {
+            sqlx::query!(
+                r#"
+                    DELETE FROM mixnode_historical_uptime
+                    WHERE mixnode_details_id = ? AND date IN (
+                        SELECT date FROM mixnode_historical_uptime WHERE mixnode_details_id = ?
+                    )
+                "#,
+                new_id,
+                old_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE mixnode_historical_uptime SET mixnode_details_id = ? WHERE mixnode_details_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE mixnode_status SET mixnode_details_id = ? WHERE mixnode_details_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE testing_route SET layer1_mix_id = ? WHERE layer1_mix_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE testing_route SET layer2_mix_id = ? WHERE layer2_mix_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE testing_route SET layer3_mix_id = ? WHERE layer3_mix_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!("DELETE FROM mixnode_details WHERE id = ?", new_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE mixnode_details SET identity = ? WHERE id = ?",
+            new_identity,
+            old_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// Moves all data of the gateway stored under its previous identity over to its new one.
+    /// If the gateway has already been tested under the new identity, both histories are merged,
+    /// with the old entries taking precedence for the days present in both.
+    /// Does nothing if there's no data stored under the previous identity.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_identity`: identity (base58-encoded public key) the gateway has rotated away from.
+    /// * `new_identity`: current identity (base58-encoded public key) of the gateway.
+    pub(super) async fn rotate_gateway_identity(
+        &self,
+        old_identity: &str,
+        new_identity: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.connection_pool.begin().await?;
+
+        let old_id = match sqlx::query!(
+            "SELECT id FROM gateway_details WHERE identity = ?",
+            old_identity
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        {
+            Some(row) => row.id,
+            None => return tx.commit().await,
+        };
+
+        let new_id = sqlx::query!(
+            "SELECT id FROM gateway_details WHERE identity = ?",
+            new_identity
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .map(|row| row.id);
+
+        if let Some(new_id) = new_id {
+            sqlx::query!(
+                r#"
+                    DELETE FROM gateway_historical_uptime
+                    WHERE gateway_details_id = ? AND date IN (
+                        SELECT date FROM gateway_historical_uptime WHERE gateway_details_id = ?
+                    )
+                "#,
+                new_id,
+                old_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE gateway_historical_uptime SET gateway_details_id = ? WHERE gateway_details_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE gateway_status SET gateway_details_id = ? WHERE gateway_details_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!(
+                "UPDATE testing_route SET gateway_id = ? WHERE gateway_id = ?",
+                old_id,
+                new_id
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query!("DELETE FROM gateway_details WHERE id = ?", new_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE gateway_details SET identity = ? WHERE id = ?",
+            new_identity,
+            old_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await
+    }
+
     /// Tries to obtain owner value of given mixnode given its identity
     ///
     /// # Arguments
@@ -886,4 +1070,194 @@ impl StorageManager {
 
         Ok(active_day_statuses)
     }
+
+    /// Marks the coconut credential with the provided blinded serial number as spent.
+    /// Returns whether the credential has not been spent before.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number`: base58-encoded blinded serial number of the credential.
+    /// * `timestamp`: unix timestamp of when the credential got spent.
+    #[cfg(feature = "coconut")]
+    pub(super) async fn mark_credential_spent(
+        &self,
+        blinded_serial_number: &str,
+        timestamp: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query!(
+            r#"
+                INSERT OR IGNORE INTO spent_credentials(blinded_serial_number_bs58, timestamp) VALUES (?, ?);
+            "#,
+            blinded_serial_number,
+            timestamp
+        )
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_storage() -> StorageManager {
+        // every connection to an in-memory database gets its own one, so there can only be one
+        let connection_pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations")
+            .run(&connection_pool)
+            .await
+            .unwrap();
+        StorageManager { connection_pool }
+    }
+
+    async fn add_mixnode(storage: &StorageManager, identity: &str, uptimes: &[(&str, u8)]) -> i64 {
+        sqlx::query("INSERT INTO mixnode_details(identity, owner) VALUES (?, ?)")
+            .bind(identity)
+            .bind("owner")
+            .execute(&storage.connection_pool)
+            .await
+            .unwrap();
+        let id = storage.get_mixnode_id(identity).await.unwrap().unwrap();
+        for (date, uptime) in uptimes {
+            storage
+                .insert_mixnode_historical_uptime(id, date, *uptime)
+                .await
+                .unwrap();
+        }
+        id
+    }
+
+    async fn add_gateway(storage: &StorageManager, identity: &str, uptimes: &[(&str, u8)]) -> i64 {
+        sqlx::query("INSERT INTO gateway_details(identity, owner) VALUES (?, ?)")
+            .bind(identity)
+            .bind("owner")
+            .execute(&storage.connection_pool)
+            .await
+            .unwrap();
+        let id = storage.get_gateway_id(identity).await.unwrap().unwrap();
+        for (date, uptime) in uptimes {
+            storage
+                .insert_gateway_historical_uptime(id, date, *uptime)
+                .await
+                .unwrap();
+        }
+        id
+    }
+
+    fn as_pairs(uptimes: Vec<HistoricalUptime>) -> Vec<(String, u8)> {
+        uptimes
+            .into_iter()
+            .map(|uptime| (uptime.date, uptime.uptime.u8()))
+            .collect()
+    }
+
+    fn owned(uptimes: &[(&str, u8)]) -> Vec<(String, u8)> {
+        uptimes
+            .iter()
+            .map(|(date, uptime)| (date.to_string(), *uptime))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rotating_mixnode_identity_without_old_data_keeps_the_new_data() {
+        let storage = test_storage().await;
+        let uptimes = [("2022-03-01", 90), ("2022-03-02", 95)];
+        let new_id = add_mixnode(&storage, "new", &uptimes).await;
+
+        storage.rotate_mixnode_identity("old", "new").await.unwrap();
+
+        assert_eq!(None, storage.get_mixnode_id("old").await.unwrap());
+        assert_eq!(Some(new_id), storage.get_mixnode_id("new").await.unwrap());
+        assert_eq!(
+            owned(&uptimes),
+            as_pairs(storage.get_mixnode_historical_uptimes("new").await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn rotating_mixnode_identity_without_new_data_moves_the_old_data() {
+        let storage = test_storage().await;
+        let uptimes = [("2022-03-01", 90), ("2022-03-02", 95)];
+        let old_id = add_mixnode(&storage, "old", &uptimes).await;
+
+        storage.rotate_mixnode_identity("old", "new").await.unwrap();
+
+        assert_eq!(None, storage.get_mixnode_id("old").await.unwrap());
+        assert_eq!(Some(old_id), storage.get_mixnode_id("new").await.unwrap());
+        assert_eq!(
+            owned(&uptimes),
+            as_pairs(storage.get_mixnode_historical_uptimes("new").await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn rotating_mixnode_identity_merges_histories_preferring_the_old_entries() {
+        let storage = test_storage().await;
+        let old_id = add_mixnode(&storage, "old", &[("2022-03-01", 90), ("2022-03-02", 95)]).await;
+        add_mixnode(&storage, "new", &[("2022-03-02", 10), ("2022-03-03", 80)]).await;
+
+        storage.rotate_mixnode_identity("old", "new").await.unwrap();
+
+        assert_eq!(None, storage.get_mixnode_id("old").await.unwrap());
+        assert_eq!(Some(old_id), storage.get_mixnode_id("new").await.unwrap());
+        assert_eq!(
+            owned(&[("2022-03-01", 90), ("2022-03-02", 95), ("2022-03-03", 80)]),
+            as_pairs(storage.get_mixnode_historical_uptimes("new").await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn rotating_gateway_identity_without_old_data_keeps_the_new_data() {
+        let storage = test_storage().await;
+        let uptimes = [("2022-03-01", 90), ("2022-03-02", 95)];
+        let new_id = add_gateway(&storage, "new", &uptimes).await;
+
+        storage.rotate_gateway_identity("old", "new").await.unwrap();
+
+        assert_eq!(None, storage.get_gateway_id("old").await.unwrap());
+        assert_eq!(Some(new_id), storage.get_gateway_id("new").await.unwrap());
+        assert_eq!(
+            owned(&uptimes),
+            as_pairs(storage.get_gateway_historical_uptimes("new").await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn rotating_gateway_identity_without_new_data_moves_the_old_data() {
+        let storage = test_storage().await;
+        let uptimes = [("2022-03-01", 90), ("2022-03-02", 95)];
+        let old_id = add_gateway(&storage, "old", &uptimes).await;
+
+        storage.rotate_gateway_identity("old", "new").await.unwrap();
+
+        assert_eq!(None, storage.get_gateway_id("old").await.unwrap());
+        assert_eq!(Some(old_id), storage.get_gateway_id("new").await.unwrap());
+        assert_eq!(
+            owned(&uptimes),
+            as_pairs(storage.get_gateway_historical_uptimes("new").await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn rotating_gateway_identity_merges_histories_preferring_the_old_entries() {
+        let storage = test_storage().await;
+        let old_id = add_gateway(&storage, "old", &[("2022-03-01", 90), ("2022-03-02", 95)]).await;
+        add_gateway(&storage, "new", &[("2022-03-02", 10), ("2022-03-03", 80)]).await;
+
+        storage.rotate_gateway_identity("old", "new").await.unwrap();
+
+        assert_eq!(None, storage.get_gateway_id("old").await.unwrap());
+        assert_eq!(Some(old_id), storage.get_gateway_id("new").await.unwrap());
+        assert_eq!(
+            owned(&[("2022-03-01", 90), ("2022-03-02", 95), ("2022-03-03", 80)]),
+            as_pairs(storage.get_gateway_historical_uptimes("new").await.unwrap())
+        );
+    }
 }
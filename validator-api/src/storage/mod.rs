@@ -647,6 +647,42 @@ impl ValidatorApiStorage {
         Ok(())
     }
 
+    /// Carries over all statuses and historical uptimes of the mixnode gathered under its previous
+    /// identity key to its new one. It is safe to call it multiple times for the same rotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_identity`: identity the mixnode has rotated away from.
+    /// * `new_identity`: current identity of the mixnode.
+    pub(crate) async fn rotate_mixnode_identity(
+        &self,
+        old_identity: &str,
+        new_identity: &str,
+    ) -> Result<(), ValidatorApiStorageError> {
+        self.manager
+            .rotate_mixnode_identity(old_identity, new_identity)
+            .await
+            .map_err(|_| ValidatorApiStorageError::InternalDatabaseError)
+    }
+
+    /// Carries over all statuses and historical uptimes of the gateway gathered under its previous
+    /// identity key to its new one. It is safe to call it multiple times for the same rotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_identity`: identity the gateway has rotated away from.
+    /// * `new_identity`: current identity of the gateway.
+    pub(crate) async fn rotate_gateway_identity(
+        &self,
+        old_identity: &str,
+        new_identity: &str,
+    ) -> Result<(), ValidatorApiStorageError> {
+        self.manager
+            .rotate_gateway_identity(old_identity, new_identity)
+            .await
+            .map_err(|_| ValidatorApiStorageError::InternalDatabaseError)
+    }
+
     pub(crate) async fn check_if_historical_uptimes_exist_for_date(
         &self,
         date_iso_8601: &str,
@@ -779,4 +815,23 @@ impl ValidatorApiStorage {
             .await
             .map_err(|_| ValidatorApiStorageError::InternalDatabaseError)
     }
+
+    /// Marks the coconut credential with the provided blinded serial number as spent.
+    /// Returns whether the credential has not been spent before.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number`: base58-encoded blinded serial number of the credential.
+    #[cfg(feature = "coconut")]
+    pub(crate) async fn mark_credential_spent(
+        &self,
+        blinded_serial_number: &str,
+    ) -> Result<bool, ValidatorApiStorageError> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        self.manager
+            .mark_credential_spent(blinded_serial_number, now)
+            .await
+            .map_err(|_| ValidatorApiStorageError::InternalDatabaseError)
+    }
 }